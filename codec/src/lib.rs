@@ -30,6 +30,7 @@ use std::sync::Arc;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::Line;
 use varbincode;
+use wezterm_term::color::ColorPalette;
 use wezterm_term::{ClipboardSelection, StableRowIndex};
 
 /// Returns the encoded length of the leb128 representation of value
@@ -400,7 +401,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 7;
+pub const CODEC_VERSION: usize = 22;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -436,6 +437,27 @@ pdu! {
     SplitPane: 34,
     KillPane: 35,
     SpawnV2: 36,
+    GetPaneDimensions: 37,
+    GetPaneDimensionsResponse: 38,
+    ScreenshotPane: 39,
+    ScreenshotPaneResponse: 40,
+    LogFilter: 41,
+    LogFilterResponse: 42,
+    KillTab: 43,
+    KillWindow: 44,
+    ListWorkspaces: 45,
+    ListWorkspacesResponse: 46,
+    RenameWorkspace: 47,
+    SetWindowWorkspace: 48,
+    Authenticate: 49,
+    GetPaneBookmarks: 50,
+    GetPaneBookmarksResponse: 51,
+    SetPaneBookmark: 52,
+    GetWindowPerf: 53,
+    GetWindowPerfResponse: 54,
+    EmitEvent: 55,
+    GetPanePalette: 56,
+    GetPanePaletteResponse: 57,
 }
 
 impl Pdu {
@@ -587,11 +609,66 @@ pub struct SpawnV2 {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub size: PtySize,
+    /// The workspace for the new window.  If None, the active
+    /// workspace of the server is used.
+    /// Ignored when spawning into an existing window.
+    pub workspace: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct KillPane {
     pub pane_id: PaneId,
+    /// Kill the pane even if it would normally require
+    /// confirmation to close
+    pub force: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct KillTab {
+    pub tab_id: TabId,
+    pub force: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct KillWindow {
+    pub window_id: WindowId,
+    pub force: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListWorkspaces {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct WorkspaceInfo {
+    pub name: String,
+    pub num_windows: usize,
+    /// true if this is the workspace that new windows are placed into
+    pub active: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListWorkspacesResponse {
+    pub workspaces: Vec<WorkspaceInfo>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RenameWorkspace {
+    pub old_workspace: String,
+    pub new_workspace: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetWindowWorkspace {
+    pub window_id: WindowId,
+    pub workspace: String,
+}
+
+/// Presents the auth_token of a unix domain to the server.
+/// Servers with an auth_token reject all other requests, apart
+/// from the version check and pings, until this has succeeded.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct Authenticate {
+    pub token: String,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -685,6 +762,9 @@ pub struct SetPaneZoomed {
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct GetPaneRenderChanges {
     pub pane_id: PaneId,
+    /// Ask the server to forget which lines it has already sent,
+    /// so that the next update includes the whole viewport
+    pub resync: bool,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -707,6 +787,12 @@ pub struct GetPaneRenderChangesResponse {
     pub bonus_lines: SerializedLines,
 
     pub input_serial: Option<InputSerial>,
+    /// Incremented for each update sent for this pane
+    pub seqno: u64,
+    /// Viewport lines that are not in bonus_lines are unchanged since
+    /// the update with this seqno.  Zero means that the whole viewport
+    /// is included in bonus_lines.
+    pub base_seqno: u64,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -853,6 +939,55 @@ pub struct GetLinesResponse {
     pub lines: SerializedLines,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneDimensions {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneDimensionsResponse {
+    pub pane_id: PaneId,
+    pub dimensions: RenderableDimensions,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPanePalette {
+    pub pane_id: PaneId,
+}
+
+/// The effective palette of the pane, including any changes
+/// that the application has made to it
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPanePaletteResponse {
+    pub pane_id: PaneId,
+    pub palette: ColorPalette,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ScreenshotPane {
+    pub pane_id: PaneId,
+    pub scale: f32,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ScreenshotPaneResponse {
+    pub pane_id: PaneId,
+    /// The PNG encoded image
+    pub png: Vec<u8>,
+}
+
+/// Replaces the log filter of the server when `filter` is set.
+/// The response holds the filter that is in effect.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct LogFilter {
+    pub filter: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct LogFilterResponse {
+    pub filter: String,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SearchScrollbackRequest {
     pub pane_id: PaneId,
@@ -864,9 +999,51 @@ pub struct SearchScrollbackResponse {
     pub results: Vec<mux::pane::SearchResult>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneBookmarks {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneBookmarksResponse {
+    pub bookmarks: Vec<mux::pane::Bookmark>,
+}
+
+/// Bookmarks `row` as `name`; a `row` of None removes the bookmark
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneBookmark {
+    pub pane_id: PaneId,
+    pub name: String,
+    pub row: Option<StableRowIndex>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetWindowPerf {
+    pub window_id: WindowId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetWindowPerfResponse {
+    pub window_id: WindowId,
+    /// The timings of the most recently painted frames, oldest first
+    pub frames: Vec<mux::FrameTiming>,
+    pub glyph_cache: mux::GlyphCacheStats,
+}
+
+/// Emits the user defined lua event `name` in the gui window that
+/// is displaying the pane, passing the window, the pane and `args`
+/// to its handlers
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct EmitEvent {
+    pub pane_id: PaneId,
+    pub name: String,
+    pub args: Vec<String>,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use wezterm_term::color::RgbColor;
 
     #[test]
     fn test_frame() {
@@ -958,6 +1135,29 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_pdu_pane_palette() {
+        let response = || {
+            let mut palette = ColorPalette::default();
+            palette.colors.0[4] = RgbColor::new(0x12, 0x34, 0x56);
+            palette.background = RgbColor::new(0x01, 0x02, 0x03);
+            Pdu::GetPanePaletteResponse(GetPanePaletteResponse {
+                pane_id: 3,
+                palette,
+            })
+        };
+
+        let mut encoded = Vec::new();
+        response().encode(&mut encoded, 0x43).unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x43,
+                pdu: response()
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
     #[test]
     fn test_pdu_pong() {
         let mut encoded = Vec::new();
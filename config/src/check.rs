@@ -0,0 +1,274 @@
+//! Validates a lua config file without applying it, so that mistakes
+//! can be reported by `wezterm check-config`, for example as part of
+//! the CI of a dotfiles repository.
+use crate::lua::make_lua_context;
+use crate::Config;
+use anyhow::Context;
+use mlua::Value;
+use std::path::{Path, PathBuf};
+
+/// Options that are still accepted, but that no longer have any effect
+const DEPRECATED_OPTIONS: &[(&str, &str)] = &[
+    ("font_antialias", "Use `freetype_load_target` instead."),
+    ("font_hinting", "Use `freetype_load_target` instead."),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The line of the config file to which the problem refers,
+    /// if it could be located
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct CheckReport {
+    pub file_name: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Evaluates the config file at `path` and checks each of the options
+/// in the table that it returns against the Config struct
+pub fn check_config_file(path: &Path) -> anyhow::Result<CheckReport> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading {}", path.display()))?;
+    check_config_source(path, &source)
+}
+
+fn check_config_source(path: &Path, source: &str) -> anyhow::Result<CheckReport> {
+    let mut diagnostics = vec![];
+    let lua = make_lua_context(path)?;
+
+    let config: mlua::Result<Value> = smol::block_on(
+        lua.load(source)
+            .set_name(path.to_string_lossy().as_bytes())?
+            .eval_async(),
+    );
+    let config = match config {
+        Ok(Value::Table(config)) => config,
+        Ok(_) => {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line: None,
+                message: "the config file must return a table of options".to_string(),
+            });
+            return Ok(CheckReport {
+                file_name: path.to_path_buf(),
+                diagnostics,
+            });
+        }
+        Err(err) => {
+            let message = err.to_string();
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line: lua_error_line(&message),
+                message,
+            });
+            return Ok(CheckReport {
+                file_name: path.to_path_buf(),
+                diagnostics,
+            });
+        }
+    };
+
+    for pair in config.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let name = match &key {
+            Value::String(s) => s.to_str()?.to_string(),
+            _ => {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    line: None,
+                    message: format!(
+                        "the config table has a key of type {} rather than an option name",
+                        key.type_name()
+                    ),
+                });
+                continue;
+            }
+        };
+        let line = assignment_line(source, &name, 1);
+
+        if let Some((_, advice)) = DEPRECATED_OPTIONS.iter().find(|(opt, _)| *opt == name) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                line,
+                message: format!(
+                    "`{}` is deprecated and no longer has any effect. {}",
+                    name, advice
+                ),
+            });
+        }
+
+        // Check the option by itself, so that a problem can be
+        // attributed to it; every other option takes its default
+        let single = lua.create_table()?;
+        single.set(key, value)?;
+        let (result, unknown_fields) = luahelper::collect_unknown_fields(|| {
+            luahelper::from_lua_value::<Config>(Value::Table(single))
+        });
+
+        for unknown in unknown_fields {
+            let (message, field_line) = if unknown.struct_name == "Config" {
+                (format!("unknown option `{}`.", unknown.field), line)
+            } else {
+                (
+                    format!(
+                        "unknown field `{}` in `{}` (of type `{}`).",
+                        unknown.field, name, unknown.struct_name
+                    ),
+                    line.and_then(|line| assignment_line(source, &unknown.field, line))
+                        .or(line),
+                )
+            };
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line: field_line,
+                message: match unknown.suggestions.as_slice() {
+                    [] => message,
+                    suggestions => format!(
+                        "{} Did you mean {}?",
+                        message,
+                        suggestions
+                            .iter()
+                            .map(|s| format!("`{}`", s))
+                            .collect::<Vec<_>>()
+                            .join(" or ")
+                    ),
+                },
+            });
+        }
+
+        if let Err(err) = result {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line,
+                message: format!("invalid value for `{}`: {:#}", name, err),
+            });
+        }
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.severity));
+
+    Ok(CheckReport {
+        file_name: path.to_path_buf(),
+        diagnostics,
+    })
+}
+
+/// Extracts the line number from a lua error message, which refers to
+/// the location of the problem in the form `chunkname:line:`
+fn lua_error_line(message: &str) -> Option<usize> {
+    let parts: Vec<&str> = message.split(':').collect();
+    parts
+        .windows(2)
+        .skip(1)
+        .find_map(|pair| pair[0].trim().parse().ok())
+}
+
+/// Returns the number of the first line of `source`, starting at line
+/// `from_line`, on which a value appears to be assigned to `name`, as
+/// in `name = value` or `config.name = value`
+fn assignment_line(source: &str, name: &str, from_line: usize) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    source
+        .lines()
+        .enumerate()
+        .skip(from_line.saturating_sub(1))
+        .find_map(|(idx, line)| {
+            // Ignore comments
+            let code = line.split("--").next().unwrap_or("");
+            let mut start = 0;
+            while let Some(pos) = code[start..].find(name) {
+                let pos = start + pos;
+                let end = pos + name.len();
+                let preceded_by_ident = code[..pos].chars().next_back().map_or(false, is_ident);
+                let rest = code[end..].trim_start();
+                if !preceded_by_ident
+                    && !code[end..].starts_with(is_ident)
+                    && rest.starts_with('=')
+                    && !rest.starts_with("==")
+                {
+                    return Some(idx + 1);
+                }
+                start = end;
+            }
+            None
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locates_assignments() {
+        let source = "local config = {}\n\
+                      -- font_size = 10\n\
+                      config.font_size = 12\n\
+                      return {\n  keys = {\n    {key=\"a\", mods=\"CTRL\"},\n  },\n}\n";
+        assert_eq!(assignment_line(source, "font_size", 1), Some(3));
+        assert_eq!(assignment_line(source, "size", 1), None);
+        assert_eq!(assignment_line(source, "keys", 1), Some(5));
+        assert_eq!(assignment_line(source, "mods", 5), Some(6));
+        assert_eq!(assignment_line(source, "config", 1), Some(1));
+    }
+
+    #[test]
+    fn extracts_lua_error_lines() {
+        assert_eq!(
+            lua_error_line("runtime error: [string \"/home/w/.wezterm.lua\"]:7: boom"),
+            Some(7)
+        );
+        assert_eq!(lua_error_line("error converting lua nil to table"), None);
+    }
+
+    #[test]
+    fn reports_problems_with_options() -> anyhow::Result<()> {
+        let source = "local wezterm = require 'wezterm'\n\
+                      return {\n  font_size = \"big\",\n  font_sise = 12,\n  \
+                      font_hinting = \"Full\",\n  initial_rows = 30,\n}\n";
+        let report = check_config_source(&std::env::current_dir()?, source)?;
+        assert!(report.has_errors());
+
+        let lines: Vec<(Option<usize>, Severity)> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.severity))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (Some(3), Severity::Error),
+                (Some(4), Severity::Error),
+                (Some(5), Severity::Warning),
+            ]
+        );
+        assert!(report.diagnostics[1].message.contains("`font_size`"));
+        Ok(())
+    }
+
+    #[test]
+    fn reports_lua_errors() -> anyhow::Result<()> {
+        let source = "return {\n  font_size = 12 +,\n}\n";
+        let report = check_config_source(&std::env::current_dir()?, source)?;
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].line, Some(2));
+        Ok(())
+    }
+}
@@ -49,6 +49,21 @@ pub struct Palette {
     pub scrollbar_thumb: Option<RgbColor>,
     /// The color of the split line between panes
     pub split: Option<RgbColor>,
+    /// Overrides for individual slots of the 256 color palette,
+    /// keyed by the palette index.  Entries for indices 0-15
+    /// take precedence over `ansi` and `brights`.
+    #[serde(default)]
+    pub indexed: HashMap<u8, RgbColor>,
+    /// The colors of the cursor while copy mode is active.
+    /// If omitted, the normal cursor colors are used.
+    pub copy_mode_cursor_fg: Option<RgbColor>,
+    pub copy_mode_cursor_bg: Option<RgbColor>,
+    /// The colors used to highlight search matches
+    pub search_match_fg: Option<RgbColor>,
+    pub search_match_bg: Option<RgbColor>,
+    /// The colors used to highlight the currently selected search match
+    pub search_active_match_fg: Option<RgbColor>,
+    pub search_active_match_bg: Option<RgbColor>,
 }
 impl_lua_conversion!(Palette);
 
@@ -82,10 +97,49 @@ impl From<Palette> for wezterm_term::color::ColorPalette {
                 p.colors.0[idx + 8] = *col;
             }
         }
+        for (&idx, &col) in &cfg.indexed {
+            p.colors.0[idx as usize] = col;
+        }
         p
     }
 }
 
+impl From<&wezterm_term::color::ColorPalette> for Palette {
+    /// Describes a fully resolved palette; all 256 colors are
+    /// reported, with 16-255 in `indexed`
+    fn from(p: &wezterm_term::color::ColorPalette) -> Palette {
+        let mut ansi = [RgbColor::default(); 8];
+        ansi.copy_from_slice(&p.colors.0[0..8]);
+        let mut brights = [RgbColor::default(); 8];
+        brights.copy_from_slice(&p.colors.0[8..16]);
+        let indexed = (16..=255u8)
+            .map(|idx| (idx, p.colors.0[idx as usize]))
+            .collect();
+
+        Palette {
+            foreground: Some(p.foreground),
+            background: Some(p.background),
+            cursor_fg: Some(p.cursor_fg),
+            cursor_bg: Some(p.cursor_bg),
+            cursor_border: Some(p.cursor_border),
+            selection_fg: Some(p.selection_fg),
+            selection_bg: Some(p.selection_bg),
+            ansi: Some(ansi),
+            brights: Some(brights),
+            tab_bar: None,
+            scrollbar_thumb: Some(p.scrollbar_thumb),
+            split: Some(p.split),
+            indexed,
+            copy_mode_cursor_fg: None,
+            copy_mode_cursor_bg: None,
+            search_match_fg: None,
+            search_match_bg: None,
+            search_active_match_fg: None,
+            search_active_match_bg: None,
+        }
+    }
+}
+
 /// Specify the text styling for a tab in the tab bar
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct TabBarColor {
@@ -205,6 +259,21 @@ pub struct TabBarStyle {
     pub new_tab_hover_left: String,
     #[serde(default = "default_tab_right")]
     pub new_tab_hover_right: String,
+
+    /// Prefixed to the title of tabs containing panes that have
+    /// triggered monitor_pane_activity or monitor_pane_silence
+    #[serde(default = "default_tab_attention")]
+    pub tab_attention_indicator: String,
+
+    /// Shown before the title of inactive tabs that have produced
+    /// output since they were last active.  Empty by default.
+    #[serde(default)]
+    pub unseen_output_indicator: String,
+
+    /// Prefixed to the title of tabs whose active pane is
+    /// reading a password
+    #[serde(default = "default_password_input_indicator")]
+    pub password_input_indicator: String,
 }
 
 impl Default for TabBarStyle {
@@ -220,10 +289,17 @@ impl Default for TabBarStyle {
             new_tab_right: default_tab_right(),
             new_tab_hover_left: default_tab_left(),
             new_tab_hover_right: default_tab_right(),
+            tab_attention_indicator: default_tab_attention(),
+            unseen_output_indicator: String::new(),
+            password_input_indicator: default_password_input_indicator(),
         }
     }
 }
 
+fn default_password_input_indicator() -> String {
+    "🔒 ".to_string()
+}
+
 fn default_tab_left() -> String {
     format_as_escapes(vec![FormatItem::Text(" ".to_string())]).unwrap()
 }
@@ -232,6 +308,10 @@ fn default_tab_right() -> String {
     format_as_escapes(vec![FormatItem::Text(" ".to_string())]).unwrap()
 }
 
+fn default_tab_attention() -> String {
+    "* ".to_string()
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ColorSchemeFile {
     /// The color palette
@@ -128,6 +128,10 @@ pub struct FontAttributes {
     #[serde(default)]
     pub italic: bool,
     pub is_fallback: bool,
+    /// If set, the harfbuzz features to use when shaping text
+    /// with this font, in place of the global `harfbuzz_features`
+    #[serde(default)]
+    pub harfbuzz_features: Option<Vec<String>>,
 }
 impl_lua_conversion!(FontAttributes);
 
@@ -148,6 +152,7 @@ impl FontAttributes {
             bold: false,
             italic: false,
             is_fallback: false,
+            harfbuzz_features: None,
         }
     }
 
@@ -157,6 +162,7 @@ impl FontAttributes {
             bold: false,
             italic: false,
             is_fallback: true,
+            harfbuzz_features: None,
         }
     }
 }
@@ -168,6 +174,7 @@ impl Default for FontAttributes {
             bold: false,
             italic: false,
             is_fallback: false,
+            harfbuzz_features: None,
         }
     }
 }
@@ -358,6 +365,24 @@ impl Default for AllowSquareGlyphOverflow {
     }
 }
 
+/// Controls the presentation of emoji that are not followed
+/// by a variation selector
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum EmojiPresentation {
+    /// Use the default presentation defined by unicode
+    Unicode,
+    /// Display them as text, occupying a single cell
+    Text,
+    /// Display them as emoji, occupying two cells
+    Emoji,
+}
+
+impl Default for EmojiPresentation {
+    fn default() -> Self {
+        Self::Unicode
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum FontLocatorSelection {
     /// Use fontconfig APIs to resolve fonts (!macos, posix systems)
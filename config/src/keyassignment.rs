@@ -1,5 +1,5 @@
 use crate::configuration;
-use crate::LeaderKey;
+use crate::{KeyCondition, LeaderKey};
 use luahelper::impl_lua_conversion;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -75,6 +75,23 @@ impl Default for SpawnTabDomain {
     }
 }
 
+/// What ActivateLinkHintMode does with the chosen link
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum LinkHintAction {
+    /// Open the link, as though it had been clicked
+    Open,
+    /// Copy the link to the clipboard
+    CopyTo(ClipboardCopyDestination),
+}
+
+impl_lua_conversion!(LinkHintAction);
+
+impl Default for LinkHintAction {
+    fn default() -> Self {
+        Self::Open
+    }
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct SpawnCommand {
     /// Optional descriptive label
@@ -101,6 +118,12 @@ pub struct SpawnCommand {
 
     #[serde(default)]
     pub domain: SpawnTabDomain,
+
+    /// When spawning a new window, the X11 WM_CLASS, Wayland app_id
+    /// or Windows window class to use for that window, which allows
+    /// window manager rules to distinguish it from other wezterm windows.
+    /// Ignored when spawning tabs and panes.
+    pub class: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
@@ -130,18 +153,90 @@ pub enum ClipboardCopyDestination {
     ClipboardAndPrimarySelection,
 }
 
+impl_lua_conversion!(ClipboardCopyDestination);
+
 impl Default for ClipboardCopyDestination {
     fn default() -> Self {
         Self::ClipboardAndPrimarySelection
     }
 }
 
+/// Controls which representations of the selection are
+/// placed in the clipboard by CopyTextTo.  The plain text
+/// is always included.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CopyTextFormat {
+    PlainText,
+    Html,
+    Rtf,
+    HtmlAndRtf,
+}
+
+impl_lua_conversion!(CopyTextFormat);
+
+impl Default for CopyTextFormat {
+    fn default() -> Self {
+        Self::HtmlAndRtf
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CopyText {
+    #[serde(default)]
+    pub format: CopyTextFormat,
+    #[serde(default)]
+    pub destination: ClipboardCopyDestination,
+}
+
+impl_lua_conversion!(CopyText);
+
+/// The file format produced by ExportPane
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PaneExportFormat {
+    Html,
+    Svg,
+}
+
+impl_lua_conversion!(PaneExportFormat);
+
+impl Default for PaneExportFormat {
+    fn default() -> Self {
+        Self::Html
+    }
+}
+
+impl PaneExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Svg => "svg",
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PaneExport {
+    #[serde(default)]
+    pub format: PaneExportFormat,
+    /// Include the scrollback rather than just the viewport
+    #[serde(default)]
+    pub scrollback: bool,
+    /// Where to write the file.  If omitted, a file named
+    /// after the pane is created in the home directory.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl_lua_conversion!(PaneExport);
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ClipboardPasteSource {
     Clipboard,
     PrimarySelection,
 }
 
+impl_lua_conversion!(ClipboardPasteSource);
+
 impl Default for ClipboardPasteSource {
     fn default() -> Self {
         Self::Clipboard
@@ -155,6 +250,8 @@ pub enum KeyAssignment {
     ToggleFullScreen,
     Copy,
     CopyTo(ClipboardCopyDestination),
+    CopyTextTo(CopyText),
+    ExportPane(PaneExport),
     Paste,
     PastePrimarySelection,
     PasteFrom(ClipboardPasteSource),
@@ -163,6 +260,9 @@ pub enum KeyAssignment {
     DecreaseFontSize,
     ResetFontSize,
     ResetFontAndWindowSize,
+    IncreasePresentationZoom,
+    DecreasePresentationZoom,
+    ResetPresentationZoom,
     ActivateTab(isize),
     SendString(String),
     Nop,
@@ -184,27 +284,162 @@ pub enum KeyAssignment {
     SplitHorizontal(SpawnCommand),
     SplitVertical(SpawnCommand),
     ShowLauncher,
+    ShowDebugOverlay,
     ClearScrollback(ScrollbackEraseMode),
     Search(Pattern),
     ActivateCopyMode,
+    ActivateLinkHintMode(LinkHintAction),
 
     SelectTextAtMouseCursor(SelectionMode),
     ExtendSelectionToMouseCursor(Option<SelectionMode>),
     OpenLinkAtMouseCursor,
     CompleteSelection(ClipboardCopyDestination),
     CompleteSelectionOrOpenLinkAtMouseCursor(ClipboardCopyDestination),
+    MoveCursorToMouseCursor,
     StartWindowDrag,
 
     AdjustPaneSize(PaneDirection, usize),
     ActivatePaneDirection(PaneDirection),
     TogglePaneZoomState,
+    TogglePaneIme,
+    ToggleOutputMasking,
+    TogglePaneReadOnly,
     CloseCurrentPane { confirm: bool },
     EmitEvent(String),
+    ShowKeybindingHelp,
+    AddBookmark,
+    ShowBookmarks,
+    ToggleLineTimestamps,
+    TogglePaneOutputPaused,
+    ToggleDebugHud,
 }
 impl_lua_conversion!(KeyAssignment);
 
+impl KeyAssignment {
+    /// Returns the heading under which the assignment is
+    /// listed by ShowKeybindingHelp
+    pub fn category(&self) -> &'static str {
+        use KeyAssignment::*;
+        match self {
+            Copy
+            | CopyTo(_)
+            | CopyTextTo(_)
+            | ExportPane(_)
+            | Paste
+            | PastePrimarySelection
+            | PasteFrom(_) => "Clipboard",
+            SpawnTab(_)
+            | SpawnCommandInNewTab(_)
+            | ActivateTabRelative(_)
+            | ActivateTab(_)
+            | CloseCurrentTab { .. }
+            | MoveTabRelative(_)
+            | MoveTab(_)
+            | ShowTabNavigator => "Tabs",
+            SplitHorizontal(_)
+            | SplitVertical(_)
+            | AdjustPaneSize(..)
+            | ActivatePaneDirection(_)
+            | TogglePaneZoomState
+            | TogglePaneIme
+            | ToggleOutputMasking
+            | TogglePaneReadOnly
+            | TogglePaneOutputPaused
+            | CloseCurrentPane { .. } => "Panes",
+            SpawnWindow
+            | SpawnCommandInNewWindow(_)
+            | ToggleFullScreen
+            | Hide
+            | Show
+            | HideApplication
+            | QuitApplication
+            | StartWindowDrag => "Windows",
+            IncreaseFontSize
+            | DecreaseFontSize
+            | ResetFontSize
+            | ResetFontAndWindowSize
+            | IncreasePresentationZoom
+            | DecreasePresentationZoom
+            | ResetPresentationZoom => "Font Size",
+            ScrollByPage(_) | ScrollByLine(_) | ScrollToPrompt(_) | ClearScrollback(_)
+            | AddBookmark | ShowBookmarks | ToggleLineTimestamps => "Scrollback",
+            Search(_)
+            | ActivateCopyMode
+            | ActivateLinkHintMode(_)
+            | SelectTextAtMouseCursor(_)
+            | ExtendSelectionToMouseCursor(_)
+            | OpenLinkAtMouseCursor
+            | CompleteSelection(_)
+            | CompleteSelectionOrOpenLinkAtMouseCursor(_)
+            | MoveCursorToMouseCursor => "Search and Selection",
+            SendString(_)
+            | Nop
+            | DisableDefaultAssignment
+            | ReloadConfiguration
+            | ShowLauncher
+            | ShowDebugOverlay
+            | ToggleDebugHud
+            | ShowKeybindingHelp
+            | EmitEvent(_) => "Miscellaneous",
+        }
+    }
+}
+
+/// A key binding from the InputMap, described so that it
+/// can be displayed by ShowKeybindingHelp
+#[derive(Debug, Clone)]
+pub struct KeyBindingDescription {
+    pub category: &'static str,
+    /// The key press, eg: `CTRL|SHIFT+T`
+    pub key: String,
+    pub action: String,
+    /// Describes the `when` condition of the binding, if any
+    pub condition: Option<String>,
+}
+
+fn describe_key_press(key: &KeyCode, mods: Modifiers) -> String {
+    let mut parts = vec![];
+    for (flag, name) in &[
+        (Modifiers::LEADER, "LEADER"),
+        (Modifiers::SUPER, "SUPER"),
+        (Modifiers::CTRL, "CTRL"),
+        (Modifiers::ALT, "ALT"),
+        (Modifiers::SHIFT, "SHIFT"),
+    ] {
+        if mods.contains(*flag) {
+            parts.push(name.to_string());
+        }
+    }
+    let key = match key {
+        KeyCode::Char('\n') => "Enter".to_string(),
+        KeyCode::Char('\r') => "Return".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        key => format!("{:?}", key),
+    };
+    if parts.is_empty() {
+        key
+    } else {
+        format!("{}+{}", parts.join("|"), key)
+    }
+}
+
+fn describe_condition(condition: &KeyCondition) -> String {
+    let mut parts = vec![];
+    if !condition.foreground_process.is_empty() {
+        parts.push(format!("in {}", condition.foreground_process.join(", ")));
+    }
+    if !condition.not_foreground_process.is_empty() {
+        parts.push(format!(
+            "not in {}",
+            condition.not_foreground_process.join(", ")
+        ));
+    }
+    parts.join("; ")
+}
+
 pub struct InputMap {
     keys: HashMap<(KeyCode, Modifiers), KeyAssignment>,
+    conditional_keys: HashMap<(KeyCode, Modifiers), Vec<(KeyCondition, KeyAssignment)>>,
     mouse: HashMap<(MouseEventTrigger, Modifiers), KeyAssignment>,
     leader: Option<LeaderKey>,
 }
@@ -215,6 +450,7 @@ impl InputMap {
         let mut mouse = config.mouse_bindings();
 
         let mut keys = config.key_bindings();
+        let conditional_keys = config.conditional_key_bindings();
 
         let leader = config.leader.clone();
 
@@ -236,6 +472,8 @@ impl InputMap {
         use KeyAssignment::*;
 
         let ctrl_shift = Modifiers::CTRL | Modifiers::SHIFT;
+        let keyboard_copy = config.keyboard_copy_destination;
+        let mouse_copy = config.mouse_selection_copy_destination;
 
         if !config.disable_default_key_bindings {
             // Apply the default bindings; if the user has already mapped
@@ -252,21 +490,13 @@ impl InputMap {
                     KeyCode::Insert,
                     CopyTo(ClipboardCopyDestination::PrimarySelection)
                 ],
-                [
-                    Modifiers::SUPER,
-                    KeyCode::Char('c'),
-                    CopyTo(ClipboardCopyDestination::Clipboard)
-                ],
+                [Modifiers::SUPER, KeyCode::Char('c'), CopyTo(keyboard_copy)],
                 [
                     Modifiers::SUPER,
                     KeyCode::Char('v'),
                     PasteFrom(ClipboardPasteSource::Clipboard)
                 ],
-                [
-                    Modifiers::CTRL,
-                    KeyCode::Char('C'),
-                    CopyTo(ClipboardCopyDestination::Clipboard)
-                ],
+                [Modifiers::CTRL, KeyCode::Char('C'), CopyTo(keyboard_copy)],
                 [
                     Modifiers::CTRL,
                     KeyCode::Char('V'),
@@ -378,6 +608,12 @@ impl InputMap {
                 [Modifiers::SHIFT, KeyCode::PageDown, ScrollByPage(1)],
                 [Modifiers::ALT, KeyCode::Char('9'), ShowTabNavigator],
                 [Modifiers::CTRL, KeyCode::Char('X'), ActivateCopyMode],
+                [
+                    Modifiers::CTRL,
+                    KeyCode::Char('O'),
+                    ActivateLinkHintMode(LinkHintAction::Open)
+                ],
+                [Modifiers::CTRL, KeyCode::Char('L'), ShowDebugOverlay],
                 [
                     Modifiers::CTRL | Modifiers::ALT | Modifiers::SHIFT,
                     KeyCode::Char('"'),
@@ -478,15 +714,21 @@ impl InputMap {
                     },
                     ExtendSelectionToMouseCursor(None)
                 ],
+                [
+                    Modifiers::ALT,
+                    MouseEventTrigger::Down {
+                        streak: 1,
+                        button: MouseButton::Left
+                    },
+                    MoveCursorToMouseCursor
+                ],
                 [
                     Modifiers::NONE,
                     MouseEventTrigger::Up {
                         streak: 1,
                         button: MouseButton::Left
                     },
-                    CompleteSelectionOrOpenLinkAtMouseCursor(
-                        ClipboardCopyDestination::PrimarySelection
-                    )
+                    CompleteSelectionOrOpenLinkAtMouseCursor(mouse_copy)
                 ],
                 [
                     Modifiers::NONE,
@@ -494,7 +736,7 @@ impl InputMap {
                         streak: 2,
                         button: MouseButton::Left
                     },
-                    CompleteSelection(ClipboardCopyDestination::PrimarySelection)
+                    CompleteSelection(mouse_copy)
                 ],
                 [
                     Modifiers::NONE,
@@ -502,7 +744,7 @@ impl InputMap {
                         streak: 3,
                         button: MouseButton::Left
                     },
-                    CompleteSelection(ClipboardCopyDestination::PrimarySelection)
+                    CompleteSelection(mouse_copy)
                 ],
                 [
                     Modifiers::NONE,
@@ -560,6 +802,7 @@ impl InputMap {
 
         Self {
             keys,
+            conditional_keys,
             leader,
             mouse,
         }
@@ -580,10 +823,58 @@ impl InputMap {
         mods - (Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT)
     }
 
-    pub fn lookup_key(&self, key: &KeyCode, mods: Modifiers) -> Option<KeyAssignment> {
-        self.keys
-            .get(&key.normalize_shift(Self::remove_positional_alt(mods)))
-            .cloned()
+    /// Returns true if any of the key assignments depend on
+    /// the foreground process of the pane
+    pub fn has_conditional_keys(&self) -> bool {
+        !self.conditional_keys.is_empty()
+    }
+
+    /// Looks up the assignment for a key press.  The first conditional
+    /// assignment whose condition is satisfied by `process`, the name of
+    /// the foreground process, takes precedence over any other assignment.
+    pub fn lookup_key(
+        &self,
+        key: &KeyCode,
+        mods: Modifiers,
+        process: Option<&str>,
+    ) -> Option<KeyAssignment> {
+        let key = key.normalize_shift(Self::remove_positional_alt(mods));
+        if let Some(candidates) = self.conditional_keys.get(&key) {
+            for (condition, assignment) in candidates {
+                if condition.matches(process) {
+                    return Some(assignment.clone());
+                }
+            }
+        }
+        self.keys.get(&key).cloned()
+    }
+
+    /// Describes each of the key bindings, including the
+    /// conditional ones, ordered by category and then by key
+    pub fn describe_keys(&self) -> Vec<KeyBindingDescription> {
+        let mut bindings: Vec<KeyBindingDescription> = self
+            .keys
+            .iter()
+            .map(|((key, mods), assignment)| (key, mods, assignment, None))
+            .chain(
+                self.conditional_keys
+                    .iter()
+                    .flat_map(|((key, mods), candidates)| {
+                        candidates.iter().map(move |(condition, assignment)| {
+                            (key, mods, assignment, Some(condition))
+                        })
+                    }),
+            )
+            .map(|(key, mods, assignment, condition)| KeyBindingDescription {
+                category: assignment.category(),
+                key: describe_key_press(key, *mods),
+                action: format!("{:?}", assignment),
+                condition: condition.map(describe_condition),
+            })
+            .collect();
+        bindings
+            .sort_by(|a, b| (a.category, &a.key, &a.action).cmp(&(b.category, &b.key, &b.action)));
+        bindings
     }
 
     pub fn lookup_mouse(&self, event: MouseEventTrigger, mods: Modifiers) -> Option<KeyAssignment> {
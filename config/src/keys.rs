@@ -11,9 +11,39 @@ pub struct Key {
     #[serde(deserialize_with = "de_modifiers", default)]
     pub mods: Modifiers,
     pub action: KeyAssignment,
+    /// If set, the assignment only applies to panes that
+    /// satisfy the condition
+    #[serde(default)]
+    pub when: Option<KeyCondition>,
 }
 impl_lua_conversion!(Key);
 
+/// Restricts a key assignment based on the name of the
+/// foreground process in the active pane
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct KeyCondition {
+    /// The foreground process must have one of these names
+    #[serde(default)]
+    pub foreground_process: Vec<String>,
+    /// The foreground process must not have any of these names
+    #[serde(default)]
+    pub not_foreground_process: Vec<String>,
+}
+impl_lua_conversion!(KeyCondition);
+
+impl KeyCondition {
+    /// `process` is the name of the foreground process, which
+    /// is None if it cannot be determined
+    pub fn matches(&self, process: Option<&str>) -> bool {
+        let is_one_of = |names: &[String]| match process {
+            Some(process) => names.iter().any(|name| name == process),
+            None => false,
+        };
+        (self.foreground_process.is_empty() || is_one_of(&self.foreground_process))
+            && !is_one_of(&self.not_foreground_process)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LeaderKey {
     #[serde(deserialize_with = "de_keycode")]
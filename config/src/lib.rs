@@ -1,6 +1,8 @@
 //! Configuration for the gui portion of the terminal
 
-use crate::keyassignment::{KeyAssignment, MouseEventTrigger, SpawnCommand};
+use crate::keyassignment::{
+    ClipboardCopyDestination, KeyAssignment, MouseEventTrigger, SpawnCommand,
+};
 use anyhow::{anyhow, bail, Context, Error};
 use lazy_static::lazy_static;
 use luahelper::impl_lua_conversion;
@@ -26,6 +28,7 @@ use termwiz::surface::CursorShape;
 use toml;
 use wezterm_input_types::{KeyCode, Modifiers, WindowDecorations};
 
+pub mod check;
 mod color;
 mod daemon;
 mod font;
@@ -33,6 +36,7 @@ mod frontend;
 pub mod keyassignment;
 mod keys;
 pub mod lua;
+mod plugin;
 mod ssh;
 mod terminal;
 mod tls;
@@ -55,8 +59,15 @@ type ErrorCallback = fn(&str);
 
 lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
-    pub static ref CONFIG_DIR: PathBuf = xdg_config_home();
+    pub static ref PORTABLE_DIR: Option<PathBuf> = compute_portable_dir();
+    pub static ref CONFIG_DIR: PathBuf = match PORTABLE_DIR.as_ref() {
+        Some(dir) => dir.clone(),
+        None => xdg_config_home(),
+    };
     pub static ref RUNTIME_DIR: PathBuf = compute_runtime_dir().unwrap();
+    pub static ref PLUGIN_DIR: PathBuf = CONFIG_DIR.join("plugins");
+    pub static ref CACHE_DIR: PathBuf = compute_cache_dir();
+    pub static ref STATE_DIR: PathBuf = compute_state_dir();
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
@@ -274,13 +285,100 @@ pub fn create_user_owned_dirs(p: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Returns the path held by the environment variable `name`.
+/// The XDG base directory specification requires that relative
+/// paths be ignored, and we apply the same rule to our own
+/// overrides
+fn absolute_path_from_env(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
 fn xdg_config_home() -> PathBuf {
-    match std::env::var_os("XDG_CONFIG_HOME").map(|s| PathBuf::from(s).join("wezterm")) {
+    match absolute_path_from_env("XDG_CONFIG_HOME").map(|p| p.join("wezterm")) {
         Some(p) => p,
         None => HOME_DIR.join(".config").join("wezterm"),
     }
 }
 
+/// Files that can be regenerated if they are lost
+fn compute_cache_dir() -> PathBuf {
+    if let Some(dir) = absolute_path_from_env("WEZTERM_CACHE_DIR") {
+        return dir;
+    }
+    if let Some(portable) = PORTABLE_DIR.as_ref() {
+        return portable.join("cache");
+    }
+    // This respects XDG_CACHE_HOME on posix systems, and uses
+    // the platform conventions on macOS and Windows
+    match dirs_next::cache_dir() {
+        Some(dir) => dir.join("wezterm"),
+        None => HOME_DIR.join(".cache").join("wezterm"),
+    }
+}
+
+/// Files that should persist across restarts, but that are not
+/// important or portable enough to belong with the config
+fn compute_state_dir() -> PathBuf {
+    if let Some(dir) = absolute_path_from_env("WEZTERM_STATE_DIR") {
+        return dir;
+    }
+    if let Some(portable) = PORTABLE_DIR.as_ref() {
+        return portable.join("state");
+    }
+    if let Some(dir) = absolute_path_from_env("XDG_STATE_HOME") {
+        return dir.join("wezterm");
+    }
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        return HOME_DIR.join(".local").join("state").join("wezterm");
+    }
+    match dirs_next::data_local_dir() {
+        Some(dir) => dir.join("wezterm"),
+        None => HOME_DIR.join(".local").join("state").join("wezterm"),
+    }
+}
+
+/// The name of a file that, when present alongside the executable,
+/// causes wezterm to run in portable mode
+const PORTABLE_MARKER_FILE: &str = "wezterm-portable";
+
+/// Determines whether we are running in portable mode, in which the
+/// configuration, color schemes and runtime state live in a single
+/// directory rather than in the home directory of the user.
+/// That directory is specified by the WEZTERM_PORTABLE_DIR environment
+/// variable, or is the directory containing the executable if that
+/// directory contains the marker file.
+fn compute_portable_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("WEZTERM_PORTABLE_DIR") {
+        if !dir.is_empty() {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    let exe = std::env::current_exe().ok()?;
+    let exe_dir = exe.parent()?;
+    if exe_dir.join(PORTABLE_MARKER_FILE).exists() {
+        Some(exe_dir.to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// Enables portable mode using the directory that holds the
+/// executable, for this process and for any wezterm processes
+/// that it spawns.
+/// This must be called before anything uses the directories
+/// that portable mode relocates, such as CONFIG_DIR, so before
+/// `env_bootstrap::bootstrap`.
+pub fn enable_portable_mode() -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let exe_dir = exe
+        .parent()
+        .ok_or_else(|| anyhow!("executable {} has no parent dir", exe.display()))?;
+    std::env::set_var("WEZTERM_PORTABLE_DIR", exe_dir);
+    Ok(())
+}
+
 pub fn set_config_file_override(path: &Path) {
     CONFIG_FILE_OVERRIDE
         .lock()
@@ -635,9 +733,20 @@ pub struct Config {
     #[serde(default)]
     pub allow_square_glyphs_to_overflow_width: AllowSquareGlyphOverflow,
 
+    /// Selects the presentation of emoji that are output without
+    /// a variation selector, which determines both the font that is
+    /// used to display them and whether they are one or two cells wide
+    #[serde(default)]
+    pub emoji_presentation: EmojiPresentation,
+
     #[serde(default)]
     pub window_decorations: WindowDecorations,
 
+    /// The X11 WM_CLASS, Wayland app_id or Windows window class
+    /// to use for gui windows.  Can be overridden by `wezterm start --class`
+    /// and, for individual windows, by `SpawnCommand::class`
+    pub window_class: Option<String>,
+
     /// When using FontKitXXX font systems, a set of directories to
     /// search ahead of the standard font locations for fonts.
     /// Relative paths are taken to be relative to the directory
@@ -672,6 +781,17 @@ pub struct Config {
     #[serde(default)]
     pub tab_bar_style: TabBarStyle,
 
+    /// Controls the titles that are derived from the foreground
+    /// process and working directory of panes that haven't set
+    /// a title of their own
+    #[serde(default)]
+    pub tab_auto_title: TabAutoTitle,
+
+    /// When true, show a mask character in the tab bar for each
+    /// key typed while the active pane is reading a password
+    #[serde(default)]
+    pub password_input_feedback: bool,
+
     #[serde(skip)]
     pub resolved_palette: Palette,
 
@@ -683,6 +803,12 @@ pub struct Config {
     #[serde(default)]
     pub color_schemes: HashMap<String, Palette>,
 
+    /// When the colors change because the configuration is reloaded,
+    /// such as when switching color_scheme, the change is animated over
+    /// this many milliseconds.  0 applies the new colors immediately.
+    #[serde(default)]
+    pub color_scheme_transition_duration_ms: u64,
+
     /// How many lines of scrollback you want to retain
     #[serde(default = "default_scrollback_lines")]
     pub scrollback_lines: usize,
@@ -726,6 +852,22 @@ pub struct Config {
     #[serde(default = "default_hyperlink_rules")]
     pub hyperlink_rules: Vec<hyperlink::Rule>,
 
+    /// Regular expressions matching sensitive text, such as access
+    /// tokens, that is masked when it is displayed
+    #[serde(default)]
+    pub mask_patterns: Vec<String>,
+
+    /// The command, and its arguments, used to open a file location
+    /// from the search results; `{path}` and `{line}` are replaced by
+    /// the location.  When not set, the file is opened as a link.
+    #[serde(default)]
+    pub editor_command: Option<Vec<String>>,
+
+    /// A regular expression with `path` and `line` named captures
+    /// that recognizes file locations in the search results
+    #[serde(default = "default_editor_location_pattern")]
+    pub editor_location_pattern: String,
+
     /// What to set the TERM variable to
     #[serde(default = "default_term")]
     pub term: String,
@@ -752,6 +894,36 @@ pub struct Config {
     /// See https://freetype.org/freetype2/docs/subpixel-hinting.html
     pub freetype_interpreter_version: Option<u32>,
 
+    /// The number of horizontal positions within a pixel at which glyphs
+    /// are rasterized, so that glyphs placed at fractional offsets don't
+    /// snap to whole pixels.  0 or 1 disables sub-pixel positioning.
+    #[serde(default = "default_glyph_subpixel_positions")]
+    pub glyph_subpixel_positions: u8,
+
+    /// When enabled, rasterized glyphs are stored in the cache directory
+    /// so that they don't need to be rasterized again the next time that
+    /// wezterm is started.
+    #[serde(default)]
+    pub glyph_disk_cache: bool,
+
+    /// When enabled, glyphs are rasterized on a worker thread and
+    /// are drawn blank until they are ready, rather than delaying
+    /// the frame in which they first appear.
+    #[serde(default)]
+    pub async_glyph_rasterization: bool,
+
+    /// When enabled, the glyphs of printable ASCII and of the ranges
+    /// in glyph_cache_prewarm_ranges are rasterized in the background
+    /// once the fonts have been resolved, so that they are ready by
+    /// the time that they are first drawn.
+    #[serde(default)]
+    pub glyph_cache_prewarm: bool,
+
+    /// Additional codepoints whose glyphs are rasterized when
+    /// glyph_cache_prewarm is enabled
+    #[serde(default)]
+    pub glyph_cache_prewarm_ranges: Vec<CodepointRange>,
+
     /// Specify the features to enable when using harfbuzz for font shaping.
     /// There is some light documentation here:
     /// <https://harfbuzz.github.io/shaping-opentype-features.html>
@@ -788,6 +960,11 @@ pub struct Config {
     #[serde(default = "default_harfbuzz_features")]
     pub harfbuzz_features: Vec<String>,
 
+    /// Where ligatures should not be formed, even though they
+    /// are enabled by the font and `harfbuzz_features`
+    #[serde(default)]
+    pub disable_ligatures: Vec<DisableLigatures>,
+
     #[serde(default)]
     pub front_end: FrontEndSelection,
 
@@ -882,6 +1059,22 @@ pub struct Config {
     #[serde(default)]
     pub enable_scroll_bar: bool,
 
+    /// If true, show how many lines are below the viewport in the
+    /// top right corner of a pane while it is scrolled back
+    #[serde(default)]
+    pub show_scroll_indicator: bool,
+
+    /// If true, the scroll indicator also shows when the pane has
+    /// produced output since it was scrolled back
+    #[serde(default = "default_true")]
+    pub scroll_indicator_shows_new_output: bool,
+
+    /// If true, draw a marker in the left window padding next to
+    /// the rows that continue a line that was wrapped because it
+    /// was too long to fit in the width of the pane
+    #[serde(default)]
+    pub show_wrap_indicator: bool,
+
     /// If false, do not try to use a Wayland protocol connection
     /// when starting the gui frontend, and instead use X11.
     /// This option is only considered on X11/Wayland systems and
@@ -900,6 +1093,42 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub custom_block_glyphs: bool,
 
+    /// When custom_block_glyphs is enabled, only the glyphs in these
+    /// ranges are computed; the others are taken from the font
+    #[serde(default = "default_custom_block_glyph_ranges")]
+    pub custom_block_glyph_ranges: Vec<CustomBlockGlyphRange>,
+
+    /// Scales the thickness of the lines of underlines and of the
+    /// computed box drawing and Powerline glyphs, relative to the
+    /// underline thickness of the font.  If unset, the lines are
+    /// heavier when the font is bold.
+    #[serde(default)]
+    pub custom_block_glyph_stroke_scale: Option<f64>,
+
+    /// The distance, in pixels, by which the computed glyphs
+    /// extend into the adjacent cells, in order to hide the seams
+    /// between them that can appear with fractional scaling
+    #[serde(default)]
+    pub custom_block_glyph_bleed: f32,
+
+    /// The computed glyphs that have curved or diagonal edges, and
+    /// the curly underline, are drawn at this multiple of the cell
+    /// size and then scaled down, to smooth their edges.  1 disables
+    /// the supersampling.
+    #[serde(default = "default_custom_block_glyph_supersample")]
+    pub custom_block_glyph_supersample: u8,
+
+    /// The maximum number of rasterized glyphs that are cached by
+    /// each window.  The least recently used glyphs are evicted
+    /// when the cache is full.
+    #[serde(default = "default_glyph_cache_size")]
+    pub glyph_cache_size: usize,
+
+    /// How the shade characters are drawn when custom_block_glyphs
+    /// is enabled
+    #[serde(default)]
+    pub shade_block_style: ShadeBlockStyle,
+
     /// Controls the amount of padding to use around the terminal cell area
     #[serde(default)]
     pub window_padding: WindowPadding,
@@ -915,6 +1144,16 @@ pub struct Config {
     pub window_background_image: Option<PathBuf>,
     #[serde(default)]
     pub window_background_image_hsb: Option<HsbTransform>,
+
+    /// Specifies the path to a GLSL fragment shader that is rendered
+    /// over the window background, beneath the text.  The shader
+    /// receives `time` and `resolution` uniforms.
+    #[serde(default)]
+    pub window_background_shader: Option<PathBuf>,
+    /// How many times per second to redraw the window while a
+    /// window_background_shader is animating.  0 disables animation.
+    #[serde(default = "default_window_background_shader_fps")]
+    pub window_background_shader_fps: u8,
     #[serde(default)]
     pub foreground_text_hsb: HsbTransform,
 
@@ -931,6 +1170,12 @@ pub struct Config {
     #[serde(default = "default_one_point_oh")]
     pub window_background_opacity: f32,
 
+    /// The opacity of the tint that is applied to the background of
+    /// panes that have a background tint, which is set either by the
+    /// WEZTERM_BACKGROUND_TINT user var or by pane:set_background_tint
+    #[serde(default = "default_background_tint_opacity")]
+    pub background_tint_opacity: f32,
+
     /// inactive_pane_hue, inactive_pane_saturation and
     /// inactive_pane_brightness allow for transforming the color
     /// of inactive panes.
@@ -963,6 +1208,12 @@ pub struct Config {
     #[serde(default = "default_inactive_pane_hsb")]
     pub inactive_pane_hsb: HsbTransform,
 
+    /// Specifies how the panes of a remote domain are adjusted
+    /// while the connection to it is lost or unresponsive.
+    /// This replaces inactive_pane_hsb for those panes.
+    #[serde(default = "default_unresponsive_pane_hsb")]
+    pub unresponsive_pane_hsb: HsbTransform,
+
     #[serde(default = "default_one_point_oh")]
     pub text_background_opacity: f32,
 
@@ -999,6 +1250,35 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub scroll_to_bottom_on_input: bool,
 
+    /// If true, scroll to the bottom of the terminal when the
+    /// pane produces output.
+    /// The default is to keep the scrollback position.
+    #[serde(default)]
+    pub scroll_to_bottom_on_output: bool,
+
+    /// If true, erase the shell prompt, as marked by OSC 133 semantic
+    /// zones, when the terminal is resized to a different width while
+    /// the shell is waiting at that prompt, so that the prompt that the
+    /// shell redraws doesn't leave a rewrapped copy behind it.
+    #[serde(default)]
+    pub clear_prompt_on_resize: bool,
+
+    /// If true, copy the contents of the alternate screen into the
+    /// scrollback when an application switches back to the primary
+    /// screen, so that the output of full screen applications isn't lost
+    #[serde(default)]
+    pub capture_alternate_screen: bool,
+
+    /// If true, record the time at which output first arrives on each
+    /// line, so that it can be displayed by hovering over the line or
+    /// by using ToggleLineTimestamps
+    #[serde(default)]
+    pub record_line_timestamps: bool,
+
+    /// The strftime style format used to display line timestamps
+    #[serde(default = "default_line_timestamp_format")]
+    pub line_timestamp_format: String,
+
     #[serde(default)]
     pub use_ime: bool,
     #[serde(default = "default_true")]
@@ -1026,6 +1306,19 @@ pub struct Config {
     #[serde(default = "default_update_interval")]
     pub check_for_updates_interval_seconds: u64,
 
+    /// When set to true, a panic in the gui writes a crash report
+    /// with a backtrace, recent log records and a summary of the
+    /// configuration to a local file, and its location is shown
+    /// the next time that wezterm starts.
+    #[serde(default)]
+    pub enable_crash_reports: bool,
+
+    /// When set to true, `wezterm start` asks a gui that is already
+    /// running to open a new window, rather than starting another
+    /// gui process
+    #[serde(default)]
+    pub single_instance: bool,
+
     /// When set to true, use the CSI-U encoding scheme as described
     /// in http://www.leonerd.org.uk/hacks/fixterms/
     /// This is off by default because @wez and @jsgf find the shift-space
@@ -1042,15 +1335,83 @@ pub struct Config {
     #[serde(default = "default_word_boundary")]
     pub selection_word_boundary: String,
 
+    /// Controls how text carrying an explicit hyperlink is
+    /// represented when the selection is copied
+    #[serde(default)]
+    pub hyperlink_copy_format: HyperlinkCopyFormat,
+
+    /// Controls whether lua code is permitted to read and/or
+    /// write the clipboard via `window:get_clipboard` and
+    /// `window:set_clipboard`.
+    /// Reading requires explicitly opting in, as it allows
+    /// the config to observe anything that you have copied.
+    #[serde(default)]
+    pub lua_clipboard_access: LuaClipboardAccess,
+
+    /// Where the default mouse bindings place the text when a
+    /// selection is completed by releasing the mouse button.
+    #[serde(default = "default_mouse_selection_copy_destination")]
+    pub mouse_selection_copy_destination: ClipboardCopyDestination,
+
+    /// Where the default copy key bindings (CTRL-SHIFT-C, CMD-C)
+    /// place the selected text.
+    #[serde(default = "default_keyboard_copy_destination")]
+    pub keyboard_copy_destination: ClipboardCopyDestination,
+
     #[serde(default = "default_true")]
     pub adjust_window_size_when_changing_font_size: bool,
 
+    /// The number of lines that the viewport is scrolled by
+    /// for each tick of the mouse wheel
+    #[serde(default = "default_mouse_wheel_scroll_lines")]
+    pub mouse_wheel_scroll_lines: f64,
+
+    /// How much faster the viewport scrolls for each wheel event
+    /// that follows the previous one in quick succession.
+    /// The default of 0 disables acceleration.
+    #[serde(default)]
+    pub mouse_wheel_scroll_acceleration: f64,
+
+    /// The number of pixels that a trackpad reporting precise
+    /// scroll deltas has to move to scroll by one wheel tick
+    #[serde(default = "default_trackpad_scroll_pixels_per_line")]
+    pub trackpad_scroll_pixels_per_line: f64,
+
     #[serde(default = "default_alternate_buffer_wheel_scroll_speed")]
     pub alternate_buffer_wheel_scroll_speed: u8,
 
     #[serde(default = "default_status_update_interval")]
     pub status_update_interval: u64,
 
+    /// How long, in milliseconds, to accumulate output from panes
+    /// that are not currently visible before applying it to the
+    /// terminal model.  Larger values reduce the cost of having
+    /// many busy panes in background tabs.  0 disables throttling.
+    #[serde(default = "default_invisible_pane_update_interval")]
+    pub invisible_pane_update_interval: u64,
+
+    /// The number of actions, which is roughly the number of characters,
+    /// of output that may be held back while the output of a pane is
+    /// paused using TogglePaneOutputPaused.  When more than this is
+    /// held back, the output is resumed automatically.
+    #[serde(default = "default_pause_output_limit")]
+    pub pause_output_limit: usize,
+
+    /// When true, notify when a pane that you are not looking at
+    /// produces output.
+    #[serde(default)]
+    pub monitor_pane_activity: bool,
+
+    /// When non-zero, notify when a pane that you are not looking at
+    /// has produced no output for this many seconds after having
+    /// previously produced output.
+    #[serde(default)]
+    pub monitor_pane_silence: u64,
+
+    /// Controls how pane activity and silence are reported
+    #[serde(default)]
+    pub pane_monitor_notification: PaneMonitorNotification,
+
     #[serde(default)]
     pub experimental_shape_post_processing: bool,
 
@@ -1066,14 +1427,46 @@ fn default_stateless_process_list() -> Vec<String> {
         .collect()
 }
 
+fn default_background_tint_opacity() -> f32 {
+    0.15
+}
+
+fn default_window_background_shader_fps() -> u8 {
+    10
+}
+
 fn default_status_update_interval() -> u64 {
     1_000
 }
 
+fn default_invisible_pane_update_interval() -> u64 {
+    100
+}
+
+fn default_pause_output_limit() -> usize {
+    1_000_000
+}
+
+fn default_mouse_wheel_scroll_lines() -> f64 {
+    1.0
+}
+
+fn default_trackpad_scroll_pixels_per_line() -> f64 {
+    15.0
+}
+
 fn default_alternate_buffer_wheel_scroll_speed() -> u8 {
     3
 }
 
+fn default_mouse_selection_copy_destination() -> ClipboardCopyDestination {
+    ClipboardCopyDestination::PrimarySelection
+}
+
+fn default_keyboard_copy_destination() -> ClipboardCopyDestination {
+    ClipboardCopyDestination::Clipboard
+}
+
 fn default_word_boundary() -> String {
     " \t\n{[}]()\"'`".to_string()
 }
@@ -1102,6 +1495,14 @@ fn default_inactive_pane_hsb() -> HsbTransform {
     }
 }
 
+fn default_unresponsive_pane_hsb() -> HsbTransform {
+    HsbTransform {
+        brightness: 0.5,
+        saturation: 0.2,
+        hue: 1.0,
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 pub enum DefaultCursorStyle {
     BlinkingBlock,
@@ -1148,6 +1549,89 @@ pub struct WindowPadding {
 }
 impl_lua_conversion!(WindowPadding);
 
+/// An inclusive range of codepoints
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodepointRange {
+    pub first: u32,
+    pub last: u32,
+}
+impl_lua_conversion!(CodepointRange);
+
+impl CodepointRange {
+    /// Returns the valid chars in the range
+    pub fn chars(self) -> impl Iterator<Item = char> {
+        (self.first..=self.last).filter_map(std::char::from_u32)
+    }
+}
+
+/// The groups of characters that wezterm can compute for itself,
+/// rather than taking them from the font
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CustomBlockGlyphRange {
+    /// U+2580 - U+259F
+    BlockElements,
+    /// U+2500 - U+257F
+    BoxDrawing,
+    /// U+2800 - U+28FF
+    Braille,
+    /// The triangles from U+25A0 - U+25FF
+    GeometricShapes,
+    /// The sextants, wedges and triangles from U+1FB00 - U+1FB6F
+    LegacyComputing,
+    /// U+E0B0 - U+E0C8
+    Powerline,
+}
+impl_lua_conversion!(CustomBlockGlyphRange);
+
+impl CustomBlockGlyphRange {
+    pub fn contains(self, c: char) -> bool {
+        let c = c as u32;
+        match self {
+            Self::BlockElements => (0x2580..=0x259f).contains(&c),
+            Self::BoxDrawing => (0x2500..=0x257f).contains(&c),
+            Self::Braille => (0x2800..=0x28ff).contains(&c),
+            Self::GeometricShapes => (0x25a0..=0x25ff).contains(&c),
+            Self::LegacyComputing => (0x1fb00..=0x1fbff).contains(&c),
+            Self::Powerline => (0xe0b0..=0xe0c8).contains(&c),
+        }
+    }
+}
+
+fn default_glyph_cache_size() -> usize {
+    8192
+}
+
+fn default_custom_block_glyph_supersample() -> u8 {
+    2
+}
+
+fn default_custom_block_glyph_ranges() -> Vec<CustomBlockGlyphRange> {
+    vec![
+        CustomBlockGlyphRange::BlockElements,
+        CustomBlockGlyphRange::BoxDrawing,
+        CustomBlockGlyphRange::Braille,
+        CustomBlockGlyphRange::GeometricShapes,
+        CustomBlockGlyphRange::LegacyComputing,
+        CustomBlockGlyphRange::Powerline,
+    ]
+}
+
+/// How the shade characters U+2591 - U+2593 are drawn
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadeBlockStyle {
+    /// Fill the cell with the foreground color at 25%, 50% or 75% opacity
+    Translucent,
+    /// Fill 25%, 50% or 75% of the pixels of the cell with a dither pattern
+    Dithered,
+}
+impl_lua_conversion!(ShadeBlockStyle);
+
+impl Default for ShadeBlockStyle {
+    fn default() -> Self {
+        Self::Translucent
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 pub enum WindowCloseConfirmation {
     AlwaysPrompt,
@@ -1163,6 +1647,118 @@ impl Default for WindowCloseConfirmation {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaneMonitorNotification {
+    /// Show an indicator in the tab bar for the tab containing the pane
+    #[serde(default = "default_true")]
+    pub tab_indicator: bool,
+    /// Show a desktop notification
+    #[serde(default)]
+    pub desktop_notification: bool,
+}
+impl_lua_conversion!(PaneMonitorNotification);
+
+impl Default for PaneMonitorNotification {
+    fn default() -> Self {
+        Self {
+            tab_indicator: true,
+            desktop_notification: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct TabAutoTitle {
+    #[serde(default)]
+    pub enabled: bool,
+    /// The title template; `{process}` and `{cwd}` are replaced
+    /// by the foreground process name and working directory
+    #[serde(default = "default_tab_auto_title_format")]
+    pub format: String,
+    /// Show only the last component of the working directory
+    #[serde(default)]
+    pub cwd_basename_only: bool,
+    /// The maximum width of the title, in cells.  Longer titles
+    /// are shortened by eliding the start of the working directory.
+    /// 0 means no limit.
+    #[serde(default = "default_tab_auto_title_max_width")]
+    pub max_width: usize,
+}
+impl_lua_conversion!(TabAutoTitle);
+
+impl Default for TabAutoTitle {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: default_tab_auto_title_format(),
+            cwd_basename_only: false,
+            max_width: default_tab_auto_title_max_width(),
+        }
+    }
+}
+
+fn default_tab_auto_title_format() -> String {
+    "{process} {cwd}".to_string()
+}
+
+fn default_tab_auto_title_max_width() -> usize {
+    32
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisableLigatures {
+    /// The word under, or immediately to the left of, the cursor
+    Cursor,
+    /// The shell prompt and the command line that is being typed,
+    /// as marked up by OSC 133 semantic zones
+    Prompt,
+}
+impl_lua_conversion!(DisableLigatures);
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HyperlinkCopyFormat {
+    /// Copy only the text that is displayed
+    Text,
+    /// Replace the displayed text with the URL
+    Url,
+    /// Produce a markdown style `[text](url)` link
+    Markdown,
+}
+impl_lua_conversion!(HyperlinkCopyFormat);
+
+impl Default for HyperlinkCopyFormat {
+    fn default() -> Self {
+        HyperlinkCopyFormat::Text
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LuaClipboardAccess {
+    /// Lua code may neither read nor write the clipboard
+    Disabled,
+    /// Lua code may place text into the clipboard, but not read it
+    WriteOnly,
+    /// Lua code may both read and write the clipboard
+    ReadWrite,
+}
+impl_lua_conversion!(LuaClipboardAccess);
+
+impl Default for LuaClipboardAccess {
+    fn default() -> Self {
+        LuaClipboardAccess::WriteOnly
+    }
+}
+
+impl LuaClipboardAccess {
+    pub fn can_read(self) -> bool {
+        self == LuaClipboardAccess::ReadWrite
+    }
+
+    pub fn can_write(self) -> bool {
+        self != LuaClipboardAccess::Disabled
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         // Ask serde to provide the defaults based on the attributes
@@ -1202,17 +1798,21 @@ impl Config {
         Self::load_with_overrides(&serde_json::Value::default())
     }
 
-    pub fn load_with_overrides(overrides: &serde_json::Value) -> Result<LoadedConfig, Error> {
+    /// Returns the locations from which the config file may be loaded,
+    /// in order of precedence
+    fn config_file_paths() -> Vec<PathPossibility> {
         // Note that the directories crate has methods for locating project
         // specific config directories, but only returns one of them, not
         // multiple.  In addition, it spawns a lot of subprocesses,
         // so we do this bit "by-hand"
 
-        let mut paths = vec![
-            PathPossibility::optional(CONFIG_DIR.join("wezterm.lua")),
-            PathPossibility::optional(HOME_DIR.join(".wezterm.lua")),
-        ];
-        if cfg!(windows) {
+        let mut paths = vec![PathPossibility::optional(CONFIG_DIR.join("wezterm.lua"))];
+        if PORTABLE_DIR.is_none() {
+            // In portable mode, the config of the user of the host
+            // system must not take effect
+            paths.push(PathPossibility::optional(HOME_DIR.join(".wezterm.lua")));
+        }
+        if cfg!(windows) && PORTABLE_DIR.is_none() {
             // On Windows, a common use case is to maintain a thumb drive
             // with a set of portable tools that don't need to be installed
             // to run on a target system.  In that scenario, the user would
@@ -1237,6 +1837,21 @@ impl Config {
             paths.insert(0, PathPossibility::required(path.clone()));
         }
 
+        paths
+    }
+
+    /// Returns the path of the config file that would be loaded: the
+    /// first of the possible locations that exists, or the one that is
+    /// required to exist
+    pub fn config_file_path() -> Option<PathBuf> {
+        Self::config_file_paths()
+            .into_iter()
+            .find(|p| p.is_required || p.path.exists())
+            .map(|p| p.path)
+    }
+
+    pub fn load_with_overrides(overrides: &serde_json::Value) -> Result<LoadedConfig, Error> {
+        let paths = Self::config_file_paths();
         for path_item in &paths {
             let p = path_item.path.as_path();
             log::trace!("consider config: {}", p.display());
@@ -1338,7 +1953,7 @@ impl Config {
     pub fn key_bindings(&self) -> HashMap<(KeyCode, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
-        for k in &self.keys {
+        for k in self.keys.iter().filter(|k| k.when.is_none()) {
             let (key, mods) = k.key.normalize_shift(k.mods);
             map.insert((key, mods), k.action.clone());
         }
@@ -1346,6 +1961,25 @@ impl Config {
         map
     }
 
+    /// Returns the key assignments that have a `when` condition,
+    /// in the order in which they are defined
+    pub fn conditional_key_bindings(
+        &self,
+    ) -> HashMap<(KeyCode, Modifiers), Vec<(KeyCondition, KeyAssignment)>> {
+        let mut map: HashMap<_, Vec<_>> = HashMap::new();
+
+        for k in &self.keys {
+            if let Some(when) = &k.when {
+                let (key, mods) = k.key.normalize_shift(k.mods);
+                map.entry((key, mods))
+                    .or_default()
+                    .push((when.clone(), k.action.clone()));
+            }
+        }
+
+        map
+    }
+
     pub fn mouse_bindings(&self) -> HashMap<(MouseEventTrigger, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
@@ -1375,6 +2009,12 @@ impl Config {
                     cfg.window_background_image.replace(config_dir.join(path));
                 }
             }
+
+            if let Some(path) = self.window_background_shader.as_ref() {
+                if !path.is_absolute() {
+                    cfg.window_background_shader.replace(config_dir.join(path));
+                }
+            }
         }
 
         if cfg.font_rules.is_empty() {
@@ -1431,7 +2071,7 @@ impl Config {
     fn compute_color_scheme_dirs(&self) -> Vec<PathBuf> {
         let mut paths = self.color_scheme_dirs.clone();
         paths.push(CONFIG_DIR.join("colors"));
-        if cfg!(windows) {
+        if cfg!(windows) && PORTABLE_DIR.is_none() {
             // See commentary re: portable tools above!
             if let Ok(exe_name) = std::env::current_exe() {
                 if let Some(exe_dir) = exe_name.parent() {
@@ -1595,6 +2235,18 @@ fn default_initial_cols() -> u16 {
     80
 }
 
+fn default_line_timestamp_format() -> String {
+    "%H:%M:%S".to_string()
+}
+
+fn default_glyph_subpixel_positions() -> u8 {
+    4
+}
+
+fn default_editor_location_pattern() -> String {
+    r#"(?P<path>[^\s:'"]+):(?P<line>\d+)"#.to_string()
+}
+
 fn default_hyperlink_rules() -> Vec<hyperlink::Rule> {
     vec![
         // URL with a protocol
@@ -1622,6 +2274,14 @@ fn default_font_size() -> f64 {
 }
 
 fn compute_runtime_dir() -> Result<PathBuf, Error> {
+    if let Some(dir) = absolute_path_from_env("WEZTERM_RUNTIME_DIR") {
+        return Ok(dir);
+    }
+
+    if let Some(portable) = PORTABLE_DIR.as_ref() {
+        return Ok(portable.join("runtime"));
+    }
+
     if let Some(runtime) = dirs_next::runtime_dir() {
         return Ok(runtime.join("wezterm"));
     }
@@ -1640,3 +2300,15 @@ fn default_read_timeout() -> Duration {
 fn default_write_timeout() -> Duration {
     Duration::from_secs(60)
 }
+
+fn default_keepalive_interval() -> u64 {
+    15
+}
+
+fn default_keepalive_timeout() -> u64 {
+    30
+}
+
+fn default_local_echo_threshold_ms() -> u64 {
+    100
+}
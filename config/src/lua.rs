@@ -34,6 +34,7 @@ use termwiz::surface::change::Change;
 /// * `target_triple` - the rust compilation target triple.
 /// * `version` - the version of the running wezterm instance.
 /// * `home_dir` - the path to the user's home directory
+/// * `plugin` - a module for loading plugins; see the `plugin` module
 ///
 /// In addition to this, the lua standard library, except for
 /// the `debug` module, is also available to the script.
@@ -54,7 +55,12 @@ pub fn make_lua_context(config_dir: &Path) -> anyhow::Result<Lua> {
             array.insert(1, format!("{}/?/init.lua", path.display()));
         }
 
-        prefix_path(&mut path_array, &crate::HOME_DIR.join(".wezterm"));
+        // Each plugin is a directory, so that `require "name"` loads
+        // its entry point
+        crate::plugin::prefix_package_path(&mut path_array);
+        if crate::PORTABLE_DIR.is_none() {
+            prefix_path(&mut path_array, &crate::HOME_DIR.join(".wezterm"));
+        }
         prefix_path(&mut path_array, &crate::CONFIG_DIR);
         if let Ok(exe) = std::env::current_exe() {
             if let Some(path) = exe.parent() {
@@ -62,13 +68,16 @@ pub fn make_lua_context(config_dir: &Path) -> anyhow::Result<Lua> {
                     "executable_dir",
                     path.to_str().ok_or_else(|| anyhow!("path is not UTF-8"))?,
                 )?;
-                if cfg!(windows) {
+                if cfg!(windows) && crate::PORTABLE_DIR.is_none() {
                     // For a portable windows install, force in this path ahead
                     // of the rest
                     prefix_path(&mut path_array, &path.join("wezterm_modules"));
                 }
             }
         }
+        if let Some(portable) = crate::PORTABLE_DIR.as_ref() {
+            prefix_path(&mut path_array, &portable.join("wezterm_modules"));
+        }
 
         wezterm_mod.set(
             "config_dir",
@@ -135,8 +144,10 @@ pub fn make_lua_context(config_dir: &Path) -> anyhow::Result<Lua> {
         wezterm_mod.set("format", lua.create_function(format)?)?;
         wezterm_mod.set("strftime", lua.create_function(strftime)?)?;
         wezterm_mod.set("battery_info", lua.create_function(battery_info)?)?;
+        wezterm_mod.set("plugin", crate::plugin::make_plugin_module(&lua)?)?;
 
         package.set("path", path_array.join(";"))?;
+        crate::plugin::register_plugin_searcher(&lua, crate::PLUGIN_DIR.clone())?;
 
         let loaded: Table = package.get("loaded")?;
         loaded.set("wezterm", wezterm_mod)?;
@@ -335,14 +346,18 @@ struct TextStyleAttributes {
     /// useful in a `[[font_rules]]` section to implement changing
     /// the text color for eg: bold text.
     pub foreground: Option<termwiz::color::RgbColor>,
+    /// If set, the harfbuzz features to use with this font
+    /// in place of the global `harfbuzz_features`
+    #[serde(default)]
+    pub harfbuzz_features: Option<Vec<String>>,
 }
 impl_lua_conversion!(TextStyleAttributes);
 
 /// Given a simple font family name, returns a text style instance.
 /// The second optional argument is a list of the other TextStyle
-/// fields, which at the time of writing includes only the
-/// `foreground` color that can be used to force a particular
-/// color to be used for this text style.
+/// fields, such as the `foreground` color that can be used to force
+/// a particular color to be used for this text style, and the
+/// `harfbuzz_features` to use with this font.
 ///
 /// `wezterm.font("foo", {foreground="tomato"})`
 /// yields:
@@ -360,6 +375,7 @@ fn font<'lua>(
         bold: attrs.bold,
         italic: attrs.italic,
         is_fallback: false,
+        harfbuzz_features: attrs.harfbuzz_features,
     });
     text_style.foreground = attrs.foreground;
 
@@ -387,6 +403,7 @@ fn font_with_fallback<'lua>(
             bold: attrs.bold,
             italic: attrs.italic,
             is_fallback: idx != 0,
+            harfbuzz_features: attrs.harfbuzz_features.clone(),
         });
     }
     text_style.foreground = attrs.foreground;
@@ -552,6 +569,37 @@ pub async fn emit_event<'lua>(
     }
 }
 
+/// Emits an event whose handlers may transform a string value.
+/// Each handler is passed `args` followed by the current value.
+/// A handler may return a string to replace the value that is passed
+/// to the subsequent handlers, or `false` to cancel the default action,
+/// in which case `None` is returned.  Any other return value leaves
+/// the value unchanged.
+pub async fn emit_transform_event<'lua>(
+    lua: &'lua Lua,
+    (name, args, value): (String, mlua::MultiValue<'lua>, String),
+) -> mlua::Result<Option<String>> {
+    let decorated_name = format!("wezterm-event-{}", name);
+    let tbl: mlua::Value = lua.named_registry_value(&decorated_name)?;
+    let mut value = value;
+    if let mlua::Value::Table(tbl) = tbl {
+        for func in tbl.sequence_values::<mlua::Function>() {
+            let func = func?;
+            let mut call_args = args.clone().into_vec();
+            call_args.push(Value::String(lua.create_string(&value)?));
+            match func
+                .call_async(mlua::MultiValue::from_vec(call_args))
+                .await?
+            {
+                Value::Boolean(false) => return Ok(None),
+                Value::String(s) => value = s.to_str()?.to_string(),
+                _ => {}
+            }
+        }
+    }
+    Ok(Some(value))
+}
+
 /// Ungh: https://github.com/microsoft/WSL/issues/4456
 fn utf16_to_utf8<'lua>(_: &'lua Lua, text: mlua::String) -> mlua::Result<String> {
     let bytes = text.as_bytes();
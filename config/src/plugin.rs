@@ -0,0 +1,350 @@
+//! Plugins are lua modules that each live in their own directory.
+//! `PLUGIN_DIR` is on `package.path`, so that a plugin that has been
+//! placed there can be loaded with the lua `require` function, and
+//! `wezterm.plugin.require` can also clone a plugin from a git URL
+//! the first time that it is used.  Once cloned, `require` also
+//! accepts that URL.
+use crate::{HOME_DIR, PLUGIN_DIR};
+use anyhow::{anyhow, Context};
+use mlua::{Function, Lua, Table, Value};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// The files, relative to the directory of a plugin, that are
+/// tried in turn to find the code that implements it
+const ENTRY_POINTS: &[&str] = &["plugin/init.lua", "init.lua"];
+
+const LOADED_PLUGINS: &str = "wezterm-loaded-plugins";
+
+/// How long cloning a plugin may take before it is abandoned
+const CLONE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Adds the entry points of the plugins in PLUGIN_DIR to the front of
+/// the `package.path` entries in `path_array`, so that `require "name"`
+/// loads the same file as `wezterm.plugin.require "name"`.  The plugin
+/// directory itself is also added, so that a plugin can require its
+/// own modules.
+pub fn prefix_package_path(path_array: &mut Vec<String>) {
+    let dir = PLUGIN_DIR.display();
+    let mut entries: Vec<String> = ENTRY_POINTS
+        .iter()
+        .map(|entry| format!("{}/?/{}", dir, entry))
+        .collect();
+    entries.push(format!("{}/?.lua", dir));
+    path_array.splice(0..0, entries);
+}
+
+/// Returns the entry point of the plugin in `dir`, if it has one
+fn find_entry_point(dir: &Path) -> Option<PathBuf> {
+    ENTRY_POINTS
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// Adds a searcher to `package.searchers` that resolves a git URL to
+/// the entry point of the plugin that has been cloned from it into
+/// `plugin_root`.  Those live in nested host/owner/repo directories
+/// that `package.path` can't describe, so this is what allows
+/// `require` to load a plugin that `wezterm.plugin.require` cloned.
+pub fn register_plugin_searcher(lua: &Lua, plugin_root: PathBuf) -> anyhow::Result<()> {
+    let searcher = lua.create_function(move |lua, name: String| {
+        if !is_git_url(&name) {
+            return Ok((Value::Nil, Value::Nil));
+        }
+        let dir = plugin_root.join(repo_dir_from_url(&name).map_err(mlua::Error::external)?);
+        let entry = match find_entry_point(&dir) {
+            Some(entry) => entry,
+            None => {
+                let msg = format!("\n\tno cloned plugin in {}", dir.display());
+                return Ok((Value::String(lua.create_string(&msg)?), Value::Nil));
+            }
+        };
+        let code = std::fs::read_to_string(&entry)
+            .map_err(|err| mlua::Error::external(anyhow!("{}: {}", entry.display(), err)))?;
+        let entry = entry.to_string_lossy().to_string();
+        let loader = lua
+            .load(&code)
+            .set_name(entry.as_bytes())?
+            .into_function()?;
+        Ok((
+            Value::Function(loader),
+            Value::String(lua.create_string(&entry)?),
+        ))
+    })?;
+
+    // Consult it after package.preload, ahead of the path based searchers
+    let package: Table = lua.globals().get("package")?;
+    let searchers: Table = package.get("searchers")?;
+    let table: Table = lua.globals().get("table")?;
+    let insert: Function = table.get("insert")?;
+    insert.call::<_, ()>((searchers, 2, searcher))?;
+    Ok(())
+}
+
+/// Creates the `wezterm.plugin` module
+pub fn make_plugin_module(lua: &Lua) -> anyhow::Result<Table> {
+    let plugin_mod = lua.create_table()?;
+    plugin_mod.set("require", lua.create_async_function(require_plugin)?)?;
+    plugin_mod.set(
+        "plugin_dir",
+        PLUGIN_DIR
+            .to_str()
+            .ok_or_else(|| anyhow!("plugin dir path is not UTF-8"))?,
+    )?;
+    Ok(plugin_mod)
+}
+
+fn is_git_url(spec: &str) -> bool {
+    spec.contains("://") || spec.starts_with("git@")
+}
+
+/// Returns the directory, relative to PLUGIN_DIR, into which the
+/// repository at `url` is cloned.  It is made up of the host, the
+/// owner and the name of the repository, so that repositories of
+/// the same name from different owners or hosts don't collide.
+fn repo_dir_from_url(url: &str) -> anyhow::Result<PathBuf> {
+    let invalid = || anyhow!("cannot determine the name of the plugin at {}", url);
+    let (host, path) = if let Some(idx) = url.find("://") {
+        let rest = &url[idx + 3..];
+        match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash + 1..]),
+            None => (rest, ""),
+        }
+    } else {
+        // scp-like syntax: user@host:owner/repo
+        let colon = url.find(':').ok_or_else(invalid)?;
+        (&url[..colon], &url[colon + 1..])
+    };
+    // Drop any user name, and keep the port, if any, in a form
+    // that is valid in a file name everywhere
+    let host = host.rsplit('@').next().unwrap_or(host).replace(':', "_");
+    let host = if host.is_empty() { "localhost" } else { &host };
+
+    let mut segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let name = segments.pop().ok_or_else(invalid)?;
+    segments.push(name.strip_suffix(".git").unwrap_or(name));
+
+    if std::iter::once(host)
+        .chain(segments.iter().copied())
+        .any(|s| s.is_empty() || s == "." || s == ".." || s.contains('\\'))
+    {
+        return Err(invalid());
+    }
+    let mut dir = PathBuf::from(host);
+    dir.extend(segments);
+    Ok(dir)
+}
+
+/// Returns the directory that holds the plugin described by `spec`,
+/// which is either a git URL, a path, or the name of a directory
+/// in PLUGIN_DIR
+fn plugin_dir(spec: &str) -> anyhow::Result<PathBuf> {
+    if is_git_url(spec) {
+        return Ok(PLUGIN_DIR.join(repo_dir_from_url(spec)?));
+    }
+    if let Some(rest) = spec.strip_prefix("~/") {
+        return Ok(HOME_DIR.join(rest));
+    }
+    let path = Path::new(spec);
+    if path.is_absolute() || path.components().count() > 1 {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(PLUGIN_DIR.join(spec))
+    }
+}
+
+async fn clone_plugin(url: &str, dir: &Path) -> anyhow::Result<()> {
+    // Clone into a scratch directory, so that a failed clone doesn't
+    // leave behind a directory that looks like an installed plugin
+    let mut partial_name = dir.file_name().unwrap_or_default().to_os_string();
+    partial_name.push(".partial");
+    let partial = dir.with_file_name(partial_name);
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)
+            .with_context(|| format!("removing {}", partial.display()))?;
+    }
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+    }
+
+    log::info!("cloning plugin {} into {}", url, dir.display());
+    let mut cmd = smol::process::Command::new("git");
+    // `--` ensures that a URL that starts with `-` can't be taken
+    // as an option
+    cmd.args(&["clone", "--depth", "1", "--quiet", "--", url])
+        .arg(&partial)
+        // There is nobody to answer a prompt for credentials, so
+        // fail rather than wait for one
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    #[cfg(windows)]
+    {
+        use smol::process::windows::CommandExt;
+        cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
+    }
+
+    let output = smol::future::or(async { cmd.output().await.context("running git") }, async {
+        smol::Timer::after(CLONE_TIMEOUT).await;
+        Err(anyhow!(
+            "git clone {} did not complete within {} seconds",
+            url,
+            CLONE_TIMEOUT.as_secs()
+        ))
+    })
+    .await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git clone {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    std::fs::rename(&partial, dir)
+        .with_context(|| format!("renaming {} to {}", partial.display(), dir.display()))?;
+    Ok(())
+}
+
+/// This implements `wezterm.plugin.require`.  It returns the value
+/// that the entry point of the plugin returns, which is cached so
+/// that the plugin is only evaluated once per config evaluation.
+async fn require_plugin<'lua>(lua: &'lua Lua, spec: String) -> mlua::Result<Value<'lua>> {
+    let dir = plugin_dir(&spec).map_err(mlua::Error::external)?;
+    let key = dir.to_string_lossy().to_string();
+
+    let loaded = match lua.named_registry_value(LOADED_PLUGINS)? {
+        Value::Table(tbl) => tbl,
+        _ => {
+            let tbl = lua.create_table()?;
+            lua.set_named_registry_value(LOADED_PLUGINS, tbl.clone())?;
+            tbl
+        }
+    };
+    match loaded.get(key.as_str())? {
+        Value::Nil => {}
+        value => return Ok(value),
+    }
+
+    if !dir.exists() {
+        if is_git_url(&spec) {
+            clone_plugin(&spec, &dir)
+                .await
+                .map_err(mlua::Error::external)?;
+        } else {
+            return Err(mlua::Error::external(anyhow!(
+                "plugin {} was not found at {}",
+                spec,
+                dir.display()
+            )));
+        }
+    }
+
+    let entry = find_entry_point(&dir).ok_or_else(|| {
+        mlua::Error::external(anyhow!(
+            "plugin {} has none of {} in {}",
+            spec,
+            ENTRY_POINTS.join(", "),
+            dir.display()
+        ))
+    })?;
+    let code = smol::fs::read_to_string(&entry)
+        .await
+        .map_err(|err| mlua::Error::external(anyhow!("{}: {}", entry.display(), err)))?;
+
+    let value: Value = lua
+        .load(&code)
+        .set_name(entry.to_string_lossy().as_bytes())?
+        .eval_async()
+        .await?;
+    // Like `require`, record a plugin that returns nothing as true
+    let value = match value {
+        Value::Nil => Value::Boolean(true),
+        value => value,
+    };
+    loaded.set(key, value.clone())?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plugin_names() {
+        assert_eq!(
+            repo_dir_from_url("https://github.com/wez/tabline.wez").unwrap(),
+            Path::new("github.com/wez/tabline.wez")
+        );
+        assert_eq!(
+            repo_dir_from_url("https://github.com/wez/bar.git/").unwrap(),
+            Path::new("github.com/wez/bar")
+        );
+        assert_eq!(
+            repo_dir_from_url("git@github.com:wez/baz.git").unwrap(),
+            Path::new("github.com/wez/baz")
+        );
+        assert_eq!(
+            repo_dir_from_url("ssh://git@example.com:2222/group/sub/qux").unwrap(),
+            Path::new("example.com_2222/group/sub/qux")
+        );
+        assert_ne!(
+            repo_dir_from_url("https://github.com/wez/bar").unwrap(),
+            repo_dir_from_url("https://github.com/other/bar").unwrap()
+        );
+        assert!(repo_dir_from_url("https://example.com/..").is_err());
+        assert!(repo_dir_from_url("https://example.com/wez/../bar").is_err());
+        assert!(repo_dir_from_url("https://example.com").is_err());
+
+        assert_eq!(
+            plugin_dir("https://github.com/wez/bar.git").unwrap(),
+            PLUGIN_DIR.join("github.com/wez/bar")
+        );
+        assert_eq!(plugin_dir("bar").unwrap(), PLUGIN_DIR.join("bar"));
+        assert_eq!(plugin_dir("~/src/bar").unwrap(), HOME_DIR.join("src/bar"));
+    }
+
+    #[test]
+    fn require_cloned_plugin() {
+        let root = std::env::temp_dir().join(format!("wezterm-plugins-{}", std::process::id()));
+        let url = "https://github.com/wez/demo.wez.git";
+        let dir = root.join(repo_dir_from_url(url).unwrap()).join("plugin");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("init.lua"), "return { answer = 42 }").unwrap();
+
+        let lua = Lua::new();
+        register_plugin_searcher(&lua, root.clone()).unwrap();
+        let answer: i64 = lua
+            .load(&format!("return require('{}').answer", url))
+            .eval()
+            .unwrap();
+        assert_eq!(answer, 42);
+
+        // A URL that hasn't been cloned is still reported as missing
+        let missing: mlua::Result<Value> = lua
+            .load("return require('https://github.com/wez/missing')")
+            .eval();
+        assert!(missing.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn package_path_uses_entry_points() {
+        let mut path_array = vec!["./?.lua".to_string()];
+        prefix_package_path(&mut path_array);
+        let dir = PLUGIN_DIR.display();
+        assert_eq!(
+            path_array,
+            vec![
+                format!("{}/?/plugin/init.lua", dir),
+                format!("{}/?/init.lua", dir),
+                format!("{}/?.lua", dir),
+                "./?.lua".to_string(),
+            ]
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! Bridge our gui config into the terminal crate configuration
 
-use crate::configuration;
+use crate::{configuration, EmojiPresentation};
+use termwiz::cell::Presentation;
 use termwiz::hyperlink::Rule as HyperlinkRule;
 use wezterm_term::color::ColorPalette;
 
@@ -31,7 +32,27 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         config.resolved_palette.clone().into()
     }
 
+    fn clear_prompt_on_resize(&self) -> bool {
+        configuration().clear_prompt_on_resize
+    }
+
+    fn capture_alternate_screen(&self) -> bool {
+        configuration().capture_alternate_screen
+    }
+
+    fn record_line_timestamps(&self) -> bool {
+        configuration().record_line_timestamps
+    }
+
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         configuration().alternate_buffer_wheel_scroll_speed
     }
+
+    fn emoji_presentation(&self) -> Option<Presentation> {
+        match configuration().emoji_presentation {
+            EmojiPresentation::Unicode => None,
+            EmojiPresentation::Text => Some(Presentation::Text),
+            EmojiPresentation::Emoji => Some(Presentation::Emoji),
+        }
+    }
 }
@@ -22,6 +22,12 @@ pub struct TlsDomainServer {
     /// to the trust store.
     #[serde(default)]
     pub pem_root_certs: Vec<PathBuf>,
+
+    /// The common names that client certificates are allowed to have.
+    /// When empty, the CN must match the name of the user that is
+    /// running the server.
+    #[serde(default)]
+    pub authorized_client_cns: Vec<String>,
 }
 impl_lua_conversion!(TlsDomainServer);
 
@@ -80,6 +86,28 @@ pub struct TlsDomainClient {
     #[serde(default = "default_write_timeout")]
     pub write_timeout: Duration,
 
+    /// How often, in seconds, to send a keepalive to the server.
+    /// Set to 0 to disable keepalives.
+    #[serde(default = "default_keepalive_interval")]
+    pub keepalive_interval_seconds: u64,
+
+    /// How long, in seconds, to wait for the server to answer a
+    /// keepalive before considering it to be unresponsive
+    #[serde(default = "default_keepalive_timeout")]
+    pub keepalive_timeout_seconds: u64,
+
+    /// If true, characters that are typed into the panes of this domain
+    /// are displayed immediately, underlined, until the remote system
+    /// echoes them, whenever the round trip time is at least
+    /// local_echo_threshold_ms
+    #[serde(default = "default_true")]
+    pub predictive_local_echo: bool,
+
+    /// The round trip time, in milliseconds, above which
+    /// predictive_local_echo is used
+    #[serde(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: u64,
+
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
 }
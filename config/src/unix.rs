@@ -34,15 +34,33 @@ pub struct UnixDomain {
     /// socket_path.  This is not recommended on a multi-user
     /// system, but is useful for example when running the
     /// server inside a WSL container but with the socket
-    /// on the host NTFS volume.
+    /// on the host NTFS volume.  This also disables the check
+    /// that rejects connections from other users.
     #[serde(default)]
     pub skip_permissions_check: bool,
 
+    /// If set, clients must present this token before the server
+    /// will act on any of their requests.  The same value must be
+    /// configured for both the server and its clients.
+    pub auth_token: Option<String>,
+
     #[serde(default = "default_read_timeout")]
     pub read_timeout: Duration,
 
     #[serde(default = "default_write_timeout")]
     pub write_timeout: Duration,
+
+    /// If true, characters that are typed into the panes of this domain
+    /// are displayed immediately, underlined, until the remote system
+    /// echoes them, whenever the round trip time is at least
+    /// local_echo_threshold_ms
+    #[serde(default = "default_true")]
+    pub predictive_local_echo: bool,
+
+    /// The round trip time, in milliseconds, above which
+    /// predictive_local_echo is used
+    #[serde(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: u64,
 }
 impl_lua_conversion!(UnixDomain);
 
@@ -55,8 +73,11 @@ impl Default for UnixDomain {
             no_serve_automatically: false,
             serve_command: None,
             skip_permissions_check: false,
+            auth_token: None,
             read_timeout: default_read_timeout(),
             write_timeout: default_write_timeout(),
+            predictive_local_echo: true,
+            local_echo_threshold_ms: default_local_echo_threshold_ms(),
         }
     }
 }
@@ -1,5 +1,7 @@
 use std::path::{Path, PathBuf};
 
+pub mod ringlog;
+
 pub fn set_wezterm_executable() {
     if let Ok(exe) = std::env::current_exe() {
         if let Some(dir) = exe.parent() {
@@ -10,13 +12,18 @@ pub fn set_wezterm_executable() {
 }
 
 pub fn setup_logger() {
-    let mut builder = pretty_env_logger::formatted_timed_builder();
-    if let Ok(s) = std::env::var("WEZTERM_LOG") {
-        builder.parse_filters(&s);
-    } else {
-        builder.filter(None, log::LevelFilter::Info);
-    }
-    builder.init();
+    let spec = std::env::var("WEZTERM_LOG").unwrap_or_else(|_| "info".to_string());
+    ringlog::init(&spec);
+}
+
+/// The programs that are started in our panes, which may include a
+/// system installed wezterm, must not inherit WEZTERM_PORTABLE_DIR,
+/// so resolve the portable directory now and remove it from our
+/// environment.  The mux server that the client spawns is given
+/// the directory explicitly.
+pub fn fixup_portable_dir() {
+    lazy_static::initialize(&config::PORTABLE_DIR);
+    std::env::remove_var("WEZTERM_PORTABLE_DIR");
 }
 
 pub fn fixup_appimage() {
@@ -127,6 +134,8 @@ pub fn bootstrap() {
     #[cfg(target_os = "macos")]
     set_lang_from_locale();
 
+    fixup_portable_dir();
+
     fixup_appimage();
 
     setup_logger();
@@ -0,0 +1,192 @@
+//! The logging subsystem used by the wezterm executables.
+//! Records are formatted to stderr by env_logger, and a bounded number
+//! of recent records are retained in memory so that they can be shown
+//! in the debug overlay and included in crash reports.
+//! The filter that selects which records are logged uses the same
+//! syntax as `WEZTERM_LOG` and can be replaced at runtime; see
+//! `wezterm cli log-filter`.
+use anyhow::{anyhow, bail};
+use chrono::prelude::*;
+use env_logger::filter::Filter;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+const MAX_ENTRIES: usize = 500;
+
+lazy_static::lazy_static! {
+    static ref ENTRIES: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::with_capacity(MAX_ENTRIES));
+    static ref FILTER: RwLock<ActiveFilter> = RwLock::new(ActiveFilter::new("info"));
+}
+
+/// A log record that has been retained in memory
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub then: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub msg: String,
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:<5} {} > {}",
+            self.then.format("%Y-%m-%dT%H:%M:%S%.3f"),
+            self.level,
+            self.target,
+            self.msg
+        )
+    }
+}
+
+struct ActiveFilter {
+    spec: String,
+    filter: Filter,
+}
+
+impl ActiveFilter {
+    fn new(spec: &str) -> Self {
+        Self {
+            spec: spec.to_string(),
+            filter: env_logger::filter::Builder::new().parse(spec).build(),
+        }
+    }
+}
+
+struct RingLogger {
+    /// Responsible for formatting to stderr; it accepts everything,
+    /// as the filtering is performed using FILTER
+    output: env_logger::Logger,
+}
+
+impl Log for RingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        FILTER
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .filter
+            .enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !FILTER
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .filter
+            .matches(record)
+        {
+            return;
+        }
+        self.output.log(record);
+
+        let entry = Entry {
+            then: Local::now(),
+            level: record.level(),
+            target: record.target().to_string(),
+            msg: record.args().to_string(),
+        };
+        let mut entries = ENTRIES.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    fn flush(&self) {
+        self.output.flush();
+    }
+}
+
+/// Installs the logger, using `spec` as the initial filter
+pub fn init(spec: &str) {
+    let mut builder = pretty_env_logger::formatted_timed_builder();
+    builder.filter(None, LevelFilter::Trace);
+    let logger = RingLogger {
+        output: builder.build(),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        if let Err(err) = set_filter(spec) {
+            eprintln!("WEZTERM_LOG: {:#}; using info", err);
+            set_filter("info").ok();
+        }
+    }
+}
+
+/// Checks that `spec` uses the `WEZTERM_LOG` syntax:
+/// a comma separated list of `module=level`, `module` or `level`
+/// directives, optionally followed by `/regex`.
+/// env_logger silently ignores invalid directives; we'd rather
+/// report them to the user that is trying to change the filter.
+/// A bare `module` must be a path that includes `::`, as otherwise
+/// a misspelled level would be taken to be the name of a module.
+fn validate(spec: &str) -> anyhow::Result<()> {
+    let directives = spec.splitn(2, '/').next().unwrap_or("");
+    for directive in directives.split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        let mut parts = directive.splitn(2, '=');
+        let module = parts.next().unwrap_or("");
+        if let Some(level) = parts.next() {
+            if module.is_empty() {
+                bail!("{:?} has no module name", directive);
+            }
+            LevelFilter::from_str(level.trim())
+                .map_err(|_| anyhow!("{:?} is not a valid log level", level))?;
+        } else if LevelFilter::from_str(module).is_err() && !module.contains("::") {
+            bail!(
+                "{:?} is not a valid log level; use {}=trace to enable \
+                 all of the logging from the {} module",
+                module,
+                module,
+                module
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the filter that selects the records that are logged
+pub fn set_filter(spec: &str) -> anyhow::Result<()> {
+    validate(spec)?;
+    let active = ActiveFilter::new(spec);
+    log::set_max_level(active.filter.filter());
+    *FILTER.write().unwrap_or_else(|e| e.into_inner()) = active;
+    Ok(())
+}
+
+/// Returns the filter that is currently in effect
+pub fn get_filter() -> String {
+    FILTER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .spec
+        .clone()
+}
+
+/// Returns the retained log records, oldest first
+pub fn get_entries() -> Vec<Entry> {
+    let entries = ENTRIES.lock().unwrap_or_else(|e| e.into_inner());
+    entries.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate_directives() {
+        assert!(validate("info").is_ok());
+        assert!(validate("WARN,wezterm_font=debug").is_ok());
+        assert!(validate("mux::localpane,config=trace/regex").is_ok());
+        assert!(validate("").is_ok());
+
+        assert!(validate("wran").is_err());
+        assert!(validate("info,wezterm_gui").is_err());
+        assert!(validate("config=loud").is_err());
+        assert!(validate("=info").is_err());
+    }
+}
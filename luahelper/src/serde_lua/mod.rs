@@ -5,6 +5,7 @@ use serde::de::{
     IntoDeserializer, Unexpected, VariantAccess, Visitor,
 };
 use serde::{serde_if_integer128, Deserialize};
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::convert::TryInto;
@@ -21,6 +22,69 @@ where
     T::deserialize(ValueWrapper(value))
 }
 
+/// A field of a lua table that was ignored because it isn't one of
+/// the fields of the struct into which the table was deserialized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownField {
+    pub field: String,
+    pub struct_name: String,
+    /// The most similar of the fields of the struct, which
+    /// may have been intended
+    pub suggestions: Vec<String>,
+    /// Describes the suggestions and the other possible fields
+    pub message: String,
+}
+
+thread_local! {
+    static UNKNOWN_FIELDS: RefCell<Option<Vec<UnknownField>>> = RefCell::new(None);
+}
+
+/// Calls `func` and returns the fields that `from_lua_value` ignored
+/// while it ran, along with its result.  The ignored fields are not
+/// logged as errors while they are being collected.
+pub fn collect_unknown_fields<R, F: FnOnce() -> R>(func: F) -> (R, Vec<UnknownField>) {
+    let outer = UNKNOWN_FIELDS.with(|fields| fields.borrow_mut().replace(vec![]));
+    let result = func();
+    let collected = UNKNOWN_FIELDS.with(|fields| {
+        let mut fields = fields.borrow_mut();
+        let collected = fields.take().unwrap_or_else(Vec::new);
+        *fields = outer;
+        collected
+    });
+    (result, collected)
+}
+
+/// Records an ignored field, or logs it if the ignored fields
+/// are not being collected
+fn unknown_field(
+    field: &str,
+    struct_name: Option<&'static str>,
+    suggestions: &[&str],
+    message: String,
+) {
+    let struct_name = struct_name.unwrap_or("<unknown>");
+    let collecting = UNKNOWN_FIELDS.with(|fields| match fields.borrow_mut().as_mut() {
+        Some(fields) => {
+            fields.push(UnknownField {
+                field: field.to_string(),
+                struct_name: struct_name.to_string(),
+                suggestions: suggestions.iter().map(|s| s.to_string()).collect(),
+                message: message.clone(),
+            });
+            true
+        }
+        None => false,
+    });
+    if !collecting {
+        log::error!(
+            "Ignoring unknown field `{}` in struct of type `{}`. {}",
+            field,
+            struct_name,
+            message
+        );
+    }
+}
+
 fn unexpected<'lua>(v: &'lua Value<'lua>) -> Unexpected<'lua> {
     match v {
         Value::Nil => Unexpected::Other("lua nil"),
@@ -302,12 +366,7 @@ where
                                 }
                                 message.push('.');
                             }
-                            log::error!(
-                                "Ignoring unknown field `{}` in struct of type `{}`. {}",
-                                pair.0,
-                                struct_name.unwrap_or("<unknown>"),
-                                message
-                            );
+                            unknown_field(&pair.0, struct_name, &suggestions, message);
 
                             continue;
                         }
@@ -992,4 +1051,32 @@ mod test {
         let res: AnotherEnum = from_lua_value(lua.load("{ThisOne={}}").eval().unwrap()).unwrap();
         assert_eq!(res, AnotherEnum::ThisOne(None));
     }
+
+    #[test]
+    fn test_unknown_fields() {
+        #[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+        struct MyStruct {
+            #[serde(default)]
+            font_size: i64,
+        }
+
+        let lua = Lua::new();
+        let (res, unknown) = collect_unknown_fields(|| {
+            from_lua_value::<MyStruct>(lua.load("{font_size=12, font_sise=10}").eval().unwrap())
+        });
+        assert_eq!(res.unwrap(), MyStruct { font_size: 12 });
+        assert_eq!(
+            unknown,
+            vec![UnknownField {
+                field: "font_sise".to_string(),
+                struct_name: "MyStruct".to_string(),
+                suggestions: vec!["font_size".to_string()],
+                message: "Did you mean `font_size`?".to_string(),
+            }]
+        );
+
+        // Nothing is collected once the closure has returned
+        let (_, unknown) = collect_unknown_fields(|| ());
+        assert!(unknown.is_empty());
+    }
 }
@@ -16,6 +16,7 @@ use config::configuration;
 use downcast_rs::{impl_downcast, Downcast};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, PtySystem};
 use std::rc::Rc;
+use std::time::Duration;
 
 static DOMAIN_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type DomainId = usize;
@@ -26,6 +27,22 @@ pub enum DomainState {
     Attached,
 }
 
+/// Describes the connection between a domain and its remote host
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionStatus {
+    /// The host that the domain is connected to, if known
+    pub remote_host: Option<String>,
+    /// false while the connection is lost and we are
+    /// trying to re-establish it
+    pub connected: bool,
+    /// false if the remote host has not answered a keepalive
+    /// within the configured timeout
+    pub responsive: bool,
+    /// The round trip time of the most recent request
+    /// made to the remote host, if known
+    pub latency: Option<Duration>,
+}
+
 pub fn alloc_domain_id() -> DomainId {
     DOMAIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed)
 }
@@ -79,6 +96,12 @@ pub trait Domain: Downcast {
 
     /// Indicates the state of the domain
     fn state(&self) -> DomainState;
+
+    /// Returns the status of the connection to the remote host,
+    /// or None if the domain is not remote
+    fn connection_status(&self) -> Option<ConnectionStatus> {
+        None
+    }
 }
 impl_downcast!(Domain);
 
@@ -6,6 +6,7 @@ use config::{configuration, ExitBehavior};
 use domain::{Domain, DomainId};
 use log::error;
 use portable_pty::ExitStatus;
+use serde::{Deserialize, Serialize};
 use std::cell::{Ref, RefCell, RefMut};
 use std::collections::HashMap;
 use std::collections::VecDeque;
@@ -13,7 +14,7 @@ use std::io::Read;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use std::thread;
 use termwiz::escape::Action;
@@ -41,6 +42,108 @@ pub enum MuxNotification {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
     },
+    ScreenshotRequested(ScreenshotRequest),
+    PerfStatsRequested(PerfStatsRequest),
+    EventRequested(EventRequest),
+    /// The connection status of a remote domain has changed
+    DomainConnectionChanged(DomainId),
+}
+
+/// Asks the gui window that is displaying a pane to render it
+/// to a PNG image.  The image is sent to `reply`; if no window
+/// is able to satisfy the request then all clones of `reply`
+/// are dropped without sending anything.
+#[derive(Clone, Debug)]
+pub struct ScreenshotRequest {
+    pub pane_id: PaneId,
+    pub scale: f32,
+    pub reply: smol::channel::Sender<anyhow::Result<Vec<u8>>>,
+}
+
+/// Asks the gui window that is displaying a mux window for the
+/// timings of the frames that it painted most recently and the
+/// statistics of its glyph cache.  These are sent to `reply`; if no
+/// gui window is displaying the mux window then all clones of `reply`
+/// are dropped without sending anything.
+#[derive(Clone, Debug)]
+pub struct PerfStatsRequest {
+    pub window_id: WindowId,
+    pub reply: smol::channel::Sender<WindowPerf>,
+}
+
+/// Asks the gui window that is displaying a pane to emit the user
+/// defined lua event `name`, passing the window, the pane and `args`
+/// to its handlers.  The outcome is sent to `reply` once the handlers
+/// have run; if no window is displaying the pane then all clones of
+/// `reply` are dropped without sending anything.
+#[derive(Clone, Debug)]
+pub struct EventRequest {
+    pub pane_id: PaneId,
+    pub name: String,
+    pub args: Vec<String>,
+    pub reply: smol::channel::Sender<anyhow::Result<()>>,
+}
+
+/// The performance statistics of a gui window
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct WindowPerf {
+    /// The timings of the most recently painted frames, oldest first
+    pub frames: Vec<FrameTiming>,
+    pub glyph_cache: GlyphCacheStats,
+}
+
+/// How long the gui took to paint a frame, and how effective its
+/// caches were while doing so
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct FrameTiming {
+    /// The total time taken to paint the frame
+    pub total: Duration,
+    /// The time spent on the CPU building the quads for the frame,
+    /// which includes the time spent shaping
+    pub render: Duration,
+    /// The time spent shaping text that wasn't in the shape cache
+    pub shape: Duration,
+    /// The time spent submitting the draw calls to the GPU
+    pub draw: Duration,
+    pub shape_cache_hits: usize,
+    pub shape_cache_misses: usize,
+    pub glyph_cache_hits: usize,
+    pub glyph_cache_misses: usize,
+}
+
+/// Describes the contents of the glyph cache of a gui window, and
+/// how effective it has been since it was created
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct GlyphCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// The number of glyphs in the cache
+    pub glyphs: usize,
+    /// The number of glyphs that the cache can hold
+    pub capacity: usize,
+    /// The numbers of cached image frames, line sprites and
+    /// block sprites
+    pub images: usize,
+    pub lines: usize,
+    pub blocks: usize,
+    /// The number of glyphs that were rasterized, and the time
+    /// spent rasterizing them
+    pub rasterized: usize,
+    pub raster_time: Duration,
+    /// The number of times that an atlas was grown or compacted
+    pub atlas_replacements: usize,
+    pub color_atlas: AtlasOccupancy,
+    pub alpha_atlas: AtlasOccupancy,
+}
+
+/// Describes how much of the texture of an atlas is in use
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct AtlasOccupancy {
+    /// The width and height of the texture
+    pub side: usize,
+    pub sprites: usize,
+    /// The fraction of the texture that is occupied by sprites
+    pub occupancy: f32,
 }
 
 static SUB_ID: AtomicUsize = AtomicUsize::new(0);
@@ -54,13 +157,20 @@ pub struct Mux {
     domains_by_name: RefCell<HashMap<String, Arc<dyn Domain>>>,
     subscribers: RefCell<HashMap<usize, Box<dyn Fn(MuxNotification) -> bool>>>,
     banner: RefCell<Option<String>>,
+    active_workspace: RefCell<String>,
 }
 
+/// The name of the workspace that windows belong to unless
+/// some other workspace is specified
+pub const DEFAULT_WORKSPACE: &str = "default";
+
 /// This function bounces parsed actions over to the main thread to feed to
 /// the pty in the mux.
 /// It blocks until the mux has finished consuming the data, which provides
 /// some back-pressure so that eg: ctrl-c can remain responsive.
-fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Action>) {
+/// Applies the actions to the pane on the main thread.
+/// Returns true if the pane is currently visible in its window.
+fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Action>) -> bool {
     promise::spawn::block_on(promise::spawn::spawn_into_main_thread({
         let dead = Arc::clone(&dead);
         async move {
@@ -68,14 +178,16 @@ fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Act
             if let Some(pane) = mux.get_pane(pane_id) {
                 pane.perform_actions(actions);
                 mux.notify(MuxNotification::PaneOutput(pane_id));
+                mux.is_pane_visible(pane_id)
             } else {
                 // Something else removed the pane from
                 // the mux, so signal that we should stop
                 // trying to process it in read_from_pane_pty.
                 dead.store(true, Ordering::Relaxed);
+                true
             }
         }
-    }));
+    }))
 }
 
 struct BufState {
@@ -95,6 +207,11 @@ impl BufState {
 fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
     let mut parser = termwiz::escape::parser::Parser::new();
     let mut queue = state.queue.lock().unwrap();
+    // How long to accumulate output before sending it to the mux.
+    // Panes that are not visible can tolerate a much longer delay,
+    // which keeps sessions with lots of busy background panes from
+    // saturating the main thread.
+    let mut coalesce = Duration::from_millis(0);
 
     loop {
         if queue.is_empty() {
@@ -112,26 +229,35 @@ fn parse_buffered_data(pane_id: PaneId, state: &Arc<BufState>) {
 
         // Yield briefly to see if more data showed up and
         // lump it together with what we've got
+        let deadline = Instant::now() + coalesce;
         loop {
-            let wait_res = state
-                .cond
-                .wait_timeout(queue, Duration::from_millis(1))
-                .unwrap();
+            let wait = deadline
+                .saturating_duration_since(Instant::now())
+                .max(Duration::from_millis(1));
+            let wait_res = state.cond.wait_timeout(queue, wait).unwrap();
             queue = wait_res.0;
+            let expired = Instant::now() >= deadline;
             if queue.is_empty() {
-                break;
+                if expired || state.dead.load(Ordering::Relaxed) {
+                    break;
+                }
+                continue;
             }
             let buf = queue.make_contiguous();
             parser.parse(buf, |action| actions.push(action));
             queue.truncate(0);
-            if !actions.is_empty() {
+            if !actions.is_empty() && expired {
                 // Don't delay very long if we've got stuff to display!
                 break;
             }
         }
 
         if !actions.is_empty() {
-            send_actions_to_mux(pane_id, &state.dead, actions);
+            coalesce = if send_actions_to_mux(pane_id, &state.dead, actions) {
+                Duration::from_millis(0)
+            } else {
+                Duration::from_millis(configuration().invisible_pane_update_interval)
+            };
         }
     }
 }
@@ -270,6 +396,7 @@ impl Mux {
             domains: RefCell::new(domains),
             subscribers: RefCell::new(HashMap::new()),
             banner: RefCell::new(None),
+            active_workspace: RefCell::new(DEFAULT_WORKSPACE.to_string()),
         }
     }
 
@@ -459,6 +586,59 @@ impl Mux {
         self.remove_window_internal(window_id);
     }
 
+    /// Kills the specified pane.  Unless `force` is true, this fails
+    /// if the pane is running a program that would normally cause
+    /// the user to be prompted before closing it.
+    pub fn close_pane(&self, pane_id: PaneId, force: bool) -> anyhow::Result<()> {
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+        if !force && !pane.can_close_without_prompting() {
+            anyhow::bail!(
+                "pane {} is running a program that requires confirmation to close; \
+                 use force to close it anyway",
+                pane_id
+            );
+        }
+        self.remove_pane(pane_id);
+        Ok(())
+    }
+
+    /// Kills the specified tab and its panes, subject to the same
+    /// confirmation policy as `close_pane`.
+    pub fn close_tab(&self, tab_id: TabId, force: bool) -> anyhow::Result<()> {
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("no such tab {}", tab_id))?;
+        if !force && !tab.can_close_without_prompting() {
+            anyhow::bail!(
+                "tab {} is running a program that requires confirmation to close; \
+                 use force to close it anyway",
+                tab_id
+            );
+        }
+        self.remove_tab(tab_id);
+        Ok(())
+    }
+
+    /// Kills the specified window along with its tabs and panes,
+    /// subject to the same confirmation policy as `close_pane`.
+    pub fn close_window(&self, window_id: WindowId, force: bool) -> anyhow::Result<()> {
+        let can_close = self
+            .get_window(window_id)
+            .ok_or_else(|| anyhow!("no such window {}", window_id))?
+            .can_close_without_prompting();
+        if !force && !can_close {
+            anyhow::bail!(
+                "window {} is running a program that requires confirmation to close; \
+                 use force to close it anyway",
+                window_id
+            );
+        }
+        self.kill_window(window_id);
+        Ok(())
+    }
+
     pub fn get_window(&self, window_id: WindowId) -> Option<Ref<Window>> {
         if !self.windows.borrow().contains_key(&window_id) {
             return None;
@@ -482,8 +662,69 @@ impl Mux {
         window.get_active().map(Rc::clone)
     }
 
-    pub fn new_empty_window(&self) -> MuxWindowBuilder {
-        let window = Window::new();
+    /// Returns the workspace that new windows are placed into
+    /// when no workspace is specified
+    pub fn active_workspace(&self) -> String {
+        self.active_workspace.borrow().clone()
+    }
+
+    pub fn set_active_workspace(&self, workspace: &str) {
+        *self.active_workspace.borrow_mut() = workspace.to_string();
+    }
+
+    /// Returns the names of the workspaces along with the number of
+    /// windows in each of them, sorted by name.  The active workspace
+    /// is always included, even if it has no windows.
+    pub fn iter_workspaces(&self) -> Vec<(String, usize)> {
+        let mut workspaces: std::collections::BTreeMap<String, usize> = Default::default();
+        workspaces.insert(self.active_workspace(), 0);
+        for window in self.windows.borrow().values() {
+            *workspaces
+                .entry(window.get_workspace().to_string())
+                .or_insert(0) += 1;
+        }
+        workspaces.into_iter().collect()
+    }
+
+    /// Moves all of the windows in the workspace named `old_workspace`
+    /// into `new_workspace`, updating the active workspace if it was
+    /// the one that was renamed
+    pub fn rename_workspace(&self, old_workspace: &str, new_workspace: &str) -> anyhow::Result<()> {
+        if new_workspace.is_empty() {
+            anyhow::bail!("workspace name must not be empty");
+        }
+        let mut found = false;
+        for window in self.windows.borrow_mut().values_mut() {
+            if window.get_workspace() == old_workspace {
+                window.set_workspace(new_workspace);
+                found = true;
+            }
+        }
+        if *self.active_workspace.borrow() == old_workspace {
+            self.set_active_workspace(new_workspace);
+            found = true;
+        }
+        if !found {
+            anyhow::bail!("no such workspace {}", old_workspace);
+        }
+        Ok(())
+    }
+
+    /// Moves the specified window into a different workspace
+    pub fn set_window_workspace(&self, window_id: WindowId, workspace: &str) -> anyhow::Result<()> {
+        if workspace.is_empty() {
+            anyhow::bail!("workspace name must not be empty");
+        }
+        self.get_window_mut(window_id)
+            .ok_or_else(|| anyhow!("no such window {}", window_id))?
+            .set_workspace(workspace);
+        Ok(())
+    }
+
+    /// Creates a window in the specified workspace, or in the active
+    /// workspace if none is specified
+    pub fn new_empty_window(&self, workspace: Option<String>) -> MuxWindowBuilder {
+        let window = Window::new(workspace.unwrap_or_else(|| self.active_workspace()));
         let window_id = window.window_id();
         self.windows.borrow_mut().insert(window_id, window);
         MuxWindowBuilder {
@@ -532,6 +773,26 @@ impl Mux {
         self.domains.borrow().values().cloned().collect()
     }
 
+    /// Returns true if the pane is displayed in the active tab of its
+    /// window, taking zoom into account.  Panes that are not part of
+    /// any tab (for example, overlays) are considered to be visible.
+    pub fn is_pane_visible(&self, pane_id: PaneId) -> bool {
+        let mut found = false;
+        for window in self.windows.borrow().values() {
+            for (idx, tab) in window.iter().enumerate() {
+                if !tab.contains_pane(pane_id) {
+                    continue;
+                }
+                if idx != window.get_active_idx() {
+                    found = true;
+                    continue;
+                }
+                return tab.iter_panes().iter().any(|p| p.pane.pane_id() == pane_id);
+            }
+        }
+        !found
+    }
+
     pub fn resolve_pane_id(&self, pane_id: PaneId) -> Option<(DomainId, WindowId, TabId)> {
         let mut ids = None;
         for tab in self.tabs.borrow().values() {
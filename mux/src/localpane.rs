@@ -1,5 +1,7 @@
 use crate::domain::DomainId;
-use crate::pane::{Pane, PaneId, Pattern, SearchResult};
+use crate::pane::{
+    Bookmark, ForegroundProcessInfo, Pane, PaneId, Pattern, ReadOnlyWriter, SearchResult,
+};
 use crate::renderable::*;
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
@@ -9,14 +11,15 @@ use config::keyassignment::ScrollbackEraseMode;
 use config::{configuration, ExitBehavior};
 use portable_pty::{Child, MasterPty, PtySize};
 use rangeset::RangeSet;
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::HashSet;
 use std::ops::Range;
 use std::sync::Arc;
+use termwiz::escape::osc::Progress;
 use termwiz::escape::DeviceControlMode;
 use termwiz::surface::Line;
 use url::Url;
-use wezterm_term::color::ColorPalette;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use wezterm_term::{
     Alert, AlertHandler, CellAttributes, Clipboard, KeyCode, KeyModifiers, MouseEvent,
     SemanticZone, StableRowIndex, Terminal,
@@ -42,6 +45,12 @@ pub struct LocalPane {
     pty: RefCell<Box<dyn MasterPty>>,
     domain_id: DomainId,
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
+    read_only: Cell<bool>,
+    read_only_writer: RefCell<ReadOnlyWriter>,
+    bookmarks: RefCell<Vec<Bookmark>>,
+    /// Some while output is paused, holding the actions that
+    /// have been held back since then
+    paused_output: RefCell<Option<Vec<termwiz::escape::Action>>>,
 }
 
 #[async_trait(?Send)]
@@ -146,14 +155,41 @@ impl Pane for LocalPane {
     }
 
     fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
+        let mut paused_output = self.paused_output.borrow_mut();
+        let actions = match paused_output.as_mut() {
+            Some(held) => {
+                held.extend(actions);
+                let limit = configuration().pause_output_limit;
+                if held.len() < limit {
+                    return;
+                }
+                // Don't hold back an unbounded amount of output
+                log::warn!(
+                    "resuming output of pane {} after holding back {} actions",
+                    self.pane_id,
+                    held.len()
+                );
+                paused_output.take().unwrap_or_default()
+            }
+            None => actions,
+        };
+        drop(paused_output);
         self.terminal.borrow_mut().perform_actions(actions)
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
+        if self.read_only.get() {
+            // Mouse reports would reach the application, so
+            // they are dropped along with the keyboard input
+            return Ok(());
+        }
         self.terminal.borrow_mut().mouse_event(event)
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
+        if self.read_only.get() {
+            anyhow::bail!("pane {} is read-only", self.pane_id);
+        }
         if self.tmux_domain.borrow().is_some() {
             log::error!("key: {:?}", key);
             if key == KeyCode::Char('q') {
@@ -177,6 +213,9 @@ impl Pane for LocalPane {
     }
 
     fn writer(&self) -> RefMut<dyn std::io::Write> {
+        if self.read_only.get() {
+            return self.read_only_writer.borrow_mut();
+        }
         self.pty.borrow_mut()
     }
 
@@ -185,6 +224,9 @@ impl Pane for LocalPane {
     }
 
     fn send_paste(&self, text: &str) -> Result<(), Error> {
+        if self.read_only.get() {
+            anyhow::bail!("pane {} is read-only", self.pane_id);
+        }
         if self.tmux_domain.borrow().is_some() {
             Ok(())
         } else {
@@ -196,6 +238,14 @@ impl Pane for LocalPane {
         self.terminal.borrow_mut().get_title().to_string()
     }
 
+    fn has_explicit_title(&self) -> bool {
+        if self.tmux_domain.borrow().is_some() {
+            true
+        } else {
+            self.terminal.borrow().has_explicit_title()
+        }
+    }
+
     fn palette(&self) -> ColorPalette {
         self.terminal.borrow().palette()
     }
@@ -243,6 +293,110 @@ impl Pane for LocalPane {
             .or_else(|| self.divine_current_working_dir())
     }
 
+    fn is_reading_password(&self) -> bool {
+        if self.tmux_domain.borrow().is_some() {
+            return false;
+        }
+        match self.pty.borrow().is_reading_password() {
+            Some(reading) => reading,
+            // ConPTY doesn't expose the input mode of the program
+            // attached to it, so look at the prompt instead
+            None => self.cursor_follows_password_prompt(),
+        }
+    }
+
+    fn get_foreground_process_name(&self) -> Option<String> {
+        if self.tmux_domain.borrow().is_some() {
+            return None;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            return self.divine_foreground_process_name_linux();
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            return self.divine_foreground_process_name_macos();
+        }
+
+        #[allow(unreachable_code)]
+        None
+    }
+
+    fn get_foreground_process_info(&self) -> Option<ForegroundProcessInfo> {
+        if self.tmux_domain.borrow().is_some() {
+            return None;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let pid = self.pty.borrow().process_group_leader()?;
+            return Some(foreground_process_info_linux(pid));
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let pid = self.pty.borrow().process_group_leader()?;
+            return Some(ForegroundProcessInfo {
+                pid: pid as u32,
+                executable: executable_path_macos(pid),
+                argv: argv_macos(pid),
+                cwd: current_working_dir_macos(pid),
+            });
+        }
+
+        #[allow(unreachable_code)]
+        None
+    }
+
+    fn get_background_tint(&self) -> Option<RgbColor> {
+        self.terminal.borrow().get_background_tint()
+    }
+
+    fn set_background_tint(&self, tint: Option<RgbColor>) {
+        self.terminal.borrow_mut().set_background_tint(tint);
+    }
+
+    fn is_ime_enabled(&self) -> bool {
+        self.terminal.borrow().is_ime_enabled()
+    }
+
+    fn set_ime_enabled(&self, enabled: bool) {
+        self.terminal.borrow_mut().set_ime_enabled(enabled);
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only.get()
+    }
+
+    fn set_read_only(&self, read_only: bool) {
+        self.read_only.set(read_only);
+    }
+
+    fn is_output_paused(&self) -> bool {
+        self.paused_output.borrow().is_some()
+    }
+
+    fn set_output_paused(&self, paused: bool) {
+        let mut paused_output = self.paused_output.borrow_mut();
+        if paused {
+            paused_output.get_or_insert_with(Vec::new);
+            return;
+        }
+        if let Some(held) = paused_output.take() {
+            drop(paused_output);
+            self.terminal.borrow_mut().perform_actions(held);
+            if let Some(mux) = Mux::get() {
+                mux.notify(MuxNotification::PaneOutput(self.pane_id));
+            }
+        }
+    }
+
+    fn get_progress(&self) -> Progress {
+        self.terminal.borrow().get_progress()
+    }
+
     fn can_close_without_prompting(&self) -> bool {
         let proc_list = self.divine_process_list();
         if !proc_list.is_empty() {
@@ -281,6 +435,24 @@ impl Pane for LocalPane {
         term.get_semantic_zones()
     }
 
+    async fn get_bookmarks(&self) -> anyhow::Result<Vec<Bookmark>> {
+        // Forget the bookmarks whose rows have been trimmed
+        // from the scrollback
+        let scrollback_top = self.get_dimensions().scrollback_top;
+        let mut bookmarks = self.bookmarks.borrow_mut();
+        bookmarks.retain(|bookmark| bookmark.row >= scrollback_top);
+        Ok(bookmarks.clone())
+    }
+
+    async fn set_bookmark(&self, name: String, row: Option<StableRowIndex>) -> anyhow::Result<()> {
+        let mut bookmarks = self.bookmarks.borrow_mut();
+        bookmarks.retain(|bookmark| bookmark.name != name);
+        if let Some(row) = row {
+            bookmarks.push(Bookmark { name, row });
+        }
+        Ok(())
+    }
+
     async fn search(&self, mut pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
         let term = self.terminal.borrow();
         let screen = term.screen();
@@ -494,79 +666,18 @@ impl LocalPane {
             pty: RefCell::new(pty),
             domain_id,
             tmux_domain: RefCell::new(None),
+            read_only: Cell::new(false),
+            read_only_writer: RefCell::new(ReadOnlyWriter::new(pane_id)),
+            bookmarks: RefCell::new(vec![]),
+            paused_output: RefCell::new(None),
         }
     }
 
     #[cfg(target_os = "macos")]
     fn divine_current_working_dir_macos(&self) -> Option<Url> {
-        if let Some(pid) = self.pty.borrow().process_group_leader() {
-            extern "C" {
-                fn proc_pidinfo(
-                    pid: libc::pid_t,
-                    flavor: libc::c_int,
-                    arg: u64,
-                    buffer: *mut proc_vnodepathinfo,
-                    buffersize: libc::c_int,
-                ) -> libc::c_int;
-            }
-            const PROC_PIDVNODEPATHINFO: libc::c_int = 9;
-            #[repr(C)]
-            struct vinfo_stat {
-                vst_dev: u32,
-                vst_mode: u16,
-                vst_nlink: u16,
-                vst_ino: u64,
-                vst_uid: libc::uid_t,
-                vst_gid: libc::gid_t,
-                vst_atime: i64,
-                vst_atimensec: i64,
-                vst_mtime: i64,
-                vst_mtimensec: i64,
-                vst_ctime: i64,
-                vst_ctimensec: i64,
-                vst_birthtime: i64,
-                vst_birthtimensec: i64,
-                vst_size: libc::off_t,
-                vst_blocks: i64,
-                vst_blksize: i32,
-                vst_flags: u32,
-                vst_gen: u32,
-                vst_rdev: u32,
-                vst_qspare_1: i64,
-                vst_qspare_2: i64,
-            }
-            #[repr(C)]
-            struct vnode_info {
-                vi_stat: vinfo_stat,
-                vi_type: libc::c_int,
-                vi_pad: libc::c_int,
-                vi_fsid: libc::fsid_t,
-            }
-
-            const MAXPATHLEN: usize = 1024;
-            #[repr(C)]
-            struct vnode_info_path {
-                vip_vi: vnode_info,
-                vip_path: [i8; MAXPATHLEN],
-            }
-
-            #[repr(C)]
-            struct proc_vnodepathinfo {
-                pvi_cdir: vnode_info_path,
-                pvi_rdir: vnode_info_path,
-            }
-
-            let mut pathinfo: proc_vnodepathinfo = unsafe { std::mem::zeroed() };
-            let size = std::mem::size_of_val(&pathinfo) as libc::c_int;
-            let ret = unsafe { proc_pidinfo(pid, PROC_PIDVNODEPATHINFO, 0, &mut pathinfo, size) };
-            if ret == size {
-                let path = unsafe { std::ffi::CStr::from_ptr(pathinfo.pvi_cdir.vip_path.as_ptr()) };
-                if let Ok(s) = path.to_str() {
-                    return Url::parse(&format!("file://localhost{}", s)).ok();
-                }
-            }
-        }
-        None
+        let pid = self.pty.borrow().process_group_leader()?;
+        let path = current_working_dir_macos(pid)?;
+        Url::parse(&format!("file://localhost{}", path.display())).ok()
     }
 
     #[cfg(target_os = "linux")]
@@ -594,6 +705,42 @@ impl LocalPane {
         None
     }
 
+    #[cfg(target_os = "linux")]
+    fn divine_foreground_process_name_linux(&self) -> Option<String> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        if let Ok(path) = std::fs::read_link(format!("/proc/{}/exe", pid)) {
+            if let Some(name) = path.file_name() {
+                return Some(name.to_string_lossy().into_owned());
+            }
+        }
+        // The exe link isn't readable for processes that we don't own,
+        // but the command name is
+        std::fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|comm| comm.trim_end().to_string())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn divine_foreground_process_name_macos(&self) -> Option<String> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        let path = executable_path_macos(pid)?;
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
+    /// Returns true if the text to the left of the cursor looks
+    /// like a prompt for a password or passphrase
+    fn cursor_follows_password_prompt(&self) -> bool {
+        let cursor = self.get_cursor_position();
+        let (_, lines) = self.get_lines(cursor.y..cursor.y + 1);
+        let prompt = match lines.first() {
+            Some(line) => line.columns_as_str(0..cursor.x).to_lowercase(),
+            None => return false,
+        };
+        let prompt = prompt.trim_end();
+        prompt.ends_with(':') && (prompt.contains("password") || prompt.contains("passphrase"))
+    }
+
     fn divine_process_list(&self) -> Vec<String> {
         #[allow(unused_mut)]
         let mut proc_names = vec![];
@@ -628,6 +775,173 @@ impl LocalPane {
     }
 }
 
+#[cfg(target_os = "linux")]
+fn foreground_process_info_linux(pid: libc::pid_t) -> ForegroundProcessInfo {
+    let argv = std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|cmdline| {
+            cmdline
+                .split(|&b| b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    ForegroundProcessInfo {
+        pid: pid as u32,
+        // These links aren't readable for processes that we don't own
+        executable: std::fs::read_link(format!("/proc/{}/exe", pid)).ok(),
+        argv,
+        cwd: std::fs::read_link(format!("/proc/{}/cwd", pid)).ok(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn executable_path_macos(pid: libc::pid_t) -> Option<std::path::PathBuf> {
+    extern "C" {
+        fn proc_pidpath(
+            pid: libc::pid_t,
+            buffer: *mut libc::c_void,
+            buffersize: u32,
+        ) -> libc::c_int;
+    }
+    const PROC_PIDPATHINFO_MAXSIZE: usize = 4096;
+    let mut buffer = vec![0u8; PROC_PIDPATHINFO_MAXSIZE];
+    let len = unsafe {
+        proc_pidpath(
+            pid,
+            buffer.as_mut_ptr() as *mut _,
+            PROC_PIDPATHINFO_MAXSIZE as u32,
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    buffer.truncate(len as usize);
+    Some(std::path::PathBuf::from(
+        String::from_utf8_lossy(&buffer).into_owned(),
+    ))
+}
+
+/// Returns the arguments of the process, which the kernel stores
+/// after the argument count and the path to the executable
+#[cfg(target_os = "macos")]
+fn argv_macos(pid: libc::pid_t) -> Vec<String> {
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+    let mut size: libc::size_t = 0;
+    let res = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as libc::c_uint,
+            std::ptr::null_mut(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if res != 0 {
+        return vec![];
+    }
+    let mut buffer = vec![0u8; size];
+    let res = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as libc::c_uint,
+            buffer.as_mut_ptr() as *mut _,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    let int_size = std::mem::size_of::<libc::c_int>();
+    if res != 0 || size < int_size {
+        return vec![];
+    }
+    buffer.truncate(size);
+
+    let mut argc = [0u8; 4];
+    argc.copy_from_slice(&buffer[..int_size]);
+    let argc = libc::c_int::from_ne_bytes(argc) as usize;
+
+    // Skip the path to the executable and the padding that follows it
+    buffer[int_size..]
+        .split(|&b| b == 0)
+        .skip(1)
+        .skip_while(|field| field.is_empty())
+        .take(argc)
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn current_working_dir_macos(pid: libc::pid_t) -> Option<std::path::PathBuf> {
+    extern "C" {
+        fn proc_pidinfo(
+            pid: libc::pid_t,
+            flavor: libc::c_int,
+            arg: u64,
+            buffer: *mut proc_vnodepathinfo,
+            buffersize: libc::c_int,
+        ) -> libc::c_int;
+    }
+    const PROC_PIDVNODEPATHINFO: libc::c_int = 9;
+    #[repr(C)]
+    struct vinfo_stat {
+        vst_dev: u32,
+        vst_mode: u16,
+        vst_nlink: u16,
+        vst_ino: u64,
+        vst_uid: libc::uid_t,
+        vst_gid: libc::gid_t,
+        vst_atime: i64,
+        vst_atimensec: i64,
+        vst_mtime: i64,
+        vst_mtimensec: i64,
+        vst_ctime: i64,
+        vst_ctimensec: i64,
+        vst_birthtime: i64,
+        vst_birthtimensec: i64,
+        vst_size: libc::off_t,
+        vst_blocks: i64,
+        vst_blksize: i32,
+        vst_flags: u32,
+        vst_gen: u32,
+        vst_rdev: u32,
+        vst_qspare_1: i64,
+        vst_qspare_2: i64,
+    }
+    #[repr(C)]
+    struct vnode_info {
+        vi_stat: vinfo_stat,
+        vi_type: libc::c_int,
+        vi_pad: libc::c_int,
+        vi_fsid: libc::fsid_t,
+    }
+
+    const MAXPATHLEN: usize = 1024;
+    #[repr(C)]
+    struct vnode_info_path {
+        vip_vi: vnode_info,
+        vip_path: [i8; MAXPATHLEN],
+    }
+
+    #[repr(C)]
+    struct proc_vnodepathinfo {
+        pvi_cdir: vnode_info_path,
+        pvi_rdir: vnode_info_path,
+    }
+
+    let mut pathinfo: proc_vnodepathinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of_val(&pathinfo) as libc::c_int;
+    let ret = unsafe { proc_pidinfo(pid, PROC_PIDVNODEPATHINFO, 0, &mut pathinfo, size) };
+    if ret == size {
+        let path = unsafe { std::ffi::CStr::from_ptr(pathinfo.pvi_cdir.vip_path.as_ptr()) };
+        if let Ok(s) = path.to_str() {
+            return Some(std::path::PathBuf::from(s));
+        }
+    }
+    None
+}
+
 fn bounded_kill_wait(child: &mut Box<dyn Child + 'static>) {
     for attempt in 0..5 {
         let _ = child.kill();
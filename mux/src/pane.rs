@@ -9,11 +9,13 @@ use rangeset::RangeSet;
 use serde::{Deserialize, Serialize};
 use std::cell::RefMut;
 use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use termwiz::escape::osc::Progress;
 use termwiz::hyperlink::Rule;
 use termwiz::surface::Line;
 use url::Url;
-use wezterm_term::color::ColorPalette;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use wezterm_term::{Clipboard, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex};
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -23,6 +25,15 @@ pub fn alloc_pane_id() -> PaneId {
     PANE_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed)
 }
 
+/// A named position in the scrollback of a pane
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    /// The row that was at the top of the viewport when the
+    /// bookmark was added
+    pub row: StableRowIndex,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct SearchResult {
     pub start_y: StableRowIndex,
@@ -35,6 +46,42 @@ pub struct SearchResult {
 
 pub use config::keyassignment::Pattern;
 
+/// The writer that is returned in place of the pty of a read-only
+/// pane; it rejects everything that is written to it
+pub struct ReadOnlyWriter {
+    pane_id: PaneId,
+}
+
+impl ReadOnlyWriter {
+    pub fn new(pane_id: PaneId) -> Self {
+        Self { pane_id }
+    }
+}
+
+impl std::io::Write for ReadOnlyWriter {
+    fn write(&mut self, _data: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("pane {} is read-only", self.pane_id),
+        ))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Describes the foreground process of a pane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForegroundProcessInfo {
+    pub pid: u32,
+    /// The path to the executable, which may not be known if
+    /// the process belongs to another user
+    pub executable: Option<PathBuf>,
+    pub argv: Vec<String>,
+    pub cwd: Option<PathBuf>,
+}
+
 const PASTE_CHUNK_SIZE: usize = 1024;
 
 struct Paste {
@@ -262,6 +309,14 @@ pub trait Pane: Downcast {
     fn get_dimensions(&self) -> RenderableDimensions;
 
     fn get_title(&self) -> String;
+
+    /// Returns true if the application in the pane has set its title.
+    /// When false, the gui may derive a title from the foreground
+    /// process and working directory instead.
+    fn has_explicit_title(&self) -> bool {
+        true
+    }
+
     fn send_paste(&self, text: &str) -> anyhow::Result<()>;
     fn reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>>;
     fn writer(&self) -> RefMut<dyn std::io::Write>;
@@ -299,6 +354,22 @@ pub trait Pane: Downcast {
         Ok(vec![])
     }
 
+    /// Returns the bookmarks that are still within the scrollback,
+    /// in the order that they were added
+    async fn get_bookmarks(&self) -> anyhow::Result<Vec<Bookmark>> {
+        Ok(vec![])
+    }
+
+    /// Bookmarks `row` as `name`, replacing any existing bookmark of
+    /// that name.  If `row` is None then the bookmark is removed.
+    async fn set_bookmark(
+        &self,
+        _name: String,
+        _row: Option<StableRowIndex>,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!("pane {} doesn't support bookmarks", self.pane_id())
+    }
+
     /// Returns true if the terminal has grabbed the mouse and wants to
     /// give the embedded application a chance to process events.
     /// In practice this controls whether the gui will perform local
@@ -310,6 +381,63 @@ pub trait Pane: Downcast {
 
     fn get_current_working_dir(&self) -> Option<Url>;
 
+    /// Returns the name of the executable of the foreground
+    /// process in the pane, if it can be determined
+    fn get_foreground_process_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the pid, executable, arguments and working directory
+    /// of the foreground process in the pane, if they can be determined
+    fn get_foreground_process_info(&self) -> Option<ForegroundProcessInfo> {
+        None
+    }
+
+    /// Returns true if the program in the pane appears to be
+    /// reading a password, so that input isn't echoed
+    fn is_reading_password(&self) -> bool {
+        false
+    }
+
+    /// Returns the color with which the background of the pane
+    /// is tinted, if any
+    fn get_background_tint(&self) -> Option<RgbColor> {
+        None
+    }
+
+    fn set_background_tint(&self, _tint: Option<RgbColor>) {}
+
+    /// Returns true if the input method should be enabled
+    /// while the pane is active
+    fn is_ime_enabled(&self) -> bool {
+        true
+    }
+
+    fn set_ime_enabled(&self, _enabled: bool) {}
+
+    /// Returns true if keyboard input and pastes directed
+    /// at the pane are rejected
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn set_read_only(&self, _read_only: bool) {}
+
+    /// Returns true if new output is being held back rather
+    /// than applied to the terminal model
+    fn is_output_paused(&self) -> bool {
+        false
+    }
+
+    /// Pauses or resumes applying new output to the pane.
+    /// The output that was held back is applied when it resumes.
+    fn set_output_paused(&self, _paused: bool) {}
+
+    /// Returns the progress reported by the application, if any
+    fn get_progress(&self) -> Progress {
+        Progress::None
+    }
+
     fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
         if text.len() <= PASTE_CHUNK_SIZE {
             // Send it all now
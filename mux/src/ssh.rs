@@ -1,5 +1,5 @@
 use crate::connui::ConnectionUI;
-use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
+use crate::domain::{alloc_domain_id, ConnectionStatus, Domain, DomainId, DomainState};
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::tab::{SplitDirection, Tab, TabId};
@@ -148,6 +148,7 @@ pub struct RemoteSshDomain {
     session: Session,
     id: DomainId,
     name: String,
+    remote_host: Option<String>,
     events: RefCell<Option<smol::channel::Receiver<SessionEvent>>>,
 }
 
@@ -158,6 +159,7 @@ impl RemoteSshDomain {
         Ok(Self {
             id,
             name: format!("SSH to {}", name),
+            remote_host: ssh_config.get("hostname").cloned(),
             session,
             events: RefCell::new(Some(events)),
         })
@@ -650,6 +652,15 @@ impl Domain for RemoteSshDomain {
     fn state(&self) -> DomainState {
         DomainState::Attached
     }
+
+    fn connection_status(&self) -> Option<ConnectionStatus> {
+        Some(ConnectionStatus {
+            remote_host: self.remote_host.clone(),
+            connected: true,
+            responsive: true,
+            latency: None,
+        })
+    }
 }
 
 #[derive(Debug)]
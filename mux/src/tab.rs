@@ -153,14 +153,19 @@ fn pane_tree(
     tree: &Tree,
     tab_id: TabId,
     window_id: WindowId,
+    workspace: &str,
     active: Option<&Rc<dyn Pane>>,
     zoomed: Option<&Rc<dyn Pane>>,
 ) -> PaneNode {
     match tree {
         Tree::Empty => PaneNode::Empty,
         Tree::Node { left, right, data } => PaneNode::Split {
-            left: Box::new(pane_tree(&*left, tab_id, window_id, active, zoomed)),
-            right: Box::new(pane_tree(&*right, tab_id, window_id, active, zoomed)),
+            left: Box::new(pane_tree(
+                &*left, tab_id, window_id, workspace, active, zoomed,
+            )),
+            right: Box::new(pane_tree(
+                &*right, tab_id, window_id, workspace, active, zoomed,
+            )),
             node: data.unwrap(),
         },
         Tree::Leaf(pane) => {
@@ -170,6 +175,7 @@ fn pane_tree(
             PaneNode::Leaf(PaneEntry {
                 window_id,
                 tab_id,
+                workspace: workspace.to_string(),
                 pane_id: pane.pane_id(),
                 title: pane.get_title(),
                 is_active_pane: is_pane(pane, &active),
@@ -483,10 +489,22 @@ impl Tab {
             }
         };
 
+        let workspace = match mux.get_window(window_id) {
+            Some(window) => window.get_workspace().to_string(),
+            None => crate::DEFAULT_WORKSPACE.to_string(),
+        };
+
         let zoomed = self.zoomed.borrow();
         let active = self.get_active_pane();
         if let Some(root) = self.pane.borrow().as_ref() {
-            pane_tree(root, tab_id, window_id, active.as_ref(), zoomed.as_ref())
+            pane_tree(
+                root,
+                tab_id,
+                window_id,
+                &workspace,
+                active.as_ref(),
+                zoomed.as_ref(),
+            )
         } else {
             PaneNode::Empty
         }
@@ -1453,6 +1471,18 @@ impl PaneNode {
             PaneNode::Leaf(entry) => Some((entry.window_id, entry.tab_id)),
         }
     }
+
+    /// Returns the workspace of the window that contains this tab
+    pub fn workspace(&self) -> Option<&str> {
+        match self {
+            PaneNode::Empty => None,
+            PaneNode::Split { left, right, .. } => match left.workspace() {
+                Some(res) => Some(res),
+                None => right.workspace(),
+            },
+            PaneNode::Leaf(entry) => Some(&entry.workspace),
+        }
+    }
 }
 
 /// This type is used directly by the codec, take care to bump
@@ -1461,6 +1491,7 @@ impl PaneNode {
 pub struct PaneEntry {
     pub window_id: WindowId,
     pub tab_id: TabId,
+    pub workspace: String,
     pub pane_id: PaneId,
     pub title: String,
     pub size: PtySize,
@@ -110,6 +110,15 @@ pub trait MasterPty: std::io::Write {
     /// of the process group or session leader
     #[cfg(unix)]
     fn process_group_leader(&self) -> Option<libc::pid_t>;
+
+    /// Returns true if the tty is in canonical (line editing) mode with
+    /// local echo disabled, which is how programs such as `sudo` and `ssh`
+    /// read passwords.  Full screen programs also disable echo, but they
+    /// disable canonical mode too, so they don't match.
+    /// Returns None if this can't be determined for this type of tty.
+    fn is_reading_password(&self) -> Option<bool> {
+        None
+    }
 }
 
 /// Represents a child process spawned into the pty.
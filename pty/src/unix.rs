@@ -325,6 +325,14 @@ impl MasterPty for UnixMasterPty {
             _ => None,
         }
     }
+
+    fn is_reading_password(&self) -> Option<bool> {
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(self.fd.0.as_raw_fd(), &mut termios) } != 0 {
+            return None;
+        }
+        Some(termios.c_lflag & libc::ICANON != 0 && termios.c_lflag & libc::ECHO == 0)
+    }
 }
 
 impl Write for UnixMasterPty {
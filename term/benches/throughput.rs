@@ -0,0 +1,128 @@
+//! Measures how quickly escape sequences are parsed and applied
+//! to the terminal model.
+//! Run with `cargo bench -p wezterm-term`.
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::sync::Arc;
+use termwiz::escape::parser::Parser;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{Terminal, TerminalConfiguration, TerminalSize};
+
+#[derive(Debug)]
+struct BenchConfig;
+
+impl TerminalConfiguration for BenchConfig {
+    fn scrollback_size(&self) -> usize {
+        3500
+    }
+
+    fn color_palette(&self) -> ColorPalette {
+        ColorPalette::default()
+    }
+}
+
+fn new_terminal() -> Terminal {
+    Terminal::new(
+        TerminalSize {
+            physical_rows: 24,
+            physical_cols: 80,
+            pixel_width: 80 * 8,
+            pixel_height: 24 * 16,
+        },
+        Arc::new(BenchConfig),
+        "WezTerm",
+        "O_o",
+        Box::new(std::io::sink()),
+    )
+}
+
+/// Plain ASCII lines, as produced by eg: `cat` of a source file
+fn plain_text(num_lines: usize) -> Vec<u8> {
+    let mut data = vec![];
+    for i in 0..num_lines {
+        data.extend_from_slice(
+            format!("{:6} the quick brown fox jumps over the lazy dog\r\n", i).as_bytes(),
+        );
+    }
+    data
+}
+
+/// Lines with frequent SGR changes and unicode, as produced by
+/// eg: a colorized `ls` or a compiler with colored diagnostics
+fn styled_text(num_lines: usize) -> Vec<u8> {
+    let mut data = vec![];
+    for i in 0..num_lines {
+        let color = 31 + (i % 7);
+        data.extend_from_slice(
+            format!(
+                "\x1b[1;{}m{:6}\x1b[0m \x1b[4mhello\x1b[24m wörld \u{1f600} \x1b[38;2;{};{};200mtrue color\x1b[0m\r\n",
+                color,
+                i,
+                i % 256,
+                (i * 7) % 256
+            )
+            .as_bytes(),
+        );
+    }
+    data
+}
+
+/// Full screen updates that move the cursor around, as produced
+/// by eg: `top` or a text editor
+fn cursor_motion(num_frames: usize) -> Vec<u8> {
+    let mut data = vec![];
+    for frame in 0..num_frames {
+        data.extend_from_slice(b"\x1b[H");
+        for row in 1..=24 {
+            data.extend_from_slice(
+                format!(
+                    "\x1b[{};1H\x1b[2K\x1b[7m{:3}\x1b[27m frame {}",
+                    row, row, frame
+                )
+                .as_bytes(),
+            );
+        }
+    }
+    data
+}
+
+fn workloads() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("plain", plain_text(10_000)),
+        ("styled", styled_text(10_000)),
+        ("cursor_motion", cursor_motion(500)),
+    ]
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, data) in workloads() {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut parser = Parser::new();
+                let mut actions = 0;
+                parser.parse(&data, |_| actions += 1);
+                actions
+            })
+        });
+    }
+    group.finish();
+}
+
+fn parse_and_apply(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_and_apply");
+    for (name, data) in workloads() {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                new_terminal,
+                |mut term| term.advance_bytes(&data),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, parse_and_apply);
+criterion_main!(benches);
@@ -1,5 +1,7 @@
 //! Colors for attributes
 
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::result::Result;
 pub use termwiz::color::{AnsiColor, ColorAttribute, RgbColor, RgbaTuple};
@@ -17,7 +19,8 @@ impl std::iter::FromIterator<RgbColor> for Palette256 {
     }
 }
 
-#[derive(Clone, Debug)]
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ColorPalette {
     pub colors: Palette256,
     pub foreground: RgbColor,
@@ -49,6 +52,59 @@ fn grey_out(color: RgbColor) -> RgbColor {
     RgbColor::new(result.red, result.green, result.blue)
 }
 
+/// Returns the color that is `t`, between 0.0 and 1.0, of the way from
+/// `from` to `to`.  The colors are interpolated in the CIE L*a*b* color
+/// space so that the perceived lightness changes evenly.
+pub fn interpolate(from: RgbColor, to: RgbColor, t: f32) -> RgbColor {
+    use palette::{Lab, Mix, Srgba};
+    let to_lab = |color: RgbColor| {
+        let color = Srgba::new(color.red, color.green, color.blue, 0xff);
+        let color: Srgba = color.into_format();
+        Lab::from(color.into_linear())
+    };
+
+    let mixed = to_lab(from).mix(&to_lab(to), t);
+    let result = Srgba::from_linear(mixed.into());
+    let result = Srgba::<u8>::from_format(result);
+
+    RgbColor::new(result.red, result.green, result.blue)
+}
+
+impl PartialEq for Palette256 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0[..] == other.0[..]
+    }
+}
+
+/// serde doesn't support arrays of this size, so the
+/// colors are serialized as a sequence
+#[cfg(feature = "use_serde")]
+impl Serialize for Palette256 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0[..].serialize(serializer)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> Deserialize<'de> for Palette256 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let colors = Vec::<RgbColor>::deserialize(deserializer)?;
+        if colors.len() != 256 {
+            return Err(serde::de::Error::invalid_length(
+                colors.len(),
+                &"256 colors",
+            ));
+        }
+        Ok(colors.into_iter().collect())
+    }
+}
+
 impl fmt::Debug for Palette256 {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         // If we wanted to dump all of the entries, we'd use this:
@@ -92,6 +148,29 @@ impl ColorPalette {
             split: grey_out(self.split),
         }
     }
+
+    /// Returns the palette that is `t`, between 0.0 and 1.0, of the
+    /// way from this palette to `to`
+    pub fn interpolate(&self, to: &Self, t: f32) -> Self {
+        Self {
+            colors: self
+                .colors
+                .0
+                .iter()
+                .zip(to.colors.0.iter())
+                .map(|(&from, &to)| interpolate(from, to, t))
+                .collect(),
+            foreground: interpolate(self.foreground, to.foreground, t),
+            background: interpolate(self.background, to.background, t),
+            cursor_fg: interpolate(self.cursor_fg, to.cursor_fg, t),
+            cursor_bg: interpolate(self.cursor_bg, to.cursor_bg, t),
+            cursor_border: interpolate(self.cursor_border, to.cursor_border, t),
+            selection_fg: interpolate(self.selection_fg, to.selection_fg, t),
+            selection_bg: interpolate(self.selection_bg, to.selection_bg, t),
+            scrollbar_thumb: interpolate(self.scrollbar_thumb, to.scrollbar_thumb, t),
+            split: interpolate(self.split, to.split, t),
+        }
+    }
 }
 
 lazy_static::lazy_static! {
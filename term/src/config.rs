@@ -1,4 +1,5 @@
 use crate::color::ColorPalette;
+use termwiz::cell::Presentation;
 use termwiz::hyperlink::Rule as HyperlinkRule;
 
 /// TerminalConfiguration allows for the embedding application to pass configuration
@@ -69,7 +70,40 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         cfg!(windows)
     }
 
+    /// Return true if a resize that changes the width of the terminal
+    /// should erase a shell prompt that was marked up using OSC 133
+    /// semantic zones when the shell is waiting at that prompt.
+    /// The shell is expected to redraw its prompt when it receives
+    /// SIGWINCH; erasing it first avoids leaving behind a rewrapped
+    /// copy of the prompt above the redrawn one.
+    fn clear_prompt_on_resize(&self) -> bool {
+        false
+    }
+
+    /// Return true if the contents of the alternate screen should be
+    /// copied into the scrollback of the primary screen when switching
+    /// back to it, so that the output of full screen applications such
+    /// as `less` is still available after they exit.
+    fn capture_alternate_screen(&self) -> bool {
+        false
+    }
+
+    /// Return true if the time at which text is first printed to
+    /// each line should be recorded, so that the embedding application
+    /// can display when output arrived.
+    fn record_line_timestamps(&self) -> bool {
+        false
+    }
+
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         3
     }
+
+    /// If set, emoji that are printed without a variation selector
+    /// are displayed using this presentation rather than their
+    /// default presentation.  This affects both the font used to
+    /// display them and the number of cells that they occupy.
+    fn emoji_presentation(&self) -> Option<Presentation> {
+        None
+    }
 }
@@ -0,0 +1,485 @@
+//! Serializes lines from the terminal into formats that retain
+//! the colors and text attributes, so that they can be shared
+//! with applications outside of the terminal.
+use crate::color::ColorPalette;
+use std::fmt::Write;
+use std::ops::Range;
+use termwiz::cell::{unicode_column_width, Intensity, Underline};
+use termwiz::color::RgbColor;
+use termwiz::surface::Line;
+
+/// The fully resolved presentation of a run of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunStyle {
+    pub foreground: RgbColor,
+    pub background: RgbColor,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+/// A span of text that shares the same style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledRun {
+    pub style: RunStyle,
+    pub text: String,
+}
+
+/// Walks the cells in the `cols` range of `line` and groups them
+/// into runs of identically styled text, along with the range of
+/// columns occupied by each run.
+fn column_runs(
+    line: &Line,
+    cols: Range<usize>,
+    palette: &ColorPalette,
+) -> Vec<(Range<usize>, StyledRun)> {
+    let mut runs: Vec<(Range<usize>, StyledRun)> = vec![];
+    for (idx, cell) in line.visible_cells() {
+        if idx < cols.start {
+            continue;
+        }
+        if idx >= cols.end {
+            break;
+        }
+        let attrs = cell.attrs();
+        let mut foreground = palette.resolve_fg(attrs.foreground);
+        let mut background = palette.resolve_bg(attrs.background);
+        if attrs.reverse() {
+            std::mem::swap(&mut foreground, &mut background);
+        }
+        if attrs.invisible() {
+            foreground = background;
+        }
+        let style = RunStyle {
+            foreground,
+            background,
+            bold: attrs.intensity() == Intensity::Bold,
+            italic: attrs.italic(),
+            underline: attrs.underline() != Underline::None,
+            strikethrough: attrs.strikethrough(),
+        };
+        let end = idx + cell.width().max(1);
+
+        match runs.last_mut() {
+            Some((range, run)) if run.style == style => {
+                run.text.push_str(cell.str());
+                range.end = end;
+            }
+            _ => runs.push((
+                idx..end,
+                StyledRun {
+                    style,
+                    text: cell.str().to_string(),
+                },
+            )),
+        }
+    }
+    runs
+}
+
+/// Walks the cells in the `cols` range of `line` and groups them
+/// into runs of identically styled text.
+/// Trailing whitespace on the default background is removed.
+pub fn styled_runs(line: &Line, cols: Range<usize>, palette: &ColorPalette) -> Vec<StyledRun> {
+    let mut runs: Vec<StyledRun> = column_runs(line, cols, palette)
+        .into_iter()
+        .map(|(_, run)| run)
+        .collect();
+
+    while let Some(run) = runs.last_mut() {
+        if run.style.background != palette.background {
+            break;
+        }
+        let len = run.text.trim_end().len();
+        run.text.truncate(len);
+        if !run.text.is_empty() {
+            break;
+        }
+        runs.pop();
+    }
+
+    runs
+}
+
+/// Groups the lines into logical lines of styled runs, joining
+/// together lines that were wrapped by the terminal.
+fn logical_lines(lines: &[(Line, Range<usize>)], palette: &ColorPalette) -> Vec<Vec<StyledRun>> {
+    let mut result = vec![];
+    let mut current: Vec<StyledRun> = vec![];
+    for (line, cols) in lines {
+        current.extend(styled_runs(line, cols.clone(), palette));
+        let wrapped = line
+            .cells()
+            .get(cols.end.min(line.cells().len()).saturating_sub(1))
+            .map(|cell| cell.attrs().wrapped() && cell.str() != " ")
+            .unwrap_or(false);
+        if !wrapped {
+            result.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+fn html_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Produces an HTML `<pre>` fragment holding the text of the lines,
+/// using inline styles for the colors and attributes.
+/// `cols` for each line specifies the range of columns to include.
+pub fn lines_to_html(
+    lines: &[(Line, Range<usize>)],
+    palette: &ColorPalette,
+    font_family: Option<&str>,
+) -> String {
+    let mut html = String::new();
+    write!(
+        html,
+        "<pre style=\"font-family: {}monospace; color: {}; background-color: {};\">",
+        font_family
+            .map(|f| format!("'{}', ", html_escape(f)))
+            .unwrap_or_default(),
+        palette.foreground.to_rgb_string(),
+        palette.background.to_rgb_string()
+    )
+    .ok();
+
+    for (idx, runs) in logical_lines(lines, palette).iter().enumerate() {
+        if idx > 0 {
+            html.push('\n');
+        }
+        for run in runs {
+            let style = &run.style;
+            let mut css = String::new();
+            if style.foreground != palette.foreground {
+                write!(css, "color: {};", style.foreground.to_rgb_string()).ok();
+            }
+            if style.background != palette.background {
+                write!(
+                    css,
+                    "background-color: {};",
+                    style.background.to_rgb_string()
+                )
+                .ok();
+            }
+            if style.bold {
+                css.push_str("font-weight: bold;");
+            }
+            if style.italic {
+                css.push_str("font-style: italic;");
+            }
+            match (style.underline, style.strikethrough) {
+                (true, true) => css.push_str("text-decoration: underline line-through;"),
+                (true, false) => css.push_str("text-decoration: underline;"),
+                (false, true) => css.push_str("text-decoration: line-through;"),
+                (false, false) => {}
+            }
+
+            if css.is_empty() {
+                html.push_str(&html_escape(&run.text));
+            } else {
+                write!(
+                    html,
+                    "<span style=\"{}\">{}</span>",
+                    css,
+                    html_escape(&run.text)
+                )
+                .ok();
+            }
+        }
+    }
+
+    html.push_str("</pre>");
+    html
+}
+
+/// Produces a standalone HTML document holding the text of the lines,
+/// using the background color of the palette for the page.
+/// `cols` for each line specifies the range of columns to include.
+pub fn lines_to_html_document(
+    lines: &[(Line, Range<usize>)],
+    palette: &ColorPalette,
+    font_family: Option<&str>,
+    title: &str,
+) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n</head>\n\
+         <body style=\"margin: 0; background-color: {};\">\n{}\n</body>\n</html>\n",
+        html_escape(title),
+        palette.background.to_rgb_string(),
+        lines_to_html(lines, palette, font_family)
+    )
+}
+
+/// Produces an SVG image of the lines, laid out on a grid of cells
+/// in the same way as the terminal displays them.
+/// `cols` for each line specifies the range of columns to include.
+pub fn lines_to_svg(
+    lines: &[(Line, Range<usize>)],
+    palette: &ColorPalette,
+    font_family: Option<&str>,
+) -> String {
+    const FONT_SIZE: f32 = 14.0;
+    // The typical advance of a monospace font is around 0.6em
+    const CELL_WIDTH: f32 = FONT_SIZE * 0.6;
+    const LINE_HEIGHT: f32 = FONT_SIZE * 1.2;
+
+    let num_cols = lines
+        .iter()
+        .map(|(_, cols)| cols.end - cols.start)
+        .max()
+        .unwrap_or(0);
+    let width = num_cols as f32 * CELL_WIDTH;
+    let height = lines.len() as f32 * LINE_HEIGHT;
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.2}\" height=\"{h:.2}\" \
+         viewBox=\"0 0 {w:.2} {h:.2}\" font-family=\"{}monospace\" font-size=\"{}\">\n",
+        font_family
+            .map(|f| format!("'{}', ", html_escape(f)))
+            .unwrap_or_default(),
+        FONT_SIZE,
+        w = width,
+        h = height,
+    )
+    .ok();
+    write!(
+        svg,
+        "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+        palette.background.to_rgb_string()
+    )
+    .ok();
+
+    for (row, (line, cols)) in lines.iter().enumerate() {
+        let top = row as f32 * LINE_HEIGHT;
+        let runs = column_runs(line, cols.clone(), palette);
+
+        for (range, run) in &runs {
+            if run.style.background == palette.background {
+                continue;
+            }
+            write!(
+                svg,
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"/>\n",
+                (range.start - cols.start) as f32 * CELL_WIDTH,
+                top,
+                (range.end - range.start) as f32 * CELL_WIDTH,
+                LINE_HEIGHT,
+                run.style.background.to_rgb_string()
+            )
+            .ok();
+        }
+
+        for (range, run) in &runs {
+            let text = run.text.trim_end();
+            if text.is_empty() {
+                continue;
+            }
+            let style = &run.style;
+            let text_cols = unicode_column_width(text);
+            let mut attrs = String::new();
+            if style.bold {
+                attrs.push_str(" font-weight=\"bold\"");
+            }
+            if style.italic {
+                attrs.push_str(" font-style=\"italic\"");
+            }
+            match (style.underline, style.strikethrough) {
+                (true, true) => attrs.push_str(" text-decoration=\"underline line-through\""),
+                (true, false) => attrs.push_str(" text-decoration=\"underline\""),
+                (false, true) => attrs.push_str(" text-decoration=\"line-through\""),
+                (false, false) => {}
+            }
+            // textLength pins the run to the grid even if the
+            // viewer substitutes a font with different metrics
+            write!(
+                svg,
+                "<text x=\"{:.2}\" y=\"{:.2}\" textLength=\"{:.2}\" \
+                 lengthAdjust=\"spacingAndGlyphs\" xml:space=\"preserve\" fill=\"{}\"{}>{}</text>\n",
+                (range.start - cols.start) as f32 * CELL_WIDTH,
+                top + FONT_SIZE,
+                text_cols as f32 * CELL_WIDTH,
+                style.foreground.to_rgb_string(),
+                attrs,
+                html_escape(text)
+            )
+            .ok();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn rtf_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c if c.is_ascii() => result.push(c),
+            c => {
+                // RTF represents unicode as signed 16-bit code units,
+                // each followed by a fallback character for readers
+                // that don't understand \u
+                let mut buf = [0u16; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    write!(result, "\\u{}?", *unit as i16).ok();
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Produces an RTF document holding the text of the lines along
+/// with their colors and attributes.
+/// `cols` for each line specifies the range of columns to include.
+pub fn lines_to_rtf(
+    lines: &[(Line, Range<usize>)],
+    palette: &ColorPalette,
+    font_family: Option<&str>,
+) -> String {
+    let lines = logical_lines(lines, palette);
+
+    // RTF requires that the colors be declared up front in a table
+    let mut colors = vec![palette.foreground, palette.background];
+    for run in lines.iter().flatten() {
+        for color in &[run.style.foreground, run.style.background] {
+            if !colors.contains(color) {
+                colors.push(*color);
+            }
+        }
+    }
+    // Color table indices are 1-based; 0 is the "auto" color
+    let color_index = |color: RgbColor| colors.iter().position(|c| *c == color).unwrap() + 1;
+
+    let mut rtf = String::new();
+    write!(
+        rtf,
+        "{{\\rtf1\\ansi\\deff0{{\\fonttbl{{\\f0\\fmodern {};}}}}{{\\colortbl;",
+        rtf_escape(font_family.unwrap_or("Courier New"))
+    )
+    .ok();
+    for color in &colors {
+        write!(
+            rtf,
+            "\\red{}\\green{}\\blue{};",
+            color.red, color.green, color.blue
+        )
+        .ok();
+    }
+    rtf.push_str("}\\f0\n");
+
+    for (idx, runs) in lines.iter().enumerate() {
+        if idx > 0 {
+            rtf.push_str("\\line\n");
+        }
+        for run in runs {
+            let style = &run.style;
+            let bg = color_index(style.background);
+            write!(
+                rtf,
+                "{{\\cf{}\\cb{}\\highlight{}",
+                color_index(style.foreground),
+                bg,
+                bg
+            )
+            .ok();
+            if style.bold {
+                rtf.push_str("\\b");
+            }
+            if style.italic {
+                rtf.push_str("\\i");
+            }
+            if style.underline {
+                rtf.push_str("\\ul");
+            }
+            if style.strikethrough {
+                rtf.push_str("\\strike");
+            }
+            write!(rtf, " {}}}", rtf_escape(&run.text)).ok();
+        }
+    }
+
+    rtf.push_str("}");
+    rtf
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::cell::{Cell, CellAttributes};
+    use termwiz::color::ColorAttribute;
+
+    fn line_with_red_word() -> Line {
+        let mut line = Line::from_text("hello world  ", &CellAttributes::default());
+        let mut red = CellAttributes::default();
+        red.set_foreground(ColorAttribute::TrueColorWithDefaultFallback(RgbColor::new(
+            0xff, 0, 0,
+        )));
+        for (idx, c) in "world".chars().enumerate() {
+            line.set_cell(6 + idx, Cell::new(c, red.clone()));
+        }
+        line
+    }
+
+    #[test]
+    fn runs() {
+        let palette = ColorPalette::default();
+        let line = line_with_red_word();
+        let runs = styled_runs(&line, 0..line.cells().len(), &palette);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].text, "hello ");
+        assert_eq!(runs[1].text, "world");
+        assert_eq!(runs[1].style.foreground, RgbColor::new(0xff, 0, 0));
+    }
+
+    #[test]
+    fn html() {
+        let palette = ColorPalette::default();
+        let line = line_with_red_word();
+        let len = line.cells().len();
+        let html = lines_to_html(&[(line, 0..len)], &palette, None);
+        assert!(html.ends_with("hello <span style=\"color: #ff0000;\">world</span></pre>"));
+    }
+
+    #[test]
+    fn svg() {
+        let palette = ColorPalette::default();
+        let line = line_with_red_word();
+        let len = line.cells().len();
+        let svg = lines_to_svg(&[(line, 0..len)], &palette, Some("Fira Code"));
+        assert!(svg.contains("font-family=\"'Fira Code', monospace\""));
+        assert!(svg.contains("<text x=\"50.40\" y=\"14.00\" textLength=\"42.00\""));
+        assert!(svg.contains("fill=\"#ff0000\">world</text>"));
+    }
+
+    #[test]
+    fn rtf() {
+        let palette = ColorPalette::default();
+        let line = Line::from_text("{a\u{1f915}}", &CellAttributes::default());
+        let len = line.cells().len();
+        let rtf = lines_to_rtf(&[(line, 0..len)], &palette, None);
+        assert!(rtf.contains(" \\{a\\u-10178?\\u-8939?\\}}"));
+    }
+}
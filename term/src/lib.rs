@@ -34,6 +34,7 @@ pub use termwiz::surface::line::*;
 pub mod screen;
 pub use crate::screen::*;
 
+pub mod export;
 pub mod selection;
 
 use termwiz::hyperlink::Hyperlink;
@@ -3,6 +3,7 @@ use super::*;
 use log::debug;
 use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 /// Holds the model of a screen.  This can either be the primary screen
 /// which includes lines of scrollback text, or the alternate screen
@@ -316,6 +317,13 @@ impl Screen {
         line.set_cell(x, cell.clone())
     }
 
+    /// Records `when` as the time at which text was first printed to
+    /// the line, which is relative to the visible screen origin
+    pub fn set_line_timestamp(&mut self, y: VisibleRowIndex, when: SystemTime) {
+        let line_idx = self.phys_row(y);
+        self.line_mut(line_idx).set_timestamp_if_unset(when);
+    }
+
     pub fn clear_line(
         &mut self,
         y: VisibleRowIndex,
@@ -573,6 +581,36 @@ impl Screen {
         }
     }
 
+    /// Inserts `lines` above the visible row `y`, as though they had
+    /// been output there: the lines above `y` scroll up into the
+    /// scrollback, discarding the oldest lines if the scrollback is full.
+    /// Has no effect on a screen that doesn't allow scrollback.
+    pub fn insert_lines_above(&mut self, y: VisibleRowIndex, lines: Vec<Line>) {
+        if !self.allow_scrollback || lines.is_empty() {
+            return;
+        }
+
+        let y = y.max(0).min(self.physical_rows as VisibleRowIndex - 1);
+        let mut idx = self.phys_row(y);
+        for mut line in lines {
+            line.set_dirty();
+            self.lines.insert(idx, line);
+            idx += 1;
+        }
+
+        let capacity = self.physical_rows + self.scrollback_size();
+        while self.lines.len() > capacity {
+            self.lines.pop_front();
+            self.stable_row_index_offset += 1;
+        }
+
+        // The visible lines move to different StableRowIndex values,
+        // so they need to be invalidated
+        for y in self.lines.len() - self.physical_rows..self.lines.len() {
+            self.line_mut(y).set_dirty();
+        }
+    }
+
     pub fn erase_scrollback(&mut self) {
         let len = self.lines.len();
         let to_clear = len - self.physical_rows;
@@ -47,6 +47,9 @@ pub enum Alert {
         /// window/tab/pane that generated it
         focus: bool,
     },
+    /// The palette was changed or reset by an escape sequence
+    /// such as OSC 4 or OSC 104
+    PaletteChanged,
 }
 
 pub trait AlertHandler {
@@ -18,8 +18,8 @@ use termwiz::escape::csi::{
     EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window,
 };
 use termwiz::escape::osc::{
-    ChangeColorPair, ColorOrQuery, FinalTermSemanticPrompt, ITermFileData, ITermProprietary,
-    Selection,
+    ChangeColorPair, ColorOrQuery, FinalTermPromptKind, FinalTermSemanticPrompt, ITermFileData,
+    ITermProprietary, Progress, Selection,
 };
 use termwiz::escape::{
     Action, ControlCode, DeviceControlMode, Esc, EscCode, OneBased, OperatingSystemCommand, Sixel,
@@ -181,6 +181,17 @@ impl ScreenOrAlt {
         self.dirty_top_phys_rows();
     }
 
+    /// Copies the lines of the alternate screen, less any blank lines
+    /// at the bottom, into the primary screen above the row `cursor_y`,
+    /// so that they follow the output that preceded them
+    pub fn capture_alt_screen(&mut self, cursor_y: VisibleRowIndex) {
+        let mut lines: Vec<Line> = self.alt_screen.lines.iter().cloned().collect();
+        while lines.last().map(Line::is_whitespace).unwrap_or(false) {
+            lines.pop();
+        }
+        self.screen.insert_lines_above(cursor_y, lines);
+    }
+
     // When switching between alt and primary screen, we implicitly change
     // the content associated with StableRowIndex 0..num_rows.  The muxer
     // use case needs to know to invalidate its cache, so we mark those rows
@@ -282,8 +293,15 @@ pub struct TerminalState {
     title: String,
     /// The icon title string (OSC 1)
     icon_title: Option<String>,
+    /// Whether the application has set a non-empty window title
+    title_was_set: bool,
 
     palette: Option<ColorPalette>,
+    /// The color with which the pane background is tinted, if any
+    background_tint: Option<RgbColor>,
+    /// Whether the input method should be enabled while this
+    /// terminal is focused
+    ime_enabled: bool,
 
     pixel_width: usize,
     pixel_height: usize,
@@ -293,6 +311,15 @@ pub struct TerminalState {
     alert_handler: Option<Box<dyn AlertHandler>>,
 
     current_dir: Option<Url>,
+    /// The hostname reported by the iTerm2 `OSC 1337;RemoteHost` sequence
+    remote_host: Option<String>,
+
+    /// Progress reported via the ConEmu OSC 9;4 sequence
+    progress: Progress,
+
+    /// The row on which the most recent OSC 133 prompt started,
+    /// if the shell is still displaying that prompt.
+    prompt_start: Option<StableRowIndex>,
 
     term_program: String,
     term_version: String,
@@ -477,13 +504,19 @@ impl TerminalState {
             tabs: TabStop::new(size.physical_cols, 8),
             title: "wezterm".to_string(),
             icon_title: None,
+            title_was_set: false,
             palette: None,
+            background_tint: None,
+            ime_enabled: true,
             pixel_height: size.pixel_height,
             pixel_width: size.pixel_width,
             clipboard: None,
             device_control_handler: None,
             alert_handler: None,
             current_dir: None,
+            remote_host: None,
+            progress: Progress::None,
+            prompt_start: None,
             term_program: term_program.to_string(),
             term_version: term_version.to_string(),
             writer: Box::new(std::io::BufWriter::new(writer)),
@@ -524,13 +557,26 @@ impl TerminalState {
         self.icon_title.as_ref().unwrap_or(&self.title)
     }
 
+    /// Returns true if the application has set either the icon title
+    /// or the window title, rather than leaving the default in place.
+    pub fn has_explicit_title(&self) -> bool {
+        self.icon_title.is_some() || self.title_was_set
+    }
+
     /// Returns the current working directory associated with the
     /// terminal session.  The working directory can be changed by
-    /// the applicaiton using the OSC 7 escape sequence.
+    /// the applicaiton using the OSC 7 escape sequence, or the
+    /// iTerm2 `OSC 1337;CurrentDir` and `OSC 1337;RemoteHost` sequences.
     pub fn get_current_dir(&self) -> Option<&Url> {
         self.current_dir.as_ref()
     }
 
+    /// Returns the progress most recently reported by the application
+    /// via the ConEmu style `OSC 9;4` sequence.
+    pub fn get_progress(&self) -> Progress {
+        self.progress
+    }
+
     /// Returns a copy of the palette.
     /// By default we don't keep a copy in the terminal state,
     /// preferring to take the config values from the users
@@ -545,6 +591,38 @@ impl TerminalState {
             .unwrap_or_else(|| self.config.color_palette())
     }
 
+    /// Returns the color with which the background of the pane
+    /// should be tinted, if any
+    pub fn get_background_tint(&self) -> Option<RgbColor> {
+        self.background_tint
+    }
+
+    /// Sets the color with which the background of the pane is tinted.
+    /// The application can also do this by setting the
+    /// `WEZTERM_BACKGROUND_TINT` user var using the iTerm2
+    /// `OSC 1337;SetUserVar` sequence.
+    pub fn set_background_tint(&mut self, tint: Option<RgbColor>) {
+        if tint != self.background_tint {
+            self.background_tint = tint;
+            self.make_all_lines_dirty();
+        }
+    }
+
+    /// Returns true if the input method should be enabled
+    /// while this terminal is focused
+    pub fn is_ime_enabled(&self) -> bool {
+        self.ime_enabled
+    }
+
+    /// Enables or disables the input method for this terminal.
+    /// The application can also do this by setting the `WEZTERM_IME`
+    /// user var to `on` or `off` using the iTerm2
+    /// `OSC 1337;SetUserVar` sequence; this is useful for eg: leaving
+    /// the input method enabled only while vim is in insert mode.
+    pub fn set_ime_enabled(&mut self, enabled: bool) {
+        self.ime_enabled = enabled;
+    }
+
     /// Called in response to dynamic color scheme escape sequences.
     /// Will make a copy of the palette from the config file if this
     /// is the first of these escapes we've seen.
@@ -817,6 +895,24 @@ impl TerminalState {
         self.screen.is_alt_screen_active()
     }
 
+    /// Called before switching from the alternate screen back to the
+    /// primary screen.  `restore_cursor` is true if the cursor is then
+    /// restored to the position that was saved on the primary screen.
+    fn capture_alt_screen(&mut self, restore_cursor: bool) {
+        if self.config.capture_alternate_screen() {
+            let cursor_y = if restore_cursor {
+                self.screen
+                    .saved_cursor
+                    .as_ref()
+                    .map(|saved| saved.position.y)
+                    .unwrap_or(0)
+            } else {
+                self.cursor.y
+            };
+            self.screen.capture_alt_screen(cursor_y);
+        }
+    }
+
     /// Returns true if the associated application has enabled
     /// bracketed paste mode, which can be helpful to the hosting
     /// GUI application to decide about fragmenting a large paste.
@@ -1166,6 +1262,9 @@ impl TerminalState {
         pixel_width: usize,
         pixel_height: usize,
     ) {
+        if physical_cols != self.screen().physical_cols {
+            self.clear_prompt_for_resize();
+        }
         let adjusted_cursor = self
             .screen
             .resize(physical_rows, physical_cols, self.cursor);
@@ -1180,6 +1279,58 @@ impl TerminalState {
         );
     }
 
+    /// If the shell is sitting at a prompt, erase the prompt and its input
+    /// so that rewrapping doesn't leave a mangled copy of it behind when
+    /// the shell redraws it in response to SIGWINCH.  The cursor is moved
+    /// to the start of the prompt, which is where the shell will redraw it.
+    fn clear_prompt_for_resize(&mut self) {
+        if !self.config.clear_prompt_on_resize() || self.screen.is_alt_screen_active() {
+            return;
+        }
+        match self.pen.semantic_type() {
+            SemanticType::Prompt | SemanticType::Input => {}
+            SemanticType::Output => return,
+        }
+        let start = match self.prompt_start {
+            Some(start) => start,
+            None => return,
+        };
+
+        let screen = self.screen();
+        let start_phys = match screen.stable_row_to_phys(start) {
+            Some(phys) => phys,
+            None => return,
+        };
+        let cursor_phys = screen.phys_row(self.cursor.y);
+        let first_visible = screen.lines.len() - screen.physical_rows;
+        if start_phys > cursor_phys || start_phys < first_visible {
+            return;
+        }
+
+        // If anything other than the prompt and its input made it into
+        // those rows, the shell may not have told us that it is running
+        // a command, so leave them alone rather than erasing output
+        let only_prompt = screen.lines.range(start_phys..=cursor_phys).all(|line| {
+            line.visible_cells().all(|(_, cell)| {
+                cell.attrs().semantic_type() != SemanticType::Output || cell.str().trim().is_empty()
+            })
+        });
+        if !only_prompt {
+            return;
+        }
+
+        let screen = self.screen_mut();
+        let num_lines = screen.lines.len();
+        let physical_cols = screen.physical_cols;
+        for idx in start_phys..num_lines {
+            *screen.line_mut(idx) = Line::with_width(physical_cols);
+        }
+        self.set_cursor_pos(
+            &Position::Absolute(0),
+            &Position::Absolute((start_phys - first_visible) as i64),
+        );
+    }
+
     /// Clear the dirty flag for all dirty lines
     pub fn clean_dirty_lines(&mut self) {
         let screen = self.screen_mut();
@@ -1912,6 +2063,7 @@ impl TerminalState {
                 DecPrivateModeCode::OptEnableAlternateScreen,
             )) => {
                 if self.screen.is_alt_screen_active() {
+                    self.capture_alt_screen(false);
                     self.pen = CellAttributes::default();
                     self.erase_in_display(EraseInDisplay::EraseDisplay);
                     self.screen.activate_primary_screen();
@@ -1922,6 +2074,7 @@ impl TerminalState {
                 DecPrivateModeCode::EnableAlternateScreen,
             )) => {
                 if self.screen.is_alt_screen_active() {
+                    self.capture_alt_screen(false);
                     self.screen.activate_primary_screen();
                     self.pen = CellAttributes::default();
                 }
@@ -2024,6 +2177,7 @@ impl TerminalState {
                 DecPrivateModeCode::ClearAndEnableAlternateScreen,
             )) => {
                 if self.screen.is_alt_screen_active() {
+                    self.capture_alt_screen(true);
                     self.screen.activate_primary_screen();
                     self.dec_restore_cursor();
                 }
@@ -2875,6 +3029,12 @@ impl<'a> Performer<'a> {
             None => return,
         };
 
+        let timestamp = if self.config.record_line_timestamps() {
+            Some(SystemTime::now())
+        } else {
+            None
+        };
+
         for g in unicode_segmentation::UnicodeSegmentation::graphemes(p.as_str(), true) {
             let g = if self.dec_line_drawing_mode {
                 match g {
@@ -2904,12 +3064,27 @@ impl<'a> Performer<'a> {
             let width = self.left_and_right_margins.end;
 
             let mut pen = self.pen.clone();
+
+            // Emoji without a variation selector may be configured
+            // to use something other than their default presentation
+            let presentation = match (
+                self.config.emoji_presentation(),
+                Presentation::for_grapheme(g),
+            ) {
+                (Some(wanted), Some(default)) if wanted != default => Some(wanted),
+                _ => None,
+            };
+            pen.set_presentation(presentation);
+
             // the max(1) here is to ensure that we advance to the next cell
             // position for zero-width graphemes.  We want to make sure that
             // they occupy a cell so that we can re-emit them when we output them.
             // If we didn't do this, then we'd effectively filter them out from
             // the model, which seems like a lossy design choice.
-            let print_width = unicode_column_width(g).max(1);
+            let print_width = match presentation {
+                Some(presentation) => presentation.column_width(),
+                None => unicode_column_width(g).max(1),
+            };
 
             if x + print_width >= width {
                 pen.set_wrapped(true);
@@ -2928,6 +3103,9 @@ impl<'a> Performer<'a> {
             // Assign the cell
             log::trace!("print x={} y={} cell={:?}", x, y, cell);
             self.screen_mut().set_cell(x, y, &cell);
+            if let Some(when) = timestamp {
+                self.screen_mut().set_line_timestamp(y, when);
+            }
 
             if x + print_width < width {
                 self.cursor.x += print_width;
@@ -3211,8 +3389,45 @@ impl<'a> Performer<'a> {
         }
     }
 
+    /// Applies `OSC 1337;CurrentDir=path`.  Unlike OSC 7, this doesn't
+    /// include the host, so we use the host from the most recent
+    /// `OSC 1337;RemoteHost`, if any.
+    fn set_iterm_current_dir(&mut self, path: &str) {
+        let mut url = match Url::parse("file:///") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        url.set_path(path);
+        if let Some(host) = self.remote_host.as_ref() {
+            if url.set_host(Some(host)).is_err() {
+                log::warn!("invalid RemoteHost {}", host);
+            }
+        }
+        self.current_dir = Some(url);
+    }
+
+    /// Applies `OSC 1337;RemoteHost=user@host`.  The host is also
+    /// applied to the current directory, as the shell integration
+    /// may emit RemoteHost after CurrentDir.
+    fn set_iterm_remote_host(&mut self, user_and_host: &str) {
+        let host = match user_and_host.rsplitn(2, '@').next() {
+            Some(host) if !host.is_empty() => host.to_string(),
+            _ => {
+                self.remote_host = None;
+                return;
+            }
+        };
+        if let Some(url) = self.current_dir.as_mut() {
+            if url.scheme() == "file" {
+                url.set_host(Some(&host)).ok();
+            }
+        }
+        self.remote_host = Some(host);
+    }
+
     fn osc_dispatch(&mut self, osc: OperatingSystemCommand) {
         self.flush_print();
+        let mut palette_changed = false;
         match osc {
             OperatingSystemCommand::SetIconNameSun(title)
             | OperatingSystemCommand::SetIconName(title) => {
@@ -3224,11 +3439,13 @@ impl<'a> Performer<'a> {
             }
             OperatingSystemCommand::SetIconNameAndWindowTitle(title) => {
                 self.icon_title.take();
+                self.title_was_set = !title.is_empty();
                 self.title = title.clone();
             }
 
             OperatingSystemCommand::SetWindowTitleSun(title)
             | OperatingSystemCommand::SetWindowTitle(title) => {
+                self.title_was_set = !title.is_empty();
                 self.title = title.clone();
             }
             OperatingSystemCommand::SetHyperlink(link) => {
@@ -3257,6 +3474,29 @@ impl<'a> Performer<'a> {
             }
             OperatingSystemCommand::ITermProprietary(iterm) => match iterm {
                 ITermProprietary::File(image) => self.set_image(*image),
+                ITermProprietary::CurrentDir(path) => self.set_iterm_current_dir(&path),
+                ITermProprietary::RemoteHost(user_and_host) => {
+                    self.set_iterm_remote_host(&user_and_host)
+                }
+                ITermProprietary::SetUserVar { name, value }
+                    if name == "WEZTERM_BACKGROUND_TINT" =>
+                {
+                    if value.is_empty() {
+                        self.set_background_tint(None);
+                    } else {
+                        match RgbColor::from_named_or_rgb_string(&value) {
+                            Some(tint) => self.set_background_tint(Some(tint)),
+                            None => log::warn!("invalid WEZTERM_BACKGROUND_TINT {:?}", value),
+                        }
+                    }
+                }
+                ITermProprietary::SetUserVar { name, value } if name == "WEZTERM_IME" => {
+                    match value.as_str() {
+                        "" | "on" => self.set_ime_enabled(true),
+                        "off" => self.set_ime_enabled(false),
+                        _ => log::warn!("invalid WEZTERM_IME {:?}", value),
+                    }
+                }
                 _ => log::warn!("unhandled iterm2: {:?}", iterm),
             },
 
@@ -3268,10 +3508,18 @@ impl<'a> Performer<'a> {
             ) => {
                 self.fresh_line();
                 self.pen.set_semantic_type(SemanticType::Prompt);
+                self.prompt_start = Some(self.screen().visible_row_to_stable_row(self.cursor.y));
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::StartPrompt(_),
+                FinalTermSemanticPrompt::StartPrompt(kind),
             ) => {
+                // A continuation of the current prompt doesn't move its start
+                if kind == FinalTermPromptKind::Initial
+                    && self.pen.semantic_type() == SemanticType::Output
+                {
+                    self.prompt_start =
+                        Some(self.screen().visible_row_to_stable_row(self.cursor.y));
+                }
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -3279,6 +3527,7 @@ impl<'a> Performer<'a> {
             ) => {
                 self.fresh_line();
                 self.pen.set_semantic_type(SemanticType::Prompt);
+                self.prompt_start = Some(self.screen().visible_row_to_stable_row(self.cursor.y));
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker { .. },
@@ -3289,6 +3538,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { .. },
             ) => {
                 self.pen.set_semantic_type(SemanticType::Output);
+                self.prompt_start = None;
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -3299,6 +3549,9 @@ impl<'a> Performer<'a> {
                 log::warn!("unhandled: {:?}", ft);
             }
 
+            OperatingSystemCommand::ConEmuProgress(progress) => {
+                self.progress = progress;
+            }
             OperatingSystemCommand::SystemNotification(message) => {
                 if let Some(handler) = self.alert_handler.as_mut() {
                     handler.alert(Alert::ToastNotification {
@@ -3351,6 +3604,7 @@ impl<'a> Performer<'a> {
                         }
                         ColorOrQuery::Color(c) => {
                             self.palette_mut().colors.0[pair.palette_index as usize] = c;
+                            palette_changed = true;
                         }
                     }
                 }
@@ -3361,7 +3615,7 @@ impl<'a> Performer<'a> {
                 log::trace!("ResetColors: {:?}", colors);
                 if colors.is_empty() {
                     // Reset all colors
-                    self.palette.take();
+                    palette_changed = self.palette.take().is_some();
                 } else {
                     // Reset individual colors
                     if self.palette.is_none() {
@@ -3372,6 +3626,7 @@ impl<'a> Performer<'a> {
                             let c = c as usize;
                             self.palette_mut().colors.0[c] = base.colors.0[c];
                         }
+                        palette_changed = true;
                     }
                 }
             }
@@ -3396,7 +3651,10 @@ impl<'a> Performer<'a> {
                                         write!(self.writer, "{}", response).ok();
                                         self.writer.flush().ok();
                                     }
-                                    ColorOrQuery::Color(c) => self.palette_mut().$name = c,
+                                    ColorOrQuery::Color(c) => {
+                                        self.palette_mut().$name = c;
+                                        palette_changed = true;
+                                    }
                                 }
                             };
                         }
@@ -3442,6 +3700,7 @@ impl<'a> Performer<'a> {
                             } else {
                                 let base = self.config.color_palette();
                                 self.palette_mut().$name = base.$name;
+                                palette_changed = true;
                             }
                         };
                     }
@@ -3466,5 +3725,11 @@ impl<'a> Performer<'a> {
                 self.make_all_lines_dirty();
             }
         }
+
+        if palette_changed {
+            if let Some(handler) = self.alert_handler.as_mut() {
+                handler.alert(Alert::PaletteChanged);
+            }
+        }
     }
 }
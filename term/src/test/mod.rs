@@ -65,8 +65,38 @@ impl TerminalConfiguration for TestTermConfig {
     }
 }
 
+/// Enables the options that are off by default, for the
+/// tests of those options
+#[derive(Debug, Default)]
+struct TestTermOptions {
+    scrollback: usize,
+    clear_prompt_on_resize: bool,
+    capture_alternate_screen: bool,
+}
+impl TerminalConfiguration for TestTermOptions {
+    fn scrollback_size(&self) -> usize {
+        self.scrollback
+    }
+
+    fn color_palette(&self) -> ColorPalette {
+        ColorPalette::default()
+    }
+
+    fn clear_prompt_on_resize(&self) -> bool {
+        self.clear_prompt_on_resize
+    }
+
+    fn capture_alternate_screen(&self) -> bool {
+        self.capture_alternate_screen
+    }
+}
+
 impl TestTerm {
     fn new(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(height, width, Arc::new(TestTermConfig { scrollback }))
+    }
+
+    fn with_config(height: usize, width: usize, config: Arc<dyn TerminalConfiguration>) -> Self {
         let _ = pretty_env_logger::formatted_builder()
             .is_test(true)
             .filter_level(log::LevelFilter::Trace)
@@ -79,7 +109,7 @@ impl TestTerm {
                 pixel_width: width * 8,
                 pixel_height: height * 16,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            config,
             "WezTerm",
             "O_o",
             Box::new(Vec::new()),
@@ -736,6 +766,105 @@ fn test_resize_wrap() {
     );
 }
 
+/// Prints a line of output followed by a prompt, marked up using
+/// OSC 133, and input that wraps onto the next line
+fn print_prompt_with_input(term: &mut TestTerm) {
+    use termwiz::escape::osc::FinalTermSemanticPrompt;
+    term.print("out\r\n");
+    term.print(format!(
+        "{}",
+        OperatingSystemCommand::FinalTermSemanticPrompt(
+            FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+                aid: None,
+                cl: None
+            }
+        )
+    ));
+    term.print("> ");
+    term.print(format!(
+        "{}",
+        OperatingSystemCommand::FinalTermSemanticPrompt(
+            FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker
+        )
+    ));
+    term.print("abcdefghij");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["out       ", "> abcdefgh", "ij        ", "          "],
+    );
+    term.assert_cursor_pos(2, 2, None);
+}
+
+#[test]
+fn test_resize_clears_prompt() {
+    use termwiz::escape::osc::FinalTermSemanticPrompt;
+    let mut term = TestTerm::with_config(
+        4,
+        10,
+        Arc::new(TestTermOptions {
+            clear_prompt_on_resize: true,
+            ..Default::default()
+        }),
+    );
+    print_prompt_with_input(&mut term);
+
+    // The shell will redraw the prompt and its input when it
+    // receives SIGWINCH, so it is erased rather than rewrapped
+    term.resize(4, 20, 0, 0);
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &[
+            "out       ",
+            "          ",
+            "                    ",
+            "                    ",
+        ],
+    );
+    term.assert_cursor_pos(0, 1, None);
+
+    // Once the command starts running, its output is rewrapped as usual
+    term.print("> abcdefghij\r\n");
+    term.print(format!(
+        "{}",
+        OperatingSystemCommand::FinalTermSemanticPrompt(
+            FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None }
+        )
+    ));
+    term.print("0123456789");
+    term.resize(4, 10, 0, 0);
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["out       ", "> abcdefgh", "ij", "0123456789"],
+    );
+}
+
+#[test]
+fn test_resize_keeps_prompt_by_default() {
+    let mut term = TestTerm::with_config(4, 10, Arc::new(TestTermOptions::default()));
+    print_prompt_with_input(&mut term);
+
+    // The prompt and its input are rewrapped like any other line
+    term.resize(4, 20, 0, 0);
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &[
+            "out       ",
+            "> abcdefghij        ",
+            "                    ",
+            "                    ",
+        ],
+    );
+    term.assert_cursor_pos(12, 1, None);
+}
+
 #[test]
 fn test_scrollup() {
     let mut term = TestTerm::new(2, 1, 4);
@@ -905,3 +1034,153 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_palette_changed_alert() {
+    struct Alerts(Arc<std::sync::Mutex<Vec<Alert>>>);
+    impl AlertHandler for Alerts {
+        fn alert(&mut self, alert: Alert) {
+            self.0.lock().unwrap().push(alert);
+        }
+    }
+
+    let alerts = Arc::new(std::sync::Mutex::new(vec![]));
+    let mut term = TestTerm::new(2, 10, 0);
+    term.set_notification_handler(Box::new(Alerts(Arc::clone(&alerts))));
+
+    // Resetting colors that were never changed is not a change
+    term.print("\x1b]104\x1b\\");
+    assert_eq!(*alerts.lock().unwrap(), vec![]);
+
+    term.print("\x1b]4;136;rgb:af/3a/03\x1b\\");
+    assert_eq!(*alerts.lock().unwrap(), vec![Alert::PaletteChanged]);
+    assert_eq!(
+        term.palette().colors.0[136],
+        termwiz::color::RgbColor::new(0xaf, 0x3a, 0x03)
+    );
+
+    term.print("\x1b]104;136\x1b\\");
+    assert_eq!(
+        *alerts.lock().unwrap(),
+        vec![Alert::PaletteChanged, Alert::PaletteChanged]
+    );
+    assert_eq!(
+        term.palette().colors.0[136],
+        ColorPalette::default().colors.0[136]
+    );
+}
+
+#[test]
+fn test_iterm_current_dir() {
+    let mut term = TestTerm::new(2, 10, 0);
+    term.print("\x1b]1337;CurrentDir=/home/wez\x07");
+    assert_eq!(
+        term.get_current_dir().map(|url| url.as_str()),
+        Some("file:///home/wez")
+    );
+
+    term.print("\x1b]1337;RemoteHost=wez@foo.example.com\x07");
+    assert_eq!(
+        term.get_current_dir().map(|url| url.as_str()),
+        Some("file://foo.example.com/home/wez")
+    );
+
+    term.print("\x1b]1337;CurrentDir=/tmp/with space\x07");
+    assert_eq!(
+        term.get_current_dir().map(|url| url.as_str()),
+        Some("file://foo.example.com/tmp/with%20space")
+    );
+}
+
+#[test]
+fn test_explicit_title() {
+    let mut term = TestTerm::new(2, 10, 0);
+    assert!(!term.has_explicit_title());
+
+    term.print("\x1b]2;\x07");
+    assert!(!term.has_explicit_title());
+
+    term.print("\x1b]2;vim\x07");
+    assert!(term.has_explicit_title());
+    assert_eq!(term.get_title(), "vim");
+
+    term.print("\x1b]0;\x07");
+    assert!(!term.has_explicit_title());
+
+    term.print("\x1b]1;tab\x07");
+    assert!(term.has_explicit_title());
+    assert_eq!(term.get_title(), "tab");
+}
+
+#[test]
+fn test_background_tint() {
+    let mut term = TestTerm::new(2, 10, 0);
+    assert_eq!(term.get_background_tint(), None);
+
+    // base64 of "#400000"
+    term.print("\x1b]1337;SetUserVar=WEZTERM_BACKGROUND_TINT=IzQwMDAwMA==\x07");
+    assert_eq!(
+        term.get_background_tint(),
+        Some(termwiz::color::RgbColor::new(0x40, 0, 0))
+    );
+
+    term.print("\x1b]1337;SetUserVar=WEZTERM_BACKGROUND_TINT=\x07");
+    assert_eq!(term.get_background_tint(), None);
+}
+
+#[test]
+fn test_ime_enabled() {
+    let mut term = TestTerm::new(2, 10, 0);
+    assert!(term.is_ime_enabled());
+
+    // base64 of "off"
+    term.print("\x1b]1337;SetUserVar=WEZTERM_IME=b2Zm\x07");
+    assert!(!term.is_ime_enabled());
+
+    // base64 of "on"
+    term.print("\x1b]1337;SetUserVar=WEZTERM_IME=b24=\x07");
+    assert!(term.is_ime_enabled());
+}
+
+#[test]
+fn test_capture_alternate_screen() {
+    let mut term = TestTerm::with_config(
+        3,
+        5,
+        Arc::new(TestTermOptions {
+            scrollback: 10,
+            capture_alternate_screen: true,
+            ..Default::default()
+        }),
+    );
+    term.print("a\r\nb\r\n");
+
+    term.print("\x1b[?1049h");
+    term.print("less");
+    assert_visible_contents(&term, file!(), line!(), &["less ", "     ", "     "]);
+
+    // The captured lines follow the earlier output, and the
+    // output that follows them lands on the restored cursor row
+    term.print("\x1b[?1049l");
+    term.print("$");
+    assert_visible_contents(&term, file!(), line!(), &["b    ", "less ", "$    "]);
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["a    ", "b    ", "less ", "$    "],
+    );
+}
+
+#[test]
+fn test_alternate_screen_not_captured_by_default() {
+    let mut term = TestTerm::new(3, 5, 10);
+    term.print("a\r\nb\r\n");
+
+    term.print("\x1b[?1049h");
+    term.print("less");
+    term.print("\x1b[?1049l");
+    term.print("$");
+    assert_visible_contents(&term, file!(), line!(), &["a    ", "b    ", "$    "]);
+    assert_all_contents(&term, file!(), line!(), &["a    ", "b    ", "$    "]);
+}
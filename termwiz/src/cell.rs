@@ -6,7 +6,7 @@ use crate::image::ImageCell;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::mem;
 use std::sync::Arc;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Holds the attributes for a cell.
 /// Most style attributes are stored internally as part of a bitfield
@@ -58,6 +58,8 @@ struct FatAttributes {
     /// The color of the underline.  If None, then
     /// the foreground color is to be used
     underline_color: ColorAttribute,
+    /// Overrides the default presentation of an emoji
+    presentation: Option<Presentation>,
 }
 
 /// Define getter and setter for the attributes bitfield.
@@ -134,6 +136,54 @@ impl Default for SemanticType {
     }
 }
 
+/// Emoji codepoints may be displayed either as text, which is
+/// typically a monochrome glyph occupying a single cell, or as
+/// emoji, which is typically a colorful glyph occupying two cells.
+/// Each such codepoint has a default presentation that may be changed
+/// by following it with a variation selector.
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Presentation {
+    Text,
+    Emoji,
+}
+
+impl Presentation {
+    /// If the grapheme is a single emoji codepoint whose presentation
+    /// can be selected, returns its default presentation.
+    /// Returns None for other graphemes, including those that
+    /// already have a variation selector.
+    pub fn for_grapheme(s: &str) -> Option<Self> {
+        use xi_unicode::EmojiExt;
+        let mut chars = s.chars();
+        let c = chars.next()?;
+        if chars.next().is_some()
+            || c.is_ascii()
+            || !c.is_emoji()
+            || c.is_emoji_modifier()
+            || ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+        {
+            // Keycap bases, skin tone modifiers and regional
+            // indicators are only emoji as part of a sequence
+            return None;
+        }
+        if UnicodeWidthChar::width(c) == Some(2) {
+            Some(Self::Emoji)
+        } else {
+            Some(Self::Text)
+        }
+    }
+
+    /// Returns the number of cells occupied by a grapheme
+    /// with this presentation
+    pub fn column_width(self) -> usize {
+        match self {
+            Self::Text => 1,
+            Self::Emoji => 2,
+        }
+    }
+}
+
 /// The `Intensity` of a cell describes its boldness.  Most terminals
 /// implement `Intensity::Bold` by either using a bold font or by simply
 /// using an alternative color.  Some terminals implement `Intensity::Half`
@@ -242,6 +292,7 @@ impl CellAttributes {
                 hyperlink: None,
                 image: None,
                 underline_color: ColorAttribute::Default,
+                presentation: None,
             }));
         }
     }
@@ -254,6 +305,7 @@ impl CellAttributes {
                 fat.image.is_none()
                     && fat.hyperlink.is_none()
                     && fat.underline_color == ColorAttribute::Default
+                    && fat.presentation.is_none()
             })
             .unwrap_or(false);
         if deallocate {
@@ -298,6 +350,19 @@ impl CellAttributes {
         }
     }
 
+    /// Sets the presentation to use for an emoji grapheme in place
+    /// of its default presentation
+    pub fn set_presentation(&mut self, presentation: Option<Presentation>) -> &mut Self {
+        if presentation.is_none() && self.fat.is_none() {
+            self
+        } else {
+            self.allocate_fat_attributes();
+            self.fat.as_mut().unwrap().presentation = presentation;
+            self.deallocate_fat_attributes_if_none();
+            self
+        }
+    }
+
     /// Clone the attributes, but exclude fancy extras such
     /// as hyperlinks or future sprite things
     pub fn clone_sgr_only(&self) -> Self {
@@ -326,6 +391,10 @@ impl CellAttributes {
             .and_then(|fat| fat.image.as_ref().map(|im| im.as_ref()))
     }
 
+    pub fn presentation(&self) -> Option<Presentation> {
+        self.fat.as_ref().and_then(|fat| fat.presentation)
+    }
+
     pub fn underline_color(&self) -> ColorAttribute {
         self.fat
             .as_ref()
@@ -539,7 +608,12 @@ impl Cell {
 
     /// Returns the number of cells visually occupied by this grapheme
     pub fn width(&self) -> usize {
-        grapheme_column_width(self.str())
+        match self.attrs.presentation() {
+            Some(presentation) if Presentation::for_grapheme(self.str()).is_some() => {
+                presentation.column_width()
+            }
+            _ => grapheme_column_width(self.str()),
+        }
     }
 
     /// Returns the attributes of the cell
@@ -696,4 +770,29 @@ mod test {
         eprintln!("font_awesome_star {}", font_awesome_star.escape_debug());
         assert_eq!(unicode_column_width(font_awesome_star), 1);
     }
+
+    #[test]
+    fn presentation() {
+        let warning = "\u{26a0}";
+        assert_eq!(
+            Presentation::for_grapheme(warning),
+            Some(Presentation::Text)
+        );
+        assert_eq!(
+            Presentation::for_grapheme("\u{1f600}"),
+            Some(Presentation::Emoji)
+        );
+        assert_eq!(Presentation::for_grapheme("\u{26a0}\u{fe0f}"), None);
+        assert_eq!(Presentation::for_grapheme("1"), None);
+        assert_eq!(Presentation::for_grapheme("a"), None);
+
+        let mut attrs = CellAttributes::default();
+        assert_eq!(Cell::new_grapheme(warning, attrs.clone()).width(), 1);
+        attrs.set_presentation(Some(Presentation::Emoji));
+        assert_eq!(Cell::new_grapheme(warning, attrs.clone()).width(), 2);
+        // The presentation only applies to emoji
+        assert_eq!(Cell::new_grapheme("a", attrs.clone()).width(), 1);
+        attrs.set_presentation(None);
+        assert_eq!(attrs, CellAttributes::default());
+    }
 }
@@ -16,12 +16,25 @@ impl CellCluster {
     /// Compute the list of CellClusters from a set of visible cells.
     /// The input is typically the result of calling `Line::visible_cells()`.
     pub fn make_cluster<'a>(iter: impl Iterator<Item = (usize, &'a Cell)>) -> Vec<CellCluster> {
+        Self::make_cluster_with_isolation(iter, |_, _| false)
+    }
+
+    /// Like `make_cluster`, but each cell for which `isolate` returns
+    /// true is placed into a cluster of its own.  Since clusters are
+    /// shaped separately, this prevents those cells from taking part
+    /// in ligatures.
+    pub fn make_cluster_with_isolation<'a>(
+        iter: impl Iterator<Item = (usize, &'a Cell)>,
+        isolate: impl Fn(usize, &Cell) -> bool,
+    ) -> Vec<CellCluster> {
         let mut last_cluster = None;
+        let mut last_isolated = false;
         let mut clusters = Vec::new();
 
         for (cell_idx, c) in iter {
             let cell_str = c.str();
             let normalized_attr = c.attrs().clone().set_wrapped(false).clone();
+            let isolated = isolate(cell_idx, c);
 
             last_cluster = match last_cluster.take() {
                 None => {
@@ -29,7 +42,7 @@ impl CellCluster {
                     Some(CellCluster::new(c.attrs().clone(), cell_str, cell_idx))
                 }
                 Some(mut last) => {
-                    if last.attrs != normalized_attr {
+                    if isolated || last_isolated || last.attrs != normalized_attr {
                         // Flush pending cluster and start a new one
                         clusters.push(last);
                         Some(CellCluster::new(normalized_attr, cell_str, cell_idx))
@@ -40,6 +53,7 @@ impl CellCluster {
                     }
                 }
             };
+            last_isolated = isolated;
         }
 
         if let Some(cluster) = last_cluster {
@@ -36,6 +36,7 @@ pub enum OperatingSystemCommand {
     QuerySelection(Selection),
     SetSelection(Selection, String),
     SystemNotification(String),
+    ConEmuProgress(Progress),
     ITermProprietary(ITermProprietary),
     FinalTermSemanticPrompt(FinalTermSemanticPrompt),
     ChangeColorNumber(Vec<ChangeColorPair>),
@@ -295,6 +296,9 @@ impl OperatingSystemCommand {
             )),
             SetHyperlink => Ok(OperatingSystemCommand::SetHyperlink(Hyperlink::parse(osc)?)),
             ManipulateSelectionData => Self::parse_selection(osc),
+            SystemNotification if osc.len() > 2 && osc[1] == b"4" => {
+                Progress::parse(osc).map(OperatingSystemCommand::ConEmuProgress)
+            }
             SystemNotification => single_string!(SystemNotification),
             SetCurrentWorkingDirectory => single_string!(CurrentWorkingDirectory),
             ITermProprietary => {
@@ -493,6 +497,7 @@ impl Display for OperatingSystemCommand {
             QuerySelection(s) => write!(f, "52;{};?", s)?,
             SetSelection(s, val) => write!(f, "52;{};{}", s, base64::encode(val))?,
             SystemNotification(s) => write!(f, "9;{}", s)?,
+            ConEmuProgress(p) => p.fmt(f)?,
             ITermProprietary(i) => i.fmt(f)?,
             FinalTermSemanticPrompt(i) => i.fmt(f)?,
             ResetColors(colors) => {
@@ -785,6 +790,59 @@ impl Display for FinalTermSemanticPrompt {
     }
 }
 
+/// ConEmu style progress reporting, `OSC 9 ; 4 ; st ; pr ST`.
+/// <https://conemu.github.io/en/AnsiEscapeCodes.html#ConEmu_specific_OSC>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// Remove the progress indicator
+    None,
+    /// Set the progress to the specified percentage
+    SetPercentage(u8),
+    /// Show the progress in an error state
+    SetError(u8),
+    /// Progress is being made, but its extent is unknown
+    Indeterminate,
+    /// Show the progress in a paused state
+    Paused(u8),
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Progress {
+    fn parse(osc: &[&[u8]]) -> Result<Self> {
+        ensure!(osc.len() >= 3 && osc.len() <= 4, "wrong param count");
+        let state: u8 = str::from_utf8(osc[2])?.parse()?;
+        let pct: u8 = match osc.get(3) {
+            Some(pct) if !pct.is_empty() => str::from_utf8(pct)?.parse::<u8>()?.min(100),
+            _ => 0,
+        };
+        Ok(match state {
+            0 => Self::None,
+            1 => Self::SetPercentage(pct),
+            2 => Self::SetError(pct),
+            3 => Self::Indeterminate,
+            4 => Self::Paused(pct),
+            _ => bail!("invalid progress state {}", state),
+        })
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::None => write!(f, "9;4;0"),
+            Self::SetPercentage(pct) => write!(f, "9;4;1;{}", pct),
+            Self::SetError(pct) => write!(f, "9;4;2;{}", pct),
+            Self::Indeterminate => write!(f, "9;4;3"),
+            Self::Paused(pct) => write!(f, "9;4;4;{}", pct),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ITermProprietary {
     /// The "Set Mark" command allows you to record a location and then jump back to it later
@@ -795,6 +853,9 @@ pub enum ITermProprietary {
     ClearScrollback,
     /// To inform iTerm2 of the current directory to help semantic history
     CurrentDir(String),
+    /// To inform iTerm2 of the user and host of the current session,
+    /// in the form `user@hostname`
+    RemoteHost(String),
     /// To change the session's profile on the fly
     SetProfile(String),
     /// Currently defined values for the string parameter are "rule", "find", "font"
@@ -1087,6 +1148,7 @@ impl ITermProprietary {
         const_arg!(HighlightCursorLine, "HighlightCursorLine", "yes", true);
         const_arg!(HighlightCursorLine, "HighlightCursorLine", "no", false);
         one_str!(CurrentDir, "CurrentDir");
+        one_str!(RemoteHost, "RemoteHost");
         one_str!(SetProfile, "SetProfile");
         one_str!(CopyToClipboard, "CopyToClipboard");
 
@@ -1148,6 +1210,7 @@ impl Display for ITermProprietary {
             StealFocus => write!(f, "StealFocus")?,
             ClearScrollback => write!(f, "ClearScrollback")?,
             CurrentDir(s) => write!(f, "CurrentDir={}", s)?,
+            RemoteHost(s) => write!(f, "RemoteHost={}", s)?,
             SetProfile(s) => write!(f, "SetProfile={}", s)?,
             CopyToClipboard(s) => write!(f, "CopyToClipboard={}", s)?,
             EndCopy => write!(f, "EndCopy")?,
@@ -1243,6 +1306,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn progress() {
+        assert_eq!(
+            parse(&["9", "4", "1", "42"], "\x1b]9;4;1;42\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::SetPercentage(42))
+        );
+        assert_eq!(
+            parse(&["9", "4", "3"], "\x1b]9;4;3\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Indeterminate)
+        );
+        assert_eq!(
+            parse(&["9", "4", "2", ""], "\x1b]9;4;2;0\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::SetError(0))
+        );
+        assert_eq!(
+            parse(&["9", "4", "0", "0"], "\x1b]9;4;0\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::None)
+        );
+        // Regular notifications are unaffected
+        assert_eq!(
+            parse(&["9", "hello"], "\x1b]9;hello\x1b\\"),
+            OperatingSystemCommand::SystemNotification("hello".into())
+        );
+    }
+
     #[test]
     fn hyperlink() {
         assert_eq!(
@@ -1512,6 +1600,16 @@ mod test {
             OperatingSystemCommand::ITermProprietary(ITermProprietary::HighlightCursorLine(true))
         );
 
+        assert_eq!(
+            parse(
+                &["1337", "RemoteHost=wez@foo.example.com"],
+                "\x1b]1337;RemoteHost=wez@foo.example.com\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::RemoteHost(
+                "wez@foo.example.com".into()
+            ))
+        );
+
         assert_eq!(
             parse(
                 &["1337", "Copy=", "aGVsbG8="],
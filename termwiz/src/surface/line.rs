@@ -3,10 +3,12 @@ use crate::cellcluster::CellCluster;
 use crate::hyperlink::Rule;
 use crate::surface::Change;
 use bitflags::bitflags;
+use regex::Regex;
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
 use std::sync::Arc;
+use std::time::SystemTime;
 use unicode_segmentation::UnicodeSegmentation;
 
 bitflags! {
@@ -30,6 +32,10 @@ bitflags! {
 pub struct Line {
     cells: Vec<Cell>,
     bits: LineBits,
+    /// When text was first printed to the line, if the terminal
+    /// is configured to record that
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    timestamp: Option<SystemTime>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -43,7 +49,11 @@ impl Line {
         let mut cells = Vec::with_capacity(width);
         cells.resize(width, Cell::default());
         let bits = LineBits::DIRTY;
-        Self { bits, cells }
+        Self {
+            bits,
+            cells,
+            timestamp: None,
+        }
     }
 
     pub fn from_text(s: &str, attrs: &CellAttributes) -> Line {
@@ -61,6 +71,7 @@ impl Line {
         Line {
             cells,
             bits: LineBits::DIRTY,
+            timestamp: None,
         }
     }
 
@@ -78,6 +89,7 @@ impl Line {
         self.cells.resize(width, blank);
         self.cells.shrink_to_fit();
         self.bits = LineBits::DIRTY;
+        self.timestamp = None;
     }
 
     pub fn resize(&mut self, width: usize) {
@@ -90,6 +102,7 @@ impl Line {
     pub fn wrap(mut self, width: usize) -> Vec<Self> {
         if let Some(end_idx) = self.cells.iter().rposition(|c| c.str() != " ") {
             self.cells.resize(end_idx + 1, Cell::default());
+            let timestamp = self.timestamp;
 
             let mut lines: Vec<_> = self
                 .cells
@@ -98,6 +111,7 @@ impl Line {
                     let mut line = Line {
                         cells: chunk.to_vec(),
                         bits: LineBits::DIRTY,
+                        timestamp,
                     };
                     if line.cells.len() == width {
                         // Ensure that we don't forget that we wrapped
@@ -219,6 +233,38 @@ impl Line {
         }
     }
 
+    /// Replaces the text of the cells that match any of `patterns` with
+    /// `mask`, preserving their attributes and the width of the line.
+    /// This is used to hide sensitive text from the display without
+    /// changing the stored line.
+    pub fn mask_matches(&mut self, patterns: &[Regex], mask: char) {
+        let line = self.as_str();
+        let ranges: Vec<Range<usize>> = patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(&line).map(|m| m.range()))
+            .collect();
+        if ranges.is_empty() {
+            return;
+        }
+
+        // The ranges are measured in bytes, so translate them to cells
+        let mut byte_idx = 0;
+        let mut to_mask = vec![];
+        for (cell_idx, cell) in self.visible_cells() {
+            if ranges.iter().any(|r| r.contains(&byte_idx)) {
+                to_mask.push(cell_idx..cell_idx + cell.width());
+            }
+            byte_idx += cell.str().len();
+        }
+
+        for range in to_mask {
+            for idx in range.start..range.end.min(self.cells.len()) {
+                let attrs = self.cells[idx].attrs().clone();
+                self.cells[idx] = Cell::new(mask, attrs);
+            }
+        }
+    }
+
     /// Returns true if the line contains a hyperlink
     #[inline]
     pub fn has_hyperlink(&self) -> bool {
@@ -226,6 +272,20 @@ impl Line {
             != LineBits::NONE
     }
 
+    /// Returns the time at which text was first printed to the line,
+    /// if that was recorded
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+
+    /// Records `when` as the time at which text was first printed to
+    /// the line, unless an earlier time has already been recorded
+    pub fn set_timestamp_if_unset(&mut self, when: SystemTime) {
+        if self.timestamp.is_none() {
+            self.timestamp = Some(when);
+        }
+    }
+
     /// Recompose line into the corresponding utf8 string.
     pub fn as_str(&self) -> String {
         let mut s = String::new();
@@ -240,6 +300,7 @@ impl Line {
         Self {
             bits: self.bits,
             cells,
+            timestamp: self.timestamp,
         }
     }
 
@@ -430,6 +491,15 @@ impl Line {
         CellCluster::make_cluster(self.visible_cells())
     }
 
+    /// Like `cluster`, but the cells for which `isolate` returns true
+    /// are each placed into a cluster of their own
+    pub fn cluster_with_isolation(
+        &self,
+        isolate: impl Fn(usize, &Cell) -> bool,
+    ) -> Vec<CellCluster> {
+        CellCluster::make_cluster_with_isolation(self.visible_cells(), isolate)
+    }
+
     pub fn cells(&self) -> &[Cell] {
         &self.cells
     }
@@ -558,6 +628,32 @@ mod test {
     use crate::hyperlink::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn cluster_isolation() {
+        let line: Line = "a != b".into();
+        let texts = |clusters: Vec<CellCluster>| -> Vec<String> {
+            clusters.into_iter().map(|c| c.text).collect()
+        };
+
+        assert_eq!(texts(line.cluster()), vec!["a != b"]);
+        assert_eq!(
+            texts(line.cluster_with_isolation(|idx, _| idx == 2 || idx == 3)),
+            vec!["a ", "!", "=", " b"]
+        );
+
+        let clusters = line.cluster_with_isolation(|idx, _| idx == 3);
+        assert_eq!(clusters[2].byte_to_cell_idx, vec![4, 5]);
+    }
+
+    #[test]
+    fn mask_matches() {
+        let mut line: Line = "token=abc123 ok 😍=x".into();
+        let patterns = vec![Regex::new(r"token=\w+").unwrap(), Regex::new("😍").unwrap()];
+        line.mask_matches(&patterns, '*');
+        assert_eq!(line.as_str(), "************ ok **=x");
+        assert_eq!(line.cells().len(), 20);
+    }
+
     #[test]
     fn hyperlinks() {
         let text =
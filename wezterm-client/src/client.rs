@@ -12,7 +12,7 @@ use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, DomainId};
 use mux::pane::PaneId;
 use mux::ssh::ssh_connect_with_ui;
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
 use openssl::x509::X509;
 use smol::channel::{bounded, unbounded, Receiver, Sender};
@@ -24,8 +24,9 @@ use std::marker::Unpin;
 use std::net::TcpStream;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 enum ReaderMessage {
@@ -36,11 +37,26 @@ enum ReaderMessage {
     Readable,
 }
 
+/// Tracks the health of the connection to the server,
+/// so that it can be reported to the user
+#[derive(Default)]
+struct ConnectionHealth {
+    /// true while the connection is lost and we are
+    /// trying to re-establish it
+    reconnecting: bool,
+    /// true if the server did not answer the most
+    /// recent keepalive in time
+    unresponsive: bool,
+    /// The round trip time of the most recent request
+    latency: Option<Duration>,
+}
+
 #[derive(Clone)]
 pub struct Client {
     sender: Sender<ReaderMessage>,
     local_domain_id: DomainId,
     pub is_reconnectable: bool,
+    health: Arc<Mutex<ConnectionHealth>>,
 }
 
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
@@ -286,6 +302,34 @@ pub fn unix_connect_with_retry(
     Err(error)
 }
 
+/// Presents the auth_token of the unix domain, if one is configured,
+/// over a newly connected stream, before it is used for anything else
+pub fn authenticate_unix_stream(
+    stream: &mut UnixStream,
+    unix_dom: &UnixDomain,
+) -> anyhow::Result<()> {
+    let token = match &unix_dom.auth_token {
+        Some(token) => token.clone(),
+        None => return Ok(()),
+    };
+
+    // The client thread hasn't started yet, so we are free
+    // to use any serial number for this exchange
+    Pdu::Authenticate(Authenticate { token })
+        .encode(&mut *stream, 1)
+        .context("sending auth_token to the server")?;
+    match Pdu::decode(&mut *stream)
+        .context("reading the authentication response from the server")?
+        .pdu
+    {
+        Pdu::UnitResponse(_) => Ok(()),
+        Pdu::ErrorResponse(ErrorResponse { reason }) => {
+            bail!("the server rejected the auth_token: {}", reason)
+        }
+        pdu => bail!("unexpected response to authentication: {:?}", pdu),
+    }
+}
+
 #[async_trait(?Send)]
 pub trait AsyncReadAndWrite: Unpin + AsyncRead + AsyncWrite + std::fmt::Debug + Send {
     async fn wait_for_readable(&self) -> anyhow::Result<()>;
@@ -470,7 +514,7 @@ impl Reconnectable {
         ui.output_str(&format!("Connect to {}\n", sock_path.display()));
         log::trace!("connect to {}", sock_path.display());
 
-        let stream = match unix_connect_with_retry(&sock_path, false) {
+        let mut stream = match unix_connect_with_retry(&sock_path, false) {
             Ok(stream) => stream,
             Err(e) => {
                 if unix_dom.no_serve_automatically || !initial {
@@ -487,6 +531,11 @@ impl Reconnectable {
 
                 let mut cmd = std::process::Command::new(&argv[0]);
                 cmd.args(&argv[1..]);
+                // The server shares our portable directory, which
+                // isn't inherited through the environment
+                if let Some(dir) = config::PORTABLE_DIR.as_ref() {
+                    cmd.env("WEZTERM_PORTABLE_DIR", dir);
+                }
                 let child = cmd
                     .spawn()
                     .with_context(|| format!("while spawning {:?}", cmd))?;
@@ -516,6 +565,7 @@ impl Reconnectable {
         ui.output_str("Connected!\n");
         stream.set_read_timeout(Some(unix_dom.read_timeout))?;
         stream.set_write_timeout(Some(unix_dom.write_timeout))?;
+        authenticate_unix_stream(&mut stream, &unix_dom)?;
         let stream: Box<dyn AsyncReadAndWrite> = Box::new(Async::new(stream)?);
         self.stream.replace(stream);
         Ok(())
@@ -734,10 +784,22 @@ impl Reconnectable {
     }
 }
 
+/// Lets the gui know that the connection status of the domain has changed
+fn notify_connection_changed(local_domain_id: DomainId) {
+    promise::spawn::spawn_into_main_thread(async move {
+        if let Some(mux) = Mux::get() {
+            mux.notify(MuxNotification::DomainConnectionChanged(local_domain_id));
+        }
+    })
+    .detach();
+}
+
 impl Client {
     fn new(local_domain_id: DomainId, mut reconnectable: Reconnectable) -> Self {
         let is_reconnectable = reconnectable.reconnectable();
         let (sender, mut receiver) = unbounded();
+        let health = Arc::new(Mutex::new(ConnectionHealth::default()));
+        let thread_health = Arc::clone(&health);
 
         thread::spawn(move || {
             const BASE_INTERVAL: Duration = Duration::from_secs(1);
@@ -764,6 +826,8 @@ impl Client {
                         break;
                     }
 
+                    thread_health.lock().unwrap().reconnecting = true;
+                    notify_connection_changed(local_domain_id);
                     let mut ui = ConnectionUI::new();
                     ui.title("wezterm: Reconnecting...");
 
@@ -776,6 +840,8 @@ impl Client {
                         match reconnectable.connect(false, &mut ui) {
                             Ok(_) => {
                                 backoff = BASE_INTERVAL;
+                                *thread_health.lock().unwrap() = ConnectionHealth::default();
+                                notify_connection_changed(local_domain_id);
                                 log::error!("Reconnected!");
                                 promise::spawn::spawn_into_main_thread(async move {
                                     ClientDomain::reattach(local_domain_id, ui).await.ok();
@@ -823,6 +889,61 @@ impl Client {
             sender,
             local_domain_id,
             is_reconnectable,
+            health,
+        }
+    }
+
+    /// Returns true while the connection is lost and we are
+    /// trying to re-establish it
+    pub fn is_reconnecting(&self) -> bool {
+        self.health.lock().unwrap().reconnecting
+    }
+
+    /// Returns the round trip time of the most recent request
+    pub fn latency(&self) -> Option<Duration> {
+        self.health.lock().unwrap().latency
+    }
+
+    /// Returns true if the server did not answer the most
+    /// recent keepalive in time
+    pub fn is_unresponsive(&self) -> bool {
+        self.health.lock().unwrap().unresponsive
+    }
+
+    /// Sends a ping and waits up to `timeout` for the reply,
+    /// updating the responsiveness of the connection.
+    /// Interested parties are notified if it changed.
+    pub async fn keepalive(&self, timeout: Duration) {
+        let responsive = smol::future::or(async { self.ping().await.is_ok() }, async {
+            smol::Timer::after(timeout).await;
+            false
+        })
+        .await;
+
+        let changed = {
+            let mut health = self.health.lock().unwrap();
+            if health.reconnecting {
+                // The reconnect logic is responsible for the
+                // connection state until it succeeds
+                return;
+            }
+            let unresponsive = !responsive;
+            std::mem::replace(&mut health.unresponsive, unresponsive) != unresponsive
+        };
+        if changed {
+            if responsive {
+                log::warn!(
+                    "domain {}: server is responding again",
+                    self.local_domain_id
+                );
+            } else {
+                log::error!(
+                    "domain {}: server did not respond to keepalive within {:?}",
+                    self.local_domain_id,
+                    timeout
+                );
+            }
+            notify_connection_changed(self.local_domain_id);
         }
     }
 
@@ -923,10 +1044,15 @@ impl Client {
 
     pub async fn send_pdu(&self, pdu: Pdu) -> anyhow::Result<Pdu> {
         let (promise, rx) = bounded(1);
+        let start = Instant::now();
         self.sender
             .send(ReaderMessage::SendPdu { pdu, promise })
             .await?;
-        rx.recv().await?
+        let result = rx.recv().await?;
+        if result.is_ok() {
+            self.health.lock().unwrap().latency = Some(start.elapsed());
+        }
+        result
     }
 
     rpc!(ping, Ping = (), Pong);
@@ -946,6 +1072,12 @@ impl Client {
         LivenessResponse
     );
     rpc!(get_lines, GetLines, GetLinesResponse);
+    rpc!(get_dimensions, GetPaneDimensions, GetPaneDimensionsResponse);
+    rpc!(get_pane_palette, GetPanePalette, GetPanePaletteResponse);
+    rpc!(screenshot_pane, ScreenshotPane, ScreenshotPaneResponse);
+    rpc!(log_filter, LogFilter, LogFilterResponse);
+    rpc!(get_window_perf, GetWindowPerf, GetWindowPerfResponse);
+    rpc!(emit_event, EmitEvent, UnitResponse);
     rpc!(get_codec_version, GetCodecVersion, GetCodecVersionResponse);
     rpc!(get_tls_creds, GetTlsCreds = (), GetTlsCredsResponse);
     rpc!(
@@ -953,5 +1085,16 @@ impl Client {
         SearchScrollbackRequest,
         SearchScrollbackResponse
     );
+    rpc!(
+        get_pane_bookmarks,
+        GetPaneBookmarks,
+        GetPaneBookmarksResponse
+    );
+    rpc!(set_pane_bookmark, SetPaneBookmark, UnitResponse);
     rpc!(kill_pane, KillPane, UnitResponse);
+    rpc!(kill_tab, KillTab, UnitResponse);
+    rpc!(kill_window, KillWindow, UnitResponse);
+    rpc!(list_workspaces, ListWorkspaces = (), ListWorkspacesResponse);
+    rpc!(rename_workspace, RenameWorkspace, UnitResponse);
+    rpc!(set_window_workspace, SetWindowWorkspace, UnitResponse);
 }
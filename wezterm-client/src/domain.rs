@@ -6,7 +6,7 @@ use codec::{ListPanesResponse, Spawn, SplitPane};
 use config::keyassignment::SpawnTabDomain;
 use config::{SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::ConnectionUI;
-use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState};
+use mux::domain::{alloc_domain_id, ConnectionStatus, Domain, DomainId, DomainState};
 use mux::pane::{Pane, PaneId};
 use mux::tab::{SplitDirection, Tab, TabId};
 use mux::window::WindowId;
@@ -17,11 +17,15 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub struct ClientInner {
     pub client: Client,
     pub local_domain_id: DomainId,
     pub remote_domain_id: DomainId,
+    /// The round trip time above which typed characters are echoed
+    /// locally, or None if predictive local echo is disabled
+    pub local_echo_threshold: Option<Duration>,
     remote_to_local_window: Mutex<HashMap<WindowId, WindowId>>,
     remote_to_local_tab: Mutex<HashMap<TabId, TabId>>,
     remote_to_local_pane: Mutex<HashMap<PaneId, PaneId>>,
@@ -134,6 +138,57 @@ impl ClientDomainConfig {
         }
     }
 
+    /// Returns the host that we connect to, if this is not
+    /// a local domain
+    pub fn remote_host(&self) -> Option<String> {
+        match self {
+            ClientDomainConfig::Unix(_) => None,
+            ClientDomainConfig::Tls(tls) => Some(tls.remote_address.clone()),
+            ClientDomainConfig::Ssh(ssh) => Some(ssh.remote_address.clone()),
+        }
+    }
+
+    /// Returns the keepalive interval and timeout, or None
+    /// if keepalives are disabled for this domain
+    pub fn keepalive(&self) -> Option<(Duration, Duration)> {
+        let (interval, timeout) = match self {
+            ClientDomainConfig::Unix(_) => return None,
+            ClientDomainConfig::Tls(tls) => (
+                tls.keepalive_interval_seconds,
+                tls.keepalive_timeout_seconds,
+            ),
+            ClientDomainConfig::Ssh(ssh) => (
+                ssh.keepalive_interval_seconds,
+                ssh.keepalive_timeout_seconds,
+            ),
+        };
+        if interval == 0 {
+            return None;
+        }
+        Some((Duration::from_secs(interval), Duration::from_secs(timeout)))
+    }
+
+    /// Returns the round trip time above which typed characters
+    /// are echoed locally, or None if that is disabled
+    pub fn local_echo_threshold(&self) -> Option<Duration> {
+        let (enabled, threshold) = match self {
+            ClientDomainConfig::Unix(unix) => {
+                (unix.predictive_local_echo, unix.local_echo_threshold_ms)
+            }
+            ClientDomainConfig::Tls(tls) => {
+                (tls.predictive_local_echo, tls.local_echo_threshold_ms)
+            }
+            ClientDomainConfig::Ssh(ssh) => {
+                (ssh.predictive_local_echo, ssh.local_echo_threshold_ms)
+            }
+        };
+        if enabled {
+            Some(Duration::from_millis(threshold))
+        } else {
+            None
+        }
+    }
+
     pub fn connect_automatically(&self) -> bool {
         match self {
             ClientDomainConfig::Unix(unix) => unix.connect_automatically,
@@ -144,7 +199,11 @@ impl ClientDomainConfig {
 }
 
 impl ClientInner {
-    pub fn new(local_domain_id: DomainId, client: Client) -> Self {
+    pub fn new(
+        local_domain_id: DomainId,
+        client: Client,
+        local_echo_threshold: Option<Duration>,
+    ) -> Self {
         // Assumption: that the domain id on the other end is
         // always the first created default domain.  In the future
         // we'll add a way to discover/enumerate domains to populate
@@ -154,6 +213,7 @@ impl ClientInner {
             client,
             local_domain_id,
             remote_domain_id,
+            local_echo_threshold,
             remote_to_local_window: Mutex::new(HashMap::new()),
             remote_to_local_tab: Mutex::new(HashMap::new()),
             remote_to_local_pane: Mutex::new(HashMap::new()),
@@ -245,6 +305,10 @@ impl ClientDomain {
             };
 
             if let Some((remote_window_id, remote_tab_id)) = tabroot.window_and_tab_ids() {
+                let entry_workspace = tabroot
+                    .workspace()
+                    .unwrap_or(mux::DEFAULT_WORKSPACE)
+                    .to_string();
                 let tab;
 
                 if let Some(tab_id) = inner.remote_to_local_tab_id(remote_tab_id) {
@@ -312,8 +376,9 @@ impl ClientDomain {
                     if window.idx_by_id(tab.tab_id()).is_none() {
                         window.push(&tab);
                     }
+                    window.set_workspace(&entry_workspace);
                 } else {
-                    let local_window_id = mux.new_empty_window();
+                    let local_window_id = mux.new_empty_window(Some(entry_workspace));
                     inner.record_remote_to_local_window_mapping(remote_window_id, *local_window_id);
                     mux.add_tab_to_window(&tab, *local_window_id)?;
                 }
@@ -323,6 +388,25 @@ impl ClientDomain {
         Ok(())
     }
 
+    /// Periodically pings the server so that a dead or unresponsive
+    /// peer is detected even while the panes are idle.
+    /// Stops when the domain is detached or re-attached.
+    async fn keepalive(
+        domain_id: DomainId,
+        inner: Arc<ClientInner>,
+        interval: Duration,
+        timeout: Duration,
+    ) {
+        loop {
+            smol::Timer::after(interval).await;
+            match Self::get_client_inner_for_domain(domain_id) {
+                Ok(current) if Arc::ptr_eq(&current, &inner) => {}
+                _ => return,
+            }
+            inner.client.keepalive(timeout).await;
+        }
+    }
+
     fn finish_attach(
         domain_id: DomainId,
         client: Client,
@@ -336,9 +420,23 @@ impl ClientDomain {
             .downcast_ref::<Self>()
             .ok_or_else(|| anyhow!("domain {} is not a ClientDomain", domain_id))?;
 
-        let inner = Arc::new(ClientInner::new(domain_id, client));
+        let inner = Arc::new(ClientInner::new(
+            domain_id,
+            client,
+            domain.config.local_echo_threshold(),
+        ));
         *domain.inner.borrow_mut() = Some(Arc::clone(&inner));
 
+        if let Some((interval, timeout)) = domain.config.keepalive() {
+            promise::spawn::spawn(Self::keepalive(
+                domain_id,
+                Arc::clone(&inner),
+                interval,
+                timeout,
+            ))
+            .detach();
+        }
+
         Self::process_pane_list(inner, panes)?;
 
         Ok(())
@@ -516,4 +614,20 @@ impl Domain for ClientDomain {
             DomainState::Detached
         }
     }
+
+    fn connection_status(&self) -> Option<ConnectionStatus> {
+        let inner = self.inner();
+        Some(ConnectionStatus {
+            remote_host: self.config.remote_host(),
+            connected: inner
+                .as_ref()
+                .map(|inner| !inner.client.is_reconnecting())
+                .unwrap_or(false),
+            responsive: inner
+                .as_ref()
+                .map(|inner| !inner.client.is_unresponsive())
+                .unwrap_or(false),
+            latency: inner.and_then(|inner| inner.client.latency()),
+        })
+    }
 }
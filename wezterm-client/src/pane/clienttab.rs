@@ -7,12 +7,13 @@ use codec::*;
 use config::configuration;
 use filedescriptor::Pipe;
 use mux::domain::DomainId;
-use mux::pane::{alloc_pane_id, Pane, PaneId, Pattern, SearchResult};
+use mux::pane::{alloc_pane_id, Bookmark, Pane, PaneId, Pattern, ReadOnlyWriter, SearchResult};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use ratelim::RateLimiter;
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::cell::RefMut;
 use std::ops::Range;
@@ -34,6 +35,8 @@ pub struct ClientPane {
     mouse: Rc<RefCell<MouseState>>,
     clipboard: RefCell<Option<Arc<dyn Clipboard>>>,
     mouse_grabbed: RefCell<bool>,
+    read_only: Cell<bool>,
+    read_only_writer: RefCell<ReadOnlyWriter>,
 }
 
 impl ClientPane {
@@ -88,6 +91,8 @@ impl ClientPane {
             reader,
             clipboard: RefCell::new(None),
             mouse_grabbed: RefCell::new(false),
+            read_only: Cell::new(false),
+            read_only_writer: RefCell::new(ReadOnlyWriter::new(local_pane_id)),
         }
     }
 
@@ -155,6 +160,9 @@ impl Pane for ClientPane {
     }
 
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
+        if self.read_only.get() {
+            bail!("pane {} is read-only", self.local_pane_id);
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         self.renderable
@@ -188,6 +196,9 @@ impl Pane for ClientPane {
     }
 
     fn writer(&self) -> RefMut<dyn std::io::Write> {
+        if self.read_only.get() {
+            return self.read_only_writer.borrow_mut();
+        }
         self.writer.borrow_mut()
     }
 
@@ -261,7 +272,33 @@ impl Pane for ClientPane {
         }
     }
 
+    async fn get_bookmarks(&self) -> anyhow::Result<Vec<Bookmark>> {
+        let GetPaneBookmarksResponse { bookmarks } = self
+            .client
+            .client
+            .get_pane_bookmarks(GetPaneBookmarks {
+                pane_id: self.remote_pane_id,
+            })
+            .await?;
+        Ok(bookmarks)
+    }
+
+    async fn set_bookmark(&self, name: String, row: Option<StableRowIndex>) -> anyhow::Result<()> {
+        self.client
+            .client
+            .set_pane_bookmark(SetPaneBookmark {
+                pane_id: self.remote_pane_id,
+                name,
+                row,
+            })
+            .await?;
+        Ok(())
+    }
+
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        if self.read_only.get() {
+            bail!("pane {} is read-only", self.local_pane_id);
+        }
         let input_serial;
         {
             let renderable = self.renderable.borrow();
@@ -302,6 +339,8 @@ impl Pane for ClientPane {
                 .client
                 .kill_pane(KillPane {
                     pane_id: remote_pane_id,
+                    // The user has already been prompted locally
+                    force: true,
                 })
                 .await
         })
@@ -309,6 +348,11 @@ impl Pane for ClientPane {
     }
 
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
+        if self.read_only.get() {
+            // Mouse reports would reach the application, so
+            // they are dropped along with the keyboard input
+            return Ok(());
+        }
         self.mouse.borrow_mut().append(event);
         if MouseState::next(Rc::clone(&self.mouse)) {
             self.renderable
@@ -345,6 +389,14 @@ impl Pane for ClientPane {
         *self.mouse_grabbed.borrow()
     }
 
+    fn is_read_only(&self) -> bool {
+        self.read_only.get()
+    }
+
+    fn set_read_only(&self, read_only: bool) {
+        self.read_only.set(read_only);
+    }
+
     fn is_alt_screen_active(&self) -> bool {
         // FIXME: retrieve this from the remote
         false
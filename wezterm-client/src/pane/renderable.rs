@@ -15,7 +15,7 @@ use std::ops::Range;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use termwiz::cell::{Cell, CellAttributes, Underline};
+use termwiz::cell::{Cell, CellAttributes, Intensity, Underline};
 use termwiz::color::AnsiColor;
 use url::Url;
 use wezterm_term::{KeyCode, KeyModifiers};
@@ -23,6 +23,10 @@ use wezterm_term::{Line, StableRowIndex};
 
 const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
 const BASE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Scrollback is fetched lazily; when the user scrolls into a region
+/// that we don't have yet, the request is rounded out to this many rows
+/// so that scrolling up doesn't issue a request for every row.
+const SCROLLBACK_PAGE_ROWS: StableRowIndex = 100;
 
 #[derive(Debug)]
 enum LineEntry {
@@ -77,6 +81,12 @@ pub struct RenderableInner {
     last_input_rtt: u64,
 
     pub input_serial: InputSerial,
+
+    /// The seqno of the most recently applied update from the server
+    seqno: u64,
+    /// Rows that hold a local prediction rather than the server's line
+    predicted_rows: RangeSet<StableRowIndex>,
+    resync_pending: bool,
 }
 
 pub struct RenderableState {
@@ -113,6 +123,9 @@ impl RenderableInner {
             last_late_dirty: now,
             last_input_rtt: 0,
             input_serial: InputSerial::empty(),
+            seqno: 0,
+            predicted_rows: RangeSet::new(),
+            resync_pending: false,
         }
     }
 
@@ -131,9 +144,13 @@ impl RenderableInner {
 
     /// Predictive echo can be noisy when the link is working well,
     /// so we only employ it when it looks like the latency is high.
-    /// We pick 100ms as the threshold for this.
+    /// The threshold for this is configured by the domain, which
+    /// may also disable it entirely.
     fn should_predict(&self) -> bool {
-        self.last_input_rtt >= 100
+        match self.client.local_echo_threshold {
+            Some(threshold) => self.last_input_rtt >= threshold.as_millis() as u64,
+            None => false,
+        }
     }
 
     /// Compute a "prediction" and apply it to the line data that we
@@ -229,11 +246,13 @@ impl RenderableInner {
             | Some(LineEntry::Dirty(mut line)) => {
                 self.apply_prediction(c, &mut line);
                 self.lines.put(row, LineEntry::Dirty(line));
+                self.predicted_rows.add(row);
             }
             Some(LineEntry::DirtyAndFetching(mut line, instant)) => {
                 self.apply_prediction(c, &mut line);
                 self.lines
                     .put(row, LineEntry::DirtyAndFetching(line, instant));
+                self.predicted_rows.add(row);
             }
             Some(entry) => {
                 self.lines.put(row, entry);
@@ -279,11 +298,13 @@ impl RenderableInner {
                 | Some(LineEntry::Dirty(mut line)) => {
                     self.apply_paste_prediction(idx, paste_line, &mut line);
                     self.lines.put(row, LineEntry::Dirty(line));
+                    self.predicted_rows.add(row);
                 }
                 Some(LineEntry::DirtyAndFetching(mut line, instant)) => {
                     self.apply_paste_prediction(idx, paste_line, &mut line);
                     self.lines
                         .put(row, LineEntry::DirtyAndFetching(line, instant));
+                    self.predicted_rows.add(row);
                 }
                 Some(entry) => {
                     self.lines.put(row, entry);
@@ -342,11 +363,47 @@ impl RenderableInner {
         self.working_dir = delta.working_dir.map(Into::into);
 
         let config = configuration();
+        let mut bonus_rows = RangeSet::new();
         for (stable_row, line) in delta.bonus_lines.lines() {
             self.put_line(stable_row, line, &config, None);
             dirty.remove(stable_row);
+            bonus_rows.add(stable_row);
         }
 
+        // The viewport lines that are not in bonus_lines are unchanged
+        // since the update with base_seqno.  We can keep our copy if that
+        // is the update that we most recently applied and we still have
+        // the line; otherwise we need the server to resend the viewport.
+        if delta.base_seqno == 0 {
+            self.resync_pending = false;
+        } else {
+            let in_sequence = delta.base_seqno == self.seqno;
+            let viewport = delta.dimensions.physical_top
+                ..delta.dimensions.physical_top + delta.dimensions.viewport_rows as StableRowIndex;
+            let mut need_resync = false;
+            for stable_row in viewport {
+                if bonus_rows.contains(stable_row) {
+                    continue;
+                }
+                if self.predicted_rows.contains(stable_row) {
+                    // Our copy holds a prediction; fetch the real line
+                    dirty.add(stable_row);
+                    continue;
+                }
+                match self.lines.peek(&stable_row) {
+                    Some(LineEntry::Line(_)) | Some(LineEntry::Dirty(_)) if in_sequence => {
+                        dirty.remove(stable_row);
+                    }
+                    _ => need_resync = true,
+                }
+            }
+            if need_resync {
+                self.request_resync();
+            }
+        }
+        self.seqno = delta.seqno;
+        self.predicted_rows = RangeSet::new();
+
         if !dirty.is_empty() {
             Mux::get()
                 .unwrap()
@@ -398,6 +455,40 @@ impl RenderableInner {
         }
     }
 
+    /// Ask the server to resend the whole viewport with its next update
+    fn request_resync(&mut self) {
+        if self.resync_pending {
+            return;
+        }
+        self.resync_pending = true;
+        log::trace!("requesting resync of remote pane {}", self.remote_pane_id);
+
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        let local_pane_id = self.local_pane_id;
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .get_tab_render_changes(GetPaneRenderChanges {
+                    pane_id: remote_pane_id,
+                    resync: true,
+                })
+                .await
+            {
+                log::error!("resync of remote pane {} failed: {}", remote_pane_id, err);
+                // Allow a later update to request it again
+                let mux = Mux::get().unwrap();
+                if let Some(pane) = mux.get_pane(local_pane_id) {
+                    if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                        let renderable = client_pane.renderable.borrow_mut();
+                        renderable.inner.borrow_mut().resync_pending = false;
+                    }
+                }
+            }
+        })
+        .detach();
+    }
+
     pub fn make_all_stale(&mut self) {
         let mut lines = LruCache::unbounded();
         while let Some((stable_row, entry)) = self.lines.pop_lru() {
@@ -473,6 +564,43 @@ impl RenderableInner {
         self.lines.put(stable_row, entry);
     }
 
+    /// Returns the line to display for a row that hasn't been received
+    /// from the server yet.  Rows in the scrollback show a dim indicator
+    /// so that it is clear that the region is still being fetched.
+    fn placeholder_line(&self, stable_row: StableRowIndex) -> Line {
+        let mut line = Line::with_width(self.dimensions.cols);
+        if stable_row < self.dimensions.physical_top {
+            let mut attr = CellAttributes::default();
+            attr.set_intensity(Intensity::Half);
+            attr.set_italic(true);
+            line.overlay_text_with_attribute(0, "⋯ fetching scrollback", attr);
+        }
+        line
+    }
+
+    /// Rounds any scrollback rows in `to_fetch` out to a page of
+    /// SCROLLBACK_PAGE_ROWS rows above the requested region, so that
+    /// the scrollback is transferred in pages as the user scrolls up.
+    /// The additional rows are marked as Fetching.
+    fn extend_scrollback_fetch(&mut self, to_fetch: &mut RangeSet<StableRowIndex>, now: Instant) {
+        let ranges: Vec<Range<StableRowIndex>> = to_fetch.iter().cloned().collect();
+        for range in ranges {
+            if range.start >= self.dimensions.physical_top {
+                continue;
+            }
+            let page_start = range
+                .start
+                .saturating_sub(SCROLLBACK_PAGE_ROWS)
+                .max(self.dimensions.scrollback_top);
+            for stable_row in page_start..range.start {
+                if !self.lines.contains(&stable_row) {
+                    self.lines.put(stable_row, LineEntry::Fetching(now));
+                    to_fetch.add(stable_row);
+                }
+            }
+        }
+    }
+
     fn schedule_fetch_lines(&mut self, to_fetch: RangeSet<StableRowIndex>, now: Instant) {
         if to_fetch.is_empty() {
             return;
@@ -575,6 +703,7 @@ impl RenderableInner {
                 .client
                 .get_tab_render_changes(GetPaneRenderChanges {
                     pane_id: remote_pane_id,
+                    resync: false,
                 })
                 .await
             {
@@ -631,7 +760,7 @@ impl RenderableState {
                     LineEntry::DirtyAndFetching(line, then)
                 }
                 Some(LineEntry::Fetching(then)) => {
-                    result.push(Line::with_width(inner.dimensions.cols));
+                    result.push(inner.placeholder_line(idx));
                     LineEntry::Fetching(then)
                 }
                 Some(LineEntry::Stale(line)) => {
@@ -640,7 +769,7 @@ impl RenderableState {
                     LineEntry::DirtyAndFetching(line, now)
                 }
                 None => {
-                    result.push(Line::with_width(inner.dimensions.cols));
+                    result.push(inner.placeholder_line(idx));
                     to_fetch.add(idx);
                     LineEntry::Fetching(now)
                 }
@@ -672,6 +801,7 @@ impl RenderableState {
             inner.lines.put(idx, entry);
         }
 
+        inner.extend_scrollback_fetch(&mut to_fetch, now);
         inner.schedule_fetch_lines(to_fetch, now);
         (lines.start, result)
     }
@@ -202,11 +202,15 @@ impl Face {
         ft_result(unsafe { FT_Select_Size(self.face, idx as i32) }, ()).context("FT_Select_Size")
     }
 
+    /// Loads and renders the glyph, shifting its outline to the right
+    /// by `x_offset`, a fraction of a pixel, before it is rendered.
+    /// Bitmap glyphs can't be shifted and are rendered as-is.
     pub fn load_and_render_glyph(
         &mut self,
         glyph_index: FT_UInt,
         load_flags: FT_Int32,
         render_mode: FT_Render_Mode,
+        x_offset: f64,
     ) -> anyhow::Result<&FT_GlyphSlotRec_> {
         unsafe {
             ft_result(FT_Load_Glyph(self.face, glyph_index, load_flags), ()).with_context(
@@ -218,6 +222,9 @@ impl Face {
                 },
             )?;
             let slot = &mut *(*self.face).glyph;
+            if x_offset != 0.0 && slot.format == FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                FT_Outline_Translate(&slot.outline, (x_offset * 64.0).round() as FT_Pos, 0);
+            }
             ft_result(FT_Render_Glyph(slot, render_mode), ())
                 .context("load_and_render_glyph: FT_Render_Glyph")?;
             Ok(slot)
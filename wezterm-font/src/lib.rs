@@ -8,6 +8,7 @@ use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
+use termwiz::cell::Presentation;
 use thiserror::Error;
 use wezterm_term::CellAttributes;
 use window::default_dpi;
@@ -25,7 +26,7 @@ pub mod units;
 #[cfg(all(unix, not(target_os = "macos")))]
 pub mod fcwrap;
 
-pub use crate::rasterizer::RasterizedGlyph;
+pub use crate::rasterizer::{RasterJob, RasterizedGlyph};
 pub use crate::shaper::{FallbackIdx, FontMetrics, GlyphInfo};
 
 #[derive(Debug, Error)]
@@ -35,12 +36,18 @@ pub struct ClearShapeCache {}
 pub struct LoadedFont {
     rasterizers: RefCell<HashMap<FallbackIdx, Box<dyn FontRasterizer>>>,
     handles: RefCell<Vec<FontDataHandle>>,
+    /// The harfbuzz_features specified for the font
+    /// corresponding to each of the handles
+    features: RefCell<Vec<Option<Vec<String>>>>,
     shaper: RefCell<Box<dyn FontShaper>>,
     metrics: FontMetrics,
     font_size: f64,
     dpi: u32,
     font_config: Weak<FontConfigInner>,
     pending_fallback: Arc<Mutex<Vec<FontDataHandle>>>,
+    /// The rasterizer_identity of each of the handles, which
+    /// is costly to compute as it involves a filesystem lookup
+    identities: RefCell<HashMap<FallbackIdx, Option<String>>>,
 }
 
 impl LoadedFont {
@@ -52,10 +59,12 @@ impl LoadedFont {
         let mut loaded = false;
         {
             let mut handles = self.handles.borrow_mut();
+            let mut features = self.features.borrow_mut();
             for h in extra_handles {
                 if !handles.iter().any(|existing| *existing == h) {
                     let idx = handles.len() - 1;
                     handles.insert(idx, h);
+                    features.insert(idx, None);
                     self.rasterizers.borrow_mut().remove(&idx);
                     loaded = true;
                 }
@@ -65,17 +74,24 @@ impl LoadedFont {
             }
         }
         if loaded {
+            self.identities.borrow_mut().clear();
             if let Some(font_config) = self.font_config.upgrade() {
-                *self.shaper.borrow_mut() =
-                    new_shaper(&*font_config.config.borrow(), &self.handles.borrow())?;
+                *self.shaper.borrow_mut() = new_shaper(
+                    &*font_config.config.borrow(),
+                    &self.handles.borrow(),
+                    &self.features.borrow(),
+                )?;
             }
         }
         Ok(loaded)
     }
 
+    /// Shapes `text`.  If `presentation` is set, emoji in the text are
+    /// displayed using that presentation rather than their default.
     pub fn shape<F: FnOnce() + Send + Sync + 'static>(
         &self,
         text: &str,
+        presentation: Option<Presentation>,
         completion: F,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         let mut no_glyphs = vec![];
@@ -93,10 +109,13 @@ impl LoadedFont {
             }
         }
 
-        let result = self
-            .shaper
-            .borrow()
-            .shape(text, self.font_size, self.dpi, &mut no_glyphs);
+        let result = self.shaper.borrow().shape(
+            text,
+            presentation,
+            self.font_size,
+            self.dpi,
+            &mut no_glyphs,
+        );
 
         if !no_glyphs.is_empty() {
             if let Some(font_config) = self.font_config.upgrade() {
@@ -117,14 +136,101 @@ impl LoadedFont {
             .metrics_for_idx(font_idx, self.font_size, self.dpi)
     }
 
+    /// Returns a description of everything that influences the bitmaps
+    /// produced by `rasterize_glyph` for the font at `fallback`: the font
+    /// file and its modification time, the size, the dpi and the
+    /// rasterizer settings.  Bitmaps rasterized for the same identity
+    /// can be reused, for example across restarts.
+    pub fn rasterizer_identity(&self, fallback: FallbackIdx) -> Option<String> {
+        self.identities
+            .borrow_mut()
+            .entry(fallback)
+            .or_insert_with(|| self.compute_rasterizer_identity(fallback))
+            .clone()
+    }
+
+    fn compute_rasterizer_identity(&self, fallback: FallbackIdx) -> Option<String> {
+        let face = match self.handles.borrow().get(fallback)? {
+            FontDataHandle::OnDisk { path, index } => {
+                let meta = std::fs::metadata(path).ok()?;
+                let modified = meta
+                    .modified()
+                    .ok()?
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()?;
+                format!(
+                    "{}#{} len={} mtime={}",
+                    path.display(),
+                    index,
+                    meta.len(),
+                    modified.as_nanos()
+                )
+            }
+            // Built-in fonts are compiled into the executable, so
+            // they can only change along with its version
+            FontDataHandle::Memory { name, data, index } => format!(
+                "{}#{} len={} version={}",
+                name,
+                index,
+                data.len(),
+                config::wezterm_version()
+            ),
+        };
+        let config = self
+            .font_config
+            .upgrade()
+            .map_or_else(configuration, |c| c.config.borrow().clone());
+        Some(format!(
+            "{} size={} dpi={} rasterizer={:?} target={:?} flags={} interpreter={:?}",
+            face,
+            self.font_size,
+            self.dpi,
+            config.font_rasterizer,
+            config.freetype_load_target,
+            config.freetype_load_flags.bits(),
+            config.freetype_interpreter_version
+        ))
+    }
+
+    /// Returns a job that rasterizes the glyph in the same way that
+    /// `rasterize_glyph` does, but that can be run on another thread
+    pub fn raster_job(
+        &self,
+        glyph_pos: u32,
+        fallback: FallbackIdx,
+        x_offset: f64,
+    ) -> anyhow::Result<RasterJob> {
+        let handle = self
+            .handles
+            .borrow()
+            .get(fallback)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("no font at fallback index {}", fallback))?;
+        let rasterizer = self
+            .font_config
+            .upgrade()
+            .map_or(FontRasterizerSelection::default(), |c| {
+                c.config.borrow().font_rasterizer
+            });
+        Ok(RasterJob {
+            handle,
+            rasterizer,
+            glyph_pos,
+            size: self.font_size,
+            dpi: self.dpi,
+            x_offset,
+        })
+    }
+
     pub fn rasterize_glyph(
         &self,
         glyph_pos: u32,
         fallback: FallbackIdx,
+        x_offset: f64,
     ) -> anyhow::Result<RasterizedGlyph> {
         let mut rasterizers = self.rasterizers.borrow_mut();
         if let Some(raster) = rasterizers.get(&fallback) {
-            raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi)
+            raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi, x_offset)
         } else {
             let raster_selection = self
                 .font_config
@@ -133,7 +239,7 @@ impl LoadedFont {
                     c.config.borrow().font_rasterizer
                 });
             let raster = new_rasterizer(raster_selection, &(self.handles.borrow())[fallback])?;
-            let result = raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi);
+            let result = raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi, x_offset);
             rasterizers.insert(fallback, raster);
             result
         }
@@ -269,14 +375,34 @@ impl FontConfigInner {
         let mut loaded = HashSet::new();
 
         let mut handles = vec![];
+        let mut features = vec![];
         for attrs in &[&preferred_attributes, &fallback_attributes] {
-            self.font_dirs
-                .borrow()
-                .resolve_multiple(attrs, &mut handles, &mut loaded);
-            handles.append(&mut self.locator.load_fonts(attrs, &mut loaded)?);
-            self.built_in
-                .borrow()
-                .resolve_multiple(attrs, &mut handles, &mut loaded);
+            // Each attribute is resolved individually so that we know
+            // which harfbuzz_features apply to the resulting handles
+            for attr in attrs.iter() {
+                self.font_dirs.borrow().resolve_multiple(
+                    std::slice::from_ref(attr),
+                    &mut handles,
+                    &mut loaded,
+                );
+                features.resize(handles.len(), attr.harfbuzz_features.clone());
+            }
+            for attr in attrs.iter() {
+                handles.append(
+                    &mut self
+                        .locator
+                        .load_fonts(std::slice::from_ref(attr), &mut loaded)?,
+                );
+                features.resize(handles.len(), attr.harfbuzz_features.clone());
+            }
+            for attr in attrs.iter() {
+                self.built_in.borrow().resolve_multiple(
+                    std::slice::from_ref(attr),
+                    &mut handles,
+                    &mut loaded,
+                );
+                features.resize(handles.len(), attr.harfbuzz_features.clone());
+            }
         }
 
         for attr in &attributes {
@@ -324,7 +450,7 @@ impl FontConfigInner {
             }
         }
 
-        let shaper = new_shaper(&*config, &handles)?;
+        let shaper = new_shaper(&*config, &handles, &features)?;
 
         let font_size = config.font_size * *self.font_scale.borrow();
         let dpi =
@@ -339,12 +465,14 @@ impl FontConfigInner {
         let loaded = Rc::new(LoadedFont {
             rasterizers: RefCell::new(HashMap::new()),
             handles: RefCell::new(handles),
+            features: RefCell::new(features),
             shaper: RefCell::new(shaper),
             metrics,
             font_size,
             dpi,
             font_config: Rc::downgrade(myself),
             pending_fallback: Arc::new(Mutex::new(vec![])),
+            identities: RefCell::new(HashMap::new()),
         });
 
         fonts.insert(style.clone(), Rc::clone(&loaded));
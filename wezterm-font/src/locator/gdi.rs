@@ -283,6 +283,7 @@ impl FontLocator for GdiFontLocator {
                         italic: false,
                         family: font.family_name(),
                         is_fallback: true,
+                        harfbuzz_features: None,
                     };
 
                     if !resolved.contains(&attr) {
@@ -21,6 +21,7 @@ impl FontRasterizer for FreeTypeRasterizer {
         glyph_pos: u32,
         size: f64,
         dpi: u32,
+        x_offset: f64,
     ) -> anyhow::Result<RasterizedGlyph> {
         self.face.borrow_mut().set_font_size(size, dpi)?;
 
@@ -28,7 +29,7 @@ impl FontRasterizer for FreeTypeRasterizer {
 
         let mut face = self.face.borrow_mut();
         let descender = unsafe { (*(*face.face).size).metrics.descender as f64 / 64.0 };
-        let ft_glyph = face.load_and_render_glyph(glyph_pos, load_flags, render_mode)?;
+        let ft_glyph = face.load_and_render_glyph(glyph_pos, load_flags, render_mode, x_offset)?;
 
         let mode: ftwrap::FT_Pixel_Mode =
             unsafe { mem::transmute(u32::from(ft_glyph.bitmap.pixel_mode)) };
@@ -152,12 +153,12 @@ impl FreeTypeRasterizer {
             let src_offset = y * pitch as usize;
             let dest_offset = y * width * 4;
             for x in 0..width {
-                // Note: it is unclear whether the LCD data format
-                // is BGR or RGB.  I'm using RGB here because the
-                // antialiasing in other apps seems to do this.
-                let blue = data[src_offset + (x * 3)];
+                // FreeType stores the coverage of the sub-pixels of
+                // each pixel in RGB order, which is the horizontal order
+                // of the sub-pixels on the majority of LCD displays
+                let red = data[src_offset + (x * 3)];
                 let green = data[src_offset + (x * 3) + 1];
-                let red = data[src_offset + (x * 3) + 2];
+                let blue = data[src_offset + (x * 3) + 2];
                 let alpha = red.max(green).max(blue);
 
                 // Texture is SRGBA, which in OpenGL means
@@ -17,16 +17,33 @@ pub struct RasterizedGlyph {
 }
 
 /// Rasterizes the specified glyph index in the associated font
-/// and returns the generated bitmap
+/// and returns the generated bitmap.
+/// The glyph is shifted to the right by `x_offset`, which is a
+/// fraction of a pixel, so that it can be positioned more precisely
+/// than on whole pixel boundaries.
 pub trait FontRasterizer {
     fn rasterize_glyph(
         &self,
         glyph_pos: u32,
         size: f64,
         dpi: u32,
+        x_offset: f64,
     ) -> anyhow::Result<RasterizedGlyph>;
 }
 
+/// Describes a glyph to be rasterized on another thread.  The font
+/// is referred to by its handle, as the rasterizers of a `LoadedFont`
+/// can't leave the thread that owns it.
+#[derive(Clone, Debug)]
+pub struct RasterJob {
+    pub handle: FontDataHandle,
+    pub rasterizer: FontRasterizerSelection,
+    pub glyph_pos: u32,
+    pub size: f64,
+    pub dpi: u32,
+    pub x_offset: f64,
+}
+
 pub fn new_rasterizer(
     rasterizer: FontRasterizerSelection,
     handle: &FontDataHandle,
@@ -14,7 +14,7 @@ use allsorts::tables::{
 };
 use allsorts::tag;
 use anyhow::{anyhow, bail, Context};
-use termwiz::cell::unicode_column_width;
+use termwiz::cell::{unicode_column_width, Presentation};
 use tinyvec::*;
 use unicode_general_category::{get_general_category, GeneralCategory};
 
@@ -573,6 +573,7 @@ impl FontShaper for AllsortsShaper {
     fn shape(
         &self,
         text: &str,
+        _presentation: Option<Presentation>,
         size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
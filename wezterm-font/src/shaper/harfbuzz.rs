@@ -9,7 +9,7 @@ use log::error;
 use ordered_float::NotNan;
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
-use termwiz::cell::unicode_column_width;
+use termwiz::cell::{grapheme_column_width, unicode_column_width, Presentation};
 use thiserror::Error;
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -31,8 +31,28 @@ impl<'a> std::fmt::Debug for Info<'a> {
     }
 }
 
-fn make_glyphinfo(text: &str, font_idx: usize, info: &Info) -> GlyphInfo {
-    let num_cells = unicode_column_width(text) as u8;
+/// Returns the number of cells occupied by text that is to be
+/// displayed using the specified emoji presentation
+fn column_width(text: &str, presentation: Option<Presentation>) -> usize {
+    match presentation {
+        None => unicode_column_width(text),
+        Some(presentation) => text
+            .graphemes(true)
+            .map(|g| match Presentation::for_grapheme(g) {
+                Some(_) => presentation.column_width(),
+                None => grapheme_column_width(g),
+            })
+            .sum(),
+    }
+}
+
+fn make_glyphinfo(
+    text: &str,
+    presentation: Option<Presentation>,
+    font_idx: usize,
+    info: &Info,
+) -> GlyphInfo {
+    let num_cells = column_width(text, presentation) as u8;
     let is_space = text == " ";
     GlyphInfo {
         #[cfg(debug_assertions)]
@@ -52,6 +72,7 @@ fn make_glyphinfo(text: &str, font_idx: usize, info: &Info) -> GlyphInfo {
 struct FontPair {
     face: ftwrap::Face,
     font: harfbuzz::Font,
+    has_color: bool,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -63,6 +84,8 @@ struct MetricsKey {
 
 pub struct HarfbuzzShaper {
     handles: Vec<FontDataHandle>,
+    /// Per-font overrides for the harfbuzz_features configuration
+    features: Vec<Option<Vec<String>>>,
     fonts: Vec<RefCell<Option<FontPair>>>,
     lib: ftwrap::Library,
     metrics: RefCell<HashMap<MetricsKey, FontMetrics>>,
@@ -103,9 +126,14 @@ fn is_question_string(s: &str) -> bool {
 }
 
 impl HarfbuzzShaper {
-    pub fn new(config: &ConfigHandle, handles: &[FontDataHandle]) -> anyhow::Result<Self> {
+    pub fn new(
+        config: &ConfigHandle,
+        handles: &[FontDataHandle],
+        features: &[Option<Vec<String>>],
+    ) -> anyhow::Result<Self> {
         let lib = ftwrap::Library::new()?;
         let handles = handles.to_vec();
+        let features = features.to_vec();
         let mut fonts = vec![];
         for _ in 0..handles.len() {
             fonts.push(RefCell::new(None));
@@ -113,6 +141,7 @@ impl HarfbuzzShaper {
         Ok(Self {
             fonts,
             handles,
+            features,
             lib,
             metrics: RefCell::new(HashMap::new()),
             config: config.clone(),
@@ -133,7 +162,15 @@ impl HarfbuzzShaper {
                     let mut font = harfbuzz::Font::new(face.face);
                     let (load_flags, _) = ftwrap::compute_load_flags_from_config();
                     font.set_load_flags(load_flags);
-                    *opt_pair = Some(FontPair { face, font });
+                    let has_color = unsafe {
+                        (((*face.face).face_flags as u32) & (ftwrap::FT_FACE_FLAG_COLOR as u32))
+                            != 0
+                    };
+                    *opt_pair = Some(FontPair {
+                        face,
+                        font,
+                        has_color,
+                    });
                 }
 
                 Ok(Some(RefMut::map(opt_pair, |opt_pair| {
@@ -143,17 +180,56 @@ impl HarfbuzzShaper {
         }
     }
 
+    fn has_color(&self, font_idx: FallbackIdx) -> Option<bool> {
+        match self.load_fallback(font_idx) {
+            Ok(Some(pair)) => Some(pair.has_color),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the font at font_idx should be skipped when
+    /// shaping text with the specified presentation, because it is of
+    /// the wrong kind and there is a later fallback of the right kind.
+    /// Color fonts are considered to be emoji fonts.
+    /// The last resort font isn't considered to be a candidate.
+    fn skip_for_presentation(&self, font_idx: FallbackIdx, presentation: Presentation) -> bool {
+        let want_color = presentation == Presentation::Emoji;
+        match self.has_color(font_idx) {
+            Some(has_color) if has_color != want_color => {}
+            _ => return false,
+        }
+        (font_idx + 1..self.handles.len().saturating_sub(1))
+            .any(|idx| self.has_color(idx) == Some(want_color))
+    }
+
     fn do_shape(
         &self,
         font_idx: FallbackIdx,
         s: &str,
+        presentation: Option<Presentation>,
         font_size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
+        if let Some(presentation) = presentation {
+            if self.skip_for_presentation(font_idx, presentation) {
+                return self.do_shape(
+                    font_idx + 1,
+                    s,
+                    Some(presentation),
+                    font_size,
+                    dpi,
+                    no_glyphs,
+                );
+            }
+        }
+
         let config = &self.config;
-        let features: Vec<harfbuzz::hb_feature_t> = config
-            .harfbuzz_features
+        let features: Vec<harfbuzz::hb_feature_t> = self
+            .features
+            .get(font_idx)
+            .and_then(Option::as_ref)
+            .unwrap_or(&config.harfbuzz_features)
             .iter()
             .filter_map(|s| harfbuzz::feature_from_string(s).ok())
             .collect();
@@ -271,12 +347,25 @@ impl HarfbuzzShaper {
                 }
                 */
 
-                let mut shape = match self.do_shape(font_idx + 1, substr, font_size, dpi, no_glyphs)
-                {
+                let mut shape = match self.do_shape(
+                    font_idx + 1,
+                    substr,
+                    presentation,
+                    font_size,
+                    dpi,
+                    no_glyphs,
+                ) {
                     Ok(shape) => Ok(shape),
                     Err(e) => {
                         error!("{:?} for {:?}", e, substr);
-                        self.do_shape(0, &make_question_string(substr), font_size, dpi, no_glyphs)
+                        self.do_shape(
+                            0,
+                            &make_question_string(substr),
+                            None,
+                            font_size,
+                            dpi,
+                            no_glyphs,
+                        )
                     }
                 }?;
 
@@ -311,9 +400,9 @@ impl HarfbuzzShaper {
 
                 let glyph = if len > 0 {
                     let text = &substr[next_idx..next_idx + len];
-                    make_glyphinfo(text, font_idx, info)
+                    make_glyphinfo(text, presentation, font_idx, info)
                 } else {
-                    make_glyphinfo("__", font_idx, info)
+                    make_glyphinfo("__", presentation, font_idx, info)
                 };
 
                 if glyph.x_advance != PixelLength::new(0.0) {
@@ -333,12 +422,13 @@ impl FontShaper for HarfbuzzShaper {
     fn shape(
         &self,
         text: &str,
+        presentation: Option<Presentation>,
         size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         let start = std::time::Instant::now();
-        let result = self.do_shape(0, text, size, dpi, no_glyphs);
+        let result = self.do_shape(0, text, presentation, size, dpi, no_glyphs);
         metrics::histogram!("shape.harfbuzz", start.elapsed());
         /*
         if let Ok(glyphs) = &result {
@@ -449,16 +539,17 @@ mod test {
                 bold: false,
                 is_fallback: false,
                 italic: false,
+                harfbuzz_features: None,
             })
             .unwrap()
             .clone();
 
         let config = config::configuration();
 
-        let shaper = HarfbuzzShaper::new(&config, &[handle]).unwrap();
+        let shaper = HarfbuzzShaper::new(&config, &[handle], &[None]).unwrap();
         {
             let mut no_glyphs = vec![];
-            let info = shaper.shape("abc", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper.shape("abc", None, 10., 72, &mut no_glyphs).unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -507,7 +598,7 @@ mod test {
         }
         {
             let mut no_glyphs = vec![];
-            let info = shaper.shape("<", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper.shape("<", None, 10., 72, &mut no_glyphs).unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -530,7 +621,7 @@ mod test {
             // This is a ligatured sequence, but you wouldn't know
             // from this info :-/
             let mut no_glyphs = vec![];
-            let info = shaper.shape("<-", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper.shape("<-", None, 10., 72, &mut no_glyphs).unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -566,7 +657,7 @@ mod test {
         }
         {
             let mut no_glyphs = vec![];
-            let info = shaper.shape("<--", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper.shape("<--", None, 10., 72, &mut no_glyphs).unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
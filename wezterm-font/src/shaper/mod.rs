@@ -1,5 +1,6 @@
 use crate::locator::FontDataHandle;
 use crate::units::PixelLength;
+use termwiz::cell::Presentation;
 
 pub mod allsorts;
 pub mod harfbuzz;
@@ -54,10 +55,13 @@ pub struct FontMetrics {
 }
 
 pub trait FontShaper {
-    /// Shape text and return a vector of GlyphInfo
+    /// Shape text and return a vector of GlyphInfo.
+    /// If `presentation` is set, emoji in the text are to be displayed
+    /// using that presentation rather than their default presentation.
     fn shape(
         &self,
         text: &str,
+        presentation: Option<Presentation>,
         size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
@@ -73,14 +77,17 @@ pub trait FontShaper {
 
 pub use config::FontShaperSelection;
 
+/// `features` holds the `harfbuzz_features` that were specified
+/// for the font corresponding to each of the `handles`, if any.
 pub fn new_shaper(
     config: &config::ConfigHandle,
     handles: &[FontDataHandle],
+    features: &[Option<Vec<String>>],
 ) -> anyhow::Result<Box<dyn FontShaper>> {
     match config.font_shaper {
-        FontShaperSelection::Harfbuzz => {
-            Ok(Box::new(harfbuzz::HarfbuzzShaper::new(config, handles)?))
-        }
+        FontShaperSelection::Harfbuzz => Ok(Box::new(harfbuzz::HarfbuzzShaper::new(
+            config, handles, features,
+        )?)),
         FontShaperSelection::Allsorts => {
             Ok(Box::new(allsorts::AllsortsShaper::new(config, handles)?))
         }
@@ -20,6 +20,46 @@ pub fn name_equals_value(arg: &str) -> Result<(String, String), String> {
     }
 }
 
+/// Configuration related options that can be specified for an
+/// individual subcommand, so that a one-off window can be launched
+/// with alternate settings.
+/// These take precedence over the equivalent top level options.
+#[derive(Debug, StructOpt, Default, Clone)]
+pub struct ConfigOverrides {
+    /// Specify the configuration file to use, overrides the normal
+    /// configuration file resolution
+    #[structopt(long = "config-file", parse(from_os_str))]
+    pub config_file: Option<OsString>,
+
+    /// Override specific configuration values.
+    /// The value is evaluated as a lua expression, so strings
+    /// must be quoted, for example:
+    /// `--config font_size=14 --config 'color_scheme="Builtin Dark"'`
+    #[structopt(
+        long = "config",
+        value_name = "name=value",
+        parse(try_from_str = name_equals_value),
+        number_of_values = 1)]
+    pub config_override: Vec<(String, String)>,
+}
+
+impl ConfigOverrides {
+    /// Combines the top level configuration options with these.
+    /// Returns the effective config file and the list of overrides;
+    /// the overrides from the subcommand are applied last, so that
+    /// they take precedence.
+    pub fn merge_with(
+        &self,
+        config_file: Option<&OsString>,
+        config_override: &[(String, String)],
+    ) -> (Option<OsString>, Vec<(String, String)>) {
+        let config_file = self.config_file.clone().or_else(|| config_file.cloned());
+        let mut overrides = config_override.to_vec();
+        overrides.extend(self.config_override.iter().cloned());
+        (config_file, overrides)
+    }
+}
+
 #[derive(Debug, StructOpt, Default, Clone)]
 pub struct StartCommand {
     /// If true, do not connect to domains marked as connect_automatically
@@ -33,7 +73,8 @@ pub struct StartCommand {
     pub cwd: Option<OsString>,
 
     /// Override the default windowing system class.
-    /// The default is "org.wezfurlong.wezterm".
+    /// The default is the `window_class` setting from the
+    /// config, or "org.wezfurlong.wezterm" if that is not set.
     /// Under X11 and Windows this changes the window class.
     /// Under Wayland this changes the app_id.
     /// This changes the class for all windows spawned by this
@@ -42,6 +83,27 @@ pub struct StartCommand {
     #[structopt(long = "class")]
     pub class: Option<String>,
 
+    /// Place the initial window in the named workspace, and make that
+    /// the workspace for windows that are subsequently created.
+    /// The default is "default".
+    #[structopt(long = "workspace")]
+    pub workspace: Option<String>,
+
+    #[structopt(flatten)]
+    pub config: ConfigOverrides,
+
+    /// When `single_instance` is enabled, start a new gui process
+    /// rather than asking the gui that is already running to open
+    /// a window
+    #[structopt(long = "always-new-process")]
+    pub always_new_process: bool,
+
+    /// When `single_instance` is enabled and a gui is already running,
+    /// open a new tab in its most recently created window rather
+    /// than opening a new window
+    #[structopt(long = "new-tab")]
+    pub new_tab: bool,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -76,6 +138,9 @@ pub struct SshCommand {
         number_of_values = 1)]
     pub config_override: Vec<(String, String)>,
 
+    #[structopt(flatten)]
+    pub config: ConfigOverrides,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm ssh user@host -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -94,6 +159,9 @@ pub struct SerialCommand {
     /// On posix systems this will be something like `/dev/ttyUSB0`
     #[structopt(parse(from_os_str))]
     pub port: OsString,
+
+    #[structopt(flatten)]
+    pub config: ConfigOverrides,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -102,6 +170,9 @@ pub struct ConnectCommand {
     /// to which you'd like to connect
     pub domain_name: String,
 
+    #[structopt(flatten)]
+    pub config: ConfigOverrides,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -0,0 +1,12 @@
+// This file is automatically prepended to the user supplied
+// window_background_shader.
+
+precision highp float;
+
+// The number of seconds since the window was created
+uniform float time;
+// The size of the window, in pixels
+uniform vec2 resolution;
+
+// The color of the fragment, which is blended over the window background
+out vec4 color;
@@ -0,0 +1,16 @@
+// This is the vertex shader for the window_background_shader effect.
+// It places the window background quad in the full viewport.
+
+// Note: vertex-common.glsl is automatically prepended!
+
+void main() {
+  pass_through_vertex();
+
+  if (o_has_color == 2.0 || o_has_color == 3.0) {
+    // The window background quad takes up its full coordinates
+    gl_Position = projection * vec4(position, 0.0, 1.0);
+  } else {
+    // Nothing else should render on the effect layer
+    gl_Position = off_screen();
+  }
+}
@@ -0,0 +1,149 @@
+//! Opt-in crash reports; see `enable_crash_reports`.
+//! When a panic occurs, a report is written to a pending directory.
+//! The next time that the gui starts, any pending reports are moved
+//! alongside the previously seen reports and their locations are shown
+//! to the user so that they can be attached to a bug report.
+use config::ConfigHandle;
+use mux::connui::ConnectionUI;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+fn report_dir() -> PathBuf {
+    config::STATE_DIR.join("crash-reports")
+}
+
+fn pending_dir() -> PathBuf {
+    report_dir().join("pending")
+}
+
+/// Produces a summary of the parts of the configuration that are most
+/// relevant to rendering and driver related crashes.
+/// This deliberately excludes anything that might be sensitive, such
+/// as paths, environment variables, domains and key bindings.
+fn config_summary(config: &ConfigHandle) -> String {
+    let fonts: Vec<&str> = config
+        .font
+        .font
+        .iter()
+        .map(|attr| attr.family.as_str())
+        .collect();
+    let mut summary = String::new();
+    writeln!(summary, "front_end = {:?}", config.front_end).ok();
+    writeln!(summary, "prefer_egl = {}", config.prefer_egl).ok();
+    writeln!(summary, "enable_wayland = {}", config.enable_wayland).ok();
+    writeln!(summary, "font = {:?}", fonts).ok();
+    writeln!(summary, "font_size = {}", config.font_size).ok();
+    writeln!(summary, "dpi = {:?}", config.dpi).ok();
+    writeln!(summary, "font_locator = {:?}", config.font_locator).ok();
+    writeln!(summary, "font_rasterizer = {:?}", config.font_rasterizer).ok();
+    writeln!(summary, "font_shaper = {:?}", config.font_shaper).ok();
+    summary
+}
+
+fn write_report(info: &std::panic::PanicInfo, config_summary: &str) -> anyhow::Result<PathBuf> {
+    let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "(no message)".to_string()
+    };
+    let location = info
+        .location()
+        .map(|loc| format!("{}:{}:{}", loc.file(), loc.line(), loc.column()))
+        .unwrap_or_else(|| "(unknown)".to_string());
+    let thread = std::thread::current();
+
+    let mut report = String::new();
+    writeln!(report, "wezterm crash report")?;
+    writeln!(report, "version: {}", config::wezterm_version())?;
+    writeln!(
+        report,
+        "platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(report, "thread: {}", thread.name().unwrap_or("(unnamed)"))?;
+    writeln!(report, "panic: {}", message)?;
+    writeln!(report, "location: {}", location)?;
+    writeln!(report, "\nconfiguration summary:\n{}", config_summary)?;
+    writeln!(report, "backtrace:\n{:?}", backtrace::Backtrace::new())?;
+    writeln!(report, "recent log records:")?;
+    for entry in env_bootstrap::ringlog::get_entries() {
+        writeln!(report, "{}", entry)?;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let dir = pending_dir();
+    config::create_user_owned_dirs(&dir)?;
+    let path = dir.join(format!("crash-{}-{}.txt", now, std::process::id()));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// If enabled by the configuration, chains a panic hook that writes
+/// a crash report.  The configuration summary is captured now, rather
+/// than in the hook, so that a panic while the configuration is being
+/// reloaded cannot prevent the report from being written.
+pub fn install(config: &ConfigHandle) {
+    if !config.enable_crash_reports {
+        return;
+    }
+    let summary = config_summary(config);
+    let next_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(info, &summary) {
+            Ok(path) => log::error!("wrote crash report to {}", path.display()),
+            Err(err) => log::error!("failed to write crash report: {:#}", err),
+        }
+        next_hook(info);
+    }));
+}
+
+/// Shows the location of any crash reports written since the
+/// last time that the gui was started
+pub fn show_pending_reports() {
+    let entries = match std::fs::read_dir(pending_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut reports = vec![];
+    for entry in entries.filter_map(Result::ok) {
+        let dest = report_dir().join(entry.file_name());
+        match std::fs::rename(entry.path(), &dest) {
+            Ok(_) => reports.push(dest),
+            Err(err) => log::error!(
+                "failed to move crash report {}: {:#}",
+                entry.path().display(),
+                err
+            ),
+        }
+    }
+    if reports.is_empty() {
+        return;
+    }
+    reports.sort();
+
+    // Wrap the explanation, but not the paths, so that they
+    // can be copied intact
+    let mut message = textwrap::fill(
+        "wezterm crashed the last time that it was run. \
+         If you file a bug report, please consider attaching the \
+         following crash report(s), after reviewing them for anything \
+         that you don't wish to share:",
+        78,
+    );
+    message.push_str("\n\n");
+    for path in &reports {
+        message.push_str(&format!("{}\n", path.display()));
+    }
+
+    let ui = ConnectionUI::new_with_no_close_delay();
+    ui.title("wezterm Crash Report");
+    ui.output_str(&message);
+}
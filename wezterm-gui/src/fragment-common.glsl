@@ -12,7 +12,14 @@ in vec4 o_cursor_color;
 in vec4 o_fg_color;
 in vec4 o_underline_color;
 
+#ifdef SUBPIXEL_AA
+// The second output holds the factors by which each channel of the
+// frame buffer is retained when the glyph is blended over it
+layout(location = 0, index = 0) out vec4 color;
+layout(location = 0, index = 1) out vec4 colorMask;
+#else
 out vec4 color;
+#endif
 
 uniform vec3 foreground_text_hsb;
 
@@ -35,6 +35,10 @@ impl GuiFrontEnd {
                         }
                     }
                     MuxNotification::PaneOutput(_) => {}
+                    MuxNotification::ScreenshotRequested(_) => {}
+                    MuxNotification::PerfStatsRequested(_) => {}
+                    MuxNotification::EventRequested(_) => {}
+                    MuxNotification::DomainConnectionChanged(_) => {}
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -58,6 +62,10 @@ impl GuiFrontEnd {
                         // persistent_toast_notification("Ding!", "This is the bell");
                         log::info!("Ding! (this is the bell)");
                     }
+                    MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::PaletteChanged,
+                    } => {}
                 }
                 true
             } else {
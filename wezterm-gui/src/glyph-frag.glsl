@@ -5,17 +5,44 @@
 // Note: fragment-common.glsl is automatically prepended!
 
 uniform sampler2D atlas_nearest_sampler;
+uniform sampler2D atlas_alpha_sampler;
 
 void main() {
-  if (o_has_color >= 2.0) {
+  if (o_has_color == 2.0 || o_has_color == 3.0) {
     // Don't render the background image on anything other than
     // the window_bg_layer.
     discard;
     return;
   }
 
-  color = sample_texture(atlas_nearest_sampler, o_tex);
-  if (o_has_color == 0.0) {
+  bool monochrome = o_has_color == 0.0 || o_has_color == 4.0;
+  if (o_has_color == 4.0) {
+    // The single channel atlas holds only the coverage of the glyph;
+    // expand it to the gray that the color atlas would have held
+    color = to_gamma(vec4(texture(atlas_alpha_sampler, o_tex).r));
+  } else {
+    color = sample_texture(atlas_nearest_sampler, o_tex);
+  }
+#ifdef SUBPIXEL_AA
+  if (monochrome) {
+    if (o_fg_color == o_bg_color) {
+      discard;
+      return;
+    }
+    // The glyph holds the coverage of the red, green and blue
+    // sub-pixels, so each channel of the foreground color is
+    // blended over the frame buffer by its own coverage
+    vec4 fg = apply_hsv(apply_hsv(o_fg_color, foreground_text_hsb), o_hsv);
+    colorMask = vec4(color.rgb * fg.a, color.a * fg.a);
+    color = vec4(fg.rgb * colorMask.rgb, colorMask.a);
+  } else {
+    // Regular alpha blending for color glyphs
+    color = apply_hsv(color, o_hsv);
+    colorMask = vec4(color.a);
+    color.rgb *= color.a;
+  }
+#else
+  if (monochrome) {
     // if it's not a color emoji it will be grayscale
     // and we need to tint with the fg_color
     if (o_fg_color == o_bg_color) {
@@ -33,4 +60,5 @@ void main() {
   }
 
   color = apply_hsv(color, o_hsv);
+#endif
 }
@@ -1,33 +1,75 @@
 use super::utilsprites::RenderMetrics;
-use ::window::bitmaps::atlas::{Atlas, Sprite};
+use crate::rastercache::RasterCache;
+use crate::rasterqueue::RasterQueue;
+use ::window::bitmaps::atlas::{Atlas, OutOfTextureSpace, Sprite};
 #[cfg(test)]
 use ::window::bitmaps::ImageTexture;
-use ::window::bitmaps::{BitmapImage, Image, Texture2d};
+use ::window::bitmaps::{point_in_polygon, segment_distance, BitmapImage, Image, Texture2d};
 use ::window::color::{LinearRgba, SrgbaPixel};
 use ::window::glium;
 use ::window::glium::backend::Context as GliumContext;
 use ::window::glium::texture::SrgbTexture2d;
-use ::window::{Point, Rect};
+use ::window::{Point, PointF, Rect, Size};
 use anyhow::{anyhow, Context};
-use config::{configuration, AllowSquareGlyphOverflow, TextStyle};
+use config::{
+    configuration, AllowSquareGlyphOverflow, CodepointRange, CustomBlockGlyphRange,
+    ShadeBlockStyle, TextStyle,
+};
 use euclid::num::Zero;
 use lru::LruCache;
-use std::collections::HashMap;
+use mux::{AtlasOccupancy, GlyphCacheStats};
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use termwiz::cell::CellAttributes;
 use termwiz::image::ImageData;
 use wezterm_font::units::*;
-use wezterm_font::{FontConfiguration, GlyphInfo};
+use wezterm_font::{FontConfiguration, GlyphInfo, RasterizedGlyph};
 use wezterm_term::Underline;
 
+/// Splits the horizontal offset at which the shaper placed a glyph into
+/// the whole number of pixels at which its sprite is positioned, and
+/// the fraction of a pixel, in steps of 1/`steps`, by which the glyph
+/// is shifted when it is rasterized.
+/// When `steps` is less than 2, sub-pixel positioning is disabled and
+/// the offset is returned unchanged.
+pub fn split_subpixel_offset(x_offset: PixelLength, steps: u8) -> (PixelLength, u8) {
+    if steps < 2 {
+        return (x_offset, 0);
+    }
+    let whole = x_offset.get().floor();
+    let step = ((x_offset.get() - whole) * steps as f64).round() as u8;
+    if step >= steps {
+        (PixelLength::new(whole + 1.0), 0)
+    } else {
+        (PixelLength::new(whole), step)
+    }
+}
+
+/// Returns the fraction of a pixel by which a glyph is shifted
+/// to the right when it is rasterized
+fn subpixel_x_offset(subpixel_x: u8, steps: u8) -> f64 {
+    if subpixel_x == 0 {
+        0.0
+    } else {
+        subpixel_x as f64 / steps as f64
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GlyphKey {
     pub font_idx: usize,
     pub glyph_pos: u32,
     pub style: TextStyle,
     pub followed_by_space: bool,
+    /// The number of cells that the glyph occupies, which
+    /// influences the scaling of eg: emoji glyphs
+    pub num_cells: u8,
+    /// The fraction of a pixel, in steps of 1/glyph_subpixel_positions,
+    /// by which the glyph is shifted to the right when rasterized
+    pub subpixel_x: u8,
 }
 
 /// We'd like to avoid allocating when resolving from the cache
@@ -41,6 +83,8 @@ pub struct BorrowedGlyphKey<'a> {
     pub glyph_pos: u32,
     pub style: &'a TextStyle,
     pub followed_by_space: bool,
+    pub num_cells: u8,
+    pub subpixel_x: u8,
 }
 
 impl<'a> BorrowedGlyphKey<'a> {
@@ -50,6 +94,8 @@ impl<'a> BorrowedGlyphKey<'a> {
             glyph_pos: self.glyph_pos,
             style: self.style.clone(),
             followed_by_space: self.followed_by_space,
+            num_cells: self.num_cells,
+            subpixel_x: self.subpixel_x,
         }
     }
 }
@@ -65,6 +111,8 @@ impl GlyphKeyTrait for GlyphKey {
             glyph_pos: self.glyph_pos,
             style: &self.style,
             followed_by_space: self.followed_by_space,
+            num_cells: self.num_cells,
+            subpixel_x: self.subpixel_x,
         }
     }
 }
@@ -81,6 +129,13 @@ impl<'a> std::borrow::Borrow<dyn GlyphKeyTrait + 'a> for GlyphKey {
     }
 }
 
+impl<'a> std::borrow::Borrow<dyn GlyphKeyTrait + 'a> for lru::KeyRef<GlyphKey> {
+    fn borrow(&self) -> &(dyn GlyphKeyTrait + 'a) {
+        let k: &GlyphKey = self.borrow();
+        k
+    }
+}
+
 impl<'a> PartialEq for (dyn GlyphKeyTrait + 'a) {
     fn eq(&self, other: &Self) -> bool {
         self.key().eq(&other.key())
@@ -99,24 +154,31 @@ impl<'a> std::hash::Hash for (dyn GlyphKeyTrait + 'a) {
 /// The image data may be None for whitespace glyphs.
 pub struct CachedGlyph<T: Texture2d> {
     pub has_color: bool,
+    /// The atlas that holds the texture of the glyph
+    pub atlas: AtlasKind,
     pub x_offset: PixelLength,
     pub y_offset: PixelLength,
     pub bearing_x: PixelLength,
     pub bearing_y: PixelLength,
     pub texture: Option<Sprite<T>>,
     pub scale: f64,
+    /// Set for a blank placeholder that stands in for a glyph that is
+    /// still being rasterized by the raster queue
+    pub pending: bool,
 }
 
 impl<T: Texture2d> std::fmt::Debug for CachedGlyph<T> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
         fmt.debug_struct("CachedGlyph")
             .field("has_color", &self.has_color)
+            .field("atlas", &self.atlas)
             .field("x_offset", &self.x_offset)
             .field("y_offset", &self.y_offset)
             .field("bearing_x", &self.bearing_x)
             .field("bearing_y", &self.bearing_y)
             .field("scale", &self.scale)
             .field("texture", &self.texture)
+            .field("pending", &self.pending)
             .finish()
     }
 }
@@ -162,14 +224,153 @@ pub enum BlockKey {
     Left(u8),
     /// Number of 1/8ths in the right half
     Right(u8),
+    /// One eighth wide stripes; bit n of `rows` is the nth eighth
+    /// from the top, and bit n of `columns` the nth eighth from
+    /// the left
+    Eighths { rows: u8, columns: u8 },
     /// Full block with alpha level
     Full(BlockAlpha),
+    /// Full block drawn as a dither pattern that covers the
+    /// proportion of the cell given by the alpha level
+    Dithered(BlockAlpha),
     /// A combination of quadrants
     Quadrants(Quadrant),
+    /// A Symbols for Legacy Computing sextant; each bit is one of the
+    /// cells of a grid of two columns and three rows, with bit 0 being
+    /// the upper left and bit 5 the lower right
+    /// <https://www.unicode.org/charts/PDF/U1FB00.pdf>
+    Sextants(u8),
+    /// A Symbols for Legacy Computing wedge, or a triangle from the
+    /// Geometric Shapes block.  The polygon is described in sixths of
+    /// the cell width and height; when `inverse` is true, the area
+    /// outside of the polygon is filled
+    Wedge {
+        polygon: &'static [(u8, u8)],
+        inverse: bool,
+    },
+    /// A glyph from the Box Drawing block
+    BoxDrawing(BoxDrawingKey),
+    /// A Braille pattern; each bit is one of the eight dots,
+    /// numbered as in <https://www.unicode.org/charts/PDF/U2800.pdf>
+    Braille(u8),
+    /// One of the Powerline dividers from the private use area
+    Powerline(PowerlineKey),
 }
 
+/// Represents one of the Powerline dividers, including those from
+/// the extended set that is used by nerd fonts
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PowerlineKey {
+    /// A triangle whose base spans the height of one edge of the cell
+    /// and whose tip touches the middle of the other, pointing either
+    /// to the right or the left; either filled or the outline of one
+    Arrow { right: bool, filled: bool },
+    /// A half circle whose flat side spans the height of one edge
+    /// of the cell and whose curve touches the other
+    HalfCircle { right: bool, filled: bool },
+    /// A triangle that fills half of the cell, identified by the
+    /// corner that it has in common with the cell
+    Corner { upper: bool, left: bool },
+    /// A line from one corner of the cell to the opposite corner;
+    /// `forward` runs from the lower left to the upper right
+    Slant { forward: bool },
+    /// A flame whose flat side spans the height of one edge of the
+    /// cell and whose flickering edge points towards the other;
+    /// either filled or just the flickering edge
+    Flame { right: bool, filled: bool },
+    /// Columns of squares that become sparser away from the
+    /// edge of the cell that they fill
+    Pixelated { right: bool, big: bool },
+    /// Shards of ice that point to the right
+    IceWaveform,
+}
+
+/// The outline of the Powerline flame U+E0C0, as fractions of the
+/// width and height of the cell.  The flat side runs down the left
+/// edge and the points in between trace the flickering edge.
+const FLAME: [(f32, f32); 14] = [
+    (0.0, 0.0),
+    (0.35, 0.0),
+    (0.6, 0.08),
+    (0.5, 0.16),
+    (0.9, 0.24),
+    (0.65, 0.34),
+    (0.75, 0.46),
+    (1.0, 0.52),
+    (0.7, 0.62),
+    (0.85, 0.76),
+    (0.55, 0.84),
+    (0.65, 0.94),
+    (0.4, 1.0),
+    (0.0, 1.0),
+];
+
+/// The outline of the Powerline ice waveform U+E0C8, in the same
+/// terms as `FLAME`
+const ICE_WAVEFORM: [(f32, f32); 14] = [
+    (0.0, 0.0),
+    (0.5, 0.0),
+    (0.25, 0.1),
+    (0.9, 0.2),
+    (0.35, 0.3),
+    (0.6, 0.38),
+    (0.3, 0.46),
+    (1.0, 0.56),
+    (0.35, 0.66),
+    (0.7, 0.76),
+    (0.3, 0.84),
+    (0.8, 0.94),
+    (0.45, 1.0),
+    (0.0, 1.0),
+];
+
+/// The smooth mosaic wedges U+1FB3C through U+1FB51, expressed in sixths
+/// of the cell.  U+1FB52 through U+1FB67 are their inverse, in the
+/// same order.
+const WEDGES: [&[(u8, u8)]; 22] = [
+    // Lower left block
+    &[(0, 4), (3, 6), (0, 6)],
+    &[(0, 4), (6, 6), (0, 6)],
+    &[(0, 2), (3, 6), (0, 6)],
+    &[(0, 2), (6, 6), (0, 6)],
+    &[(0, 0), (3, 6), (0, 6)],
+    // Lower right block, diagonal from the left edge to the top
+    &[(0, 2), (3, 0), (6, 0), (6, 6), (0, 6)],
+    &[(0, 2), (6, 0), (6, 6), (0, 6)],
+    &[(0, 4), (3, 0), (6, 0), (6, 6), (0, 6)],
+    &[(0, 4), (6, 0), (6, 6), (0, 6)],
+    &[(0, 6), (3, 0), (6, 0), (6, 6)],
+    &[(0, 4), (6, 2), (6, 6), (0, 6)],
+    // Lower right block, diagonal from the bottom to the right edge
+    &[(3, 6), (6, 4), (6, 6)],
+    &[(0, 6), (6, 4), (6, 6)],
+    &[(3, 6), (6, 2), (6, 6)],
+    &[(0, 6), (6, 2), (6, 6)],
+    &[(3, 6), (6, 0), (6, 6)],
+    // Lower left block, diagonal from the top to the right edge
+    &[(0, 0), (3, 0), (6, 2), (6, 6), (0, 6)],
+    &[(0, 0), (6, 2), (6, 6), (0, 6)],
+    &[(0, 0), (3, 0), (6, 4), (6, 6), (0, 6)],
+    &[(0, 0), (6, 4), (6, 6), (0, 6)],
+    &[(0, 0), (3, 0), (6, 6), (0, 6)],
+    &[(0, 2), (6, 4), (6, 6), (0, 6)],
+];
+
+/// The left, upper, right and lower triangular quarter blocks
+/// U+1FB6C through U+1FB6F.  U+1FB68 through U+1FB6B are the three
+/// quarter blocks that are their inverse, in the same order.
+const QUARTER_TRIANGLES: [&[(u8, u8)]; 4] = [
+    &[(0, 0), (3, 3), (0, 6)],
+    &[(0, 0), (6, 0), (3, 3)],
+    &[(6, 0), (6, 6), (3, 3)],
+    &[(0, 6), (3, 3), (6, 6)],
+];
+
 impl BlockKey {
     pub fn from_char(c: char) -> Option<Self> {
+        if let Some(key) = BoxDrawingKey::from_char(c) {
+            return Some(Self::BoxDrawing(key));
+        }
         let c = c as u32;
         Some(match c {
             // Upper half block
@@ -204,10 +405,194 @@ impl BlockKey {
             0x259f => Self::Quadrants(
                 Quadrant::UPPER_RIGHT | Quadrant::LOWER_LEFT | Quadrant::LOWER_RIGHT,
             ),
+            // Black up, right, down and left pointing triangles
+            0x25b2 => Self::triangle(&[(3, 0), (6, 6), (0, 6)]),
+            0x25b6 => Self::triangle(&[(0, 0), (6, 3), (0, 6)]),
+            0x25bc => Self::triangle(&[(0, 0), (6, 0), (3, 6)]),
+            0x25c0 => Self::triangle(&[(6, 0), (6, 6), (0, 3)]),
+            // Black lower right, lower left, upper left and upper
+            // right triangles
+            0x25e2 => Self::triangle(&[(6, 0), (6, 6), (0, 6)]),
+            0x25e3 => Self::triangle(&[(0, 0), (6, 6), (0, 6)]),
+            0x25e4 => Self::triangle(&[(0, 0), (6, 0), (0, 6)]),
+            0x25e5 => Self::triangle(&[(0, 0), (6, 0), (6, 6)]),
+            0x2800..=0x28ff => Self::Braille((c - 0x2800) as u8),
+            0x1fb00..=0x1fb3b => {
+                // The sextants are numbered by their bit pattern, but
+                // skip the empty and full patterns along with the left
+                // and right halves, as those are Block Elements
+                let mut bits = (c - 0x1fb00 + 1) as u8;
+                if bits >= 0b010101 {
+                    bits += 1;
+                }
+                if bits >= 0b101010 {
+                    bits += 1;
+                }
+                Self::Sextants(bits)
+            }
+            0x1fb3c..=0x1fb51 => Self::Wedge {
+                polygon: WEDGES[(c - 0x1fb3c) as usize],
+                inverse: false,
+            },
+            0x1fb52..=0x1fb67 => Self::Wedge {
+                polygon: WEDGES[(c - 0x1fb52) as usize],
+                inverse: true,
+            },
+            0x1fb68..=0x1fb6b => Self::Wedge {
+                polygon: QUARTER_TRIANGLES[(c - 0x1fb68) as usize],
+                inverse: true,
+            },
+            0x1fb6c..=0x1fb6f => Self::Wedge {
+                polygon: QUARTER_TRIANGLES[(c - 0x1fb6c) as usize],
+                inverse: false,
+            },
+            // Vertical and horizontal one eighth blocks 2..7
+            0x1fb70..=0x1fb75 => Self::Eighths {
+                rows: 0,
+                columns: 1 << (c - 0x1fb70 + 1),
+            },
+            0x1fb76..=0x1fb7b => Self::Eighths {
+                rows: 1 << (c - 0x1fb76 + 1),
+                columns: 0,
+            },
+            // Left and lower, left and upper, right and upper,
+            // right and lower one eighth
+            0x1fb7c => Self::Eighths {
+                rows: 0x80,
+                columns: 0x01,
+            },
+            0x1fb7d => Self::Eighths {
+                rows: 0x01,
+                columns: 0x01,
+            },
+            0x1fb7e => Self::Eighths {
+                rows: 0x01,
+                columns: 0x80,
+            },
+            0x1fb7f => Self::Eighths {
+                rows: 0x80,
+                columns: 0x80,
+            },
+            // Upper and lower one eighth
+            0x1fb80 => Self::Eighths {
+                rows: 0x81,
+                columns: 0,
+            },
+            // Horizontal one eighth block-1358
+            0x1fb81 => Self::Eighths {
+                rows: 0b1001_0101,
+                columns: 0,
+            },
+            // Upper and right 2, 3, 5, 6 and 7 eighths
+            0x1fb82 => Self::Upper(2),
+            0x1fb83 => Self::Upper(3),
+            0x1fb84 => Self::Upper(5),
+            0x1fb85 => Self::Upper(6),
+            0x1fb86 => Self::Upper(7),
+            0x1fb87 => Self::Right(2),
+            0x1fb88 => Self::Right(3),
+            0x1fb89 => Self::Right(5),
+            0x1fb8a => Self::Right(6),
+            0x1fb8b => Self::Right(7),
+            0xe0b0..=0xe0c8 => {
+                use PowerlineKey::*;
+                Self::Powerline(match c {
+                    0xe0b0 => Arrow {
+                        right: true,
+                        filled: true,
+                    },
+                    0xe0b1 => Arrow {
+                        right: true,
+                        filled: false,
+                    },
+                    0xe0b2 => Arrow {
+                        right: false,
+                        filled: true,
+                    },
+                    0xe0b3 => Arrow {
+                        right: false,
+                        filled: false,
+                    },
+                    0xe0b4 => HalfCircle {
+                        right: true,
+                        filled: true,
+                    },
+                    0xe0b5 => HalfCircle {
+                        right: true,
+                        filled: false,
+                    },
+                    0xe0b6 => HalfCircle {
+                        right: false,
+                        filled: true,
+                    },
+                    0xe0b7 => HalfCircle {
+                        right: false,
+                        filled: false,
+                    },
+                    0xe0b8 => Corner {
+                        upper: false,
+                        left: true,
+                    },
+                    0xe0ba => Corner {
+                        upper: false,
+                        left: false,
+                    },
+                    0xe0bc => Corner {
+                        upper: true,
+                        left: true,
+                    },
+                    0xe0be => Corner {
+                        upper: true,
+                        left: false,
+                    },
+                    0xe0b9 | 0xe0bf => Slant { forward: false },
+                    0xe0bb | 0xe0bd => Slant { forward: true },
+                    0xe0c0 => Flame {
+                        right: true,
+                        filled: true,
+                    },
+                    0xe0c1 => Flame {
+                        right: true,
+                        filled: false,
+                    },
+                    0xe0c2 => Flame {
+                        right: false,
+                        filled: true,
+                    },
+                    0xe0c3 => Flame {
+                        right: false,
+                        filled: false,
+                    },
+                    0xe0c4 => Pixelated {
+                        right: true,
+                        big: false,
+                    },
+                    0xe0c5 => Pixelated {
+                        right: false,
+                        big: false,
+                    },
+                    0xe0c6 => Pixelated {
+                        right: true,
+                        big: true,
+                    },
+                    0xe0c7 => Pixelated {
+                        right: false,
+                        big: true,
+                    },
+                    _ => IceWaveform,
+                })
+            }
             _ => return None,
         })
     }
 
+    fn triangle(polygon: &'static [(u8, u8)]) -> Self {
+        Self::Wedge {
+            polygon,
+            inverse: false,
+        }
+    }
+
     pub fn from_cell(cell: &termwiz::cell::Cell) -> Option<Self> {
         let mut chars = cell.str().chars();
         let first_char = chars.next()?;
@@ -217,6 +602,253 @@ impl BlockKey {
             Self::from_char(first_char)
         }
     }
+
+    /// Like `from_cell`, but only returns a key for characters that
+    /// are in one of the specified ranges
+    pub fn from_cell_in_ranges(
+        cell: &termwiz::cell::Cell,
+        ranges: &[CustomBlockGlyphRange],
+    ) -> Option<Self> {
+        let key = Self::from_cell(cell)?;
+        let c = cell.str().chars().next()?;
+        if ranges.iter().any(|range| range.contains(c)) {
+            Some(key)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the glyph has curved or diagonal edges,
+    /// which benefit from being supersampled
+    pub fn is_curved(&self) -> bool {
+        match self {
+            Self::Powerline(PowerlineKey::Pixelated { .. }) => false,
+            Self::Wedge { .. } | Self::Braille(_) | Self::Powerline(_) => true,
+            Self::BoxDrawing(BoxDrawingKey::Arc { .. })
+            | Self::BoxDrawing(BoxDrawingKey::Diagonal { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the key to use when the shade characters are
+    /// drawn in the specified style
+    pub fn with_shade_style(self, style: ShadeBlockStyle) -> Self {
+        match (self, style) {
+            (Self::Full(alpha), ShadeBlockStyle::Dithered) if alpha != BlockAlpha::Full => {
+                Self::Dithered(alpha)
+            }
+            _ => self,
+        }
+    }
+}
+
+/// The weight of one of the arms of a Box Drawing glyph
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum LineWeight {
+    None,
+    Light,
+    Heavy,
+    Double,
+}
+
+/// Represents a Box Drawing glyph, decoded from
+/// <https://en.wikipedia.org/wiki/Box-drawing_character>
+/// <https://www.unicode.org/charts/PDF/U2500.pdf>
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum BoxDrawingKey {
+    /// Lines that run from the center of the cell to the
+    /// top, bottom, left and right edges.  Each arm has its own
+    /// weight, so a line that changes from light to heavy, such
+    /// as `╼`, is split at the center of the cell.
+    Lines {
+        up: LineWeight,
+        down: LineWeight,
+        left: LineWeight,
+        right: LineWeight,
+    },
+    /// A horizontal line broken into this many dashes
+    HorizontalDash(LineWeight, u8),
+    /// A vertical line broken into this many dashes
+    VerticalDash(LineWeight, u8),
+    /// A rounded corner that joins either the bottom or the top
+    /// edge to either the right or the left edge
+    Arc { down: bool, right: bool },
+    /// `forward` is a line from the upper right to the lower left
+    /// corner, `backward` from the upper left to the lower right
+    Diagonal { forward: bool, backward: bool },
+}
+
+impl BoxDrawingKey {
+    pub fn from_char(c: char) -> Option<Self> {
+        use LineWeight::{Double as D, Heavy as H, Light as L, None as N};
+
+        fn lines(
+            up: LineWeight,
+            down: LineWeight,
+            left: LineWeight,
+            right: LineWeight,
+        ) -> BoxDrawingKey {
+            BoxDrawingKey::Lines {
+                up,
+                down,
+                left,
+                right,
+            }
+        }
+
+        Some(match c as u32 {
+            0x2500 => lines(N, N, L, L),
+            0x2501 => lines(N, N, H, H),
+            0x2502 => lines(L, L, N, N),
+            0x2503 => lines(H, H, N, N),
+            0x2504 => Self::HorizontalDash(L, 3),
+            0x2505 => Self::HorizontalDash(H, 3),
+            0x2506 => Self::VerticalDash(L, 3),
+            0x2507 => Self::VerticalDash(H, 3),
+            0x2508 => Self::HorizontalDash(L, 4),
+            0x2509 => Self::HorizontalDash(H, 4),
+            0x250a => Self::VerticalDash(L, 4),
+            0x250b => Self::VerticalDash(H, 4),
+            0x250c => lines(N, L, N, L),
+            0x250d => lines(N, L, N, H),
+            0x250e => lines(N, H, N, L),
+            0x250f => lines(N, H, N, H),
+            0x2510 => lines(N, L, L, N),
+            0x2511 => lines(N, L, H, N),
+            0x2512 => lines(N, H, L, N),
+            0x2513 => lines(N, H, H, N),
+            0x2514 => lines(L, N, N, L),
+            0x2515 => lines(L, N, N, H),
+            0x2516 => lines(H, N, N, L),
+            0x2517 => lines(H, N, N, H),
+            0x2518 => lines(L, N, L, N),
+            0x2519 => lines(L, N, H, N),
+            0x251a => lines(H, N, L, N),
+            0x251b => lines(H, N, H, N),
+            0x251c => lines(L, L, N, L),
+            0x251d => lines(L, L, N, H),
+            0x251e => lines(H, L, N, L),
+            0x251f => lines(L, H, N, L),
+            0x2520 => lines(H, H, N, L),
+            0x2521 => lines(H, L, N, H),
+            0x2522 => lines(L, H, N, H),
+            0x2523 => lines(H, H, N, H),
+            0x2524 => lines(L, L, L, N),
+            0x2525 => lines(L, L, H, N),
+            0x2526 => lines(H, L, L, N),
+            0x2527 => lines(L, H, L, N),
+            0x2528 => lines(H, H, L, N),
+            0x2529 => lines(H, L, H, N),
+            0x252a => lines(L, H, H, N),
+            0x252b => lines(H, H, H, N),
+            0x252c => lines(N, L, L, L),
+            0x252d => lines(N, L, H, L),
+            0x252e => lines(N, L, L, H),
+            0x252f => lines(N, L, H, H),
+            0x2530 => lines(N, H, L, L),
+            0x2531 => lines(N, H, H, L),
+            0x2532 => lines(N, H, L, H),
+            0x2533 => lines(N, H, H, H),
+            0x2534 => lines(L, N, L, L),
+            0x2535 => lines(L, N, H, L),
+            0x2536 => lines(L, N, L, H),
+            0x2537 => lines(L, N, H, H),
+            0x2538 => lines(H, N, L, L),
+            0x2539 => lines(H, N, H, L),
+            0x253a => lines(H, N, L, H),
+            0x253b => lines(H, N, H, H),
+            0x253c => lines(L, L, L, L),
+            0x253d => lines(L, L, H, L),
+            0x253e => lines(L, L, L, H),
+            0x253f => lines(L, L, H, H),
+            0x2540 => lines(H, L, L, L),
+            0x2541 => lines(L, H, L, L),
+            0x2542 => lines(H, H, L, L),
+            0x2543 => lines(H, L, H, L),
+            0x2544 => lines(H, L, L, H),
+            0x2545 => lines(L, H, H, L),
+            0x2546 => lines(L, H, L, H),
+            0x2547 => lines(H, L, H, H),
+            0x2548 => lines(L, H, H, H),
+            0x2549 => lines(H, H, H, L),
+            0x254a => lines(H, H, L, H),
+            0x254b => lines(H, H, H, H),
+            0x254c => Self::HorizontalDash(L, 2),
+            0x254d => Self::HorizontalDash(H, 2),
+            0x254e => Self::VerticalDash(L, 2),
+            0x254f => Self::VerticalDash(H, 2),
+            0x2550 => lines(N, N, D, D),
+            0x2551 => lines(D, D, N, N),
+            0x2552 => lines(N, L, N, D),
+            0x2553 => lines(N, D, N, L),
+            0x2554 => lines(N, D, N, D),
+            0x2555 => lines(N, L, D, N),
+            0x2556 => lines(N, D, L, N),
+            0x2557 => lines(N, D, D, N),
+            0x2558 => lines(L, N, N, D),
+            0x2559 => lines(D, N, N, L),
+            0x255a => lines(D, N, N, D),
+            0x255b => lines(L, N, D, N),
+            0x255c => lines(D, N, L, N),
+            0x255d => lines(D, N, D, N),
+            0x255e => lines(L, L, N, D),
+            0x255f => lines(D, D, N, L),
+            0x2560 => lines(D, D, N, D),
+            0x2561 => lines(L, L, D, N),
+            0x2562 => lines(D, D, L, N),
+            0x2563 => lines(D, D, D, N),
+            0x2564 => lines(N, L, D, D),
+            0x2565 => lines(N, D, L, L),
+            0x2566 => lines(N, D, D, D),
+            0x2567 => lines(L, N, D, D),
+            0x2568 => lines(D, N, L, L),
+            0x2569 => lines(D, N, D, D),
+            0x256a => lines(L, L, D, D),
+            0x256b => lines(D, D, L, L),
+            0x256c => lines(D, D, D, D),
+            0x256d => Self::Arc {
+                down: true,
+                right: true,
+            },
+            0x256e => Self::Arc {
+                down: true,
+                right: false,
+            },
+            0x256f => Self::Arc {
+                down: false,
+                right: false,
+            },
+            0x2570 => Self::Arc {
+                down: false,
+                right: true,
+            },
+            0x2571 => Self::Diagonal {
+                forward: true,
+                backward: false,
+            },
+            0x2572 => Self::Diagonal {
+                forward: false,
+                backward: true,
+            },
+            0x2573 => Self::Diagonal {
+                forward: true,
+                backward: true,
+            },
+            0x2574 => lines(N, N, L, N),
+            0x2575 => lines(L, N, N, N),
+            0x2576 => lines(N, N, N, L),
+            0x2577 => lines(N, L, N, N),
+            0x2578 => lines(N, N, H, N),
+            0x2579 => lines(H, N, N, N),
+            0x257a => lines(N, N, N, H),
+            0x257b => lines(N, H, N, N),
+            0x257c => lines(N, N, L, H),
+            0x257d => lines(L, H, N, N),
+            0x257e => lines(N, N, H, L),
+            0x257f => lines(H, L, N, N),
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -316,15 +948,113 @@ impl DecodedImage {
     }
 }
 
+/// Returns the maximum number of rasterized glyphs to keep in the
+/// cache, as configured by `glyph_cache_size`
+fn glyph_cache_size() -> usize {
+    // A frame can need every glyph that is on screen at once, so don't
+    // let the cache get too small to hold them
+    configuration().glyph_cache_size.max(1024)
+}
+
+/// When the atlas runs out of space while less than this fraction of
+/// the area of its shelves is occupied, it is compacted rather than grown
+const COMPACT_UTILIZATION: f32 = 0.5;
+
+/// Identifies one of the atlases of the glyph cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtlasKind {
+    /// Holds sprites in full color: color glyphs, images, the
+    /// coverage of LCD sub-pixels and the sprites for lines, blocks
+    /// and the cursor
+    Color,
+    /// Holds only the alpha channel of monochrome glyphs
+    Alpha,
+}
+
+/// The texture of one of the atlases of the glyph cache
+#[derive(Debug)]
+pub enum AtlasTexture {
+    Srgb(SrgbTexture2d),
+    Alpha(glium::texture::Texture2d),
+}
+
+impl Texture2d for AtlasTexture {
+    fn write(&self, rect: Rect, im: &dyn BitmapImage) {
+        match self {
+            Self::Srgb(texture) => Texture2d::write(texture, rect, im),
+            Self::Alpha(texture) => Texture2d::write(texture, rect, im),
+        }
+    }
+
+    fn read(&self, rect: Rect, im: &mut dyn BitmapImage) {
+        match self {
+            Self::Srgb(texture) => Texture2d::read(texture, rect, im),
+            Self::Alpha(texture) => Texture2d::read(texture, rect, im),
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            Self::Srgb(texture) => Texture2d::width(texture),
+            Self::Alpha(texture) => Texture2d::width(texture),
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Self::Srgb(texture) => Texture2d::height(texture),
+            Self::Alpha(texture) => Texture2d::height(texture),
+        }
+    }
+}
+
 pub struct GlyphCache<T: Texture2d> {
-    glyph_cache: HashMap<GlyphKey, Rc<CachedGlyph<T>>>,
+    glyph_cache: LruCache<GlyphKey, Rc<CachedGlyph<T>>>,
     pub atlas: Atlas<T>,
+    /// Holds the monochrome glyphs, in a quarter of the space that
+    /// they would take up in `atlas`
+    pub alpha_atlas: Atlas<T>,
     fonts: Rc<FontConfiguration>,
     pub image_cache: LruCache<usize, CachedImage>,
     frame_cache: HashMap<(usize, usize), Sprite<T>>,
     line_glyphs: HashMap<LineKey, Sprite<T>>,
     block_glyphs: HashMap<BlockKey, Sprite<T>>,
     metrics: RenderMetrics,
+    /// Creates a texture of the given size for an atlas to grow into
+    new_texture: Box<dyn Fn(AtlasKind, usize) -> anyhow::Result<Rc<T>>>,
+    /// Set when either atlas has been grown or compacted
+    atlas_replaced: bool,
+    /// The number of glyph lookups that were satisfied by, and that
+    /// missed, the cache since the counts were last taken
+    glyph_hits: usize,
+    glyph_misses: usize,
+    /// The totals since the cache was created, reported by `stats`
+    total_hits: usize,
+    total_misses: usize,
+    rasterized: usize,
+    raster_time: Duration,
+    atlas_replacements: usize,
+    /// Holds rasterized glyphs across restarts when
+    /// glyph_disk_cache is enabled
+    disk_cache: RasterCache,
+    /// Rasterizes glyphs on a worker thread when
+    /// async_glyph_rasterization is enabled
+    pub raster_queue: Option<RasterQueue>,
+    /// The glyphs that were submitted to the raster queue, by ticket
+    pending_glyphs: HashMap<u64, PendingGlyph>,
+    queued_keys: HashSet<GlyphKey>,
+    /// Glyphs that were evicted from `glyph_cache`, whose sprites are
+    /// released by `release_evicted_glyphs`
+    evicted_glyphs: Vec<Rc<CachedGlyph<T>>>,
+}
+
+/// A glyph that is being rasterized by the raster queue
+struct PendingGlyph {
+    key: GlyphKey,
+    info: GlyphInfo,
+    x_offset: f64,
+    /// The identity under which to store the glyph in the disk cache
+    identity: Option<String>,
 }
 
 #[cfg(test)]
@@ -334,61 +1064,312 @@ impl GlyphCache<ImageTexture> {
         size: usize,
         metrics: &RenderMetrics,
     ) -> anyhow::Result<Self> {
-        let surface = Rc::new(ImageTexture::new(size, size));
-        let atlas = Atlas::new(&surface).expect("failed to create new texture atlas");
+        let new_texture = |_kind: AtlasKind, size: usize| -> anyhow::Result<Rc<ImageTexture>> {
+            Ok(Rc::new(ImageTexture::new(size, size)))
+        };
+        let atlas = Atlas::new(&new_texture(AtlasKind::Color, size)?)
+            .expect("failed to create new texture atlas");
+        let alpha_atlas = Atlas::new(&new_texture(AtlasKind::Alpha, size)?)
+            .expect("failed to create new texture atlas");
 
         Ok(Self {
             fonts: Rc::clone(fonts),
-            glyph_cache: HashMap::new(),
+            glyph_cache: LruCache::new(glyph_cache_size()),
             image_cache: LruCache::new(16),
             frame_cache: HashMap::new(),
             atlas,
+            alpha_atlas,
             metrics: metrics.clone(),
             line_glyphs: HashMap::new(),
             block_glyphs: HashMap::new(),
+            new_texture: Box::new(new_texture),
+            atlas_replaced: false,
+            glyph_hits: 0,
+            glyph_misses: 0,
+            total_hits: 0,
+            total_misses: 0,
+            rasterized: 0,
+            raster_time: Duration::default(),
+            atlas_replacements: 0,
+            disk_cache: RasterCache::new(),
+            raster_queue: None,
+            pending_glyphs: HashMap::new(),
+            queued_keys: HashSet::new(),
+            evicted_glyphs: vec![],
         })
     }
 }
 
-impl GlyphCache<SrgbTexture2d> {
+impl GlyphCache<AtlasTexture> {
+    /// Creates a glyph cache whose atlases are initially `size` pixels
+    /// square; the atlases grow as needed to fit the sprites
     pub fn new_gl(
         backend: &Rc<GliumContext>,
         fonts: &Rc<FontConfiguration>,
         size: usize,
         metrics: &RenderMetrics,
     ) -> anyhow::Result<Self> {
-        let surface = Rc::new(SrgbTexture2d::empty_with_format(
-            backend,
-            glium::texture::SrgbFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            size as u32,
-            size as u32,
-        )?);
-        let atlas = Atlas::new(&surface).expect("failed to create new texture atlas");
+        let backend = Rc::clone(backend);
+        let new_texture = move |kind: AtlasKind, size: usize| -> anyhow::Result<Rc<AtlasTexture>> {
+            let size = size as u32;
+            Ok(Rc::new(match kind {
+                AtlasKind::Color => AtlasTexture::Srgb(SrgbTexture2d::empty_with_format(
+                    &backend,
+                    glium::texture::SrgbFormat::U8U8U8U8,
+                    glium::texture::MipmapsOption::NoMipmap,
+                    size,
+                    size,
+                )?),
+                AtlasKind::Alpha => {
+                    AtlasTexture::Alpha(glium::texture::Texture2d::empty_with_format(
+                        &backend,
+                        glium::texture::UncompressedFloatFormat::U8,
+                        glium::texture::MipmapsOption::NoMipmap,
+                        size,
+                        size,
+                    )?)
+                }
+            }))
+        };
+        let atlas = Atlas::new(&new_texture(AtlasKind::Color, size)?)
+            .expect("failed to create new texture atlas");
+        let alpha_atlas = Atlas::new(&new_texture(AtlasKind::Alpha, size)?)
+            .expect("failed to create new texture atlas");
 
         Ok(Self {
             fonts: Rc::clone(fonts),
-            glyph_cache: HashMap::new(),
+            glyph_cache: LruCache::new(glyph_cache_size()),
             image_cache: LruCache::new(16),
             frame_cache: HashMap::new(),
             atlas,
+            alpha_atlas,
             metrics: metrics.clone(),
             line_glyphs: HashMap::new(),
             block_glyphs: HashMap::new(),
+            new_texture: Box::new(new_texture),
+            atlas_replaced: false,
+            glyph_hits: 0,
+            glyph_misses: 0,
+            total_hits: 0,
+            total_misses: 0,
+            rasterized: 0,
+            raster_time: Duration::default(),
+            atlas_replacements: 0,
+            disk_cache: RasterCache::new(),
+            raster_queue: None,
+            pending_glyphs: HashMap::new(),
+            queued_keys: HashSet::new(),
+            evicted_glyphs: vec![],
         })
     }
 
     pub fn clear(&mut self) {
         self.atlas.clear();
+        self.alpha_atlas.clear();
+        self.atlas_replaced = false;
         // self.image_cache.clear(); - relatively expensive to re-populate
         self.frame_cache.clear();
         self.glyph_cache.clear();
         self.line_glyphs.clear();
         self.block_glyphs.clear();
+        // Glyphs that are still being rasterized are discarded when
+        // they complete
+        self.pending_glyphs.clear();
+        self.queued_keys.clear();
+        self.evicted_glyphs.clear();
     }
 }
 
 impl<T: Texture2d> GlyphCache<T> {
+    /// Reserve space in the color atlas for a sprite.  If the atlas is
+    /// full then the cached sprites are repacked into a new atlas, which
+    /// is larger unless repacking at the same size is enough to reclaim
+    /// the space left by released sprites.  Use `take_atlas_replaced` to
+    /// find out whether sprites obtained earlier need to be requested
+    /// again.
+    pub fn allocate(
+        &mut self,
+        im: &dyn BitmapImage,
+        padding: Option<usize>,
+    ) -> anyhow::Result<Sprite<T>> {
+        self.allocate_in(AtlasKind::Color, im, padding)
+    }
+
+    /// Like `allocate`, but reserves the space in the atlas of the
+    /// given kind
+    pub fn allocate_in(
+        &mut self,
+        kind: AtlasKind,
+        im: &dyn BitmapImage,
+        padding: Option<usize>,
+    ) -> anyhow::Result<Sprite<T>> {
+        let mut compacted = false;
+        loop {
+            match self.atlas_of_mut(kind).allocate_with_padding(im, padding) {
+                Ok(sprite) => return Ok(sprite),
+                Err(OutOfTextureSpace {
+                    size: Some(size), ..
+                }) => {
+                    // If the shelves are mostly holes left by evicted
+                    // sprites, the live sprites fit in an atlas of the
+                    // same size once they are packed together again
+                    let stats = self.atlas_of(kind).stats();
+                    if !compacted
+                        && stats.allocations > 0
+                        && stats.utilization() < COMPACT_UTILIZATION
+                    {
+                        log::trace!("compact {:?} texture atlas {:?}", kind, stats);
+                        self.replace_atlas(kind, stats.side)?;
+                        compacted = true;
+                    } else {
+                        log::trace!("grow {:?} texture atlas to {}", kind, size);
+                        self.replace_atlas(kind, size)?;
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn atlas_of(&self, kind: AtlasKind) -> &Atlas<T> {
+        match kind {
+            AtlasKind::Color => &self.atlas,
+            AtlasKind::Alpha => &self.alpha_atlas,
+        }
+    }
+
+    fn atlas_of_mut(&mut self, kind: AtlasKind) -> &mut Atlas<T> {
+        match kind {
+            AtlasKind::Color => &mut self.atlas,
+            AtlasKind::Alpha => &mut self.alpha_atlas,
+        }
+    }
+
+    /// Replaces the atlas of the given kind with a new one of the given
+    /// size, moving the cached sprites into it.  If they don't all fit
+    /// then all of the cached sprites are discarded instead, to be
+    /// rasterized into the atlases as they are next requested.
+    fn replace_atlas(&mut self, kind: AtlasKind, size: usize) -> anyhow::Result<()> {
+        let texture = (self.new_texture)(kind, size)?;
+        let mut atlas = Atlas::new(&texture)?;
+        let repacked = self.repack_sprites(kind, &mut atlas);
+        *self.atlas_of_mut(kind) = atlas;
+        // The evicted glyphs weren't moved into the new atlas
+        self.evicted_glyphs.retain(|glyph| glyph.atlas != kind);
+        if let Err(err) = repacked {
+            log::trace!("discarding cached sprites: {:#}", err);
+            self.discard_sprites();
+        }
+        self.atlas_replaced = true;
+        self.atlas_replacements += 1;
+        Ok(())
+    }
+
+    /// Copies each of the cached sprites from the current atlas of the
+    /// given kind into `atlas`, updating the caches to refer to the copies
+    fn repack_sprites(&mut self, kind: AtlasKind, atlas: &mut Atlas<T>) -> anyhow::Result<()> {
+        let side = self.atlas_of(kind).size() as isize;
+        let mut snapshot = Image::new(side as usize, side as usize);
+        self.atlas_of(kind)
+            .texture()
+            .read(Rect::from_size(Size::new(side, side)), &mut snapshot);
+
+        let move_sprite = |atlas: &mut Atlas<T>, sprite: &Sprite<T>| -> anyhow::Result<Sprite<T>> {
+            let mut image = Image::new(
+                sprite.coords.size.width as usize,
+                sprite.coords.size.height as usize,
+            );
+            image.draw_image(Point::new(0, 0), Some(sprite.coords), &snapshot);
+            Ok(atlas.allocate(&image)?)
+        };
+
+        if kind == AtlasKind::Color {
+            for sprite in self
+                .frame_cache
+                .values_mut()
+                .chain(self.line_glyphs.values_mut())
+                .chain(self.block_glyphs.values_mut())
+            {
+                *sprite = move_sprite(atlas, sprite)?;
+            }
+        }
+
+        for (_, glyph) in self.glyph_cache.iter_mut() {
+            if glyph.atlas != kind {
+                continue;
+            }
+            let texture = match glyph.texture.as_ref() {
+                Some(sprite) => Some(move_sprite(atlas, sprite)?),
+                None => continue,
+            };
+            *glyph = Rc::new(CachedGlyph {
+                has_color: glyph.has_color,
+                atlas: glyph.atlas,
+                x_offset: glyph.x_offset,
+                y_offset: glyph.y_offset,
+                bearing_x: glyph.bearing_x,
+                bearing_y: glyph.bearing_y,
+                texture,
+                scale: glyph.scale,
+                pending: glyph.pending,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn discard_sprites(&mut self) {
+        self.atlas.clear();
+        self.alpha_atlas.clear();
+        self.frame_cache.clear();
+        self.glyph_cache.clear();
+        self.line_glyphs.clear();
+        self.block_glyphs.clear();
+        self.evicted_glyphs.clear();
+    }
+
+    /// Returns true if either atlas has been grown or compacted since
+    /// the last call.  Sprites obtained before then are no longer valid.
+    pub fn take_atlas_replaced(&mut self) -> bool {
+        std::mem::replace(&mut self.atlas_replaced, false)
+    }
+
+    /// Returns the number of glyph lookups that were satisfied by, and
+    /// that missed, the cache since the last call
+    pub fn take_lookup_counts(&mut self) -> (usize, usize) {
+        (
+            std::mem::take(&mut self.glyph_hits),
+            std::mem::take(&mut self.glyph_misses),
+        )
+    }
+
+    /// Returns the statistics of the cache, which are shown by the
+    /// debug HUD and reported by `wezterm cli perf`
+    pub fn stats(&self) -> GlyphCacheStats {
+        let occupancy = |atlas: &Atlas<T>| {
+            let stats = atlas.stats();
+            AtlasOccupancy {
+                side: stats.side,
+                sprites: stats.allocations,
+                occupancy: stats.occupancy(),
+            }
+        };
+        GlyphCacheStats {
+            hits: self.total_hits,
+            misses: self.total_misses,
+            glyphs: self.glyph_cache.len(),
+            capacity: self.glyph_cache.cap(),
+            images: self.frame_cache.len(),
+            lines: self.line_glyphs.len(),
+            blocks: self.block_glyphs.len(),
+            rasterized: self.rasterized,
+            raster_time: self.raster_time,
+            atlas_replacements: self.atlas_replacements,
+            color_atlas: occupancy(&self.atlas),
+            alpha_atlas: occupancy(&self.alpha_atlas),
+        }
+    }
+
     /// Resolve a glyph from the cache, rendering the glyph on-demand if
     /// the cache doesn't already hold the desired glyph.
     pub fn cached_glyph(
@@ -397,41 +1378,317 @@ impl<T: Texture2d> GlyphCache<T> {
         style: &TextStyle,
         followed_by_space: bool,
     ) -> anyhow::Result<Rc<CachedGlyph<T>>> {
+        let steps = configuration().glyph_subpixel_positions;
+        let (_, subpixel_x) = split_subpixel_offset(info.x_offset, steps);
         let key = BorrowedGlyphKey {
             font_idx: info.font_idx,
             glyph_pos: info.glyph_pos,
             style,
             followed_by_space,
+            num_cells: info.num_cells,
+            subpixel_x,
         };
 
         if let Some(entry) = self.glyph_cache.get(&key as &dyn GlyphKeyTrait) {
+            self.glyph_hits += 1;
+            self.total_hits += 1;
             return Ok(Rc::clone(entry));
         }
+        self.glyph_misses += 1;
+        self.total_misses += 1;
 
+        if self.raster_queue.is_some() {
+            self.land_rasterized_glyphs();
+            if let Some(entry) = self.glyph_cache.get(&key as &dyn GlyphKeyTrait) {
+                return Ok(Rc::clone(entry));
+            }
+            // The queue also exists to prewarm the cache, in which
+            // case the glyph is loaded right away
+            if configuration().async_glyph_rasterization {
+                if let Some(placeholder) = self.queue_glyph(info, key.to_owned(), steps, true)? {
+                    return Ok(placeholder);
+                }
+            }
+        }
+
+        self.make_room();
         let glyph = self
-            .load_glyph(info, style, followed_by_space)
+            .load_glyph(info, style, followed_by_space, subpixel_x, steps)
             .with_context(|| anyhow!("load_glyph {:?} {:?}", info, style))?;
-        self.glyph_cache.insert(key.to_owned(), Rc::clone(&glyph));
+        self.glyph_cache.put(key.to_owned(), Rc::clone(&glyph));
         Ok(glyph)
     }
 
+    /// Make room before loading a glyph.  The sprite of the evicted
+    /// glyph is released by `release_evicted_glyphs`, as the shape cache
+    /// and the quads of the frame being painted may still refer to it.
+    fn make_room(&mut self) {
+        if self.glyph_cache.len() >= self.glyph_cache.cap() {
+            if let Some((_, evicted)) = self.glyph_cache.pop_lru() {
+                if evicted.texture.is_some() {
+                    self.evicted_glyphs.push(evicted);
+                }
+            }
+        }
+    }
+
+    /// Returns the glyphs that were evicted from the cache and whose
+    /// sprites have yet to be released
+    pub fn evicted_glyphs(&self) -> &[Rc<CachedGlyph<T>>] {
+        &self.evicted_glyphs
+    }
+
+    /// Releases the sprites of the evicted glyphs, so that their space in
+    /// the atlas can be reused.  This must only be called once nothing
+    /// else that will be drawn refers to them.  A glyph that is still
+    /// referenced keeps its space until the atlas is next compacted.
+    pub fn release_evicted_glyphs(&mut self) {
+        for glyph in std::mem::take(&mut self.evicted_glyphs) {
+            if let Ok(glyph) = Rc::try_unwrap(glyph) {
+                if let Some(sprite) = glyph.texture.as_ref() {
+                    self.atlas_of_mut(glyph.atlas).release(sprite);
+                }
+            }
+        }
+    }
+
+    /// A blank glyph that stands in for one that is being rasterized,
+    /// or for one that failed to rasterize when `pending` is false
+    fn placeholder_glyph(info: &GlyphInfo, pending: bool) -> Rc<CachedGlyph<T>> {
+        Rc::new(CachedGlyph {
+            has_color: false,
+            atlas: AtlasKind::Color,
+            texture: None,
+            x_offset: info.x_offset,
+            y_offset: info.y_offset,
+            bearing_x: PixelLength::zero(),
+            bearing_y: PixelLength::zero(),
+            scale: 1.0,
+            pending,
+        })
+    }
+
+    /// Submits the glyph to the raster queue, unless it has already
+    /// been submitted, and returns a placeholder for it.  Returns None
+    /// if the glyph is in the disk cache and `prefer_disk_cache` is set,
+    /// in which case it is quicker to load it right away.
+    fn queue_glyph(
+        &mut self,
+        info: &GlyphInfo,
+        key: GlyphKey,
+        steps: u8,
+        prefer_disk_cache: bool,
+    ) -> anyhow::Result<Option<Rc<CachedGlyph<T>>>> {
+        if self.queued_keys.contains(&key) {
+            return Ok(Some(Self::placeholder_glyph(info, true)));
+        }
+
+        let x_offset = subpixel_x_offset(key.subpixel_x, steps);
+        let font = self.fonts.resolve_font(&key.style)?;
+        let mut identity = if configuration().glyph_disk_cache {
+            font.rasterizer_identity(info.font_idx)
+        } else {
+            None
+        };
+        if let Some(id) = &identity {
+            if self.disk_cache.contains(id, info.glyph_pos, x_offset) {
+                if prefer_disk_cache {
+                    return Ok(None);
+                }
+                // Don't store it in the disk cache a second time
+                identity = None;
+            }
+        }
+
+        let job = font.raster_job(info.glyph_pos, info.font_idx, x_offset)?;
+        let queue = match self.raster_queue.as_mut() {
+            Some(queue) => queue,
+            None => return Ok(None),
+        };
+        let ticket = queue.submit(job)?;
+        self.queued_keys.insert(key.clone());
+        self.pending_glyphs.insert(
+            ticket,
+            PendingGlyph {
+                key,
+                info: info.clone(),
+                x_offset,
+                identity,
+            },
+        );
+        Ok(Some(Self::placeholder_glyph(info, true)))
+    }
+
+    /// Adds the glyphs that the raster queue has completed to the cache
+    fn land_rasterized_glyphs(&mut self) {
+        let completed = match self.raster_queue.as_ref() {
+            Some(queue) => queue.take_completed(),
+            None => return,
+        };
+        for done in completed {
+            let pending = match self.pending_glyphs.remove(&done.ticket) {
+                Some(pending) => pending,
+                // The cache was cleared while it was being rasterized
+                None => continue,
+            };
+            self.queued_keys.remove(&pending.key);
+            self.raster_time += done.elapsed;
+            self.rasterized += 1;
+            if self
+                .glyph_cache
+                .peek(&pending.key as &dyn GlyphKeyTrait)
+                .is_some()
+            {
+                // A prewarmed glyph that was displayed, and therefore
+                // loaded, before the queue got to it
+                continue;
+            }
+
+            let style = pending.key.style.clone();
+            let glyph = done.glyph.and_then(|glyph| {
+                if let Some(id) = &pending.identity {
+                    self.disk_cache
+                        .put(id, pending.key.glyph_pos, pending.x_offset, &glyph);
+                }
+                self.make_room();
+                self.cache_rasterized(&pending.info, &style, pending.key.followed_by_space, glyph)
+            });
+            let glyph = match glyph {
+                Ok(glyph) => glyph,
+                Err(err) => {
+                    // Keep the placeholder, rather than rasterizing
+                    // the glyph again each time that it is looked up
+                    log::error!("load_glyph {:?} {:?}: {:#}", pending.info, style, err);
+                    Self::placeholder_glyph(&pending.info, false)
+                }
+            };
+            self.glyph_cache.put(pending.key, glyph);
+        }
+    }
+
+    /// Queues the glyphs of printable ASCII, and of the codepoints in
+    /// glyph_cache_prewarm_ranges, in the default text style to be
+    /// rasterized by the raster queue, so that the first screenful of
+    /// text doesn't stutter while the cache fills up.  This is called
+    /// once the cache has been created for the current fonts.
+    pub fn prewarm(&mut self) -> anyhow::Result<()> {
+        let config = configuration();
+        if !config.glyph_cache_prewarm || self.raster_queue.is_none() {
+            return Ok(());
+        }
+        let style = self
+            .fonts
+            .match_style(&config, &CellAttributes::default())
+            .clone();
+        let font = self.fonts.resolve_font(&style)?;
+        let steps = config.glyph_subpixel_positions;
+        // Leave room for the glyphs that are actually displayed, so
+        // that a large range doesn't cause them to be evicted
+        let limit = self.glyph_cache.cap() / 2;
+
+        let ascii = CodepointRange {
+            first: 0x20,
+            last: 0x7e,
+        };
+        let chars = std::iter::once(ascii)
+            .chain(config.glyph_cache_prewarm_ranges.iter().copied())
+            .flat_map(CodepointRange::chars);
+        let mut buf = [0u8; 4];
+        for c in chars {
+            if self.glyph_cache.len() + self.pending_glyphs.len() >= limit {
+                log::trace!("stopped prewarming the glyph cache at {:?}", c);
+                break;
+            }
+            let infos = match font.shape(c.encode_utf8(&mut buf), None, || {}) {
+                Ok(infos) => infos,
+                Err(err) => {
+                    log::trace!(
+                        "while shaping {:?} to prewarm the glyph cache: {:#}",
+                        c,
+                        err
+                    );
+                    continue;
+                }
+            };
+            for info in &infos {
+                let (_, subpixel_x) = split_subpixel_offset(info.x_offset, steps);
+                // Whether the glyph is followed by a space is part of
+                // its key, and both are common
+                for &followed_by_space in &[false, true] {
+                    let key = GlyphKey {
+                        font_idx: info.font_idx,
+                        glyph_pos: info.glyph_pos,
+                        style: style.clone(),
+                        followed_by_space,
+                        num_cells: info.num_cells,
+                        subpixel_x,
+                    };
+                    if self.glyph_cache.peek(&key as &dyn GlyphKeyTrait).is_some() {
+                        continue;
+                    }
+                    // Even glyphs that are in the disk cache are left to
+                    // the worker, so that prewarming doesn't block the
+                    // window while it is being opened
+                    self.queue_glyph(info, key, steps, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Perform the load and render of a glyph
-    #[allow(clippy::float_cmp)]
     fn load_glyph(
         &mut self,
         info: &GlyphInfo,
         style: &TextStyle,
         followed_by_space: bool,
+        subpixel_x: u8,
+        steps: u8,
+    ) -> anyhow::Result<Rc<CachedGlyph<T>>> {
+        let glyph = {
+            let font = self.fonts.resolve_font(style)?;
+            let x_offset = subpixel_x_offset(subpixel_x, steps);
+            let identity = if configuration().glyph_disk_cache {
+                font.rasterizer_identity(info.font_idx)
+            } else {
+                None
+            };
+            let cached = identity
+                .as_ref()
+                .and_then(|id| self.disk_cache.get(id, info.glyph_pos, x_offset));
+            match cached {
+                Some(glyph) => glyph,
+                None => {
+                    let start = Instant::now();
+                    let glyph = font.rasterize_glyph(info.glyph_pos, info.font_idx, x_offset)?;
+                    self.raster_time += start.elapsed();
+                    self.rasterized += 1;
+                    if let Some(id) = &identity {
+                        self.disk_cache.put(id, info.glyph_pos, x_offset, &glyph);
+                    }
+                    glyph
+                }
+            }
+        };
+
+        self.cache_rasterized(info, style, followed_by_space, glyph)
+    }
+
+    /// Scales the rasterized glyph to fit the cell and places it in
+    /// the appropriate atlas
+    #[allow(clippy::float_cmp)]
+    fn cache_rasterized(
+        &mut self,
+        info: &GlyphInfo,
+        style: &TextStyle,
+        followed_by_space: bool,
+        glyph: RasterizedGlyph,
     ) -> anyhow::Result<Rc<CachedGlyph<T>>> {
         let base_metrics;
         let idx_metrics;
-        let glyph;
-
         {
             let font = self.fonts.resolve_font(style)?;
             base_metrics = font.metrics();
-            glyph = font.rasterize_glyph(info.glyph_pos, info.font_idx)?;
-
             idx_metrics = font.metrics_for_idx(info.font_idx)?;
         }
 
@@ -467,14 +1724,30 @@ impl<T: Texture2d> GlyphCache<T> {
             // a whitespace glyph
             CachedGlyph {
                 has_color: glyph.has_color,
+                atlas: AtlasKind::Color,
                 texture: None,
                 x_offset: info.x_offset * scale,
                 y_offset: info.y_offset * scale,
                 bearing_x: PixelLength::zero(),
                 bearing_y: PixelLength::zero(),
                 scale,
+                pending: false,
             }
         } else {
+            // Monochrome glyphs are shades of gray, so only their alpha
+            // channel needs to be kept.  The coverage of LCD sub-pixels
+            // differs per channel and needs the color atlas.
+            let atlas = if !glyph.has_color
+                && glyph
+                    .data
+                    .chunks_exact(4)
+                    .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2])
+            {
+                AtlasKind::Alpha
+            } else {
+                AtlasKind::Color
+            };
+
             let raw_im = Image::with_rgba32(
                 glyph.width as usize,
                 glyph.height as usize,
@@ -503,16 +1776,18 @@ impl<T: Texture2d> GlyphCache<T> {
                 (scale, raw_im)
             };
 
-            let tex = self.atlas.allocate(&raw_im)?;
+            let tex = self.allocate_in(atlas, &raw_im, None)?;
 
             let g = CachedGlyph {
                 has_color: glyph.has_color,
+                atlas,
                 texture: Some(tex),
                 x_offset,
                 y_offset,
                 bearing_x,
                 bearing_y,
                 scale,
+                pending: false,
             };
 
             if info.font_idx != 0 {
@@ -567,10 +1842,8 @@ impl<T: Texture2d> GlyphCache<T> {
                         return Ok((sprite.clone(), next));
                     }
 
-                    let sprite = self.atlas.allocate_with_padding(
-                        &decoded.frames[decoded.current_frame].image,
-                        padding,
-                    )?;
+                    let sprite =
+                        self.allocate(&decoded.frames[decoded.current_frame].image, padding)?;
 
                     self.frame_cache
                         .insert((id, decoded.current_frame), sprite.clone());
@@ -589,9 +1862,7 @@ impl<T: Texture2d> GlyphCache<T> {
                 // Use a placeholder instead
                 Ok(DecodedImage::placeholder())
             })?;
-        let sprite = self
-            .atlas
-            .allocate_with_padding(&decoded.frames[0].image, padding)?;
+        let sprite = self.allocate(&decoded.frames[0].image, padding)?;
         self.frame_cache.insert((id, 0), sprite.clone());
         if decoded.frames.len() > 1 {
             let next = Some(decoded.frame_start + decoded.frames[0].duration);
@@ -604,6 +1875,43 @@ impl<T: Texture2d> GlyphCache<T> {
     }
 
     fn block_sprite(&mut self, block: BlockKey) -> anyhow::Result<Sprite<T>> {
+        let buffer = if block.is_curved() {
+            self.supersampled(|cache| cache.draw_block(block))
+        } else {
+            self.draw_block(block)
+        };
+
+        let padding = self.metrics.block_bleed.ceil() as usize;
+        let buffer = if padding > 0 {
+            extend_edges(&buffer, padding)
+        } else {
+            buffer
+        };
+
+        let sprite = self.allocate(&buffer, None)?;
+        self.block_glyphs.insert(block, sprite.clone());
+        Ok(sprite)
+    }
+
+    /// Draws `draw` at `metrics.supersample` times the size of the cell,
+    /// and then scales it down to the size of the cell.  The edges of
+    /// curves and diagonals are smoother than those of a shape that is
+    /// anti-aliased at the size of the cell.
+    fn supersampled<F: FnOnce(&Self) -> Image>(&mut self, draw: F) -> Image {
+        let factor = self.metrics.supersample;
+        if factor <= 1 {
+            return draw(self);
+        }
+        // The drawing functions work in terms of self.metrics, so
+        // substitute the scaled metrics while drawing
+        let metrics = self.metrics;
+        self.metrics = metrics.scaled(factor);
+        let buffer = draw(self);
+        self.metrics = metrics;
+        buffer.downsample(factor)
+    }
+
+    fn draw_block(&self, block: BlockKey) -> Image {
         let mut buffer = Image::new(
             self.metrics.cell_size.width as usize,
             self.metrics.cell_size.height as usize,
@@ -613,38 +1921,25 @@ impl<T: Texture2d> GlyphCache<T> {
 
         let cell_rect = Rect::new(Point::new(0, 0), self.metrics.cell_size);
 
-        let y_eighth = self.metrics.cell_size.height as f32 / 8.;
-        let x_eighth = self.metrics.cell_size.width as f32 / 8.;
-
-        fn scale(f: f32) -> usize {
-            f.ceil().max(1.) as usize
+        let width = self.metrics.cell_size.width as usize;
+        let height = self.metrics.cell_size.height as usize;
+
+        /// Returns the pixel offset of the `n`th of `parts` divisions
+        /// of `total`.  Each boundary is rounded from the exact fraction
+        /// rather than accumulating rounded part sizes, so that the stripes
+        /// differ by at most one pixel, the last boundary always lands on
+        /// `total`, and the blocks that share a boundary (eg: upper 3/8
+        /// and lower 5/8) tile without gaps or overlap.
+        fn boundary(n: usize, parts: usize, total: usize) -> usize {
+            (n * total + parts / 2) / parts
         }
 
         buffer.clear_rect(cell_rect, black);
 
-        let draw_horizontal = |buffer: &mut Image, y: usize| {
-            buffer.draw_line(
-                Point::new(cell_rect.origin.x, cell_rect.origin.y + y as isize),
-                Point::new(
-                    cell_rect.origin.x + self.metrics.cell_size.width,
-                    cell_rect.origin.y + y as isize,
-                ),
-                white,
-            );
-        };
-
-        let draw_vertical = |buffer: &mut Image, x: usize| {
-            buffer.draw_line(
-                Point::new(cell_rect.origin.x + x as isize, cell_rect.origin.y),
-                Point::new(
-                    cell_rect.origin.x + x as isize,
-                    cell_rect.origin.y + self.metrics.cell_size.height,
-                ),
-                white,
-            );
-        };
-
         let draw_quad = |buffer: &mut Image, x: Range<usize>, y: Range<usize>| {
+            if x.is_empty() {
+                return;
+            }
             for y in y {
                 buffer.draw_line(
                     Point::new(
@@ -666,34 +1961,33 @@ impl<T: Texture2d> GlyphCache<T> {
 
         match block {
             BlockKey::Upper(num) => {
-                for n in 0..usize::from(num) {
-                    for a in 0..scale(y_eighth) {
-                        draw_horizontal(&mut buffer, (n as f32 * y_eighth).floor() as usize + a);
-                    }
-                }
+                let y = boundary(num.into(), 8, height);
+                draw_quad(&mut buffer, 0..width, 0..y);
             }
             BlockKey::Lower(num) => {
-                for n in 0..usize::from(num) {
-                    let y =
-                        (self.metrics.cell_size.height - 1) as usize - scale(n as f32 * y_eighth);
-                    for a in 0..scale(y_eighth) {
-                        draw_horizontal(&mut buffer, y + a);
-                    }
-                }
+                let y = boundary(8 - usize::from(num), 8, height);
+                draw_quad(&mut buffer, 0..width, y..height);
             }
             BlockKey::Left(num) => {
-                for n in 0..usize::from(num) {
-                    for a in 0..scale(x_eighth) {
-                        draw_vertical(&mut buffer, (n as f32 * x_eighth).floor() as usize + a);
-                    }
-                }
+                let x = boundary(num.into(), 8, width);
+                draw_quad(&mut buffer, 0..x, 0..height);
             }
             BlockKey::Right(num) => {
-                for n in 0..usize::from(num) {
-                    let x =
-                        (self.metrics.cell_size.width - 1) as usize - scale(n as f32 * x_eighth);
-                    for a in 0..scale(x_eighth) {
-                        draw_vertical(&mut buffer, x + a);
+                let x = boundary(8 - usize::from(num), 8, width);
+                draw_quad(&mut buffer, x..width, 0..height);
+            }
+            BlockKey::Eighths { rows, columns } => {
+                // Using the same boundaries as the eighth blocks means
+                // that these line up with them in the adjacent cells,
+                // as in the progress bars that combine them
+                for n in 0..8 {
+                    if rows & (1 << n) != 0 {
+                        let y = boundary(n, 8, height)..boundary(n + 1, 8, height);
+                        draw_quad(&mut buffer, 0..width, y);
+                    }
+                    if columns & (1 << n) != 0 {
+                        let x = boundary(n, 8, width)..boundary(n + 1, 8, width);
+                        draw_quad(&mut buffer, x, 0..height);
                     }
                 }
             }
@@ -708,24 +2002,72 @@ impl<T: Texture2d> GlyphCache<T> {
 
                 buffer.clear_rect(cell_rect, fill.srgba_pixel());
             }
+            BlockKey::Dithered(alpha) => {
+                // An ordered dither, which produces the classic patterns:
+                // scattered dots, a checkerboard and inverted scattered dots.
+                // The dots are scaled up in larger cells so that the pattern
+                // looks the same at any pixel density.
+                const BAYER: [[u8; 4]; 4] =
+                    [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+                let threshold = match alpha {
+                    BlockAlpha::Full => 16,
+                    BlockAlpha::Dark => 12,
+                    BlockAlpha::Medium => 8,
+                    BlockAlpha::Light => 4,
+                };
+                let dot = (self.metrics.cell_size.width as usize / 8).max(1);
+                for y in 0..self.metrics.cell_size.height as usize {
+                    for x in 0..self.metrics.cell_size.width as usize {
+                        if BAYER[(y / dot) % 4][(x / dot) % 4] < threshold {
+                            *buffer.pixel_mut(x, y) = white.as_srgba32();
+                        }
+                    }
+                }
+            }
             BlockKey::Quadrants(quads) => {
-                let y_half = self.metrics.cell_size.height as f32 / 2.;
-                let x_half = self.metrics.cell_size.width as f32 / 2.;
-                let width = self.metrics.cell_size.width as usize;
-                let height = self.metrics.cell_size.height as usize;
+                let x_half = boundary(1, 2, width);
+                let y_half = boundary(1, 2, height);
                 if quads.contains(Quadrant::UPPER_LEFT) {
-                    draw_quad(&mut buffer, 0..scale(x_half), 0..scale(y_half));
+                    draw_quad(&mut buffer, 0..x_half, 0..y_half);
                 }
                 if quads.contains(Quadrant::UPPER_RIGHT) {
-                    draw_quad(&mut buffer, scale(x_half)..width, 0..scale(y_half));
+                    draw_quad(&mut buffer, x_half..width, 0..y_half);
                 }
                 if quads.contains(Quadrant::LOWER_LEFT) {
-                    draw_quad(&mut buffer, 0..scale(x_half), scale(y_half)..height);
+                    draw_quad(&mut buffer, 0..x_half, y_half..height);
                 }
                 if quads.contains(Quadrant::LOWER_RIGHT) {
-                    draw_quad(&mut buffer, scale(x_half)..width, scale(y_half)..height);
+                    draw_quad(&mut buffer, x_half..width, y_half..height);
+                }
+            }
+            BlockKey::Sextants(bits) => {
+                let x_half = boundary(1, 2, width);
+                let y_third = boundary(1, 3, height);
+                let y_two_thirds = boundary(2, 3, height);
+                let columns = [0..x_half, x_half..width];
+                let rows = [0..y_third, y_third..y_two_thirds, y_two_thirds..height];
+                for bit in 0..6 {
+                    if bits & (1 << bit) != 0 {
+                        draw_quad(&mut buffer, columns[bit % 2].clone(), rows[bit / 2].clone());
+                    }
                 }
             }
+            BlockKey::Wedge { polygon, inverse } => {
+                let x_sixth = self.metrics.cell_size.width as f32 / 6.;
+                let y_sixth = self.metrics.cell_size.height as f32 / 6.;
+                let points: Vec<PointF> = polygon
+                    .iter()
+                    .map(|&(x, y)| PointF::new(x as f32 * x_sixth, y as f32 * y_sixth))
+                    .collect();
+                if inverse {
+                    buffer.fill_shape(white, &|x, y| !point_in_polygon(PointF::new(x, y), &points));
+                } else {
+                    buffer.fill_polygon(&points, white);
+                }
+            }
+            BlockKey::BoxDrawing(key) => self.draw_box_drawing(&mut buffer, key),
+            BlockKey::Braille(dots) => self.draw_braille(&mut buffer, dots),
+            BlockKey::Powerline(key) => self.draw_powerline(&mut buffer, key),
         }
 
         /*
@@ -733,9 +2075,366 @@ impl<T: Texture2d> GlyphCache<T> {
         buffer.log_bits();
         */
 
-        let sprite = self.atlas.allocate(&buffer)?;
-        self.block_glyphs.insert(block, sprite.clone());
-        Ok(sprite)
+        buffer
+    }
+
+    /// Draws the dots of a Braille pattern in a grid of two columns
+    /// and four rows that spans the cell, so that adjacent cells form
+    /// an evenly spaced grid of dots, as used by plots in the terminal
+    fn draw_braille(&self, buffer: &mut Image, dots: u8) {
+        let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
+        let width = self.metrics.cell_size.width as f32;
+        let height = self.metrics.cell_size.height as f32;
+        let col_width = width / 2.;
+        let row_height = height / 4.;
+        // Leave a gap between the dots of at least one pixel
+        let radius = ((col_width.min(row_height) * 0.6) / 2.)
+            .min((col_width.min(row_height) - 1.) / 2.)
+            .max(0.5);
+
+        // The (column, row) of each of the dots, indexed by its bit
+        const POSITIONS: [(usize, usize); 8] = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (0, 3),
+            (1, 3),
+        ];
+
+        for (bit, &(col, row)) in POSITIONS.iter().enumerate() {
+            if dots & (1 << bit) == 0 {
+                continue;
+            }
+            let center = PointF::new(
+                (col as f32 + 0.5) * col_width,
+                (row as f32 + 0.5) * row_height,
+            );
+            buffer.fill_circle(center, radius, white);
+        }
+    }
+
+    /// Draws a Powerline divider.  The shapes span the full height of
+    /// the cell, and those that have a flat side span the full height of
+    /// that edge, so that the segments of a prompt join up without seams.
+    fn draw_powerline(&self, buffer: &mut Image, key: PowerlineKey) {
+        let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
+        let width = self.metrics.cell_size.width as f32;
+        let height = self.metrics.cell_size.height as f32;
+        let half_height = height / 2.;
+        let thickness = self.metrics.stroke_thickness.max(1) as f32;
+
+        // The shapes are described as pointing to the right, and
+        // are mirrored in order to point to the left
+        let mirror = |right: bool, x: f32| if right { x } else { width - x };
+
+        match key {
+            PowerlineKey::Arrow {
+                right,
+                filled: true,
+            } => {
+                let points = [
+                    PointF::new(mirror(right, 0.), 0.),
+                    PointF::new(mirror(right, width), half_height),
+                    PointF::new(mirror(right, 0.), height),
+                ];
+                buffer.fill_polygon(&points, white);
+            }
+            PowerlineKey::Arrow {
+                right,
+                filled: false,
+            } => {
+                let tip = PointF::new(mirror(right, width), half_height);
+                let top = PointF::new(mirror(right, 0.), 0.);
+                let bottom = PointF::new(mirror(right, 0.), height);
+                buffer.stroke_shape(white, thickness, &|x, y| {
+                    let point = PointF::new(x, y);
+                    segment_distance(point, top, tip).min(segment_distance(point, tip, bottom))
+                });
+            }
+            PowerlineKey::HalfCircle { right, filled } => {
+                // An ellipse centered on the middle of the flat side,
+                // which reaches the opposite edge of the cell
+                let (rx, ry) = (width, half_height);
+                let ellipse = move |x: f32, y: f32| {
+                    let (dx, dy) = (x / rx, (y - half_height) / ry);
+                    (dx * dx + dy * dy, dx / rx, dy / ry)
+                };
+                if filled {
+                    buffer.fill_shape(white, &|x, y| ellipse(mirror(right, x), y).0 <= 1.);
+                } else {
+                    buffer.stroke_shape(white, thickness, &|x, y| {
+                        let x = mirror(right, x);
+                        // Approximate the distance to the curve by
+                        // dividing the implicit function by its gradient
+                        let (f, gx, gy) = ellipse(x, y);
+                        let gradient = 2. * gx.hypot(gy);
+                        if gradient > 0. {
+                            (f - 1.).abs() / gradient
+                        } else {
+                            rx.min(ry)
+                        }
+                    });
+                }
+            }
+            PowerlineKey::Corner { upper, left } => {
+                let x = if left { 0. } else { width };
+                let y = if upper { 0. } else { height };
+                let points = [
+                    PointF::new(x, y),
+                    PointF::new(width - x, y),
+                    PointF::new(x, height - y),
+                ];
+                buffer.fill_polygon(&points, white);
+            }
+            PowerlineKey::Slant { forward } => {
+                let (start, end) = if forward {
+                    (PointF::new(0., height), PointF::new(width, 0.))
+                } else {
+                    (PointF::new(0., 0.), PointF::new(width, height))
+                };
+                buffer.draw_thick_line(start, end, thickness, white);
+            }
+            PowerlineKey::Flame { right, filled } => {
+                let points: Vec<PointF> = FLAME
+                    .iter()
+                    .map(|&(x, y)| PointF::new(mirror(right, x * width), y * height))
+                    .collect();
+                if filled {
+                    buffer.fill_polygon(&points, white);
+                } else {
+                    // Leave out the flat side, which is the first and
+                    // last edge of the outline
+                    let edge = &points[1..points.len() - 1];
+                    buffer.stroke_shape(white, thickness, &|x, y| {
+                        let point = PointF::new(x, y);
+                        edge.windows(2)
+                            .map(|pair| segment_distance(point, pair[0], pair[1]))
+                            .fold(f32::INFINITY, f32::min)
+                    });
+                }
+            }
+            PowerlineKey::Pixelated { right, big } => {
+                // The first column is solid, and each of the others
+                // holds a square in one of every `column + 1` rows, so
+                // that the squares thin out towards the pointed side
+                let columns = if big { 2 } else { 4 };
+                let size = width / columns as f32;
+                let rows = ((height / size).round() as usize).max(1);
+                let row_height = height / rows as f32;
+                buffer.fill_shape(white, &|x, y| {
+                    // Classify the whole pixel by its center, so
+                    // that the edges of the squares remain crisp
+                    let x = mirror(right, x.floor() + 0.5);
+                    let y = y.floor() + 0.5;
+                    let column = ((x / size) as usize).min(columns - 1);
+                    let row = ((y / row_height) as usize).min(rows - 1);
+                    (row + column) % (column + 1) == 0
+                });
+            }
+            PowerlineKey::IceWaveform => {
+                let points: Vec<PointF> = ICE_WAVEFORM
+                    .iter()
+                    .map(|&(x, y)| PointF::new(x * width, y * height))
+                    .collect();
+                buffer.fill_polygon(&points, white);
+            }
+        }
+    }
+
+    fn draw_box_drawing(&self, buffer: &mut Image, key: BoxDrawingKey) {
+        let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
+        let width = self.metrics.cell_size.width;
+        let height = self.metrics.cell_size.height;
+        let cx = width / 2;
+        let cy = height / 2;
+        let light = self.metrics.stroke_thickness.max(1);
+        // The distance from the center to each of the strokes of a double
+        // line.  The strokes are separated by at least the width of a stroke,
+        // and by more in larger cells so that they remain distinguishable,
+        // but both strokes must fit within the cell.
+        let gap = light.max(width.min(height) / 8);
+        let off = ((gap + light + 1) / 2)
+            .min((width.min(height) - light) / 2)
+            .max(1);
+
+        let fill = |buffer: &mut Image, x: Range<isize>, y: Range<isize>| {
+            if x.start < x.end && y.start < y.end {
+                buffer.clear_rect(
+                    Rect::new(
+                        Point::new(x.start, y.start),
+                        Size::new(x.end - x.start, y.end - y.start),
+                    ),
+                    white,
+                );
+            }
+        };
+
+        // The pixels, relative to `center`, that are covered by
+        // a stroke of the specified thickness
+        let band = |center: isize, thickness: isize| {
+            center - thickness / 2..center - thickness / 2 + thickness
+        };
+
+        // The (offset, thickness) of each of the parallel strokes
+        // that make up a line of the specified weight
+        let strokes = |weight: LineWeight| match weight {
+            LineWeight::None => vec![],
+            LineWeight::Light => vec![(0, light)],
+            LineWeight::Heavy => vec![(0, light * 2)],
+            LineWeight::Double => vec![(-off, light), (off, light)],
+        };
+
+        // The pixels, relative to the center, covered by a line
+        let span = |weight: LineWeight| match weight {
+            LineWeight::None => None,
+            LineWeight::Light => Some(band(0, light)),
+            LineWeight::Heavy => Some(band(0, light * 2)),
+            LineWeight::Double => Some(band(-off, light).start..band(off, light).end),
+        };
+
+        // Computes how far past the center a stroke of an arm has to
+        // extend in order to join up with the perpendicular arms
+        // `before` and `after`.  Returns the (start, end) offsets for
+        // arms that approach the center from the far and the near edge
+        // of the cell respectively.
+        let join = |stroke: isize, weight: LineWeight, before: LineWeight, after: LineWeight| {
+            let (near, far) = if stroke < 0 {
+                (before, after)
+            } else {
+                (after, before)
+            };
+            if weight == LineWeight::Double && near == LineWeight::Double {
+                // Stop at the closest stroke of the perpendicular line
+                (band(off, light).start, band(-off, light).end)
+            } else if weight == LineWeight::Double && far == LineWeight::Double {
+                // Turn the outside corner of the perpendicular line
+                let span = band(-off, light).start..band(off, light).end;
+                (span.start, span.end)
+            } else {
+                match (span(before), span(after)) {
+                    (Some(a), Some(b)) => (a.start.min(b.start), a.end.max(b.end)),
+                    (Some(a), None) | (None, Some(a)) => (a.start, a.end),
+                    (None, None) => (0, 0),
+                }
+            }
+        };
+
+        // Splits a line of the specified length into evenly spaced dashes.
+        // Every dash has the same gap, which is divided between both ends
+        // of the cell so that the pattern continues into adjacent cells.
+        let dash_ranges = |length: isize, dashes: u8| {
+            let dashes = dashes as isize;
+            let gap = (length / dashes / 4).max(1);
+            (0..dashes)
+                .map(|n| {
+                    let start = n * length / dashes + gap / 2;
+                    let end = (n + 1) * length / dashes - (gap - gap / 2);
+                    start..end.max(start + 1)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Draws a light stroke with anti-aliased edges, used for the
+        // curved and diagonal glyphs.  `distance` returns the distance from
+        // the specified point to the center line of the stroke, or None if
+        // the point is not near it.
+        let draw_stroke = |buffer: &mut Image, distance: &dyn Fn(f32, f32) -> Option<f32>| {
+            buffer.stroke_shape(white, light as f32, &|x, y| {
+                distance(x, y).unwrap_or(f32::INFINITY)
+            });
+        };
+
+        match key {
+            BoxDrawingKey::Lines {
+                up,
+                down,
+                left,
+                right,
+            } => {
+                for &(weight, is_left) in &[(left, true), (right, false)] {
+                    for (stroke, thickness) in strokes(weight) {
+                        let (start, end) = join(stroke, weight, up, down);
+                        let x = if is_left {
+                            0..cx + end
+                        } else {
+                            cx + start..width
+                        };
+                        let y = band(stroke, thickness);
+                        fill(buffer, x, cy + y.start..cy + y.end);
+                    }
+                }
+                for &(weight, is_up) in &[(up, true), (down, false)] {
+                    for (stroke, thickness) in strokes(weight) {
+                        let (start, end) = join(stroke, weight, left, right);
+                        let x = band(stroke, thickness);
+                        let y = if is_up {
+                            0..cy + end
+                        } else {
+                            cy + start..height
+                        };
+                        fill(buffer, cx + x.start..cx + x.end, y);
+                    }
+                }
+            }
+            BoxDrawingKey::HorizontalDash(weight, dashes) => {
+                let y = span(weight).unwrap_or(0..0);
+                for x in dash_ranges(width, dashes) {
+                    fill(buffer, x, cy + y.start..cy + y.end);
+                }
+            }
+            BoxDrawingKey::VerticalDash(weight, dashes) => {
+                let x = span(weight).unwrap_or(0..0);
+                for y in dash_ranges(height, dashes) {
+                    fill(buffer, cx + x.start..cx + x.end, y);
+                }
+            }
+            BoxDrawingKey::Arc { down, right } => {
+                // The center lines of the light strokes that the arc joins,
+                // measured in the same pixel-center coordinates as the strokes
+                // drawn by `Lines`, so that the arc meets them exactly
+                let stroke = band(0, light);
+                let x_line = cx as f32 + (stroke.start + stroke.end) as f32 / 2.;
+                let y_line = cy as f32 + (stroke.start + stroke.end) as f32 / 2.;
+                let sx = if right { 1. } else { -1. };
+                let sy = if down { 1. } else { -1. };
+                let radius = x_line
+                    .min(width as f32 - x_line)
+                    .min(y_line)
+                    .min(height as f32 - y_line);
+                let (ox, oy) = (x_line + sx * radius, y_line + sy * radius);
+
+                // The quarter circle, and the straight segments that
+                // run from its ends to the edges of the cell
+                draw_stroke(
+                    buffer,
+                    &|x, y| match ((x - ox) * sx > 0., (y - oy) * sy > 0.) {
+                        (false, false) => Some(((x - ox).hypot(y - oy) - radius).abs()),
+                        (false, true) => Some((x - x_line).abs()),
+                        (true, false) => Some((y - y_line).abs()),
+                        (true, true) => None,
+                    },
+                );
+            }
+            BoxDrawingKey::Diagonal { forward, backward } => {
+                // The lines run exactly between the corners of the cell,
+                // so that they continue into the diagonally adjacent cells
+                let (w, h) = (width as f32, height as f32);
+                let length = w.hypot(h);
+                draw_stroke(buffer, &|x, y| {
+                    let to_forward = (x * h + y * w - w * h).abs() / length;
+                    let to_backward = (x * h - y * w).abs() / length;
+                    match (forward, backward) {
+                        (true, true) => Some(to_forward.min(to_backward)),
+                        (true, false) => Some(to_forward),
+                        (false, true) => Some(to_backward),
+                        (false, false) => None,
+                    }
+                });
+            }
+        }
     }
 
     pub fn cached_block(&mut self, block: BlockKey) -> anyhow::Result<Sprite<T>> {
@@ -746,6 +2445,17 @@ impl<T: Texture2d> GlyphCache<T> {
     }
 
     fn line_sprite(&mut self, key: LineKey) -> anyhow::Result<Sprite<T>> {
+        let buffer = if key.underline == Underline::Curly {
+            self.supersampled(|cache| cache.draw_line_key(key))
+        } else {
+            self.draw_line_key(key)
+        };
+        let sprite = self.allocate(&buffer, None)?;
+        self.line_glyphs.insert(key, sprite.clone());
+        Ok(sprite)
+    }
+
+    fn draw_line_key(&self, key: LineKey) -> Image {
         let mut buffer = Image::new(
             self.metrics.cell_size.width as usize,
             self.metrics.cell_size.height as usize,
@@ -754,25 +2464,29 @@ impl<T: Texture2d> GlyphCache<T> {
         let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
 
         let cell_rect = Rect::new(Point::new(0, 0), self.metrics.cell_size);
+        let thickness = self.metrics.stroke_thickness as f32;
+
+        // Draws a line across the width of the cell, whose top edge
+        // is at the specified row
+        let draw_horizontal = |buffer: &mut Image, row: isize| {
+            let y = (cell_rect.origin.y + row) as f32 + thickness / 2.;
+            buffer.draw_thick_line(
+                PointF::new(cell_rect.origin.x as f32, y),
+                PointF::new(
+                    (cell_rect.origin.x + self.metrics.cell_size.width) as f32,
+                    y,
+                ),
+                thickness,
+                white,
+            );
+        };
 
         let draw_single = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + self.metrics.descender_row + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + self.metrics.descender_row + row,
-                    ),
-                    white,
-                );
-            }
+            draw_horizontal(buffer, self.metrics.descender_row);
         };
 
         let draw_dotted = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
+            for row in 0..self.metrics.stroke_thickness {
                 let y = (cell_rect.origin.y + self.metrics.descender_row + row) as usize;
                 if y >= self.metrics.cell_size.height as usize {
                     break;
@@ -795,7 +2509,7 @@ impl<T: Texture2d> GlyphCache<T> {
         };
 
         let draw_dashed = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
+            for row in 0..self.metrics.stroke_thickness {
                 let y = (cell_rect.origin.y + self.metrics.descender_row + row) as usize;
                 if y >= self.metrics.cell_size.height as usize {
                     break;
@@ -817,94 +2531,43 @@ impl<T: Texture2d> GlyphCache<T> {
         };
 
         let draw_curly = |buffer: &mut Image| {
-            let max_y = self.metrics.cell_size.height as usize - 1;
             let x_factor = (2. * std::f32::consts::PI) / self.metrics.cell_size.width as f32;
 
-            // Have the wave go from the descender to the bottom of the cell
-            let wave_height =
-                self.metrics.cell_size.height - (cell_rect.origin.y + self.metrics.descender_row);
-
-            let half_height = (wave_height as f32 / 2.).max(1.);
-            let y =
-                (cell_rect.origin.y + self.metrics.descender_row) as usize - half_height as usize;
-
-            fn add(x: usize, y: usize, val: u8, max_y: usize, buffer: &mut Image) {
-                let y = y.min(max_y);
-                let pixel = buffer.pixel_mut(x, y);
-                let (current, _, _, _) = SrgbaPixel::with_srgba_u32(*pixel).as_rgba();
-                let value = current.saturating_add(val);
-                *pixel = SrgbaPixel::rgba(value, value, value, 0xff).as_srgba32();
-            }
-
-            for x in 0..self.metrics.cell_size.width as usize {
-                let vertical = wave_height as f32 * (x as f32 * x_factor).cos();
-                let v1 = vertical.floor();
-                let v2 = vertical.ceil();
-
-                for row in 0..self.metrics.underline_height as usize {
-                    let value = (255. * (vertical - v1).abs()) as u8;
-                    add(x, row + y + v1 as usize, 255 - value, max_y, buffer);
-                    add(x, row + y + v2 as usize, value, max_y, buffer);
-                }
-            }
+            // Have the wave go from the descender to the bottom of the
+            // cell, with one full period per cell so that it continues
+            // into the adjacent cells
+            let top = (cell_rect.origin.y + self.metrics.descender_row) as f32 + thickness / 2.;
+            let bottom =
+                (cell_rect.origin.y + self.metrics.cell_size.height) as f32 - thickness / 2.;
+            let middle = (top + bottom) / 2.;
+            let amplitude = ((bottom - top) / 2.).max(0.);
+
+            buffer.stroke_shape(white, thickness, &|x, y| {
+                let angle = (x - cell_rect.origin.x as f32) * x_factor;
+                let wave = middle + amplitude * angle.cos();
+                let slope = amplitude * x_factor * angle.sin();
+                // Approximate the distance to the curve by dividing the
+                // vertical distance by the length of its gradient
+                (y - wave).abs() / (1. + slope * slope).sqrt()
+            });
         };
 
         let draw_double = |buffer: &mut Image| {
             let first_line = self
                 .metrics
                 .descender_row
-                .min(self.metrics.descender_plus_two - 2 * self.metrics.underline_height);
+                .min(self.metrics.descender_plus_two - 2 * self.metrics.stroke_thickness);
 
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(cell_rect.origin.x, cell_rect.origin.y + first_line + row),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + first_line + row,
-                    ),
-                    white,
-                );
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + self.metrics.descender_plus_two + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + self.metrics.descender_plus_two + row,
-                    ),
-                    white,
-                );
-            }
+            draw_horizontal(buffer, first_line);
+            draw_horizontal(buffer, self.metrics.descender_plus_two);
         };
 
         let draw_strike = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + self.metrics.strike_row + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + self.metrics.strike_row + row,
-                    ),
-                    white,
-                );
-            }
+            draw_horizontal(buffer, self.metrics.strike_row);
         };
 
         let draw_overline = |buffer: &mut Image| {
-            for row in 0..self.metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(cell_rect.origin.x, cell_rect.origin.y + row),
-                    Point::new(
-                        cell_rect.origin.x + self.metrics.cell_size.width,
-                        cell_rect.origin.y + row,
-                    ),
-                    white,
-                );
-            }
+            draw_horizontal(buffer, 0);
         };
 
         buffer.clear_rect(cell_rect, black);
@@ -922,9 +2585,7 @@ impl<T: Texture2d> GlyphCache<T> {
         if key.strike_through {
             draw_strike(&mut buffer);
         }
-        let sprite = self.atlas.allocate(&buffer)?;
-        self.line_glyphs.insert(key, sprite.clone());
-        Ok(sprite)
+        buffer
     }
 
     /// Figure out what we're going to draw for the underline.
@@ -957,3 +2618,110 @@ impl<T: Texture2d> GlyphCache<T> {
         self.line_sprite(key)
     }
 }
+
+/// Returns a copy of `image` with `padding` pixels added around it,
+/// which repeat the pixels at its edges.  Strokes that reach the edge
+/// of a cell continue into the padding, so that they overlap the
+/// strokes of the adjacent cells when the sprite bleeds into them.
+fn extend_edges(image: &Image, padding: usize) -> Image {
+    let (width, height) = image.image_dimensions();
+    let mut extended = Image::new(width + 2 * padding, height + 2 * padding);
+    for y in 0..height + 2 * padding {
+        let src_y = y.saturating_sub(padding).min(height - 1);
+        for x in 0..width + 2 * padding {
+            let src_x = x.saturating_sub(padding).min(width - 1);
+            *extended.pixel_mut(x, y) = *image.pixel(src_x, src_y);
+        }
+    }
+    extended
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn split(x_offset: f64, steps: u8) -> (f64, u8) {
+        let (whole, step) = split_subpixel_offset(PixelLength::new(x_offset), steps);
+        (whole.get(), step)
+    }
+
+    #[test]
+    fn subpixel_offset_disabled() {
+        assert_eq!(split(3.7, 0), (3.7, 0));
+        assert_eq!(split(3.7, 1), (3.7, 0));
+    }
+
+    #[test]
+    fn subpixel_offset_rounds_to_nearest_step() {
+        assert_eq!(split(2.0, 4), (2.0, 0));
+        assert_eq!(split(2.1, 4), (2.0, 0));
+        // Half way between two steps rounds up
+        assert_eq!(split(2.125, 4), (2.0, 1));
+        assert_eq!(split(2.25, 4), (2.0, 1));
+        assert_eq!(split(2.5, 4), (2.0, 2));
+        assert_eq!(split(2.6, 4), (2.0, 2));
+        assert_eq!(split(2.75, 4), (2.0, 3));
+        assert_eq!(split(2.874, 4), (2.0, 3));
+
+        assert_eq!(split(1.2, 2), (1.0, 0));
+        assert_eq!(split(1.3, 2), (1.0, 1));
+    }
+
+    #[test]
+    fn subpixel_offset_carries_into_next_pixel() {
+        // Rounding up to a whole step moves the glyph to the next
+        // pixel rather than producing an out of range step
+        assert_eq!(split(2.875, 4), (3.0, 0));
+        assert_eq!(split(2.9, 4), (3.0, 0));
+        assert_eq!(split(1.75, 2), (2.0, 0));
+    }
+
+    #[test]
+    fn subpixel_offset_negative() {
+        // The step is always a shift to the right of the whole pixel
+        assert_eq!(split(-0.25, 4), (-1.0, 3));
+        assert_eq!(split(-0.1, 4), (0.0, 0));
+    }
+
+    #[test]
+    fn queued_glyph_is_replaced_once_rasterized() {
+        config::use_test_configuration();
+        let mut config: config::Config = (*configuration()).clone();
+        config.async_glyph_rasterization = true;
+        config.glyph_disk_cache = false;
+        config::use_this_configuration(config);
+
+        let fonts = Rc::new(FontConfiguration::new(None).unwrap());
+        let render_metrics = RenderMetrics::new(&fonts).unwrap();
+        let mut glyph_cache = GlyphCache::new_in_memory(&fonts, 128, &render_metrics).unwrap();
+        let (notify_tx, notify_rx) = channel();
+        glyph_cache.raster_queue = Some(
+            RasterQueue::new(move || {
+                notify_tx.send(()).ok();
+            })
+            .unwrap(),
+        );
+
+        let style = TextStyle::default();
+        let font = fonts.resolve_font(&style).unwrap();
+        let infos = font.shape("a", None, || {}).unwrap();
+        let info = &infos[0];
+
+        let placeholder = glyph_cache.cached_glyph(info, &style, false).unwrap();
+        assert!(placeholder.pending);
+        assert!(placeholder.texture.is_none());
+
+        // Looking the glyph up again while it is queued doesn't queue it twice
+        let again = glyph_cache.cached_glyph(info, &style, false).unwrap();
+        assert!(again.pending);
+        assert_eq!(glyph_cache.pending_glyphs.len(), 1);
+
+        notify_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        let glyph = glyph_cache.cached_glyph(info, &style, false).unwrap();
+        assert!(!glyph.pending);
+        assert!(glyph.texture.is_some());
+        assert!(glyph_cache.pending_glyphs.is_empty());
+        assert!(glyph_cache.queued_keys.is_empty());
+    }
+}
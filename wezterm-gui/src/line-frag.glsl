@@ -6,7 +6,7 @@
 uniform sampler2D atlas_nearest_sampler;
 
 void main() {
-  if (o_has_color >= 2.0) {
+  if (o_has_color == 2.0 || o_has_color == 3.0) {
     // Don't render the background image on anything other than
     // the window_bg_layer.
     discard;
@@ -18,16 +18,20 @@ use wezterm_gui_subcommands::*;
 use wezterm_ssh::*;
 use wezterm_toast_notification::*;
 
+mod crashreport;
 mod frontend;
 mod glyphcache;
 mod markdown;
 mod overlay;
 mod quad;
+mod rastercache;
+mod rasterqueue;
 mod renderstate;
 mod scripting;
 mod scrollbar;
 mod selection;
 mod shapecache;
+mod singleinstance;
 mod stats;
 mod tabbar;
 mod termwindow;
@@ -67,6 +71,12 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Run in portable mode: the configuration, color schemes and
+    /// runtime state are kept in the directory that contains the
+    /// executable rather than in your home directory
+    #[structopt(long = "portable")]
+    portable: bool,
+
     #[structopt(subcommand)]
     cmd: Option<SubCommand>,
 }
@@ -86,6 +96,17 @@ enum SubCommand {
     Connect(ConnectCommand),
 }
 
+impl SubCommand {
+    fn config_overrides(&self) -> &ConfigOverrides {
+        match self {
+            SubCommand::Start(start) => &start.config,
+            SubCommand::Ssh(ssh) => &ssh.config,
+            SubCommand::Serial(serial) => &serial.config,
+            SubCommand::Connect(connect) => &connect.config,
+        }
+    }
+}
+
 async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     let mut ssh_config = Config::new();
     ssh_config.add_default_config_files();
@@ -132,7 +153,7 @@ async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     let local_domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     mux.add_domain(&local_domain);
 
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = domain
         .spawn(config.initial_size(), cmd, None, *window_id)
         .await?;
@@ -160,6 +181,7 @@ fn run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     .detach();
 
     maybe_show_configuration_error_window();
+    crashreport::show_pending_reports();
     gui.run_forever()
 }
 
@@ -179,12 +201,13 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
     block_on(domain.attach())?; // FIXME: blocking
 
     {
-        let window_id = mux.new_empty_window();
+        let window_id = mux.new_empty_window(None);
         // FIXME: blocking
         let _tab = block_on(domain.spawn(config.initial_size(), None, None, *window_id))?;
     }
 
     maybe_show_configuration_error_window();
+    crashreport::show_pending_reports();
     gui.run_forever()
 }
 
@@ -258,7 +281,7 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     }
 
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = mux
         .default_domain()
         .spawn(config.initial_size(), cmd, None, *window_id)
@@ -297,8 +320,7 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         crate::set_window_class(cls);
     }
 
-    let unix_socket_path =
-        config::RUNTIME_DIR.join(format!("gui-sock-{}", unsafe { libc::getpid() }));
+    let unix_socket_path = singleinstance::gui_socket_path(std::process::id());
     std::env::set_var("WEZTERM_UNIX_SOCKET", unix_socket_path.clone());
 
     if let Ok(mut listener) =
@@ -332,6 +354,9 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
         let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
         Mux::set_mux(&mux);
+        if let Some(workspace) = opts.workspace.as_ref() {
+            mux.set_active_workspace(workspace);
+        }
         crate::update::load_last_release_info_and_set_banner();
 
         let gui = crate::frontend::try_new()?;
@@ -347,6 +372,7 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         .detach();
 
         maybe_show_configuration_error_window();
+        crashreport::show_pending_reports();
         gui.run_forever()
     };
 
@@ -451,18 +477,36 @@ fn run() -> anyhow::Result<()> {
         }
     };
 
+    // Portable mode changes where the directories are, so it must
+    // be enabled before bootstrapping resolves them
+    let opts = Opt::from_args();
+    if opts.portable {
+        config::enable_portable_mode()?;
+    }
+
     env_bootstrap::bootstrap();
 
     stats::Stats::init()?;
     let _saver = umask::UmaskSaver::new();
-
-    let opts = Opt::from_args();
-    config::common_init(
-        opts.config_file.as_ref(),
-        &opts.config_override,
-        opts.skip_config,
-    );
+    let (config_file, config_override, skip_config) = match opts.cmd.as_ref() {
+        Some(cmd) => {
+            let sub_config = cmd.config_overrides();
+            let (config_file, config_override) =
+                sub_config.merge_with(opts.config_file.as_ref(), &opts.config_override);
+            // A config file specified for the subcommand is loaded even
+            // if -n was used to skip the default config file
+            let skip_config = opts.skip_config && sub_config.config_file.is_none();
+            (config_file, config_override, skip_config)
+        }
+        None => (
+            opts.config_file.clone(),
+            opts.config_override.clone(),
+            opts.skip_config,
+        ),
+    };
+    config::common_init(config_file.as_ref(), &config_override, skip_config);
     let config = config::configuration();
+    crashreport::install(&config);
 
     match opts
         .cmd
@@ -472,6 +516,17 @@ fn run() -> anyhow::Result<()> {
     {
         SubCommand::Start(start) => {
             log::trace!("Using configuration: {:#?}\nopts: {:#?}", config, opts);
+            // Settings that were specified on the command line can't
+            // be applied to a gui that is already running
+            let custom_config = config_file.is_some() || !config_override.is_empty() || skip_config;
+            if config.single_instance
+                && !start.always_new_process
+                && !custom_config
+                && start.class.is_none()
+                && singleinstance::spawn_in_existing_gui(&start)?
+            {
+                return Ok(());
+            }
             run_terminal_gui(start)
         }
         SubCommand::Ssh(ssh) => run_ssh(ssh),
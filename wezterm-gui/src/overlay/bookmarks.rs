@@ -0,0 +1,247 @@
+//! Overlays that prompt for the name of a new bookmark, and that
+//! list the bookmarks of a pane so that its viewport can be scrolled
+//! back to one of them.
+use crate::overlay::positioned_text;
+use crate::termwindow::TermWindow;
+use anyhow::anyhow;
+use mux::pane::{Bookmark, PaneId};
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::lineedit::{LineEditor, NopLineEditorHost};
+use termwiz::surface::{Change, CursorVisibility};
+use termwiz::terminal::Terminal;
+use wezterm_term::StableRowIndex;
+
+/// Bookmarks `row` as `name` in the pane, or removes the bookmark
+/// if `row` is None
+fn set_bookmark(pane_id: PaneId, name: String, row: Option<StableRowIndex>) {
+    promise::spawn::spawn_into_main_thread(async move {
+        promise::spawn::spawn(async move {
+            let mux = Mux::get().unwrap();
+            let pane = mux
+                .get_pane(pane_id)
+                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+            if let Err(err) = pane.set_bookmark(name, row).await {
+                log::error!("failed to set bookmark: {:#}", err);
+            }
+            anyhow::Result::<()>::Ok(())
+        })
+        .detach();
+    })
+    .detach();
+}
+
+pub fn add_bookmark(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    row: StableRowIndex,
+) -> anyhow::Result<()> {
+    term.render(&[
+        Change::Title("Add Bookmark".to_string()),
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::Text(
+            "Enter a name for the bookmark, or press CTRL-C to cancel.  \
+             An existing bookmark with that name is replaced.\r\n"
+                .to_string(),
+        ),
+    ])?;
+
+    let name = {
+        let mut editor = LineEditor::new(&mut term);
+        editor.set_prompt("Bookmark: ");
+        let mut host = NopLineEditorHost::default();
+        editor.read_line(&mut host)?
+    };
+
+    if let Some(name) = name {
+        let name = name.trim().to_string();
+        if !name.is_empty() {
+            set_bookmark(pane_id, name, Some(row));
+        }
+    }
+
+    Ok(())
+}
+
+fn render(
+    bookmarks: &[Bookmark],
+    active_idx: usize,
+    top: usize,
+    physical_top: StableRowIndex,
+    term: &mut TermWizTerminal,
+) -> termwiz::Result<()> {
+    let size = term.get_screen_size()?;
+    let width = size.cols.saturating_sub(1);
+    let header = "Select a bookmark and press Enter to scroll to it, or Delete to remove it.  \
+                  Press Escape to cancel";
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+    changes.extend(positioned_text(0, header, width));
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+
+    if bookmarks.is_empty() {
+        changes.extend(positioned_text(
+            1,
+            "There are no bookmarks in this pane",
+            width,
+        ));
+    }
+
+    for (row, (idx, bookmark)) in bookmarks
+        .iter()
+        .enumerate()
+        .skip(top)
+        .take(size.rows.saturating_sub(1))
+        .enumerate()
+    {
+        if idx == active_idx {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+
+        let position = if bookmark.row >= physical_top {
+            "on screen".to_string()
+        } else {
+            format!("{} lines up", physical_top - bookmark.row)
+        };
+        let text = format!(" {}. {} ({})", idx + 1, bookmark.name, position);
+        changes.extend(positioned_text(row + 1, &text, width));
+
+        if idx == active_idx {
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+    }
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+fn jump_to_bookmark(pane_id: PaneId, row: StableRowIndex, window: ::window::Window) {
+    window.apply(move |term_window, _window| {
+        if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+            let mux = Mux::get().unwrap();
+            let pane = mux
+                .get_pane(pane_id)
+                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+            term_window.set_viewport(pane_id, Some(row), pane.get_dimensions());
+        }
+        Ok(())
+    });
+}
+
+pub fn bookmark_list(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    mut bookmarks: Vec<Bookmark>,
+    physical_top: StableRowIndex,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Bookmarks".to_string())])?;
+
+    let mut active_idx = 0;
+    let mut top = 0;
+
+    loop {
+        let page = term.get_screen_size()?.rows.saturating_sub(1).max(1);
+        if active_idx < top {
+            top = active_idx;
+        } else if active_idx >= top + page {
+            top = active_idx + 1 - page;
+        }
+        render(&bookmarks, active_idx, top, physical_top, &mut term)?;
+
+        let last = bookmarks.len().saturating_sub(1);
+
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })) => break,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('k'),
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })) => active_idx = active_idx.saturating_sub(1),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j'),
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })) => active_idx = (active_idx + 1).min(last),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            })) => active_idx = active_idx.saturating_sub(page),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            })) => active_idx = (active_idx + page).min(last),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Delete,
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('d'),
+                ..
+            })) => {
+                if active_idx < bookmarks.len() {
+                    let bookmark = bookmarks.remove(active_idx);
+                    set_bookmark(pane_id, bookmark.name, None);
+                    active_idx = active_idx.min(bookmarks.len().saturating_sub(1));
+                }
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                ..
+            })) => {
+                if c >= '1' && c <= '9' {
+                    let idx = c as usize - '1' as usize;
+                    if let Some(bookmark) = bookmarks.get(idx) {
+                        jump_to_bookmark(pane_id, bookmark.row, window);
+                        break;
+                    }
+                }
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            })) => {
+                if let Some(bookmark) = bookmarks.get(active_idx) {
+                    jump_to_bookmark(pane_id, bookmark.row, window);
+                }
+                break;
+            }
+            Some(InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            })) => {
+                let idx = top + y as usize;
+                if y > 0 && idx <= bookmarks.len() {
+                    active_idx = idx - 1;
+
+                    if mouse_buttons == MouseButtons::LEFT {
+                        jump_to_bookmark(pane_id, bookmarks[active_idx].row, window);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    Ok(())
+}
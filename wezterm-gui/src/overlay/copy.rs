@@ -1,5 +1,6 @@
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::TermWindow;
+use config::configuration;
 use config::keyassignment::ScrollbackEraseMode;
 use mux::domain::DomainId;
 use mux::pane::{Pane, PaneId};
@@ -12,7 +13,7 @@ use std::rc::Rc;
 use std::sync::Arc;
 use unicode_segmentation::*;
 use url::Url;
-use wezterm_term::color::ColorPalette;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use wezterm_term::{
     unicode_column_width, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex,
 };
@@ -491,7 +492,16 @@ impl Pane for CopyOverlay {
     }
 
     fn palette(&self) -> ColorPalette {
-        self.delegate.palette()
+        let mut palette = self.delegate.palette();
+        let config = configuration();
+        if let Some(fg) = config.resolved_palette.copy_mode_cursor_fg {
+            palette.cursor_fg = fg;
+        }
+        if let Some(bg) = config.resolved_palette.copy_mode_cursor_bg {
+            palette.cursor_bg = bg;
+            palette.cursor_border = bg;
+        }
+        palette
     }
 
     fn domain_id(&self) -> DomainId {
@@ -519,6 +529,18 @@ impl Pane for CopyOverlay {
         self.delegate.get_current_working_dir()
     }
 
+    fn get_background_tint(&self) -> Option<RgbColor> {
+        self.delegate.get_background_tint()
+    }
+
+    fn is_ime_enabled(&self) -> bool {
+        self.delegate.is_ime_enabled()
+    }
+
+    fn set_ime_enabled(&self, enabled: bool) {
+        self.delegate.set_ime_enabled(enabled)
+    }
+
     fn get_cursor_position(&self) -> StableCursorPosition {
         self.render.borrow_mut().cursor
     }
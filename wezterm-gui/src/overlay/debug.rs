@@ -0,0 +1,149 @@
+use env_bootstrap::ringlog::{get_entries, get_filter, Entry};
+use log::Level;
+use mux::termwiztermtab::TermWizTerminal;
+use std::time::Duration;
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// How often to check for newly logged records
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+fn level_color(level: Level) -> ColorAttribute {
+    match level {
+        Level::Error => AnsiColor::Red.into(),
+        Level::Warn => AnsiColor::Yellow.into(),
+        Level::Info => ColorAttribute::Default,
+        Level::Debug | Level::Trace => AnsiColor::Grey.into(),
+    }
+}
+
+fn render(
+    entries: &[Entry],
+    scroll: usize,
+    rows: usize,
+    cols: usize,
+    term: &mut TermWizTerminal,
+) -> termwiz::Result<()> {
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        AttributeChange::Reverse(true).into(),
+        Change::Text(
+            format!(
+                "Log filter: {}.  Up/Down/PageUp/PageDown to scroll, End to follow, Escape to close",
+                get_filter()
+            )
+            .chars()
+            .take(cols)
+            .collect(),
+        ),
+        Change::AllAttributes(CellAttributes::default()),
+    ];
+
+    let end = entries.len() - scroll;
+    let start = end.saturating_sub(rows);
+    for (y, entry) in entries[start..end].iter().enumerate() {
+        let line = format!(
+            "{} {:<5} {} > {}",
+            entry.then.format("%H:%M:%S%.3f"),
+            entry.level,
+            entry.target,
+            entry.msg.replace('\n', " ")
+        );
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(y + 1),
+        });
+        changes.push(AttributeChange::Foreground(level_color(entry.level)).into());
+        changes.push(Change::Text(line.chars().take(cols).collect()));
+    }
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+/// Shows the records that have been retained by the logger,
+/// updating as new records are logged.
+pub fn debug_overlay(mut term: TermWizTerminal) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Debug".to_string())])?;
+
+    // The number of records, counting back from the most recent,
+    // that are hidden below the bottom of the screen.
+    // Zero means that new records are shown as they arrive.
+    let mut scroll = 0usize;
+    let mut last_rendered = None;
+
+    loop {
+        let size = term.get_screen_size()?;
+        // Leave room for the header
+        let rows = size.rows.saturating_sub(1);
+        let entries = get_entries();
+        scroll = scroll.min(entries.len().saturating_sub(rows));
+
+        let state = (
+            entries.len(),
+            entries.last().map(|entry| entry.then),
+            scroll,
+            size.rows,
+            size.cols,
+        );
+        if last_rendered.as_ref() != Some(&state) {
+            render(&entries, scroll, rows, size.cols, &mut term)?;
+            last_rendered = Some(state);
+        }
+
+        // An error means that the overlay has been closed
+        let event = match term.poll_input(Some(REFRESH_INTERVAL)) {
+            Ok(event) => event,
+            Err(_) => break,
+        };
+        match event {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q'),
+                ..
+            })) => break,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('k'),
+                ..
+            })) => scroll += 1,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j'),
+                ..
+            })) => scroll = scroll.saturating_sub(1),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            })) => scroll += rows,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            })) => scroll = scroll.saturating_sub(rows),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::End, ..
+            })) => scroll = 0,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+//! The key binding help overlay lists the key assignments from the
+//! live InputMap, grouped by category, and allows searching them.
+use super::positioned_text;
+use config::keyassignment::KeyBindingDescription;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{AttributeChange, CellAttributes, Intensity, Underline};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, CursorVisibility};
+use termwiz::terminal::Terminal;
+
+enum HelpLine<'a> {
+    Category(&'static str),
+    Binding(&'a KeyBindingDescription),
+}
+
+/// Returns the lines to display for the bindings that match `query`,
+/// with a heading at the start of each category
+fn filter_lines<'a>(bindings: &'a [KeyBindingDescription], query: &str) -> Vec<HelpLine<'a>> {
+    let query = query.to_lowercase();
+    let mut lines = vec![];
+    let mut category = None;
+    for binding in bindings {
+        let matches = query.is_empty()
+            || binding.category.to_lowercase().contains(&query)
+            || binding.key.to_lowercase().contains(&query)
+            || binding.action.to_lowercase().contains(&query)
+            || binding
+                .condition
+                .as_ref()
+                .map(|c| c.to_lowercase().contains(&query))
+                .unwrap_or(false);
+        if !matches {
+            continue;
+        }
+        if category != Some(binding.category) {
+            category = Some(binding.category);
+            lines.push(HelpLine::Category(binding.category));
+        }
+        lines.push(HelpLine::Binding(binding));
+    }
+    lines
+}
+
+fn render(
+    lines: &[HelpLine],
+    query: &str,
+    top: usize,
+    key_width: usize,
+    term: &mut TermWizTerminal,
+) -> termwiz::Result<()> {
+    let size = term.get_screen_size()?;
+    let width = size.cols.saturating_sub(1);
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+
+    let header = [
+        "Type to search, Up/Down/PageUp/PageDown to scroll, Escape to close".to_string(),
+        format!("Search: {}", query),
+    ];
+    for (y, text) in header.iter().enumerate() {
+        changes.extend(positioned_text(y, text, width));
+    }
+
+    if lines.is_empty() {
+        changes.extend(positioned_text(2, "No matching key bindings", width));
+    }
+
+    for (idx, line) in lines
+        .iter()
+        .skip(top)
+        .take(size.rows.saturating_sub(2))
+        .enumerate()
+    {
+        let text = match line {
+            HelpLine::Category(category) => {
+                changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+                changes.push(AttributeChange::Underline(Underline::Single).into());
+                category.to_string()
+            }
+            HelpLine::Binding(binding) => {
+                let mut text = format!(
+                    "  {:width$}  {}",
+                    binding.key,
+                    binding.action,
+                    width = key_width
+                );
+                if let Some(condition) = &binding.condition {
+                    text.push_str(&format!("  ({})", condition));
+                }
+                text
+            }
+        };
+        changes.extend(positioned_text(idx + 2, &text, width));
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+    }
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+pub fn keybinding_help(
+    mut term: TermWizTerminal,
+    bindings: Vec<KeyBindingDescription>,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Key Bindings".to_string())])?;
+
+    let key_width = bindings.iter().map(|b| b.key.len()).max().unwrap_or(0);
+    let mut query = String::new();
+    let mut top = 0;
+
+    loop {
+        let lines = filter_lines(&bindings, &query);
+        let page = term.get_screen_size()?.rows.saturating_sub(2).max(1);
+        let max_top = lines.len().saturating_sub(page);
+        top = top.min(max_top);
+        render(&lines, &query, top, key_width, &mut term)?;
+
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })) => break,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })) => top = top.saturating_sub(1),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })) => top = (top + 1).min(max_top),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            })) => top = top.saturating_sub(page),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            })) => top = (top + page).min(max_top),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            })) => {
+                query.pop();
+                top = 0;
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            })) => {
+                query.push(c);
+                top = 0;
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,390 @@
+//! The link hint overlay labels the hyperlinks in the viewport of a pane,
+//! both explicit links and those produced by hyperlink_rules, so that one
+//! of them can be opened or copied by typing its label.
+use crate::termwindow::TermWindow;
+use config::configuration;
+use config::keyassignment::{LinkHintAction, ScrollbackEraseMode};
+use mux::domain::DomainId;
+use mux::pane::{Pane, PaneId};
+use mux::renderable::*;
+use mux::Mux;
+use portable_pty::PtySize;
+use rangeset::RangeSet;
+use std::cell::{RefCell, RefMut};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use termwiz::cell::{CellAttributes, Intensity};
+use termwiz::color::AnsiColor;
+use termwiz::hyperlink::Hyperlink;
+use url::Url;
+use wezterm_term::color::{ColorPalette, RgbColor};
+use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+
+/// The characters used to build the labels, ordered so that
+/// the keys that are easiest to reach are used first
+const LABEL_ALPHABET: &str = "asdfjklghqweruiopzxcvbnmty";
+
+pub struct LinkHintOverlay {
+    renderer: RefCell<LinkHintRenderable>,
+    delegate: Rc<dyn Pane>,
+}
+
+#[derive(Debug)]
+struct LinkHint {
+    label: String,
+    row: StableRowIndex,
+    col: usize,
+    uri: String,
+}
+
+struct LinkHintRenderable {
+    delegate: Rc<dyn Pane>,
+    action: LinkHintAction,
+    hints: Vec<LinkHint>,
+    /// The portion of a label that the user has typed so far
+    typed: String,
+    dirty: RangeSet<StableRowIndex>,
+
+    /// We use this to cancel ourselves later
+    window: ::window::Window,
+}
+
+impl LinkHintOverlay {
+    pub fn with_pane(
+        term_window: &TermWindow,
+        pane: &Rc<dyn Pane>,
+        action: LinkHintAction,
+    ) -> Rc<dyn Pane> {
+        let dims = pane.get_dimensions();
+        let top = term_window
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let (first_row, lines) = pane.get_lines_with_hyperlinks_applied(
+            top..top + dims.viewport_rows as StableRowIndex,
+            &term_window.config.hyperlink_rules,
+        );
+
+        let mut hints = find_links(first_row, &lines);
+        for (hint, label) in hints.iter_mut().zip(compute_labels(hints.len())) {
+            hint.label = label;
+        }
+
+        let mut dirty = RangeSet::default();
+        for hint in &hints {
+            dirty.add(hint.row);
+        }
+
+        let window = term_window.window.clone().unwrap();
+        let renderer = LinkHintRenderable {
+            delegate: Rc::clone(pane),
+            action,
+            hints,
+            typed: String::new(),
+            dirty,
+            window,
+        };
+
+        if renderer.hints.is_empty() {
+            log::warn!("there are no links in the viewport");
+            renderer.close();
+        }
+
+        Rc::new(LinkHintOverlay {
+            renderer: RefCell::new(renderer),
+            delegate: Rc::clone(pane),
+        })
+    }
+}
+
+/// Returns a hint for the start of each distinct link in `lines`.
+/// A link that wraps onto the next line is only reported once.
+fn find_links(first_row: StableRowIndex, lines: &[Line]) -> Vec<LinkHint> {
+    let mut hints = vec![];
+    let mut current: Option<Arc<Hyperlink>> = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        for (col, cell) in line.cells().iter().enumerate() {
+            match cell.attrs().hyperlink() {
+                Some(link) => {
+                    let is_new = match current.as_ref() {
+                        Some(current) => **current != **link,
+                        None => true,
+                    };
+                    if is_new {
+                        hints.push(LinkHint {
+                            label: String::new(),
+                            row: first_row + idx as StableRowIndex,
+                            col,
+                            uri: link.uri().to_string(),
+                        });
+                        current = Some(Arc::clone(link));
+                    }
+                }
+                None => {
+                    current = None;
+                }
+            }
+        }
+        if !line.last_cell_was_wrapped() {
+            current = None;
+        }
+    }
+
+    hints
+}
+
+/// Produces `count` labels of equal length, so that
+/// no label is a prefix of another
+fn compute_labels(count: usize) -> Vec<String> {
+    let alphabet: Vec<char> = LABEL_ALPHABET.chars().collect();
+    let mut len = 1;
+    let mut capacity = alphabet.len();
+    while capacity < count {
+        len += 1;
+        capacity *= alphabet.len();
+    }
+
+    (0..count)
+        .map(|mut n| {
+            let mut label = String::new();
+            for _ in 0..len {
+                label.push(alphabet[n % alphabet.len()]);
+                n /= alphabet.len();
+            }
+            label
+        })
+        .collect()
+}
+
+impl LinkHintRenderable {
+    fn close(&self) {
+        TermWindow::schedule_cancel_overlay_for_pane(self.window.clone(), self.delegate.pane_id());
+    }
+
+    fn mark_hints_dirty(&mut self) {
+        for hint in &self.hints {
+            self.dirty.add(hint.row);
+        }
+    }
+
+    fn type_char(&mut self, c: char) {
+        self.typed.push(c.to_ascii_lowercase());
+        let candidates: Vec<&LinkHint> = self
+            .hints
+            .iter()
+            .filter(|hint| hint.label.starts_with(&self.typed))
+            .collect();
+
+        match candidates.as_slice() {
+            [] => {
+                // Not a valid label; ignore the key
+                self.typed.pop();
+            }
+            [hint] if hint.label == self.typed => {
+                self.activate(hint.uri.clone());
+                self.close();
+            }
+            _ => {}
+        }
+        self.mark_hints_dirty();
+    }
+
+    fn activate(&self, uri: String) {
+        let action = self.action;
+        let pane_id = self.delegate.pane_id();
+        self.window.apply(move |term_window, _window| {
+            if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                match action {
+                    LinkHintAction::Open => {
+                        let mux = Mux::get().expect("to be called on main thread");
+                        if let Some(pane) = mux.get_pane(pane_id) {
+                            term_window.open_link(&pane, uri.clone());
+                        }
+                    }
+                    LinkHintAction::CopyTo(dest) => {
+                        term_window.copy_to_clipboard(dest, uri.clone());
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+impl Pane for LinkHintOverlay {
+    fn pane_id(&self) -> PaneId {
+        self.delegate.pane_id()
+    }
+
+    fn get_title(&self) -> String {
+        format!("Link hints: {}", self.delegate.get_title())
+    }
+
+    fn send_paste(&self, _text: &str) -> anyhow::Result<()> {
+        // Ignore paste while the hints are shown
+        Ok(())
+    }
+
+    fn reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>> {
+        panic!("do not call reader on LinkHintOverlay instance");
+    }
+
+    fn writer(&self) -> RefMut<dyn std::io::Write> {
+        self.delegate.writer()
+    }
+
+    fn resize(&self, size: PtySize) -> anyhow::Result<()> {
+        // The link positions are no longer valid, so
+        // we need to go away
+        self.renderer.borrow().close();
+        self.delegate.resize(size)
+    }
+
+    fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE)
+            | (KeyCode::Char('c'), KeyModifiers::CTRL)
+            | (KeyCode::Char('g'), KeyModifiers::CTRL) => self.renderer.borrow().close(),
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                let mut r = self.renderer.borrow_mut();
+                r.typed.pop();
+                r.mark_hints_dirty();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.renderer.borrow_mut().type_char(c);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
+        self.delegate.mouse_event(event)
+    }
+
+    fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
+        self.delegate.perform_actions(actions)
+    }
+
+    fn is_dead(&self) -> bool {
+        self.delegate.is_dead()
+    }
+
+    fn palette(&self) -> ColorPalette {
+        self.delegate.palette()
+    }
+
+    fn domain_id(&self) -> DomainId {
+        self.delegate.domain_id()
+    }
+
+    fn erase_scrollback(&self, erase_mode: ScrollbackEraseMode) {
+        self.delegate.erase_scrollback(erase_mode)
+    }
+
+    fn is_mouse_grabbed(&self) -> bool {
+        false
+    }
+
+    fn is_alt_screen_active(&self) -> bool {
+        self.delegate.is_alt_screen_active()
+    }
+
+    fn set_clipboard(&self, clipboard: &Arc<dyn Clipboard>) {
+        self.delegate.set_clipboard(clipboard)
+    }
+
+    fn get_current_working_dir(&self) -> Option<Url> {
+        self.delegate.get_current_working_dir()
+    }
+
+    fn get_background_tint(&self) -> Option<RgbColor> {
+        self.delegate.get_background_tint()
+    }
+
+    fn is_ime_enabled(&self) -> bool {
+        self.delegate.is_ime_enabled()
+    }
+
+    fn set_ime_enabled(&self, enabled: bool) {
+        self.delegate.set_ime_enabled(enabled)
+    }
+
+    fn get_cursor_position(&self) -> StableCursorPosition {
+        let mut cursor = self.delegate.get_cursor_position();
+        cursor.visibility = termwiz::surface::CursorVisibility::Hidden;
+        cursor
+    }
+
+    fn get_dirty_lines(&self, lines: Range<StableRowIndex>) -> RangeSet<StableRowIndex> {
+        let mut dirty = self.delegate.get_dirty_lines(lines.clone());
+        dirty.add_set(&self.renderer.borrow().dirty);
+        dirty.intersection_with_range(lines)
+    }
+
+    fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
+        let mut renderer = self.renderer.borrow_mut();
+        let config = configuration();
+        let (top, mut lines) = self
+            .delegate
+            .get_lines_with_hyperlinks_applied(lines, &config.hyperlink_rules);
+
+        let label_attrs = CellAttributes::default()
+            .set_foreground(AnsiColor::Black)
+            .set_background(AnsiColor::Yellow)
+            .set_intensity(Intensity::Bold)
+            .clone();
+        let typed_attrs = CellAttributes::default()
+            .set_foreground(AnsiColor::Black)
+            .set_background(AnsiColor::Olive)
+            .clone();
+
+        for (idx, line) in lines.iter_mut().enumerate() {
+            let stable_idx = idx as StableRowIndex + top;
+            renderer.dirty.remove(stable_idx);
+
+            for hint in &renderer.hints {
+                if hint.row != stable_idx || !hint.label.starts_with(&renderer.typed) {
+                    continue;
+                }
+                let typed_len = renderer.typed.len();
+                line.overlay_text_with_attribute(
+                    hint.col,
+                    &hint.label[..typed_len],
+                    typed_attrs.clone(),
+                );
+                line.overlay_text_with_attribute(
+                    hint.col + typed_len,
+                    &hint.label[typed_len..],
+                    label_attrs.clone(),
+                );
+            }
+        }
+
+        (top, lines)
+    }
+
+    fn get_dimensions(&self) -> RenderableDimensions {
+        self.delegate.get_dimensions()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels_are_not_prefixes() {
+        assert_eq!(compute_labels(3), vec!["a", "s", "d"]);
+
+        let labels = compute_labels(LABEL_ALPHABET.len() + 1);
+        assert!(labels.iter().all(|l| l.len() == 2));
+        for a in &labels {
+            assert_eq!(
+                labels.iter().filter(|b| b.starts_with(a.as_str())).count(),
+                1
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! The lua overlay displays lines of text that are produced by a lua
+//! callback, and passes key presses back to that callback, so that
+//! custom pickers and menus can be built from lua.
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
+use crate::tabbar::parse_status_text;
+use anyhow::anyhow;
+use luahelper::impl_lua_conversion;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use termwiz::cell::CellAttributes;
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// The key press that is passed to the lua callback
+#[derive(Debug, Serialize, Deserialize)]
+struct OverlayKeyEvent {
+    key: String,
+    mods: String,
+}
+impl_lua_conversion!(OverlayKeyEvent);
+
+impl From<KeyEvent> for OverlayKeyEvent {
+    fn from(event: KeyEvent) -> Self {
+        let key = match event.key {
+            KeyCode::Char(c) => c.to_string(),
+            key => format!("{:?}", key),
+        };
+        Self {
+            key,
+            mods: format!("{:?}", event.modifiers),
+        }
+    }
+}
+
+/// Calls the lua callback, which must run on the main thread,
+/// and waits for it to produce the lines to display.
+/// Returns None if the callback wants the overlay to close.
+fn call_callback(
+    window: &GuiWin,
+    pane: &PaneObject,
+    callback: &Arc<mlua::RegistryKey>,
+    event: Option<KeyEvent>,
+) -> anyhow::Result<Option<Vec<String>>> {
+    async fn call(
+        lua: Option<Rc<mlua::Lua>>,
+        window: GuiWin,
+        pane: PaneObject,
+        callback: Arc<mlua::RegistryKey>,
+        event: Option<OverlayKeyEvent>,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let lua = lua.ok_or_else(|| anyhow!("there is no lua configuration"))?;
+        let func: mlua::Function = lua.registry_value(&callback)?;
+        let lines = func
+            .call_async::<_, Option<Vec<String>>>((window, pane, event))
+            .await?;
+        Ok(lines)
+    }
+
+    let (tx, rx) = channel();
+    let window = window.clone();
+    let pane = pane.clone();
+    let callback = Arc::clone(callback);
+    let event = event.map(OverlayKeyEvent::from);
+    promise::spawn::spawn_into_main_thread(async move {
+        promise::spawn::spawn(async move {
+            let result = config::with_lua_config_on_main_thread(move |lua| {
+                call(lua, window, pane, callback, event)
+            })
+            .await;
+            tx.send(result).ok();
+        })
+        .detach();
+    })
+    .detach();
+
+    rx.recv()?
+}
+
+fn render(lines: &[String], term: &mut TermWizTerminal) -> termwiz::Result<()> {
+    let size = term.get_screen_size()?;
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+
+    for (y, line) in lines.iter().take(size.rows).enumerate() {
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(y),
+        });
+        // Lines may be styled using the escape sequences
+        // produced by wezterm.format
+        for cell in parse_status_text(line, CellAttributes::default())
+            .into_iter()
+            .take(size.cols)
+        {
+            changes.push(Change::AllAttributes(cell.attrs().clone()));
+            changes.push(Change::Text(cell.str().to_string()));
+        }
+    }
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+fn run_lua_overlay(
+    mut term: TermWizTerminal,
+    window: GuiWin,
+    pane: PaneObject,
+    callback: Arc<mlua::RegistryKey>,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    // The callback is passed nil rather than a key press when
+    // the overlay needs to be drawn from scratch
+    let mut event = None;
+    loop {
+        match call_callback(&window, &pane, &callback, event.take())? {
+            Some(lines) => render(&lines, &mut term)?,
+            None => break,
+        }
+
+        loop {
+            match term.poll_input(None)? {
+                Some(InputEvent::Key(key)) => {
+                    event = Some(key);
+                    break;
+                }
+                Some(InputEvent::Resized { .. }) => break,
+                Some(_) => {}
+                None => return Ok(()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn lua_overlay(
+    pane_id: PaneId,
+    term: TermWizTerminal,
+    window: GuiWin,
+    pane: PaneObject,
+    callback: Arc<mlua::RegistryKey>,
+) -> anyhow::Result<()> {
+    let result = run_lua_overlay(term, window, pane, callback);
+    if let Err(err) = &result {
+        log::error!("lua overlay for pane {}: {:#}", pane_id, err);
+    }
+    result
+}
@@ -5,20 +5,35 @@ use mux::termwiztermtab::{allocate, TermWizTerminal};
 use portable_pty::PtySize;
 use std::pin::Pin;
 use std::rc::Rc;
+use termwiz::cell::grapheme_column_width;
+use termwiz::surface::{Change, Position};
+use unicode_segmentation::UnicodeSegmentation;
 
+mod bookmarks;
 mod confirm_close_pane;
 mod copy;
+mod debug;
+mod keyhelp;
 mod launcher;
+mod linkhint;
+mod luaoverlay;
 mod search;
+mod searchresults;
 mod tabnavigator;
 
+pub use bookmarks::{add_bookmark, bookmark_list};
 pub use confirm_close_pane::confirm_close_pane;
 pub use confirm_close_pane::confirm_close_tab;
 pub use confirm_close_pane::confirm_close_window;
 pub use confirm_close_pane::confirm_quit_program;
 pub use copy::CopyOverlay;
+pub use debug::debug_overlay;
+pub use keyhelp::keybinding_help;
 pub use launcher::launcher;
+pub use linkhint::LinkHintOverlay;
+pub use luaoverlay::lua_overlay;
 pub use search::SearchOverlay;
+pub use searchresults::{search_results, SearchHit};
 pub use tabnavigator::tab_navigator;
 
 pub fn start_overlay<T, F>(
@@ -83,3 +98,56 @@ where
 
     (tw_tab, Box::pin(future))
 }
+
+/// Returns the leading portion of `text` that fits within `width` cells
+fn truncate_to_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (idx, g) in text.grapheme_indices(true) {
+        used += grapheme_column_width(g);
+        if used > width {
+            return &text[..idx];
+        }
+    }
+    text
+}
+
+/// Returns the changes that display `text` at the start of row `y`,
+/// truncated to `width` cells.  The list overlays position each row
+/// explicitly, so that text that is too wide can't wrap and the last
+/// row doesn't scroll the screen.
+fn positioned_text(y: usize, text: &str, width: usize) -> [Change; 2] {
+    [
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(y),
+        },
+        Change::Text(truncate_to_width(text, width).to_string()),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn truncate_ascii() {
+        assert_eq!(truncate_to_width("hello", 10), "hello");
+        assert_eq!(truncate_to_width("hello", 3), "hel");
+        assert_eq!(truncate_to_width("hello", 0), "");
+    }
+
+    #[test]
+    fn truncate_wide() {
+        // Each of these occupies two cells, so a partial one is dropped
+        assert_eq!(truncate_to_width("日本語", 4), "日本");
+        assert_eq!(truncate_to_width("日本語", 5), "日本");
+        assert_eq!(truncate_to_width("a😀b", 2), "a");
+        assert_eq!(truncate_to_width("a😀b", 3), "a😀");
+    }
+
+    #[test]
+    fn truncate_keeps_combining_marks() {
+        let text = "e\u{301}e\u{301}e\u{301}";
+        assert_eq!(truncate_to_width(text, 2), "e\u{301}e\u{301}");
+    }
+}
@@ -1,9 +1,14 @@
+use crate::overlay::searchresults::SearchHit;
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::TermWindow;
+use anyhow::anyhow;
+use config::configuration;
 use config::keyassignment::ScrollbackEraseMode;
 use mux::domain::DomainId;
 use mux::pane::{Pane, PaneId, Pattern, SearchResult};
 use mux::renderable::*;
+use mux::tab::Tab;
+use mux::Mux;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
@@ -12,9 +17,9 @@ use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
 use termwiz::cell::{Cell, CellAttributes};
-use termwiz::color::AnsiColor;
+use termwiz::color::{AnsiColor, ColorAttribute};
 use url::Url;
-use wezterm_term::color::ColorPalette;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
 use window::WindowOps;
 
@@ -23,6 +28,15 @@ pub struct SearchOverlay {
     delegate: Rc<dyn Pane>,
 }
 
+/// Which panes are searched when listing the matches in the
+/// search results picker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchScope {
+    Pane,
+    Tab,
+    Window,
+}
+
 #[derive(Debug)]
 struct MatchResult {
     range: Range<usize>,
@@ -33,6 +47,7 @@ struct SearchRenderable {
     delegate: Rc<dyn Pane>,
     /// The text that the user entered
     pattern: Pattern,
+    scope: SearchScope,
     /// The most recently queried set of matches
     results: Vec<SearchResult>,
     by_line: HashMap<StableRowIndex, Vec<MatchResult>>,
@@ -62,6 +77,7 @@ impl SearchOverlay {
         let mut renderer = SearchRenderable {
             delegate: Rc::clone(pane),
             pattern,
+            scope: SearchScope::Pane,
             results: vec![],
             by_line: HashMap::new(),
             dirty_results: RangeSet::default(),
@@ -129,6 +145,12 @@ impl Pane for SearchOverlay {
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
         match (key, mods) {
             (KeyCode::Escape, KeyModifiers::NONE) => self.renderer.borrow().close(),
+            (KeyCode::Enter, KeyModifiers::NONE)
+                if self.renderer.borrow().scope != SearchScope::Pane =>
+            {
+                // List the matches from all of the panes in scope
+                self.renderer.borrow().show_results_picker();
+            }
             (KeyCode::UpArrow, KeyModifiers::NONE)
             | (KeyCode::Enter, KeyModifiers::NONE)
             | (KeyCode::Char('p'), KeyModifiers::CTRL) => {
@@ -201,6 +223,18 @@ impl Pane for SearchOverlay {
                 r.pattern = pattern;
                 r.update_search();
             }
+            (KeyCode::Char('s'), KeyModifiers::CTRL) => {
+                // CTRL-s cycles through the pane, tab and window scopes
+                let mut r = self.renderer.borrow_mut();
+                r.scope = match r.scope {
+                    SearchScope::Pane => SearchScope::Tab,
+                    SearchScope::Tab => SearchScope::Window,
+                    SearchScope::Window => SearchScope::Pane,
+                };
+                if let Some(idx) = r.last_bar_pos {
+                    r.dirty_results.add(idx);
+                }
+            }
             (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
                 // Type to add to the pattern
                 let mut r = self.renderer.borrow_mut();
@@ -264,6 +298,18 @@ impl Pane for SearchOverlay {
         self.delegate.get_current_working_dir()
     }
 
+    fn get_background_tint(&self) -> Option<RgbColor> {
+        self.delegate.get_background_tint()
+    }
+
+    fn is_ime_enabled(&self) -> bool {
+        self.delegate.is_ime_enabled()
+    }
+
+    fn set_ime_enabled(&self, enabled: bool) {
+        self.delegate.set_ime_enabled(enabled)
+    }
+
     fn get_cursor_position(&self) -> StableCursorPosition {
         // move to the search box
         let renderer = self.renderer.borrow();
@@ -288,6 +334,18 @@ impl Pane for SearchOverlay {
 
         let (top, mut lines) = self.delegate.get_lines(lines);
 
+        let config = configuration();
+        let colors = &config.resolved_palette;
+        let color = |color: Option<termwiz::color::RgbColor>, default: AnsiColor| {
+            color
+                .map(ColorAttribute::TrueColorWithDefaultFallback)
+                .unwrap_or_else(|| default.into())
+        };
+        let match_fg = color(colors.search_match_fg, AnsiColor::Black);
+        let match_bg = color(colors.search_match_bg, AnsiColor::Fuschia);
+        let active_match_fg = color(colors.search_active_match_fg, AnsiColor::Black);
+        let active_match_bg = color(colors.search_active_match_bg, AnsiColor::Yellow);
+
         // Process the lines; for the search row we want to render instead
         // the search UI.
         // For rows with search results, we want to highlight the matching ranges
@@ -304,14 +362,20 @@ impl Pane for SearchOverlay {
                     Pattern::CaseInSensitiveString(_) => "ignore-case",
                     Pattern::Regex(_) => "regex",
                 };
+                let scope = match renderer.scope {
+                    SearchScope::Pane => "",
+                    SearchScope::Tab => ", tab: Enter to list matches",
+                    SearchScope::Window => ", window: Enter to list matches",
+                };
                 line.overlay_text_with_attribute(
                     0,
                     &format!(
-                        "Search: {} ({}/{} matches. {})",
+                        "Search: {} ({}/{} matches. {}{})",
                         *renderer.pattern,
                         renderer.result_pos.map(|x| x + 1).unwrap_or(0),
                         renderer.results.len(),
-                        mode
+                        mode,
+                        scope
                     ),
                     rev,
                 );
@@ -319,20 +383,18 @@ impl Pane for SearchOverlay {
             } else if let Some(matches) = renderer.by_line.get(&stable_idx) {
                 for m in matches {
                     // highlight
+                    let (fg, bg) = if Some(m.result_index) == renderer.result_pos {
+                        (active_match_fg, active_match_bg)
+                    } else {
+                        (match_fg, match_bg)
+                    };
                     for cell_idx in m.range.clone() {
                         if let Some(cell) = line.cells_mut_for_attr_changes_only().get_mut(cell_idx)
                         {
-                            if Some(m.result_index) == renderer.result_pos {
-                                cell.attrs_mut()
-                                    .set_background(AnsiColor::Yellow)
-                                    .set_foreground(AnsiColor::Black)
-                                    .set_reverse(false);
-                            } else {
-                                cell.attrs_mut()
-                                    .set_background(AnsiColor::Fuschia)
-                                    .set_foreground(AnsiColor::Black)
-                                    .set_reverse(false);
-                            }
+                            cell.attrs_mut()
+                                .set_background(bg)
+                                .set_foreground(fg)
+                                .set_reverse(false);
                         }
                     }
                 }
@@ -489,25 +551,118 @@ impl SearchRenderable {
         let pane_id = self.delegate.pane_id();
         self.window.apply(move |term_window, _window| {
             if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
-                let mut selection = term_window.selection(pane_id);
-                let start = SelectionCoordinate {
-                    x: result.start_x,
-                    y: result.start_y,
-                };
-                selection.start = Some(start);
-                selection.range = Some(SelectionRange {
-                    start,
-                    end: SelectionCoordinate {
-                        // inclusive range for selection, but the result
-                        // range is exclusive
-                        x: result.end_x.saturating_sub(1),
-                        y: result.end_y,
-                    },
-                });
+                select_search_result(term_window, pane_id, &result);
             }
             Ok(())
         });
 
         self.set_viewport(Some(result.start_y));
     }
+
+    /// Searches each of the panes in the current scope and replaces
+    /// this overlay with a picker that lists the matches
+    fn show_results_picker(&self) {
+        if self.pattern.is_empty() {
+            return;
+        }
+        let pane_id = self.delegate.pane_id();
+        let scope = self.scope;
+        let pattern = self.pattern.clone();
+        let window = self.window.clone();
+        promise::spawn::spawn(async move {
+            match search_scope(pane_id, scope, pattern).await {
+                Ok(hits) => {
+                    let mut hits = Some(hits);
+                    window.apply(move |term_window, _window| {
+                        if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                            term_window.show_search_results(pane_id, hits.take().unwrap());
+                        }
+                        Ok(())
+                    });
+                }
+                Err(err) => log::error!("Failed to search the panes: {:#}", err),
+            }
+        })
+        .detach();
+    }
+}
+
+/// Searches each of the panes in `scope`, relative to `pane_id`,
+/// for `pattern`
+async fn search_scope(
+    pane_id: PaneId,
+    scope: SearchScope,
+    pattern: Pattern,
+) -> anyhow::Result<Vec<SearchHit>> {
+    let mux = Mux::get().unwrap();
+    let (_domain_id, mux_window_id, tab_id) = mux
+        .resolve_pane_id(pane_id)
+        .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+    let tabs: Vec<Rc<Tab>> = match scope {
+        SearchScope::Window => mux
+            .get_window(mux_window_id)
+            .ok_or_else(|| anyhow!("no such window"))?
+            .iter()
+            .cloned()
+            .collect(),
+        SearchScope::Pane | SearchScope::Tab => {
+            vec![mux.get_tab(tab_id).ok_or_else(|| anyhow!("no such tab"))?]
+        }
+    };
+
+    let mut hits = vec![];
+    for tab in tabs {
+        for pos in tab.iter_panes() {
+            let pane = pos.pane;
+            let mut results = pane.search(pattern.clone()).await?;
+            results.sort();
+            let title = pane.get_title();
+            for result in results {
+                let (_top, lines) = pane.get_lines(result.start_y..result.start_y + 1);
+                let (text, matched) = match lines.get(0) {
+                    Some(line) => {
+                        let end_x = if result.end_y == result.start_y {
+                            result.end_x
+                        } else {
+                            line.cells().len()
+                        };
+                        (line.as_str(), line.columns_as_str(result.start_x..end_x))
+                    }
+                    None => (String::new(), String::new()),
+                };
+                hits.push(SearchHit {
+                    tab_id: tab.tab_id(),
+                    pane_id: pane.pane_id(),
+                    title: title.clone(),
+                    result,
+                    text,
+                    matched,
+                });
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Selects the text of a search result in the specified pane
+pub(crate) fn select_search_result(
+    term_window: &mut TermWindow,
+    pane_id: PaneId,
+    result: &SearchResult,
+) {
+    let mut selection = term_window.selection(pane_id);
+    let start = SelectionCoordinate {
+        x: result.start_x,
+        y: result.start_y,
+    };
+    selection.start = Some(start);
+    selection.range = Some(SelectionRange {
+        start,
+        end: SelectionCoordinate {
+            // inclusive range for selection, but the result
+            // range is exclusive
+            x: result.end_x.saturating_sub(1),
+            y: result.end_y,
+        },
+    });
 }
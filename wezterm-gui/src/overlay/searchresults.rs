@@ -0,0 +1,253 @@
+//! The search results picker lists the matches that the search overlay
+//! found across the panes of a tab or window, and jumps to the pane
+//! and scroll position of the selected match, or opens the file
+//! location that it refers to in an editor.
+use crate::overlay::positioned_text;
+use crate::overlay::search::select_search_result;
+use crate::termwindow::TermWindow;
+use anyhow::anyhow;
+use mux::pane::{PaneId, SearchResult};
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use regex::Regex;
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility};
+use termwiz::terminal::Terminal;
+
+/// A match found in one of the panes covered by the search scope
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub tab_id: TabId,
+    pub pane_id: PaneId,
+    pub title: String,
+    pub result: SearchResult,
+    /// The text of the line on which the match starts
+    pub text: String,
+    /// The text that matched, up to the end of the first line
+    pub matched: String,
+}
+
+/// Returns the path and line number of the file location, as recognized
+/// by the `path` and `line` captures of `pattern`, in the matched text or
+/// failing that in the line that contains it
+fn editor_location(pattern: &Regex, hit: &SearchHit) -> Option<(String, usize)> {
+    [&hit.matched, &hit.text].iter().find_map(|text| {
+        let captures = pattern.captures(text)?;
+        let path = captures.name("path")?.as_str().to_string();
+        let line = captures.name("line")?.as_str().parse().ok()?;
+        Some((path, line))
+    })
+}
+
+fn render(
+    hits: &[SearchHit],
+    active_idx: usize,
+    top: usize,
+    can_open: bool,
+    term: &mut TermWizTerminal,
+) -> termwiz::Result<()> {
+    let size = term.get_screen_size()?;
+    let width = size.cols.saturating_sub(1);
+    let header = format!(
+        "{} matches.  Select a match and press Enter to jump to it{}.  \
+         Press Escape to cancel",
+        hits.len(),
+        if can_open {
+            ", or e to open its file location"
+        } else {
+            ""
+        }
+    );
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+    changes.extend(positioned_text(0, &header, width));
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+
+    for (row, (idx, hit)) in hits
+        .iter()
+        .enumerate()
+        .skip(top)
+        .take(size.rows.saturating_sub(1))
+        .enumerate()
+    {
+        if idx == active_idx {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+
+        let text = format!(
+            " {}. {}:{}: {}",
+            idx + 1,
+            hit.title,
+            hit.result.start_y,
+            hit.text.trim()
+        );
+        changes.extend(positioned_text(row + 1, &text, width));
+
+        if idx == active_idx {
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+    }
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+fn select_hit(hit: SearchHit, window: ::window::Window) {
+    promise::spawn::spawn_into_main_thread(async move {
+        if let Err(err) = activate_hit(hit, window) {
+            log::error!("Failed to jump to search result: {:#}", err);
+        }
+    })
+    .detach();
+}
+
+/// Activates the tab and pane that contain `hit` and scrolls it into view
+fn activate_hit(hit: SearchHit, window: ::window::Window) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let mux_window_id = mux
+        .window_containing_tab(hit.tab_id)
+        .ok_or_else(|| anyhow!("no window contains tab {}", hit.tab_id))?;
+    {
+        let mut mux_window = mux
+            .get_window_mut(mux_window_id)
+            .ok_or_else(|| anyhow!("no such window"))?;
+        let idx = mux_window
+            .idx_by_id(hit.tab_id)
+            .ok_or_else(|| anyhow!("no such tab"))?;
+        mux_window.set_active(idx);
+    }
+
+    let tab = mux
+        .get_tab(hit.tab_id)
+        .ok_or_else(|| anyhow!("no such tab"))?;
+    let pane = mux
+        .get_pane(hit.pane_id)
+        .ok_or_else(|| anyhow!("no such pane"))?;
+    tab.set_active_pane(&pane);
+
+    let dims = pane.get_dimensions();
+    window.apply(move |term_window, _window| {
+        if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+            select_search_result(term_window, hit.pane_id, &hit.result);
+            term_window.set_viewport(hit.pane_id, Some(hit.result.start_y), dims);
+        }
+        Ok(())
+    });
+    Ok(())
+}
+
+fn open_location(pane_id: PaneId, path: String, line: usize, window: ::window::Window) {
+    window.apply(move |term_window, _window| {
+        if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+            let mux = Mux::get().unwrap();
+            let pane = mux
+                .get_pane(pane_id)
+                .ok_or_else(|| anyhow!("no such pane"))?;
+            term_window.open_in_editor(&pane, &path, line);
+        }
+        Ok(())
+    });
+}
+
+pub fn search_results(
+    mut term: TermWizTerminal,
+    hits: Vec<SearchHit>,
+    location_pattern: Option<Regex>,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Search Results".to_string())])?;
+
+    let mut active_idx = 0;
+    let mut top = 0;
+
+    loop {
+        let page = term.get_screen_size()?.rows.saturating_sub(1).max(1);
+        if active_idx < top {
+            top = active_idx;
+        } else if active_idx >= top + page {
+            top = active_idx + 1 - page;
+        }
+        let location = match (&location_pattern, hits.get(active_idx)) {
+            (Some(pattern), Some(hit)) => editor_location(pattern, hit),
+            _ => None,
+        };
+        render(&hits, active_idx, top, location.is_some(), &mut term)?;
+
+        let last = hits.len().saturating_sub(1);
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })) => break,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('k'),
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })) => active_idx = active_idx.saturating_sub(1),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j'),
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })) => active_idx = (active_idx + 1).min(last),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            })) => active_idx = active_idx.saturating_sub(page),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            })) => active_idx = (active_idx + page).min(last),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            })) => {
+                if let Some(hit) = hits.get(active_idx) {
+                    select_hit(hit.clone(), window);
+                }
+                break;
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('e'),
+                ..
+            })) => {
+                if let Some((path, line)) = location {
+                    open_location(hits[active_idx].pane_id, path, line, window);
+                    break;
+                }
+            }
+            Some(InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            })) => {
+                let idx = top + y as usize;
+                if y > 0 && idx <= hits.len() {
+                    active_idx = idx - 1;
+
+                    if mouse_buttons == MouseButtons::LEFT {
+                        select_hit(hits[active_idx].clone(), window);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    Ok(())
+}
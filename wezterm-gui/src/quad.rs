@@ -33,6 +33,13 @@ pub struct Vertex {
     pub fg_color: (f32, f32, f32, f32),
     pub underline_color: (f32, f32, f32, f32),
     pub hsv: (f32, f32, f32),
+    // The row of the palette transition texture that holds the
+    // palette of the pane that this quad belongs to
+    pub palette_slot: f32,
+    // For each of the fg, bg, underline and cursor colors, one more
+    // than the index of the palette entry that the color came from,
+    // or 0.0 if it didn't come from the palette
+    pub palette_entries: (f32, f32, f32, f32),
     // We use a float for this because I can't get
     // bool or integer values to work:
     // "bool can't be an in in the vertex shader"
@@ -45,6 +52,8 @@ pub struct Vertex {
     //        background image of the window
     // 3.0 -> like 2.0, except that instead of an
     //        image, we use the solid bg color
+    // 4.0 -> like 0.0, except that the glyph is
+    //        sampled from the single channel atlas
     pub has_color: f32,
 }
 ::window::glium::implement_vertex!(
@@ -59,9 +68,32 @@ pub struct Vertex {
     fg_color,
     underline_color,
     hsv,
+    palette_slot,
+    palette_entries,
     has_color
 );
 
+/// A color for a quad, along with the palette entry that it was
+/// taken from.  While the palette of the pane that the quad belongs to
+/// is changing, the vertex shader blends from the color that the entry
+/// had when the change started towards `color`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct QuadColor {
+    pub color: LinearRgba,
+    /// The row of the palette transition texture, and the index
+    /// of the entry within it
+    pub palette_entry: Option<(usize, usize)>,
+}
+
+impl From<LinearRgba> for QuadColor {
+    fn from(color: LinearRgba) -> Self {
+        Self {
+            color,
+            palette_entry: None,
+        }
+    }
+}
+
 /// A helper for knowing how to locate the right quad for an element
 /// in the UI
 #[derive(Default, Debug, Clone)]
@@ -73,6 +105,9 @@ pub struct Quads {
     /// The vertex index for the first vertex of the scroll bar thumb
     pub scroll_thumb: usize,
     pub background_image: usize,
+    /// row number to the vertex index for the first vertex of the
+    /// wrap marker in the left padding of that row
+    pub wrap_markers: Vec<usize>,
 }
 
 pub struct MappedQuads<'a> {
@@ -111,6 +146,13 @@ impl<'a> MappedQuads<'a> {
             vert: &mut self.mapping[start..start + VERTICES_PER_CELL],
         }
     }
+
+    pub fn wrap_marker<'b>(&'b mut self, y: usize) -> Option<Quad<'b>> {
+        let start = *self.quads.wrap_markers.get(y)?;
+        Some(Quad {
+            vert: &mut self.mapping[start..start + VERTICES_PER_CELL],
+        })
+    }
 }
 
 impl Quads {
@@ -165,30 +207,62 @@ impl<'a> Quad<'a> {
         }
     }
 
+    /// Mark this quad as a monochrome glyph whose texture is in the
+    /// single channel atlas.
+    /// Mutually exclusive with set_has_color.
+    pub fn set_is_alpha_glyph(&mut self) {
+        for v in self.vert.iter_mut() {
+            v.has_color = 4.0;
+        }
+    }
+
     pub fn set_is_background(&mut self) {
         for v in self.vert.iter_mut() {
             v.has_color = 3.0;
         }
     }
 
-    pub fn set_fg_color(&mut self, color: LinearRgba) {
+    pub fn set_fg_color(&mut self, color: impl Into<QuadColor>) {
+        let color = color.into();
+        let slot = Self::palette_slot(&color);
         for v in self.vert.iter_mut() {
-            v.fg_color = color.tuple();
+            v.fg_color = color.color.tuple();
+            v.palette_entries.0 = Self::palette_entry(&color);
+            v.palette_slot = slot.unwrap_or(v.palette_slot);
         }
     }
 
-    pub fn set_underline_color(&mut self, color: LinearRgba) {
+    pub fn set_underline_color(&mut self, color: impl Into<QuadColor>) {
+        let color = color.into();
+        let slot = Self::palette_slot(&color);
         for v in self.vert.iter_mut() {
-            v.underline_color = color.tuple();
+            v.underline_color = color.color.tuple();
+            v.palette_entries.2 = Self::palette_entry(&color);
+            v.palette_slot = slot.unwrap_or(v.palette_slot);
         }
     }
 
-    pub fn set_bg_color(&mut self, color: LinearRgba) {
+    pub fn set_bg_color(&mut self, color: impl Into<QuadColor>) {
+        let color = color.into();
+        let slot = Self::palette_slot(&color);
         for v in self.vert.iter_mut() {
-            v.bg_color = color.tuple();
+            v.bg_color = color.color.tuple();
+            v.palette_entries.1 = Self::palette_entry(&color);
+            v.palette_slot = slot.unwrap_or(v.palette_slot);
         }
     }
 
+    fn palette_slot(color: &QuadColor) -> Option<f32> {
+        color.palette_entry.map(|(slot, _)| slot as f32)
+    }
+
+    fn palette_entry(color: &QuadColor) -> f32 {
+        color
+            .palette_entry
+            .map(|(_, entry)| entry as f32 + 1.)
+            .unwrap_or(0.)
+    }
+
     /// Assign the underline texture coordinates for the cell
     pub fn set_underline(&mut self, coords: TextureRect) {
         self.vert[V_TOP_LEFT].underline = (coords.min_x(), coords.min_y());
@@ -204,9 +278,13 @@ impl<'a> Quad<'a> {
         self.vert[V_BOT_RIGHT].cursor = (coords.max_x(), coords.max_y());
     }
 
-    pub fn set_cursor_color(&mut self, color: LinearRgba) {
+    pub fn set_cursor_color(&mut self, color: impl Into<QuadColor>) {
+        let color = color.into();
+        let slot = Self::palette_slot(&color);
         for v in self.vert.iter_mut() {
-            v.cursor_color = color.tuple();
+            v.cursor_color = color.color.tuple();
+            v.palette_entries.3 = Self::palette_entry(&color);
+            v.palette_slot = slot.unwrap_or(v.palette_slot);
         }
     }
 
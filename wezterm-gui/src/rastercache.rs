@@ -0,0 +1,538 @@
+//! An on-disk cache of rasterized glyphs, so that a large working set
+//! of glyphs (such as CJK text, or icons from a Nerd Font) doesn't need
+//! to be rasterized again each time that wezterm is started.
+//!
+//! The glyphs of each font face, at a given size and dpi, are appended
+//! to a file in the cache directory.  The file is named after a hash of
+//! the identity returned by `LoadedFont::rasterizer_identity` and starts
+//! with the identity itself, so that a change to the font file or to the
+//! rasterizer settings causes the glyphs to be rasterized afresh.
+//!
+//! The files of faces that haven't had glyphs added to them for a while
+//! are deleted, as are the least recently written files while the
+//! directory holds more than MAX_CACHE_SIZE bytes.
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use wezterm_font::units::PixelLength;
+use wezterm_font::RasterizedGlyph;
+
+const MAGIC: &[u8; 4] = b"WZGC";
+const VERSION: u32 = 1;
+/// glyph_pos, x_offset, width, height, bearing_x, bearing_y,
+/// has_color and the checksum of the record
+const RECORD_HEADER_LEN: usize = 4 + 8 + 4 + 4 + 8 + 8 + 1 + 8;
+/// How long the file of a face is kept after glyphs were last added to it
+const MAX_FACE_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// The size of the cache directory above which the least recently
+/// written face files are deleted
+const MAX_CACHE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Identifies a glyph within a face
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RecordKey {
+    glyph_pos: u32,
+    x_offset: u64,
+}
+
+/// Where the bitmap of a glyph is stored in the file of its face
+#[derive(Debug, Clone, Copy)]
+struct RecordLocation {
+    offset: u64,
+    width: usize,
+    height: usize,
+    bearing_x: f64,
+    bearing_y: f64,
+    has_color: bool,
+    checksum: u64,
+}
+
+struct FaceFile {
+    file: File,
+    records: HashMap<RecordKey, RecordLocation>,
+}
+
+pub struct RasterCache {
+    dir: PathBuf,
+    /// The open face files, keyed by identity.  None records that
+    /// the file couldn't be used, so that it isn't retried for
+    /// every glyph.
+    faces: HashMap<String, Option<FaceFile>>,
+    /// Whether the directory has been pruned by this process
+    pruned: bool,
+}
+
+/// FNV-1a, which is used rather than the std hasher because the
+/// values are persisted and must be stable across builds
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+/// Returns the length of a bitmap of the given dimensions, or None
+/// if the dimensions, which may have been read from a damaged
+/// file, are too large
+fn bitmap_len(width: usize, height: usize) -> Option<usize> {
+    width.checked_mul(height)?.checked_mul(4)
+}
+
+/// Deletes the face files in `dir` that were last written more than
+/// `max_age` ago, and then the oldest of the rest until they add up
+/// to no more than `max_size` bytes
+fn prune(dir: &Path, max_age: Duration, max_size: u64) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let now = SystemTime::now();
+    let mut files = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().map_or(true, |ext| ext != "bin") {
+            continue;
+        }
+        match entry.metadata() {
+            Ok(meta) if meta.is_file() => {
+                let modified = meta.modified().unwrap_or(now);
+                files.push((modified, meta.len(), path));
+            }
+            _ => continue,
+        }
+    }
+
+    // Newest first, so that the oldest are the ones that exceed max_size
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut total: u64 = 0;
+    for (modified, len, path) in files {
+        let age = now.duration_since(modified).unwrap_or_default();
+        if age < max_age && total.saturating_add(len) <= max_size {
+            total += len;
+            continue;
+        }
+        log::trace!("pruning {} from the glyph disk cache", path.display());
+        if let Err(err) = std::fs::remove_file(&path) {
+            // It may be in use by another wezterm process
+            log::trace!("while removing {}: {:#}", path.display(), err);
+            total = total.saturating_add(len);
+        }
+    }
+}
+
+fn face_header(identity: &str) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&(identity.len() as u32).to_le_bytes());
+    header.extend_from_slice(identity.as_bytes());
+    header
+}
+
+impl RasterCache {
+    pub fn new() -> Self {
+        Self::with_dir(config::CACHE_DIR.join("glyphs"))
+    }
+
+    fn with_dir(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            faces: HashMap::new(),
+            pruned: false,
+        }
+    }
+
+    /// Returns the glyph previously stored for `glyph_pos` at `x_offset`
+    /// in the face described by `identity`
+    pub fn get(
+        &mut self,
+        identity: &str,
+        glyph_pos: u32,
+        x_offset: f64,
+    ) -> Option<RasterizedGlyph> {
+        let key = RecordKey {
+            glyph_pos,
+            x_offset: x_offset.to_bits(),
+        };
+        let face = self.face(identity)?;
+        let location = *face.records.get(&key)?;
+        match Self::read_bitmap(&mut face.file, &location) {
+            Ok(data) => Some(RasterizedGlyph {
+                data,
+                width: location.width,
+                height: location.height,
+                bearing_x: PixelLength::new(location.bearing_x),
+                bearing_y: PixelLength::new(location.bearing_y),
+                has_color: location.has_color,
+            }),
+            Err(err) => {
+                log::warn!("while reading cached glyph: {:#}", err);
+                face.records.remove(&key);
+                None
+            }
+        }
+    }
+
+    /// Returns true if `get` would find the glyph, without reading it
+    pub fn contains(&mut self, identity: &str, glyph_pos: u32, x_offset: f64) -> bool {
+        let key = RecordKey {
+            glyph_pos,
+            x_offset: x_offset.to_bits(),
+        };
+        self.face(identity)
+            .map_or(false, |face| face.records.contains_key(&key))
+    }
+
+    /// Stores `glyph` so that a later `get` can return it
+    pub fn put(&mut self, identity: &str, glyph_pos: u32, x_offset: f64, glyph: &RasterizedGlyph) {
+        let key = RecordKey {
+            glyph_pos,
+            x_offset: x_offset.to_bits(),
+        };
+        let face = match self.face(identity) {
+            Some(face) => face,
+            None => return,
+        };
+        if face.records.contains_key(&key) {
+            return;
+        }
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + glyph.data.len());
+        record.extend_from_slice(&key.glyph_pos.to_le_bytes());
+        record.extend_from_slice(&key.x_offset.to_le_bytes());
+        record.extend_from_slice(&(glyph.width as u32).to_le_bytes());
+        record.extend_from_slice(&(glyph.height as u32).to_le_bytes());
+        record.extend_from_slice(&glyph.bearing_x.get().to_bits().to_le_bytes());
+        record.extend_from_slice(&glyph.bearing_y.get().to_bits().to_le_bytes());
+        record.push(glyph.has_color as u8);
+        let checksum = fnv1a(fnv1a(FNV_OFFSET, &record), &glyph.data);
+        record.extend_from_slice(&checksum.to_le_bytes());
+        record.extend_from_slice(&glyph.data);
+
+        // The record is appended with a single write, so that another
+        // wezterm process appending to the same file is unlikely to
+        // interleave with it; the checksum catches it if it does, or
+        // if the record didn't land at the end that we saw
+        let result = face
+            .file
+            .seek(SeekFrom::End(0))
+            .and_then(|end| face.file.write_all(&record).map(|_| end));
+        match result {
+            Ok(end) => {
+                let (_, location) = Self::parse_record_header(&record, end);
+                face.records.insert(key, location);
+            }
+            Err(err) => {
+                log::warn!("while writing glyph to disk cache: {:#}", err);
+                self.faces.insert(identity.to_string(), None);
+            }
+        }
+    }
+
+    fn face(&mut self, identity: &str) -> Option<&mut FaceFile> {
+        if !self.pruned {
+            // Prune before opening any files, so that none of those
+            // that this process uses are deleted
+            self.pruned = true;
+            prune(&self.dir, MAX_FACE_AGE, MAX_CACHE_SIZE);
+        }
+        if !self.faces.contains_key(identity) {
+            let face = match self.open_face(identity) {
+                Ok(face) => Some(face),
+                Err(err) => {
+                    log::warn!(
+                        "glyph disk cache is unavailable for {}: {:#}",
+                        identity,
+                        err
+                    );
+                    None
+                }
+            };
+            self.faces.insert(identity.to_string(), face);
+        }
+        self.faces.get_mut(identity)?.as_mut()
+    }
+
+    fn face_path(&self, identity: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{:016x}.bin",
+            fnv1a(FNV_OFFSET, identity.as_bytes())
+        ))
+    }
+
+    fn open_face(&self, identity: &str) -> anyhow::Result<FaceFile> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.face_path(identity);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let header = face_header(identity);
+        let mut existing = vec![0u8; header.len()];
+        let matches = match file.read_exact(&mut existing) {
+            Ok(()) => existing == header,
+            Err(_) => false,
+        };
+
+        let mut records = HashMap::new();
+        if matches {
+            let valid_len = Self::index_records(&file, header.len() as u64, &mut records)?;
+            if valid_len < file.metadata()?.len() {
+                // Drop a record that was cut short, so that the
+                // records appended after it can be found again
+                log::trace!("truncating {} to {}", path.display(), valid_len);
+                file.set_len(valid_len)?;
+            }
+        } else {
+            // Either a new file, or one that was written for a
+            // different identity whose hash collides with ours
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&header)?;
+        }
+
+        Ok(FaceFile { file, records })
+    }
+
+    fn parse_record_header(header: &[u8], offset: u64) -> (RecordKey, RecordLocation) {
+        let key = RecordKey {
+            glyph_pos: read_u32(&header[0..]),
+            x_offset: read_u64(&header[4..]),
+        };
+        let location = RecordLocation {
+            offset: offset + RECORD_HEADER_LEN as u64,
+            width: read_u32(&header[12..]) as usize,
+            height: read_u32(&header[16..]) as usize,
+            bearing_x: f64::from_bits(read_u64(&header[20..])),
+            bearing_y: f64::from_bits(read_u64(&header[28..])),
+            has_color: header[36] != 0,
+            checksum: read_u64(&header[37..]),
+        };
+        (key, location)
+    }
+
+    /// Reads the record headers that follow the file header, and
+    /// returns the length of the file up to the last complete record
+    fn index_records(
+        file: &File,
+        start: u64,
+        records: &mut HashMap<RecordKey, RecordLocation>,
+    ) -> anyhow::Result<u64> {
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        reader.seek(SeekFrom::Start(start))?;
+        let mut offset = start;
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        while offset + RECORD_HEADER_LEN as u64 <= len {
+            reader.read_exact(&mut header)?;
+            let (key, location) = Self::parse_record_header(&header, offset);
+            let end = match bitmap_len(location.width, location.height)
+                .and_then(|data_len| location.offset.checked_add(data_len as u64))
+            {
+                Some(end) if end <= len => end,
+                // Either cut short, or not a record at all
+                _ => break,
+            };
+            records.insert(key, location);
+            reader.seek(SeekFrom::Start(end))?;
+            offset = end;
+        }
+        Ok(offset)
+    }
+
+    fn read_bitmap(file: &mut File, location: &RecordLocation) -> anyhow::Result<Vec<u8>> {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        file.seek(SeekFrom::Start(location.offset - RECORD_HEADER_LEN as u64))?;
+        file.read_exact(&mut header)?;
+        let data_len = bitmap_len(location.width, location.height).ok_or_else(|| {
+            anyhow::anyhow!(
+                "invalid dimensions for record at offset {}",
+                location.offset
+            )
+        })?;
+        let mut data = vec![0u8; data_len];
+        file.read_exact(&mut data)?;
+
+        let checksum = fnv1a(fnv1a(FNV_OFFSET, &header[..RECORD_HEADER_LEN - 8]), &data);
+        anyhow::ensure!(
+            checksum == location.checksum,
+            "checksum mismatch for record at offset {}",
+            location.offset
+        );
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal as assert_eq;
+
+    const IDENTITY: &str = "test.ttf#0 size=12 dpi=96";
+
+    /// Returns an empty directory that is unique to `name`
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "wezterm-rastercache-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn glyph(width: usize, height: usize, fill: u8) -> RasterizedGlyph {
+        RasterizedGlyph {
+            data: vec![fill; width * height * 4],
+            width,
+            height,
+            bearing_x: PixelLength::new(1.5),
+            bearing_y: PixelLength::new(-2.0),
+            has_color: false,
+        }
+    }
+
+    fn assert_same(a: &RasterizedGlyph, b: &RasterizedGlyph) {
+        assert_eq!(a.width, b.width);
+        assert_eq!(a.height, b.height);
+        assert_eq!(a.bearing_x, b.bearing_x);
+        assert_eq!(a.bearing_y, b.bearing_y);
+        assert_eq!(a.has_color, b.has_color);
+        assert!(a.data == b.data);
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = test_dir("round_trip");
+        let a = glyph(2, 3, 0x40);
+        let b = glyph(4, 1, 0x80);
+        {
+            let mut cache = RasterCache::with_dir(dir.clone());
+            cache.put(IDENTITY, 1, 0., &a);
+            cache.put(IDENTITY, 1, 0.5, &b);
+            assert_same(&cache.get(IDENTITY, 1, 0.).unwrap(), &a);
+        }
+
+        let mut cache = RasterCache::with_dir(dir.clone());
+        assert!(cache.contains(IDENTITY, 1, 0.));
+        assert_same(&cache.get(IDENTITY, 1, 0.).unwrap(), &a);
+        assert_same(&cache.get(IDENTITY, 1, 0.5).unwrap(), &b);
+        assert!(cache.get(IDENTITY, 2, 0.).is_none());
+        assert!(cache.get("other.ttf#0 size=12 dpi=96", 1, 0.).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn truncated_record_is_dropped() {
+        let dir = test_dir("truncated");
+        let a = glyph(2, 2, 0x40);
+        let path = {
+            let mut cache = RasterCache::with_dir(dir.clone());
+            cache.put(IDENTITY, 1, 0., &a);
+            cache.put(IDENTITY, 2, 0., &glyph(3, 3, 0x80));
+            cache.face_path(IDENTITY)
+        };
+        let full_len = std::fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(full_len - 3).unwrap();
+        drop(file);
+
+        let mut cache = RasterCache::with_dir(dir.clone());
+        assert_same(&cache.get(IDENTITY, 1, 0.).unwrap(), &a);
+        assert!(!cache.contains(IDENTITY, 2, 0.));
+        let second_len = (RECORD_HEADER_LEN + 3 * 3 * 4) as u64;
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            full_len - second_len
+        );
+
+        // A glyph that is stored afterwards can be found again
+        cache.put(IDENTITY, 3, 0., &a);
+        drop(cache);
+        let mut cache = RasterCache::with_dir(dir.clone());
+        assert_same(&cache.get(IDENTITY, 3, 0.).unwrap(), &a);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_record_fails_checksum() {
+        let dir = test_dir("checksum");
+        let path = {
+            let mut cache = RasterCache::with_dir(dir.clone());
+            cache.put(IDENTITY, 1, 0., &glyph(2, 2, 0x40));
+            cache.face_path(IDENTITY)
+        };
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut cache = RasterCache::with_dir(dir.clone());
+        assert!(cache.contains(IDENTITY, 1, 0.));
+        assert!(cache.get(IDENTITY, 1, 0.).is_none());
+        assert!(!cache.contains(IDENTITY, 1, 0.));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn implausible_dimensions_are_not_indexed() {
+        let dir = test_dir("dimensions");
+        let path = {
+            let mut cache = RasterCache::with_dir(dir.clone());
+            cache.put(IDENTITY, 1, 0., &glyph(1, 1, 0x40));
+            cache.face_path(IDENTITY)
+        };
+        // Overwrite the width and height of the record
+        let mut bytes = std::fs::read(&path).unwrap();
+        let record = face_header(IDENTITY).len();
+        bytes[record + 12..record + 20].copy_from_slice(&[0xff; 8]);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut cache = RasterCache::with_dir(dir.clone());
+        assert!(!cache.contains(IDENTITY, 1, 0.));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_by_age_and_size() {
+        let dir = test_dir("prune");
+        let identities = ["a.ttf#0", "b.ttf#0", "c.ttf#0"];
+        let face_len = {
+            let mut cache = RasterCache::with_dir(dir.clone());
+            for identity in &identities {
+                cache.put(identity, 1, 0., &glyph(2, 2, 0x40));
+            }
+            std::fs::metadata(cache.face_path(identities[0]))
+                .unwrap()
+                .len()
+        };
+        let count = || std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(count(), 3);
+
+        prune(&dir, MAX_FACE_AGE, u64::MAX);
+        assert_eq!(count(), 3);
+
+        prune(&dir, MAX_FACE_AGE, 2 * face_len);
+        assert_eq!(count(), 2);
+
+        prune(&dir, Duration::from_secs(0), u64::MAX);
+        assert_eq!(count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,154 @@
+//! Rasterizes glyphs on a worker thread, so that a burst of glyphs that
+//! haven't been seen before (eg: when paging through a CJK file) doesn't
+//! stall the frame in which they first appear.  The glyph cache shows
+//! a blank placeholder until the glyph has been rasterized.
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wezterm_font::locator::FontDataHandle;
+use wezterm_font::rasterizer::{new_rasterizer, FontRasterizer};
+use wezterm_font::{RasterJob, RasterizedGlyph};
+
+/// How long the worker may keep rasterizing queued glyphs before the
+/// ones that it has finished are shown
+const NOTIFY_INTERVAL: Duration = Duration::from_millis(16);
+
+pub struct Rasterized {
+    pub ticket: u64,
+    pub glyph: anyhow::Result<RasterizedGlyph>,
+    /// The time taken to rasterize the glyph
+    pub elapsed: Duration,
+}
+
+pub struct RasterQueue {
+    sender: Sender<(u64, RasterJob)>,
+    completed: Arc<Mutex<Vec<Rasterized>>>,
+    next_ticket: u64,
+}
+
+impl RasterQueue {
+    /// Starts the worker thread.  `notify` is called from the worker
+    /// thread after it has rasterized some glyphs, which can then be
+    /// retrieved using `take_completed`.
+    pub fn new<F: Fn() + Send + 'static>(notify: F) -> anyhow::Result<Self> {
+        let (sender, receiver) = channel();
+        let completed = Arc::new(Mutex::new(vec![]));
+        let worker_completed = Arc::clone(&completed);
+        std::thread::Builder::new()
+            .name("glyph-rasterizer".to_string())
+            .spawn(move || Self::worker(receiver, worker_completed, notify))?;
+        Ok(Self {
+            sender,
+            completed,
+            next_ticket: 0,
+        })
+    }
+
+    /// Queues `job` and returns the ticket by which its result will
+    /// be identified
+    pub fn submit(&mut self, job: RasterJob) -> anyhow::Result<u64> {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.sender
+            .send((ticket, job))
+            .map_err(|_| anyhow::anyhow!("glyph rasterizer thread has terminated"))?;
+        Ok(ticket)
+    }
+
+    /// Returns the glyphs that were rasterized since the last call
+    pub fn take_completed(&self) -> Vec<Rasterized> {
+        std::mem::take(&mut *self.completed.lock().unwrap())
+    }
+
+    fn worker<F: Fn()>(
+        receiver: Receiver<(u64, RasterJob)>,
+        completed: Arc<Mutex<Vec<Rasterized>>>,
+        notify: F,
+    ) {
+        // The rasterizers can't be shared with the thread that owns the
+        // fonts, so this thread loads its own.  The thread terminates
+        // when the glyph cache that owns the queue is dropped.
+        let mut rasterizers: BTreeMap<FontDataHandle, Box<dyn FontRasterizer>> = BTreeMap::new();
+
+        while let Ok(first) = receiver.recv() {
+            let started = Instant::now();
+            let mut next = Some(first);
+            while let Some((ticket, job)) = next.take() {
+                let start = Instant::now();
+                let glyph = Self::rasterize(&mut rasterizers, &job);
+                completed.lock().unwrap().push(Rasterized {
+                    ticket,
+                    glyph,
+                    elapsed: start.elapsed(),
+                });
+                if started.elapsed() < NOTIFY_INTERVAL {
+                    next = receiver.try_recv().ok();
+                }
+            }
+            notify();
+        }
+    }
+
+    fn rasterize(
+        rasterizers: &mut BTreeMap<FontDataHandle, Box<dyn FontRasterizer>>,
+        job: &RasterJob,
+    ) -> anyhow::Result<RasterizedGlyph> {
+        if !rasterizers.contains_key(&job.handle) {
+            let raster = new_rasterizer(job.rasterizer, &job.handle)?;
+            rasterizers.insert(job.handle.clone(), raster);
+        }
+        rasterizers[&job.handle].rasterize_glyph(job.glyph_pos, job.size, job.dpi, job.x_offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k9::assert_equal as assert_eq;
+    use wezterm_font::FontConfiguration;
+
+    #[test]
+    fn rasterizes_submitted_jobs() {
+        config::use_test_configuration();
+        let fonts = FontConfiguration::new(None).unwrap();
+        let font = fonts.resolve_font(&config::TextStyle::default()).unwrap();
+        let infos = font.shape("ab", None, || {}).unwrap();
+        assert_eq!(infos.len(), 2);
+
+        let (notify_tx, notify_rx) = channel();
+        let mut queue = RasterQueue::new(move || {
+            notify_tx.send(()).ok();
+        })
+        .unwrap();
+
+        let mut tickets = vec![];
+        for info in &infos {
+            let job = font.raster_job(info.glyph_pos, info.font_idx, 0.).unwrap();
+            tickets.push(queue.submit(job).unwrap());
+        }
+        assert_eq!(tickets, vec![0, 1]);
+
+        let mut completed = vec![];
+        while completed.len() < tickets.len() {
+            notify_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+            completed.extend(queue.take_completed());
+        }
+        assert!(queue.take_completed().is_empty());
+
+        completed.sort_by_key(|done| done.ticket);
+        assert_eq!(
+            completed.iter().map(|done| done.ticket).collect::<Vec<_>>(),
+            tickets
+        );
+        for (done, info) in completed.into_iter().zip(infos.iter()) {
+            let glyph = done.glyph.unwrap();
+            let expected = font
+                .rasterize_glyph(info.glyph_pos, info.font_idx, 0.)
+                .unwrap();
+            assert_eq!(glyph.width, expected.width);
+            assert_eq!(glyph.height, expected.height);
+            assert!(glyph.data == expected.data);
+        }
+    }
+}
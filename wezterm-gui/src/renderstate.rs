@@ -1,9 +1,8 @@
-use super::glyphcache::GlyphCache;
+use super::glyphcache::{AtlasTexture, GlyphCache};
 use super::quad::*;
-use super::utilsprites::{RenderMetrics, UtilSprites};
-use ::window::bitmaps::atlas::OutOfTextureSpace;
+use super::termwindow::{PaletteEntry, MAX_PALETTE_SLOTS};
+use super::utilsprites::{wrap_marker_width, RenderMetrics, UtilSprites};
 use ::window::glium::backend::Context as GliumContext;
-use ::window::glium::texture::SrgbTexture2d;
 use ::window::glium::{IndexBuffer, VertexBuffer};
 use ::window::*;
 use config::ConfigHandle;
@@ -18,14 +17,23 @@ pub struct TripleVertexBuffer {
 
 pub struct RenderState {
     pub context: Rc<GliumContext>,
-    pub glyph_cache: RefCell<GlyphCache<SrgbTexture2d>>,
-    pub util_sprites: UtilSprites<SrgbTexture2d>,
+    pub glyph_cache: RefCell<GlyphCache<AtlasTexture>>,
+    pub util_sprites: UtilSprites<AtlasTexture>,
     pub background_prog: glium::Program,
     pub line_prog: glium::Program,
     pub glyph_prog: glium::Program,
+    /// The glyph program that blends the red, green and blue coverage
+    /// of LCD glyphs separately using dual-source blending, if the
+    /// OpenGL implementation supports it
+    pub glyph_subpixel_prog: Option<glium::Program>,
+    /// The compiled window_background_shader, if any
+    pub background_effect_prog: Option<glium::Program>,
     pub glyph_vertex_buffer: RefCell<TripleVertexBuffer>,
     pub glyph_index_buffer: IndexBuffer<u32>,
     pub quads: Quads,
+    /// Animates changes to the palettes of the panes;
+    /// see termwindow/palettetransition.rs
+    pub palette_transitions: glium::texture::Texture2d,
 }
 
 impl RenderState {
@@ -34,63 +42,111 @@ impl RenderState {
         context: Rc<GliumContext>,
         fonts: &Rc<FontConfiguration>,
         metrics: &RenderMetrics,
-        mut atlas_size: usize,
+        atlas_size: usize,
         pixel_width: usize,
         pixel_height: usize,
     ) -> anyhow::Result<Self> {
-        loop {
-            let glyph_cache =
-                RefCell::new(GlyphCache::new_gl(&context, fonts, atlas_size, metrics)?);
-            let result = UtilSprites::new(&mut *glyph_cache.borrow_mut(), metrics);
-            match result {
-                Ok(util_sprites) => {
-                    let background_prog = Self::compile_prog(
-                        &context,
-                        cfg!(target_os = "macos"),
-                        Self::background_shader,
-                    )?;
-                    let line_prog =
-                        Self::compile_prog(&context, cfg!(target_os = "macos"), Self::line_shader)?;
-
-                    // Last prog outputs srgb for gamma correction
-                    let glyph_prog = Self::compile_prog(&context, true, Self::glyph_shader)?;
-
-                    let (glyph_vertex_buffer, glyph_index_buffer, quads) = Self::compute_vertices(
-                        config,
-                        &context,
-                        metrics,
-                        pixel_width as f32,
-                        pixel_height as f32,
-                    )?;
-
-                    return Ok(Self {
-                        context,
-                        glyph_cache,
-                        util_sprites,
-                        background_prog,
-                        line_prog,
-                        glyph_prog,
-                        glyph_vertex_buffer: RefCell::new(glyph_vertex_buffer),
-                        glyph_index_buffer,
-                        quads,
-                    });
-                }
-                Err(OutOfTextureSpace {
-                    size: Some(size), ..
-                }) => {
-                    atlas_size = size;
-                }
-                Err(OutOfTextureSpace { size: None, .. }) => {
-                    anyhow::bail!("requested texture size is impossible!?")
+        let mut glyph_cache = GlyphCache::new_gl(&context, fonts, atlas_size, metrics)?;
+        let util_sprites = Self::new_util_sprites(&mut glyph_cache, metrics)?;
+
+        let background_prog =
+            Self::compile_prog(&context, cfg!(target_os = "macos"), Self::background_shader)?;
+        let line_prog = Self::compile_prog(&context, cfg!(target_os = "macos"), Self::line_shader)?;
+
+        // Last prog outputs srgb for gamma correction
+        let glyph_prog = Self::compile_prog(&context, true, Self::glyph_shader)?;
+        let glyph_subpixel_prog =
+            match Self::compile_prog(&context, true, Self::glyph_subpixel_shader) {
+                Ok(prog) => Some(prog),
+                Err(err) => {
+                    log::warn!(
+                        "Subpixel anti-aliasing is not available, \
+                         glyphs will use greyscale blending: {:#}",
+                        err
+                    );
+                    None
                 }
             };
+
+        let (glyph_vertex_buffer, glyph_index_buffer, quads) = Self::compute_vertices(
+            config,
+            &context,
+            metrics,
+            pixel_width as f32,
+            pixel_height as f32,
+        )?;
+
+        // Every row starts out with no transition in progress
+        let palette_transitions = glium::texture::Texture2d::with_format(
+            &context,
+            vec![
+                vec![(1.0f32, 0.0f32, 0.0f32, 0.0f32); 1 + PaletteEntry::COUNT];
+                MAX_PALETTE_SLOTS
+            ],
+            glium::texture::UncompressedFloatFormat::F32F32F32F32,
+            glium::texture::MipmapsOption::NoMipmap,
+        )?;
+
+        Ok(Self {
+            context,
+            glyph_cache: RefCell::new(glyph_cache),
+            util_sprites,
+            background_prog,
+            line_prog,
+            glyph_prog,
+            glyph_subpixel_prog,
+            background_effect_prog: None,
+            glyph_vertex_buffer: RefCell::new(glyph_vertex_buffer),
+            glyph_index_buffer,
+            quads,
+            palette_transitions,
+        })
+    }
+
+    /// Replaces the start of row `slot` of the palette transition texture
+    pub fn write_palette_transition(&self, slot: usize, texels: Vec<(f32, f32, f32, f32)>) {
+        let width = texels.len() as u32;
+        self.palette_transitions.write(
+            glium::Rect {
+                left: 0,
+                bottom: slot as u32,
+                width,
+                height: 1,
+            },
+            vec![texels],
+        );
+    }
+
+    /// Allocates the util sprites, starting over if the atlas is
+    /// replaced part way through, as that invalidates the earlier sprites
+    fn new_util_sprites(
+        glyph_cache: &mut GlyphCache<AtlasTexture>,
+        metrics: &RenderMetrics,
+    ) -> anyhow::Result<UtilSprites<AtlasTexture>> {
+        loop {
+            let util_sprites = UtilSprites::new(glyph_cache, metrics)?;
+            if !glyph_cache.take_atlas_replaced() {
+                return Ok(util_sprites);
+            }
         }
     }
 
+    /// Returns true if the atlas was grown or compacted since this was
+    /// last called, in which case the util sprites are allocated again
+    /// and any other sprites obtained before then are stale
+    pub fn check_atlas_replaced(&mut self, metrics: &RenderMetrics) -> anyhow::Result<bool> {
+        let mut glyph_cache = self.glyph_cache.borrow_mut();
+        if !glyph_cache.take_atlas_replaced() {
+            return Ok(false);
+        }
+        self.util_sprites = Self::new_util_sprites(&mut glyph_cache, metrics)?;
+        Ok(true)
+    }
+
     fn compile_prog(
         context: &Rc<GliumContext>,
         outputs_srgb: bool,
-        fragment_shader: fn(&str) -> (String, String),
+        fragment_shader: impl Fn(&str) -> (String, String),
     ) -> anyhow::Result<glium::Program> {
         let mut errors = vec![];
         for version in &["330", "300 es"] {
@@ -117,6 +173,20 @@ impl RenderState {
         anyhow::bail!("Failed to compile shaders: {}", errors.join("\n"))
     }
 
+    /// Compiles the fragment shader source of a window_background_shader,
+    /// replacing any previously compiled effect.  None removes the effect.
+    pub fn set_background_effect(&mut self, source: Option<&str>) -> anyhow::Result<()> {
+        self.background_effect_prog = None;
+        if let Some(source) = source {
+            self.background_effect_prog = Some(Self::compile_prog(
+                &self.context,
+                cfg!(target_os = "macos"),
+                |version| Self::background_effect_shader(version, source),
+            )?);
+        }
+        Ok(())
+    }
+
     pub fn advise_of_window_size_change(
         &mut self,
         config: &ConfigHandle,
@@ -155,6 +225,26 @@ impl RenderState {
         )
     }
 
+    /// The glyph shader with a second output that holds the coverage
+    /// of each color channel, for use with dual-source blending.
+    /// That requires GLSL 3.30, so it doesn't compile with GLSL ES.
+    fn glyph_subpixel_shader(version: &str) -> (String, String) {
+        (
+            format!(
+                "#version {}\n{}\n{}",
+                version,
+                include_str!("vertex-common.glsl"),
+                include_str!("glyph-vertex.glsl")
+            ),
+            format!(
+                "#version {}\n#define SUBPIXEL_AA\n{}\n{}",
+                version,
+                include_str!("fragment-common.glsl"),
+                include_str!("glyph-frag.glsl")
+            ),
+        )
+    }
+
     fn line_shader(version: &str) -> (String, String) {
         (
             format!(
@@ -189,6 +279,23 @@ impl RenderState {
         )
     }
 
+    fn background_effect_shader(version: &str, source: &str) -> (String, String) {
+        (
+            format!(
+                "#version {}\n{}\n{}",
+                version,
+                include_str!("vertex-common.glsl"),
+                include_str!("background-effect-vertex.glsl")
+            ),
+            format!(
+                "#version {}\n{}\n{}",
+                version,
+                include_str!("background-effect-common.glsl"),
+                source
+            ),
+        )
+    }
+
     /// Compute a vertex buffer to hold the quads that comprise the visible
     /// portion of the screen.   We recreate this when the screen is resized.
     /// The idea is that we want to minimize any heavy lifting and computation
@@ -290,6 +397,21 @@ impl RenderState {
         // And a quad for the scrollbar thumb
         quads.scroll_thumb = define_quad(0.0, 0.0, 0.0, 0.0) as usize;
 
+        // And one for the wrap marker of each row, which sits in
+        // the left padding against the first column
+        let marker_width = wrap_marker_width(config, metrics) as f32;
+        let marker_right = (width / -2.0) + padding_left;
+        for y in 0..=num_rows {
+            let y_pos = (height / -2.0) + (y as f32 * cell_height) + padding_top;
+            let idx = define_quad(
+                marker_right - marker_width,
+                y_pos,
+                marker_right,
+                y_pos + cell_height,
+            );
+            quads.wrap_markers.push(idx as usize);
+        }
+
         let buffer = TripleVertexBuffer {
             index: 0,
             bufs: [
@@ -313,57 +435,31 @@ impl RenderState {
     pub fn clear_texture_atlas(&mut self, metrics: &RenderMetrics) -> anyhow::Result<()> {
         let mut glyph_cache = self.glyph_cache.borrow_mut();
         glyph_cache.clear();
-        self.util_sprites = UtilSprites::new(&mut glyph_cache, metrics)?;
+        self.util_sprites = Self::new_util_sprites(&mut glyph_cache, metrics)?;
+        drop(glyph_cache);
+        self.prewarm_glyph_cache();
         Ok(())
     }
 
-    pub fn recreate_texture_atlas(
-        &mut self,
-        fonts: &Rc<FontConfiguration>,
-        metrics: &RenderMetrics,
-        size: Option<usize>,
-    ) -> anyhow::Result<()> {
-        // We make a a couple of passes at resizing; if the user has selected a large
-        // font size (or a large scaling factor) then the `size==None` case will not
-        // be able to fit the initial utility glyphs and apply_scale_change won't
-        // be able to deal with that error situation.  Rather than make every
-        // caller know how to deal with OutOfTextureSpace we try to absorb
-        // and accomodate that here.
-        let mut size = size;
-        let mut attempt = 10;
-        loop {
-            match self.recreate_texture_atlas_impl(fonts, metrics, size) {
-                Ok(_) => return Ok(()),
-                Err(err) => {
-                    attempt -= 1;
-                    if attempt == 0 {
-                        return Err(err);
-                    }
-
-                    if let Some(&OutOfTextureSpace {
-                        size: Some(needed_size),
-                        ..
-                    }) = err.downcast_ref::<OutOfTextureSpace>()
-                    {
-                        size.replace(needed_size);
-                        continue;
-                    }
-
-                    return Err(err);
-                }
-            }
+    /// Starts rasterizing the commonly used glyphs in the background
+    pub fn prewarm_glyph_cache(&self) {
+        if let Err(err) = self.glyph_cache.borrow_mut().prewarm() {
+            log::error!("while prewarming the glyph cache: {:#}", err);
         }
     }
 
-    fn recreate_texture_atlas_impl(
+    pub fn recreate_texture_atlas(
         &mut self,
         fonts: &Rc<FontConfiguration>,
         metrics: &RenderMetrics,
         size: Option<usize>,
     ) -> anyhow::Result<()> {
+        // The new atlas starts out at `size` and grows as needed if
+        // the utility glyphs (eg: with a large font size or scaling
+        // factor) don't fit
         let size = size.unwrap_or_else(|| self.glyph_cache.borrow().atlas.size());
         let mut new_glyph_cache = GlyphCache::new_gl(&self.context, fonts, size, metrics)?;
-        self.util_sprites = UtilSprites::new(&mut new_glyph_cache, metrics)?;
+        self.util_sprites = Self::new_util_sprites(&mut new_glyph_cache, metrics)?;
 
         let mut glyph_cache = self.glyph_cache.borrow_mut();
 
@@ -373,8 +469,13 @@ impl RenderState {
             &mut glyph_cache.image_cache,
             &mut new_glyph_cache.image_cache,
         );
+        // Keep the rasterizer thread; the glyphs that it is working
+        // on are discarded along with the old cache
+        new_glyph_cache.raster_queue = glyph_cache.raster_queue.take();
 
         *glyph_cache = new_glyph_cache;
+        drop(glyph_cache);
+        self.prewarm_glyph_cache();
         Ok(())
     }
 }
@@ -3,12 +3,15 @@ use super::luaerr;
 use super::pane::PaneObject;
 use crate::TermWindow;
 use anyhow::anyhow;
-use config::keyassignment::KeyAssignment;
+use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource, KeyAssignment};
 use luahelper::*;
 use mlua::{UserData, UserDataMethods};
+use mux::tab::TabId;
 use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
 use serde::*;
-use window::WindowOps;
+use std::sync::Arc;
+use window::{Clipboard, WindowOps, WindowState};
 
 #[derive(Clone)]
 pub struct GuiWin {
@@ -48,6 +51,84 @@ impl GuiWin {
 impl UserData for GuiWin {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method("window_id", |_, this, _: ()| Ok(this.mux_window_id));
+        methods.add_method("get_workspace", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            let window = mux
+                .get_window(this.mux_window_id)
+                .ok_or_else(|| anyhow!("window id {} is not valid", this.mux_window_id))
+                .map_err(luaerr)?;
+            Ok(window.get_workspace().to_string())
+        });
+        methods.add_method("set_workspace", |_, this, workspace: String| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            mux.set_window_workspace(this.mux_window_id, &workspace)
+                .map_err(luaerr)
+        });
+        methods.add_method(
+            "rename_workspace",
+            |_, _this, (old_workspace, new_workspace): (String, String)| {
+                let mux = Mux::get()
+                    .ok_or_else(|| anyhow!("must be called on main thread"))
+                    .map_err(luaerr)?;
+                mux.rename_workspace(&old_workspace, &new_workspace)
+                    .map_err(luaerr)
+            },
+        );
+        methods.add_method("list_workspaces", |_, _this, _: ()| {
+            #[derive(Serialize, Deserialize)]
+            struct Workspace {
+                name: String,
+                num_windows: usize,
+                active: bool,
+            }
+            impl_lua_conversion!(Workspace);
+
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            let active = mux.active_workspace();
+            Ok(mux
+                .iter_workspaces()
+                .into_iter()
+                .map(|(name, num_windows)| Workspace {
+                    active: name == active,
+                    name,
+                    num_windows,
+                })
+                .collect::<Vec<_>>())
+        });
+        methods.add_method("kill", |_, this, force: Option<bool>| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            mux.close_window(this.mux_window_id, force.unwrap_or(false))
+                .map_err(luaerr)
+        });
+        methods.add_method(
+            "kill_tab",
+            |_, this, (tab_id, force): (TabId, Option<bool>)| {
+                let mux = Mux::get()
+                    .ok_or_else(|| anyhow!("must be called on main thread"))
+                    .map_err(luaerr)?;
+                let in_window = mux
+                    .get_window(this.mux_window_id)
+                    .map(|window| window.idx_by_id(tab_id).is_some())
+                    .unwrap_or(false);
+                if !in_window {
+                    return Err(luaerr(anyhow!(
+                        "tab {} is not in window {}",
+                        tab_id,
+                        this.mux_window_id
+                    )));
+                }
+                mux.close_tab(tab_id, force.unwrap_or(false))
+                    .map_err(luaerr)
+            },
+        );
         methods.add_async_method("set_right_status", |_, this, status: String| async move {
             this.with_term_window(move |term_window, _ops| {
                 if status != term_window.right_status {
@@ -73,12 +154,61 @@ impl UserData for GuiWin {
                     pixel_width: term_window.dimensions.pixel_width,
                     pixel_height: term_window.dimensions.pixel_height,
                     dpi: term_window.dimensions.dpi,
-                    is_full_screen: term_window.is_full_screen,
+                    is_full_screen: term_window.window_state.contains(WindowState::FULL_SCREEN),
                 };
                 Ok(dims)
             })
             .await
         });
+        methods.add_async_method("glyph_cache_stats", |_, this, _: ()| async move {
+            this.with_term_window(move |term_window, _ops| {
+                #[derive(Serialize, Deserialize)]
+                struct Atlas {
+                    side: usize,
+                    sprites: usize,
+                    occupancy: f32,
+                }
+
+                #[derive(Serialize, Deserialize)]
+                struct GlyphCacheStats {
+                    hits: usize,
+                    misses: usize,
+                    glyphs: usize,
+                    capacity: usize,
+                    images: usize,
+                    lines: usize,
+                    blocks: usize,
+                    rasterized: usize,
+                    raster_time_ms: f64,
+                    atlas_replacements: usize,
+                    color_atlas: Atlas,
+                    alpha_atlas: Atlas,
+                }
+                impl_lua_conversion!(GlyphCacheStats);
+
+                let atlas = |atlas: mux::AtlasOccupancy| Atlas {
+                    side: atlas.side,
+                    sprites: atlas.sprites,
+                    occupancy: atlas.occupancy,
+                };
+                let stats = term_window.glyph_cache_stats();
+                Ok(GlyphCacheStats {
+                    hits: stats.hits,
+                    misses: stats.misses,
+                    glyphs: stats.glyphs,
+                    capacity: stats.capacity,
+                    images: stats.images,
+                    lines: stats.lines,
+                    blocks: stats.blocks,
+                    rasterized: stats.rasterized,
+                    raster_time_ms: stats.raster_time.as_secs_f64() * 1000.,
+                    atlas_replacements: stats.atlas_replacements,
+                    color_atlas: atlas(stats.color_atlas),
+                    alpha_atlas: atlas(stats.alpha_atlas),
+                })
+            })
+            .await
+        });
         methods.add_async_method(
             "get_selection_text_for_pane",
             |_, this, pane: PaneObject| async move {
@@ -88,6 +218,43 @@ impl UserData for GuiWin {
                 .await
             },
         );
+        methods.add_async_method(
+            "get_clipboard",
+            |_, this, source: Option<ClipboardPasteSource>| async move {
+                let access = this
+                    .with_term_window(move |term_window, _ops| {
+                        Ok(term_window.config.lua_clipboard_access)
+                    })
+                    .await?;
+                if !access.can_read() {
+                    return Err(luaerr(anyhow!(
+                        "reading the clipboard is not permitted by \
+                         the lua_clipboard_access configuration"
+                    )));
+                }
+                let clipboard = match source.unwrap_or_default() {
+                    ClipboardPasteSource::Clipboard => Clipboard::Clipboard,
+                    ClipboardPasteSource::PrimarySelection => Clipboard::PrimarySelection,
+                };
+                this.window.get_clipboard(clipboard).await.map_err(luaerr)
+            },
+        );
+        methods.add_async_method(
+            "set_clipboard",
+            |_, this, (text, destination): (String, Option<ClipboardCopyDestination>)| async move {
+                this.with_term_window(move |term_window, _ops| {
+                    if !term_window.config.lua_clipboard_access.can_write() {
+                        anyhow::bail!(
+                            "writing the clipboard is not permitted by \
+                             the lua_clipboard_access configuration"
+                        );
+                    }
+                    term_window.copy_to_clipboard(destination.unwrap_or_default(), text.clone());
+                    Ok(())
+                })
+                .await
+            },
+        );
         methods.add_async_method(
             "perform_action",
             |_, this, (assignment, pane): (KeyAssignment, PaneObject)| async move {
@@ -97,6 +264,30 @@ impl UserData for GuiWin {
                 .await
             },
         );
+        methods.add_async_method(
+            "show_overlay",
+            |lua, this, (pane, callback): (PaneObject, mlua::Function)| {
+                let callback = lua.create_registry_value(callback).map(Arc::new);
+                async move {
+                    let callback = callback?;
+                    this.with_term_window(move |term_window, _ops| {
+                        term_window.show_lua_overlay(&pane.pane()?, Arc::clone(&callback));
+                        Ok(())
+                    })
+                    .await
+                }
+            },
+        );
+        methods.add_async_method("tabs_with_unseen_output", |_, this, _: ()| async move {
+            this.with_term_window(
+                move |term_window, _ops| Ok(term_window.tabs_with_unseen_output()),
+            )
+            .await
+        });
+        methods.add_async_method("is_ime_composing", |_, this, _: ()| async move {
+            this.with_term_window(move |term_window, _ops| Ok(term_window.is_ime_composing()))
+                .await
+        });
         methods.add_async_method("effective_config", |_, this, _: ()| async move {
             this.with_term_window(move |term_window, _ops| Ok((*term_window.config).clone()))
                 .await
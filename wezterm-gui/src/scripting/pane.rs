@@ -1,10 +1,13 @@
 //! PaneObject represents a Mux Pane instance in lua code
 use super::luaerr;
 use anyhow::anyhow;
+use luahelper::impl_lua_conversion;
 use mlua::{UserData, UserDataMethods};
 use mux::pane::{Pane, PaneId};
 use mux::Mux;
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
+use wezterm_term::color::RgbColor;
 
 #[derive(Clone)]
 pub struct PaneObject {
@@ -38,6 +41,32 @@ impl UserData for PaneObject {
                 .get_current_working_dir()
                 .map(|u| u.to_string()))
         });
+        methods.add_method("get_foreground_process_name", |_, this, _: ()| {
+            Ok(this.pane()?.get_foreground_process_name())
+        });
+        methods.add_method("get_foreground_process_info", |_, this, _: ()| {
+            #[derive(Serialize, Deserialize)]
+            struct ProcessInfo {
+                pid: u32,
+                executable: Option<String>,
+                argv: Vec<String>,
+                cwd: Option<String>,
+            }
+            impl_lua_conversion!(ProcessInfo);
+
+            Ok(this
+                .pane()?
+                .get_foreground_process_info()
+                .map(|info| ProcessInfo {
+                    pid: info.pid,
+                    executable: info.executable.map(|p| p.to_string_lossy().into_owned()),
+                    argv: info.argv,
+                    cwd: info.cwd.map(|p| p.to_string_lossy().into_owned()),
+                }))
+        });
+        methods.add_method("is_reading_password", |_, this, _: ()| {
+            Ok(this.pane()?.is_reading_password())
+        });
         methods.add_method("paste", |_, this, text: String| {
             this.pane()?.send_paste(&text).map_err(luaerr)?;
             Ok(())
@@ -48,6 +77,84 @@ impl UserData for PaneObject {
         methods.add_method("get_dimensions", |_, this, _: ()| {
             Ok(this.pane()?.get_dimensions())
         });
+        methods.add_method("get_palette", |_, this, _: ()| {
+            Ok(config::Palette::from(&this.pane()?.palette()))
+        });
+        methods.add_method("get_background_tint", |_, this, _: ()| {
+            Ok(this
+                .pane()?
+                .get_background_tint()
+                .map(|tint| tint.to_rgb_string()))
+        });
+        methods.add_method("set_background_tint", |_, this, tint: Option<String>| {
+            let tint = match tint {
+                Some(tint) => Some(
+                    RgbColor::from_named_or_rgb_string(&tint)
+                        .ok_or_else(|| anyhow!("invalid color {:?}", tint))
+                        .map_err(luaerr)?,
+                ),
+                None => None,
+            };
+            this.pane()?.set_background_tint(tint);
+            Ok(())
+        });
+        methods.add_method("get_domain_name", |_, this, _: ()| {
+            let pane = this.pane()?;
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            Ok(mux
+                .get_domain(pane.domain_id())
+                .map(|domain| domain.domain_name().to_string()))
+        });
+        methods.add_method("get_connection_status", |_, this, _: ()| {
+            #[derive(Serialize, Deserialize)]
+            struct Status {
+                domain_name: String,
+                remote_host: Option<String>,
+                connected: bool,
+                responsive: bool,
+                latency_ms: Option<f64>,
+            }
+            impl_lua_conversion!(Status);
+
+            let pane = this.pane()?;
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            let domain = match mux.get_domain(pane.domain_id()) {
+                Some(domain) => domain,
+                None => return Ok(None),
+            };
+            Ok(domain.connection_status().map(|status| Status {
+                domain_name: domain.domain_name().to_string(),
+                remote_host: status.remote_host,
+                connected: status.connected,
+                responsive: status.responsive,
+                latency_ms: status.latency.map(|latency| latency.as_secs_f64() * 1000.0),
+            }))
+        });
+        methods.add_method("is_ime_enabled", |_, this, _: ()| {
+            Ok(this.pane()?.is_ime_enabled())
+        });
+        methods.add_method("set_ime_enabled", |_, this, enabled: bool| {
+            this.pane()?.set_ime_enabled(enabled);
+            Ok(())
+        });
+        methods.add_method("is_read_only", |_, this, _: ()| {
+            Ok(this.pane()?.is_read_only())
+        });
+        methods.add_method("set_read_only", |_, this, read_only: bool| {
+            this.pane()?.set_read_only(read_only);
+            Ok(())
+        });
+        methods.add_method("kill", |_, this, force: Option<bool>| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            mux.close_pane(this.pane, force.unwrap_or(false))
+                .map_err(luaerr)
+        });
 
         // When called with no arguments, returns the lines from the
         // viewport as plain text (no escape sequences).
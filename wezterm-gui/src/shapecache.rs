@@ -1,16 +1,18 @@
-use crate::glyphcache::CachedGlyph;
+use crate::glyphcache::{split_subpixel_offset, CachedGlyph};
 use crate::utilsprites::RenderMetrics;
 use ::window::bitmaps::Texture2d;
 use config::TextStyle;
 use std::rc::Rc;
+use termwiz::cell::Presentation;
 use termwiz::cellcluster::CellCluster;
 use wezterm_font::shaper::GlyphInfo;
 use wezterm_font::units::*;
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct ShapeCacheKey {
     pub style: TextStyle,
     pub text: String,
+    pub presentation: Option<Presentation>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -55,9 +57,15 @@ where
         let mut prior_info: Option<&GlyphInfo> = None;
 
         let cell_width = render_metrics.cell_size.width as f64;
-        let simple_mode = !config::configuration().experimental_shape_post_processing;
+        let config = config::configuration();
+        let simple_mode = !config.experimental_shape_post_processing;
 
         for (info, glyph) in infos.iter().zip(glyphs.iter()) {
+            // The fractional part of the offset has already been
+            // rasterized into the glyph
+            let (sprite_x_offset, _) =
+                split_subpixel_offset(info.x_offset, config.glyph_subpixel_positions);
+
             if simple_mode {
                 pos.push(Some(ShapedInfo {
                     pos: GlyphPosition {
@@ -68,7 +76,7 @@ where
                             .map_or(0, |t| t.coords.width() as u32),
                         cluster: info.cluster,
                         num_cells: info.num_cells,
-                        x_offset: info.x_offset,
+                        x_offset: sprite_x_offset,
                         bearing_x: glyph.bearing_x.get() as f32,
                     },
                     glyph: Rc::clone(glyph),
@@ -159,7 +167,7 @@ where
                         glyph_idx: info.glyph_pos,
                         cluster,
                         num_cells,
-                        x_offset: info.x_offset,
+                        x_offset: sprite_x_offset,
                         bearing_x: bearing_x as f32,
                         bitmap_pixel_width,
                     },
@@ -174,7 +182,7 @@ where
                             .map_or(0, |t| t.coords.width() as u32),
                         cluster: info.cluster,
                         num_cells: info.num_cells,
-                        x_offset: info.x_offset,
+                        x_offset: sprite_x_offset,
                         bearing_x: glyph.bearing_x.get() as f32,
                     },
                     glyph: Rc::clone(glyph),
@@ -198,6 +206,7 @@ where
 pub struct BorrowedShapeCacheKey<'a> {
     pub style: &'a TextStyle,
     pub text: &'a str,
+    pub presentation: Option<Presentation>,
 }
 
 impl<'a> BorrowedShapeCacheKey<'a> {
@@ -205,6 +214,7 @@ impl<'a> BorrowedShapeCacheKey<'a> {
         ShapeCacheKey {
             style: self.style.clone(),
             text: self.text.to_owned(),
+            presentation: self.presentation,
         }
     }
 }
@@ -218,6 +228,7 @@ impl ShapeCacheKeyTrait for ShapeCacheKey {
         BorrowedShapeCacheKey {
             style: &self.style,
             text: &self.text,
+            presentation: self.presentation,
         }
     }
 }
@@ -286,7 +297,7 @@ mod test {
         let cell_clusters = line.cluster();
         assert_eq!(cell_clusters.len(), 1);
         let cluster = &cell_clusters[0];
-        let infos = font.shape(&cluster.text, || {}).unwrap();
+        let infos = font.shape(&cluster.text, None, || {}).unwrap();
         let glyphs = infos
             .iter()
             .map(|info| {
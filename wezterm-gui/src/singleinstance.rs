@@ -0,0 +1,209 @@
+//! Implements `single_instance`: rather than starting another gui
+//! process, `wezterm start` asks a gui that is already running to
+//! spawn the program, using the mux socket that each gui listens on.
+use anyhow::anyhow;
+use codec::{GetCodecVersion, SpawnV2, CODEC_VERSION};
+use config::keyassignment::SpawnTabDomain;
+use mux::connui::ConnectionUI;
+use mux::window::WindowId;
+use portable_pty::cmdbuilder::CommandBuilder;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
+use wezterm_client::client::Client;
+use wezterm_gui_subcommands::StartCommand;
+
+const SOCKET_PREFIX: &str = "gui-sock-";
+
+/// Returns the path to the socket that the gui with the specified
+/// pid listens on
+pub fn gui_socket_path(pid: u32) -> PathBuf {
+    config::RUNTIME_DIR.join(format!("{}{}", SOCKET_PREFIX, pid))
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(windows)]
+fn is_process_alive(_pid: u32) -> bool {
+    // We'll find out when we try to connect
+    true
+}
+
+/// Returns the sockets of the gui processes that appear to be
+/// running, with the most recently started first, except that
+/// the gui hosting the pane that we are running in, if any, is
+/// preferred over the others
+fn running_gui_sockets() -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(&*config::RUNTIME_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut sockets: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let pid: u32 = name.to_str()?.strip_prefix(SOCKET_PREFIX)?.parse().ok()?;
+            if pid == std::process::id() || !is_process_alive(pid) {
+                return None;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    sockets.sort();
+    let mut sockets: Vec<PathBuf> = sockets.into_iter().rev().map(|(_, path)| path).collect();
+
+    if let Some(ours) = std::env::var_os("WEZTERM_UNIX_SOCKET").map(PathBuf::from) {
+        if let Some(idx) = sockets.iter().position(|path| *path == ours) {
+            let ours = sockets.remove(idx);
+            sockets.insert(0, ours);
+        }
+    }
+    sockets
+}
+
+fn connect(socket_path: PathBuf) -> anyhow::Result<Client> {
+    let mut ui = ConnectionUI::new_headless();
+    Client::new_unix_domain(
+        mux::domain::alloc_domain_id(),
+        &config::UnixDomain {
+            socket_path: Some(socket_path),
+            // Never start a mux server on behalf of a gui that went away
+            no_serve_automatically: true,
+            ..Default::default()
+        },
+        true,
+        &mut ui,
+    )
+}
+
+/// Picks the window that should receive a new tab
+async fn newest_window(client: &Client) -> anyhow::Result<Option<WindowId>> {
+    let panes = client.list_panes().await?;
+    Ok(panes
+        .tabs
+        .into_iter()
+        .filter_map(|tabroot| {
+            let mut cursor = tabroot.into_tree().cursor();
+            loop {
+                if let Some(entry) = cursor.leaf_mut() {
+                    return Some(entry.window_id);
+                }
+                match cursor.preorder_next() {
+                    Ok(c) => cursor = c,
+                    Err(_) => return None,
+                }
+            }
+        })
+        .max())
+}
+
+async fn spawn_via_socket(socket_path: PathBuf, opts: &StartCommand) -> anyhow::Result<bool> {
+    let client = match connect(socket_path.clone()) {
+        Ok(client) => client,
+        Err(err) => {
+            log::debug!("unable to connect to {}: {:#}", socket_path.display(), err);
+            return Ok(false);
+        }
+    };
+
+    match client.get_codec_version(GetCodecVersion {}).await {
+        Ok(info) if info.codec_vers == CODEC_VERSION => {}
+        Ok(info) => {
+            log::warn!(
+                "gui at {} is running version {}, which is incompatible \
+                 with this version; starting a new gui instead",
+                socket_path.display(),
+                info.version_string
+            );
+            return Ok(false);
+        }
+        Err(err) => {
+            log::debug!("{}: {:#}", socket_path.display(), err);
+            return Ok(false);
+        }
+    }
+
+    let window_id = if opts.new_tab {
+        newest_window(&client).await?
+    } else {
+        None
+    };
+
+    // The program is spawned by the other gui process, which has a
+    // different working directory than ours, so resolve the directory
+    // relative to our own.  If a program was specified, its arguments
+    // may be relative to our directory, so we need to pass that along.
+    let command_dir = match opts.cwd.as_ref() {
+        Some(cwd) => Some(std::env::current_dir()?.join(cwd)),
+        None if !opts.prog.is_empty() => Some(std::env::current_dir()?),
+        None => None,
+    };
+    let command_dir = match command_dir {
+        Some(dir) => Some(
+            dir.to_str()
+                .ok_or_else(|| anyhow!("{} is not UTF-8", dir.display()))?
+                .to_string(),
+        ),
+        None => None,
+    };
+
+    let spawned = client
+        .spawn_v2(SpawnV2 {
+            domain: SpawnTabDomain::DefaultDomain,
+            window_id,
+            command: if opts.prog.is_empty() {
+                None
+            } else {
+                Some(CommandBuilder::from_argv(opts.prog.clone()))
+            },
+            command_dir,
+            size: config::configuration().initial_size(),
+            workspace: opts.workspace.clone(),
+        })
+        .await?;
+    log::info!(
+        "spawned pane {} in the gui at {}",
+        spawned.pane_id,
+        socket_path.display()
+    );
+    Ok(true)
+}
+
+async fn spawn_in_any_gui(opts: StartCommand) -> anyhow::Result<bool> {
+    for socket_path in running_gui_sockets() {
+        if spawn_via_socket(socket_path, &opts).await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Attempts to have a gui that is already running spawn the program
+/// described by `opts`.
+/// Returns Ok(false) if there is no suitable gui, in which case the
+/// caller should start a new gui.
+pub fn spawn_in_existing_gui(opts: &StartCommand) -> anyhow::Result<bool> {
+    let executor = promise::spawn::SimpleExecutor::new();
+    let result = Rc::new(RefCell::new(None));
+    {
+        let result = Rc::clone(&result);
+        let opts = opts.clone();
+        promise::spawn::spawn(async move {
+            let res = spawn_in_any_gui(opts).await;
+            result.borrow_mut().replace(res);
+        })
+        .detach();
+    }
+    loop {
+        if let Some(res) = result.borrow_mut().take() {
+            return res;
+        }
+        executor.tick()?;
+    }
+}
@@ -1,15 +1,25 @@
-use config::{ConfigHandle, TabBarColors};
+use config::{ConfigHandle, TabAutoTitle, TabBarColors};
+use mux::pane::Pane;
+use mux::tab::TabId;
 use mux::window::Window as MuxWindow;
 use std::cell::Ref;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use termwiz::cell::unicode_column_width;
 use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::ColorSpec;
 use termwiz::escape::csi::Sgr;
+use termwiz::escape::osc::Progress;
 use termwiz::escape::parser::Parser;
 use termwiz::escape::{Action, ControlCode, CSI};
 use unicode_segmentation::UnicodeSegmentation;
 use wezterm_term::Line;
 
+/// The most mask characters to show for a password that is being
+/// typed, so that a long password doesn't crowd out the other tabs
+const MAX_PASSWORD_MASK_LEN: usize = 16;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct TabBarState {
     line: Line,
@@ -46,6 +56,12 @@ impl TabBarState {
     /// mouse_x is some if the mouse is on the same row as the tab bar.
     /// title_width is the total number of cell columns in the window.
     /// window allows access to the tabs associated with the window.
+    /// attention holds the tabs that should show the attention indicator.
+    /// unseen_output holds the tabs that have produced output since they
+    /// were last active.
+    /// password_input holds the tabs whose active pane is reading a
+    /// password, along with the number of characters typed so far.
+    /// titles holds the title of the active pane of each tab.
     pub fn new(
         title_width: usize,
         mouse_x: Option<usize>,
@@ -53,6 +69,10 @@ impl TabBarState {
         colors: Option<&TabBarColors>,
         config: &ConfigHandle,
         right_status: &str,
+        attention: &HashSet<TabId>,
+        unseen_output: &HashSet<TabId>,
+        password_input: &HashMap<TabId, usize>,
+        titles: &HashMap<TabId, String>,
     ) -> Self {
         let colors = colors.cloned().unwrap_or_else(TabBarColors::default);
 
@@ -85,6 +105,11 @@ impl TabBarState {
             inactive_hover_attrs.clone(),
         );
 
+        let unseen_output_indicator = parse_status_text(
+            &config.tab_bar_style.unseen_output_indicator,
+            inactive_cell_attrs.clone(),
+        );
+
         let new_tab_left = parse_status_text(
             &config.tab_bar_style.new_tab_left,
             inactive_cell_attrs.clone(),
@@ -113,7 +138,13 @@ impl TabBarState {
             .enumerate()
             .map(|(idx, tab)| {
                 if let Some(pane) = tab.get_active_pane() {
-                    let mut title = pane.get_title();
+                    let mut title = titles
+                        .get(&tab.tab_id())
+                        .cloned()
+                        .unwrap_or_else(|| compute_pane_title(&pane, config));
+                    if let Some(progress) = format_progress(pane.get_progress()) {
+                        title = format!("{} {}", progress, title);
+                    }
                     if config.show_tab_index_in_tab_bar {
                         title = format!(
                             "{}: {}",
@@ -125,6 +156,21 @@ impl TabBarState {
                             title
                         );
                     }
+                    if let Some(&count) = password_input.get(&tab.tab_id()) {
+                        let mask = if config.password_input_feedback && count > 0 {
+                            format!("{} ", "•".repeat(count.min(MAX_PASSWORD_MASK_LEN)))
+                        } else {
+                            String::new()
+                        };
+                        title = format!(
+                            "{}{}{}",
+                            config.tab_bar_style.password_input_indicator, mask, title
+                        );
+                    }
+                    if attention.contains(&tab.tab_id()) {
+                        title =
+                            format!("{}{}", config.tab_bar_style.tab_attention_indicator, title);
+                    }
                     // We have a preferred soft minimum on tab width to make it
                     // easier to click on tab titles, but we'll still go below
                     // this if there are too many tabs to fit the window at
@@ -138,12 +184,21 @@ impl TabBarState {
                 }
             })
             .collect();
+        let active_tab_no = window.get_active_idx();
+        let tab_unseen: Vec<bool> = window
+            .iter()
+            .enumerate()
+            .map(|(idx, tab)| idx != active_tab_no && unseen_output.contains(&tab.tab_id()))
+            .collect();
+        let num_unseen = tab_unseen.iter().filter(|&&unseen| unseen).count();
+
         let titles_len: usize = tab_titles.iter().map(|s| unicode_column_width(s)).sum();
         let number_of_tabs = tab_titles.len();
 
         let available_cells = title_width.saturating_sub(
             (number_of_tabs.saturating_sub(1)
                 * (inactive_tab_left.len() + inactive_tab_right.len()))
+                + (num_unseen * unseen_output_indicator.len())
                 + (new_tab_left.len() + new_tab_right.len() + 1),
         );
         let tab_width_max = if available_cells >= titles_len {
@@ -157,7 +212,6 @@ impl TabBarState {
 
         let mut line = Line::with_width(title_width);
 
-        let active_tab_no = window.get_active_idx();
         let mut x = 0;
         let mut items = vec![];
 
@@ -165,12 +219,18 @@ impl TabBarState {
             let tab_title_len = unicode_column_width(tab_title).min(tab_width_max);
 
             let active = tab_idx == active_tab_no;
+            let indicator: &[Cell] = if tab_unseen[tab_idx] {
+                &unseen_output_indicator
+            } else {
+                &[]
+            };
             let hover = !active
                 && mouse_x
                     .map(|mouse_x| {
                         mouse_x >= x
                             && mouse_x
                                 < x + tab_title_len
+                                    + indicator.len()
                                     + (inactive_tab_left.len() + inactive_tab_right.len())
                     })
                     .unwrap_or(false);
@@ -198,6 +258,11 @@ impl TabBarState {
                 x += 1;
             }
 
+            for c in indicator {
+                line.set_cell(x, c.clone());
+                x += 1;
+            }
+
             for (idx, sub) in tab_title.graphemes(true).enumerate() {
                 if idx >= tab_width_max {
                     break;
@@ -289,7 +354,113 @@ impl TabBarState {
     }
 }
 
-fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
+/// Returns the title to show for a pane.  Panes that haven't set
+/// a title of their own are titled after their foreground process
+/// and working directory, as configured by `tab_auto_title`.
+pub fn compute_pane_title(pane: &Rc<dyn Pane>, config: &ConfigHandle) -> String {
+    let auto_title = &config.tab_auto_title;
+    if !auto_title.enabled || pane.has_explicit_title() {
+        return pane.get_title();
+    }
+    let process = match pane.get_foreground_process_name() {
+        Some(process) => process,
+        None => return pane.get_title(),
+    };
+
+    let cwd = match pane.get_current_working_dir() {
+        Some(url) => match url.to_file_path() {
+            Ok(path) => {
+                if auto_title.cwd_basename_only {
+                    path.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.display().to_string())
+                } else if let Ok(rel) = path.strip_prefix(&*config::HOME_DIR) {
+                    if rel.as_os_str().is_empty() {
+                        "~".to_string()
+                    } else {
+                        format!("~/{}", rel.display())
+                    }
+                } else {
+                    path.display().to_string()
+                }
+            }
+            // Not a local path; show it as-is
+            Err(_) => url.path().to_string(),
+        },
+        None => String::new(),
+    };
+
+    format_auto_title(&process, &cwd, auto_title)
+}
+
+/// Expands the `tab_auto_title.format` template, eliding the start of
+/// the working directory, and then the end of the title, so that it
+/// fits within `tab_auto_title.max_width` cells
+fn format_auto_title(process: &str, cwd: &str, auto_title: &TabAutoTitle) -> String {
+    let expand = |cwd: &str| {
+        auto_title
+            .format
+            .replace("{process}", process)
+            .replace("{cwd}", cwd)
+    };
+    let mut title = expand(cwd);
+    let max_width = auto_title.max_width;
+    if max_width == 0 {
+        return title.trim().to_string();
+    }
+
+    if unicode_column_width(&title) > max_width && auto_title.format.contains("{cwd}") {
+        let available = max_width.saturating_sub(unicode_column_width(&expand("…")));
+        let mut tail = vec![];
+        let mut width = 0;
+        for g in cwd.graphemes(true).rev() {
+            width += unicode_column_width(g);
+            if width > available {
+                break;
+            }
+            tail.push(g);
+        }
+        tail.reverse();
+        title = expand(&format!("…{}", tail.concat()));
+    }
+
+    if unicode_column_width(&title) > max_width {
+        let mut truncated = String::new();
+        let mut width = 0;
+        for g in title.graphemes(true) {
+            width += unicode_column_width(g);
+            if width > max_width {
+                break;
+            }
+            truncated.push_str(g);
+        }
+        title = truncated;
+    }
+
+    title.trim().to_string()
+}
+
+/// Produces a short textual representation of the progress
+/// reported by a pane, for display in its tab title
+fn format_progress(progress: Progress) -> Option<String> {
+    match progress {
+        Progress::None => None,
+        Progress::SetPercentage(pct) => Some(format!("[{}%]", pct)),
+        Progress::SetError(pct) => Some(format!("[{}% error]", pct)),
+        Progress::Paused(pct) => Some(format!("[{}% paused]", pct)),
+        Progress::Indeterminate => {
+            const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or(0);
+            let frame = (millis / 100) as usize % SPINNER.len();
+            Some(format!("[{}]", SPINNER[frame]))
+        }
+    }
+}
+
+pub fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
     let mut pen = default_cell.clone();
     let mut cells = vec![];
     let mut ignoring = false;
@@ -1,3 +1,5 @@
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource};
 use mux::pane::Pane;
@@ -6,7 +8,7 @@ use mux::Mux;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use wezterm_term::ClipboardSelection;
-use window::{Clipboard, Window, WindowOps};
+use window::{Clipboard, RichClipboardText, Window, WindowOps};
 
 /// ClipboardHelper bridges between the window crate clipboard
 /// manipulation and the term crate clipboard interface
@@ -88,6 +90,72 @@ impl TermWindow {
         }
     }
 
+    /// Like copy_to_clipboard, but offers the richer representations
+    /// of the text on systems that support them
+    pub fn copy_rich_text_to_clipboard(
+        &self,
+        clipboard: ClipboardCopyDestination,
+        content: RichClipboardText,
+    ) {
+        let clipboard = match clipboard {
+            ClipboardCopyDestination::Clipboard => [Some(Clipboard::Clipboard), None],
+            ClipboardCopyDestination::PrimarySelection => [Some(Clipboard::PrimarySelection), None],
+            ClipboardCopyDestination::ClipboardAndPrimarySelection => [
+                Some(Clipboard::Clipboard),
+                Some(Clipboard::PrimarySelection),
+            ],
+        };
+        for &c in &clipboard {
+            if let Some(c) = c {
+                self.window
+                    .as_ref()
+                    .unwrap()
+                    .set_clipboard_rich(c, content.clone());
+            }
+        }
+    }
+
+    /// Pastes `text` into `pane`, after giving the `paste` event
+    /// a chance to transform or cancel it
+    pub fn paste_text(&self, pane: &Rc<dyn Pane>, text: String) {
+        if self.reject_read_only_input(pane, false) {
+            return;
+        }
+        let window = GuiWin::new(self);
+        let pane_object = PaneObject::new(pane);
+        let pane = Rc::clone(pane);
+
+        async fn paste(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane_object: PaneObject,
+            pane: Rc<dyn Pane>,
+            text: String,
+        ) -> anyhow::Result<()> {
+            let text = match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window, pane_object))?;
+                    config::lua::emit_transform_event(&lua, ("paste".to_string(), args, text))
+                        .await
+                        .map_err(|e| {
+                            log::error!("while processing paste event: {:#}", e);
+                            e
+                        })?
+                }
+                None => Some(text),
+            };
+            if let Some(text) = text {
+                pane.trickle_paste(text)?;
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            paste(lua, window, pane_object, pane, text)
+        }))
+        .detach();
+    }
+
     pub fn paste_from_clipboard(&mut self, pane: &Rc<dyn Pane>, clipboard: ClipboardPasteSource) {
         let pane_id = pane.pane_id();
         let window = self.window.as_ref().unwrap().clone();
@@ -109,7 +177,7 @@ impl TermWindow {
                                     mux.get_pane(pane_id)
                                 })
                             {
-                                pane.trickle_paste(clip).ok();
+                                term_window.paste_text(&pane, clip);
                             }
                         }
                         Ok(())
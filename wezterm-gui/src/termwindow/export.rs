@@ -0,0 +1,80 @@
+use config::keyassignment::{PaneExport, PaneExportFormat};
+use mux::pane::Pane;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
+use wezterm_term::StableRowIndex;
+use wezterm_toast_notification::persistent_toast_notification;
+
+impl super::TermWindow {
+    /// Renders the viewport or the scrollback of the pane to
+    /// an HTML or SVG file, as requested by the ExportPane assignment
+    pub fn export_pane(&self, pane: &Rc<dyn Pane>, export: &PaneExport) {
+        let path = match &export.path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                config::HOME_DIR.join(format!(
+                    "wezterm-pane-{}-{}.{}",
+                    pane.pane_id(),
+                    now,
+                    export.format.extension()
+                ))
+            }
+        };
+
+        let dims = pane.get_dimensions();
+        let range = if export.scrollback {
+            dims.scrollback_top..dims.physical_top + dims.viewport_rows as StableRowIndex
+        } else {
+            let top = self
+                .get_viewport(pane.pane_id())
+                .unwrap_or(dims.physical_top);
+            top..top + dims.viewport_rows as StableRowIndex
+        };
+
+        let (_first_row, lines) = pane.get_lines(range);
+        let lines: Vec<_> = lines
+            .into_iter()
+            .map(|line| {
+                let len = line.cells().len();
+                (line, 0..len)
+            })
+            .collect();
+        let palette = pane.palette();
+        let font_family = self.config.font.font.first().map(|f| f.family.as_str());
+
+        let content = match export.format {
+            PaneExportFormat::Html => wezterm_term::export::lines_to_html_document(
+                &lines,
+                &palette,
+                font_family,
+                &pane.get_title(),
+            ),
+            PaneExportFormat::Svg => {
+                wezterm_term::export::lines_to_svg(&lines, &palette, font_family)
+            }
+        };
+
+        match std::fs::write(&path, content) {
+            Ok(_) => {
+                log::info!("exported pane {} to {}", pane.pane_id(), path.display());
+                persistent_toast_notification(
+                    "Exported pane",
+                    &format!("Saved to {}", path.display()),
+                );
+            }
+            Err(err) => {
+                log::error!(
+                    "failed to export pane {} to {}: {:#}",
+                    pane.pane_id(),
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use mux::pane::Pane;
+use std::rc::Rc;
 
 pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::input::Modifiers {
     let mut result = termwiz::input::Modifiers::NONE;
@@ -50,6 +52,15 @@ impl super::TermWindow {
             None => return false,
         };
 
+        // Only look up the foreground process when there are
+        // key assignments that depend on it
+        let process = if self.input_map.has_conditional_keys() {
+            pane.get_foreground_process_name()
+        } else {
+            None
+        };
+        let process = process.as_deref();
+
         // The leader key is a kind of modal modifier key.
         // It is allowed to be active for up to the leader timeout duration,
         // after which it auto-deactivates.
@@ -91,10 +102,11 @@ impl super::TermWindow {
                 }
             }
 
-            if let Some(assignment) = self
-                .input_map
-                .lookup_key(&raw_code_key, window_key.raw_modifiers | leader_mod)
-            {
+            if let Some(assignment) = self.input_map.lookup_key(
+                &raw_code_key,
+                window_key.raw_modifiers | leader_mod,
+                process,
+            ) {
                 self.perform_key_assignment(&pane, &assignment).ok();
                 context.invalidate();
 
@@ -120,9 +132,9 @@ impl super::TermWindow {
                 }
             }
 
-            if let Some(assignment) = self
-                .input_map
-                .lookup_key(key, window_key.raw_modifiers | leader_mod)
+            if let Some(assignment) =
+                self.input_map
+                    .lookup_key(key, window_key.raw_modifiers | leader_mod, process)
             {
                 self.perform_key_assignment(&pane, &assignment).ok();
                 context.invalidate();
@@ -160,9 +172,13 @@ impl super::TermWindow {
                         && !config.send_composed_key_when_alt_is_pressed);
 
                 if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(&key) {
+                    if bypass_compose && self.reject_read_only_input(&pane, key.is_modifier()) {
+                        return true;
+                    }
                     if bypass_compose && pane.key_down(term_key, raw_modifiers).is_ok() {
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
+                            self.note_password_input_key(&pane, term_key, raw_modifiers);
                         }
                         context.invalidate();
                         return true;
@@ -184,9 +200,9 @@ impl super::TermWindow {
             }
         }
 
-        if let Some(assignment) = self
-            .input_map
-            .lookup_key(&window_key.key, window_key.modifiers | leader_mod)
+        if let Some(assignment) =
+            self.input_map
+                .lookup_key(&window_key.key, window_key.modifiers | leader_mod, process)
         {
             self.perform_key_assignment(&pane, &assignment).ok();
             context.invalidate();
@@ -208,9 +224,12 @@ impl super::TermWindow {
             let key = self.win_key_code_to_termwiz_key_code(&window_key.key);
             match key {
                 Key::Code(key) => {
-                    if pane.key_down(key, modifiers).is_ok() {
+                    if self.reject_read_only_input(&pane, key.is_modifier()) {
+                        true
+                    } else if pane.key_down(key, modifiers).is_ok() {
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
+                            self.note_password_input_key(&pane, key, modifiers);
                         }
                         context.invalidate();
                         true
@@ -224,9 +243,18 @@ impl super::TermWindow {
                         // a registered key binding; swallow this event and cancel
                         // the leader modifier.
                         self.leader_is_down.take();
+                    } else if self.reject_read_only_input(&pane, false) {
+                        // The input is dropped
                     } else {
                         pane.writer().write_all(s.as_bytes()).ok();
                         self.maybe_scroll_to_bottom_for_input(&pane);
+                        for c in s.chars() {
+                            self.note_password_input_key(
+                                &pane,
+                                ::termwiz::input::KeyCode::Char(c),
+                                ::termwiz::input::Modifiers::NONE,
+                            );
+                        }
                         context.invalidate();
                     }
                     true
@@ -236,6 +264,40 @@ impl super::TermWindow {
         }
     }
 
+    /// Keeps count of the characters typed into a pane that is reading
+    /// a password, so that they can be shown masked in the tab bar
+    /// when `password_input_feedback` is enabled
+    fn note_password_input_key(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        key: ::termwiz::input::KeyCode,
+        mods: ::termwiz::input::Modifiers,
+    ) {
+        use ::termwiz::input::KeyCode as KC;
+        use ::termwiz::input::Modifiers as Mods;
+
+        {
+            let mut state = self.pane_state(pane.pane_id());
+            let count = match state.password_input.as_mut() {
+                Some(count) => count,
+                None => return,
+            };
+            match key {
+                KC::Backspace => *count = count.saturating_sub(1),
+                KC::Enter => *count = 0,
+                KC::Char('c') | KC::Char('u') if mods == Mods::CTRL => *count = 0,
+                KC::Char(_) if !mods.intersects(Mods::CTRL | Mods::ALT | Mods::SUPER) => {
+                    *count += 1
+                }
+                _ => return,
+            }
+        }
+
+        if self.config.password_input_feedback {
+            self.update_title_impl();
+        }
+    }
+
     fn win_key_code_to_termwiz_key_code(&self, key: &::window::KeyCode) -> Key {
         use ::termwiz::input::KeyCode as KC;
         use ::window::KeyCode as WK;
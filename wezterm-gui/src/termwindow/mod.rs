@@ -2,17 +2,21 @@
 use super::quad::*;
 use super::renderstate::*;
 use super::utilsprites::RenderMetrics;
-use crate::glium::texture::SrgbTexture2d;
+use crate::glyphcache::AtlasTexture;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, SearchOverlay,
+    add_bookmark, bookmark_list, confirm_close_pane, confirm_close_tab, confirm_close_window,
+    confirm_quit_program, debug_overlay, keybinding_help, launcher, lua_overlay, search_results,
+    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, LinkHintOverlay, SearchHit,
+    SearchOverlay,
 };
+use crate::rasterqueue::RasterQueue;
 use crate::scripting::guiwin::GuiWin;
 use crate::scripting::pane::PaneObject;
 use crate::scrollbar::*;
 use crate::selection::Selection;
 use crate::shapecache::*;
 use crate::tabbar::TabBarState;
+use crate::termwindow::mouseevent::WheelScroll;
 use ::wezterm_term::input::MouseButton as TMB;
 use ::window::*;
 use anyhow::{anyhow, ensure};
@@ -23,49 +27,104 @@ use config::{configuration, ConfigHandle, WindowCloseConfirmation};
 use lru::LruCache;
 use mux::activity::Activity;
 use mux::domain::{DomainId, DomainState};
-use mux::pane::{Pane, PaneId};
+use mux::pane::{Bookmark, Pane, PaneId};
 use mux::renderable::RenderableDimensions;
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection, TabId};
 use mux::window::WindowId as MuxWindowId;
-use mux::{Mux, MuxNotification};
+use mux::{EventRequest, Mux, MuxNotification};
 use portable_pty::PtySize;
+use regex::Regex;
 use std::any::Any;
 use std::cell::{RefCell, RefMut};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Add;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use termwiz::escape::osc::Progress;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::image::ImageData;
 use wezterm_font::FontConfiguration;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
-use wezterm_term::{StableRowIndex, TerminalConfiguration};
+use wezterm_term::{Alert, StableRowIndex, TerminalConfiguration};
 
 pub mod clipboard;
+mod export;
 mod keyevent;
+mod monitor;
 mod mouseevent;
+mod palettetransition;
+mod perf;
 mod prevcursor;
 mod render;
 pub mod resize;
+mod screenshot;
 mod selection;
 pub mod spawn;
+mod zoom;
 use clipboard::ClipboardHelper;
+pub use palettetransition::{PaletteEntry, MAX_PALETTE_SLOTS};
 use prevcursor::PrevCursorPos;
 use spawn::SpawnWhere;
+use zoom::PresentationZoom;
 
 const ATLAS_SIZE: usize = 128;
 
+const DEFAULT_WINDOW_CLASS: &str = "org.wezfurlong.wezterm";
+
 lazy_static::lazy_static! {
-    static ref WINDOW_CLASS: Mutex<String> = Mutex::new("org.wezfurlong.wezterm".to_owned());
+    static ref WINDOW_CLASS: Mutex<Option<String>> = Mutex::new(None);
+    static ref MUX_WINDOW_CLASS: Mutex<HashMap<MuxWindowId, String>> = Mutex::new(HashMap::new());
 }
 
 pub const ICON_DATA: &'static [u8] = include_bytes!("../../../assets/icon/terminal.png");
 
 pub fn set_window_class(cls: &str) {
-    *WINDOW_CLASS.lock().unwrap() = cls.to_owned();
+    WINDOW_CLASS.lock().unwrap().replace(cls.to_owned());
+}
+
+/// Arranges for the gui window that is subsequently created for
+/// `mux_window_id` to use `cls` as its class, rather than the
+/// class that is used for all other windows
+pub fn set_window_class_for_mux_window(mux_window_id: MuxWindowId, cls: &str) {
+    MUX_WINDOW_CLASS
+        .lock()
+        .unwrap()
+        .insert(mux_window_id, cls.to_owned());
+}
+
+/// Compiles the mask_patterns from the config, skipping
+/// and logging any that are invalid
+fn compile_mask_patterns(config: &ConfigHandle) -> Vec<Regex> {
+    config
+        .mask_patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(err) => {
+                log::error!("invalid mask_patterns entry {:?}: {}", pattern, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Resolves the class for a new gui window; a class requested for
+/// the specific window takes precedence over `wezterm start --class`,
+/// which in turn takes precedence over the `window_class` config
+fn window_class_for_mux_window(mux_window_id: MuxWindowId, config: &ConfigHandle) -> String {
+    if let Some(cls) = MUX_WINDOW_CLASS.lock().unwrap().remove(&mux_window_id) {
+        return cls;
+    }
+    if let Some(cls) = WINDOW_CLASS.lock().unwrap().as_ref() {
+        return cls.clone();
+    }
+    config
+        .window_class
+        .clone()
+        .unwrap_or_else(|| DEFAULT_WINDOW_CLASS.to_owned())
 }
 
 #[derive(Default, Clone)]
@@ -79,6 +138,31 @@ pub struct PaneState {
     /// contents, we're overlaying a little internal application
     /// tab.  We'll also route input to it.
     pub overlay: Option<Rc<dyn Pane>>,
+    /// Some while the pane is reading a password, holding the
+    /// number of characters that have been typed since the
+    /// prompt appeared
+    password_input: Option<usize>,
+    /// Set when the text matched by mask_patterns is
+    /// revealed using ToggleOutputMasking
+    output_unmasked: bool,
+    /// When input was last rejected because the pane is
+    /// read-only; the pane is flashed for a moment after this
+    read_only_flash: Option<Instant>,
+    /// Set when the pane produces output while its viewport is
+    /// scrolled back; cleared when it returns to the bottom
+    output_while_scrolled: bool,
+    /// Set when the timestamps of all lines are displayed
+    /// using ToggleLineTimestamps
+    show_line_timestamps: bool,
+    /// The palette of the pane when it was last painted, and the
+    /// generation of the configuration at that time
+    palette: Option<(ColorPalette, usize)>,
+    /// Set while a change to the palette of the pane is animated
+    palette_transition: Option<palettetransition::PaletteTransition>,
+    /// The default title that was last passed to the format-pane-title
+    /// event, and the title that the event produced for it
+    title_event_input: Option<String>,
+    formatted_title: Option<String>,
 }
 
 #[derive(Default, Clone)]
@@ -87,6 +171,9 @@ pub struct TabState {
     /// contents, we're overlaying a little internal application
     /// tab.  We'll also route input to it.
     pub overlay: Option<Rc<dyn Pane>>,
+    /// Set when a pane in the tab produces output while the
+    /// tab is not active; cleared when the tab is activated.
+    pub has_unseen_output: bool,
 }
 
 /// Manages the state/queue of lua based event handlers.
@@ -113,10 +200,12 @@ pub struct TermWindow {
     fonts: Rc<FontConfiguration>,
     /// Window dimensions and dpi
     pub dimensions: Dimensions,
-    pub is_full_screen: bool,
+    pub window_state: WindowState,
     /// Terminal dimensions
     terminal_size: PtySize,
     pub mux_window_id: MuxWindowId,
+    /// The X11 WM_CLASS, Wayland app_id or Windows window class
+    window_class: String,
     pub render_metrics: RenderMetrics,
     render_state: Option<RenderState>,
     input_map: InputMap,
@@ -139,6 +228,11 @@ pub struct TermWindow {
     pane_state: RefCell<HashMap<PaneId, PaneState>>,
 
     window_background: Option<Arc<ImageData>>,
+    /// The source of the window_background_shader
+    background_shader: Option<String>,
+    /// The reference point for the `time` uniform of the
+    /// window_background_shader
+    created_at: Instant,
 
     /// Gross workaround for managing async keyboard fetching
     /// just for middle mouse button paste function
@@ -149,19 +243,52 @@ pub struct TermWindow {
     /// Keeps track of double and triple clicks
     last_mouse_click: Option<LastMouseClick>,
 
+    /// Keeps track of the speed of the mouse wheel
+    wheel_scroll: WheelScroll,
+
     /// The URL over which we are currently hovering
     current_highlight: Option<Arc<Hyperlink>>,
+    /// The line with a timestamp over which we are currently hovering
+    hovered_line_timestamp: Option<(PaneId, StableRowIndex)>,
 
     shape_cache:
-        RefCell<LruCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>>>>,
+        RefCell<LruCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo<AtlasTexture>>>>>>,
+    /// Timings of the recently painted frames
+    perf: RefCell<perf::FramePerf>,
+    /// Whether the glyph cache statistics are shown over the
+    /// active pane, as toggled by ToggleDebugHud
+    show_debug_hud: bool,
 
     last_blink_paint: Instant,
     last_status_call: Instant,
 
     palette: Option<ColorPalette>,
+    /// Set while a change to the palette of the window is animated
+    palette_transition: Option<palettetransition::PaletteTransition>,
+    /// The panes whose palettes are held by each row of the palette
+    /// transition texture, in the order that they were painted
+    palette_slots: RefCell<Vec<PaneId>>,
 
     event_states: HashMap<String, EventState>,
     has_animation: RefCell<Option<Instant>>,
+
+    pane_activity: HashMap<PaneId, monitor::PaneActivity>,
+
+    /// The progress reported by the active pane of each tab,
+    /// as of the last periodic_window_maintenance call
+    tab_progress: Vec<Progress>,
+    taskbar_progress: TaskbarProgress,
+
+    /// Whether the input method is enabled for the window; this
+    /// follows the IME state of the active pane
+    ime_enabled: bool,
+    /// Whether the input method is in the middle of composing text
+    ime_composing: bool,
+
+    /// The compiled mask_patterns
+    mask_patterns: Vec<Regex>,
+
+    zoom: PresentationZoom,
 }
 
 impl WindowCallbacks for TermWindow {
@@ -226,28 +353,36 @@ impl WindowCallbacks for TermWindow {
         }
     }
 
+    fn ime_composition_status(&mut self, composing: bool) {
+        if composing != self.ime_composing {
+            self.ime_composing = composing;
+            self.schedule_status_update();
+        }
+    }
+
     fn mouse_event(&mut self, event: &MouseEvent, context: &dyn WindowOps) {
-        self.mouse_event_impl(event, context)
+        let event = self.presentation_zoom_mouse_event(event);
+        self.mouse_event_impl(&event, context)
     }
 
-    fn resize(&mut self, dimensions: Dimensions, is_full_screen: bool) {
+    fn resize(&mut self, dimensions: Dimensions, window_state: WindowState) {
         log::trace!(
-            "resize event, current cells: {:?}, new dims: {:?} is_full_screen:{}",
+            "resize event, current cells: {:?}, new dims: {:?} window_state:{:?}",
             self.current_cell_dimensions(),
             dimensions,
-            is_full_screen,
+            window_state,
         );
         if dimensions.pixel_width == 0 || dimensions.pixel_height == 0 {
             // on windows, this can happen when minimizing the window.
             // NOP!
             return;
         }
-        if self.dimensions == dimensions && self.is_full_screen == is_full_screen {
+        if self.dimensions == dimensions && self.window_state == window_state {
             // It didn't really change
             return;
         }
-        self.is_full_screen = is_full_screen;
-        self.scaling_changed(dimensions, self.fonts.get_font_scale());
+        self.window_state = window_state;
+        self.scaling_changed(dimensions, self.base_font_scale());
         self.emit_window_event("window-resized");
     }
 
@@ -259,11 +394,15 @@ impl WindowCallbacks for TermWindow {
         log::error!("context was lost, set up a new window");
         let activity = Activity::new();
 
+        // The replacement window starts out unzoomed
+        self.discard_presentation_zoom_raster();
+
         let render_state = None;
 
         let clipboard_contents = Arc::clone(&self.clipboard_contents);
         let dimensions = self.dimensions.clone();
         let mux_window_id = self.mux_window_id;
+        let window_class = self.window_class.clone();
         let config = self.config.clone();
 
         let guts = Box::new(Self {
@@ -271,13 +410,18 @@ impl WindowCallbacks for TermWindow {
             config: self.config.clone(),
             config_overrides: self.config_overrides.clone(),
             window_background: self.window_background.clone(),
+            background_shader: self.background_shader.clone(),
+            created_at: Instant::now(),
             palette: None,
+            palette_transition: None,
+            palette_slots: RefCell::new(vec![]),
             focused: None,
             mux_window_id,
+            window_class: self.window_class.clone(),
             fonts: Rc::clone(&self.fonts),
             render_metrics: self.render_metrics.clone(),
             dimensions,
-            is_full_screen: self.is_full_screen,
+            window_state: self.window_state,
             terminal_size: self.terminal_size.clone(),
             render_state,
             input_map: InputMap::new(),
@@ -299,12 +443,23 @@ impl WindowCallbacks for TermWindow {
             pane_state: RefCell::new(self.pane_state.borrow().clone()),
             current_mouse_button: self.current_mouse_button.clone(),
             last_mouse_click: self.last_mouse_click.clone(),
+            wheel_scroll: self.wheel_scroll.clone(),
             current_highlight: self.current_highlight.clone(),
+            hovered_line_timestamp: self.hovered_line_timestamp,
             shape_cache: RefCell::new(LruCache::new(65536)),
+            perf: RefCell::new(perf::FramePerf::default()),
+            show_debug_hud: self.show_debug_hud,
             last_blink_paint: Instant::now(),
             last_status_call: Instant::now(),
             event_states: HashMap::new(),
             has_animation: RefCell::new(None),
+            pane_activity: self.pane_activity.clone(),
+            tab_progress: vec![],
+            taskbar_progress: TaskbarProgress::None,
+            ime_enabled: true,
+            ime_composing: false,
+            mask_patterns: self.mask_patterns.clone(),
+            zoom: PresentationZoom::default(),
         });
         prior_window.close();
 
@@ -312,7 +467,7 @@ impl WindowCallbacks for TermWindow {
             smol::Timer::after(Duration::from_millis(300)).await;
             log::error!("now try making that new window");
             let window = Window::new_window(
-                &*WINDOW_CLASS.lock().unwrap(),
+                &window_class,
                 "wezterm",
                 dimensions.pixel_width,
                 dimensions.pixel_height,
@@ -350,7 +505,35 @@ impl WindowCallbacks for TermWindow {
             self.dimensions.pixel_width,
             self.dimensions.pixel_height,
         ) {
-            Ok(gl) => {
+            Ok(mut gl) => {
+                if let Err(err) = gl.set_background_effect(self.background_shader.as_deref()) {
+                    log::error!("Failed to compile window_background_shader: {:#}", err);
+                }
+                if self.config.async_glyph_rasterization || self.config.glyph_cache_prewarm {
+                    let window = window.clone();
+                    match RasterQueue::new(move || {
+                        // Repaint once glyphs have been rasterized, so
+                        // that their placeholders are replaced.  Runs
+                        // holding placeholders aren't in the shape cache,
+                        // so they are shaped again.  Glyphs that were
+                        // only prewarmed haven't been shown.
+                        if configuration().async_glyph_rasterization {
+                            let window = window.clone();
+                            promise::spawn::spawn_into_main_thread(async move {
+                                window.invalidate();
+                            })
+                            .detach();
+                        }
+                    }) {
+                        Ok(queue) => {
+                            gl.glyph_cache.borrow_mut().raster_queue.replace(queue);
+                            gl.prewarm_glyph_cache();
+                        }
+                        Err(err) => {
+                            log::error!("Failed to start glyph rasterizer thread: {:#}", err);
+                        }
+                    }
+                }
                 log::info!(
                     "OpenGL initialized! {} {} is_context_loss_possible={} wezterm version: {}",
                     gl.context.get_opengl_renderer_string(),
@@ -432,11 +615,27 @@ fn reload_background_image(
     }
 }
 
+fn load_background_shader(config: &ConfigHandle) -> Option<String> {
+    let path = config.window_background_shader.as_ref()?;
+    match std::fs::read_to_string(path) {
+        Ok(source) => Some(source),
+        Err(err) => {
+            log::error!(
+                "Failed to load window_background_shader {}: {}",
+                path.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
 impl TermWindow {
     pub fn new_window(mux_window_id: MuxWindowId) -> anyhow::Result<()> {
         let config = configuration();
 
         let window_background = load_background_image(&config);
+        let background_shader = load_background_shader(&config);
 
         let fontconfig = Rc::new(FontConfiguration::new(Some(config.clone()))?);
         let mux = Mux::get().expect("to be main thread with mux running");
@@ -487,24 +686,30 @@ impl TermWindow {
         let render_state = None;
 
         let clipboard_contents = Arc::new(Mutex::new(None));
+        let window_class = window_class_for_mux_window(mux_window_id, &config);
 
         let window = Window::new_window(
-            &*WINDOW_CLASS.lock().unwrap(),
+            &window_class,
             "wezterm",
             dimensions.pixel_width,
             dimensions.pixel_height,
             Box::new(Self {
                 window: None,
                 window_background,
+                background_shader,
+                created_at: Instant::now(),
                 config: config.clone(),
                 config_overrides: serde_json::Value::default(),
                 palette: None,
+                palette_transition: None,
+                palette_slots: RefCell::new(vec![]),
                 focused: None,
                 mux_window_id,
+                window_class: window_class.clone(),
                 fonts: fontconfig,
                 render_metrics,
                 dimensions,
-                is_full_screen: false,
+                window_state: WindowState::empty(),
                 terminal_size,
                 render_state,
                 input_map: InputMap::new(),
@@ -526,12 +731,23 @@ impl TermWindow {
                 pane_state: RefCell::new(HashMap::new()),
                 current_mouse_button: None,
                 last_mouse_click: None,
+                wheel_scroll: WheelScroll::default(),
                 current_highlight: None,
+                hovered_line_timestamp: None,
                 shape_cache: RefCell::new(LruCache::new(65536)),
+                perf: RefCell::new(perf::FramePerf::default()),
+                show_debug_hud: false,
                 last_blink_paint: Instant::now(),
                 last_status_call: Instant::now(),
                 event_states: HashMap::new(),
                 has_animation: RefCell::new(None),
+                pane_activity: HashMap::new(),
+                tab_progress: vec![],
+                taskbar_progress: TaskbarProgress::None,
+                ime_enabled: true,
+                ime_composing: false,
+                mask_patterns: compile_mask_patterns(&config),
+                zoom: PresentationZoom::default(),
             }),
             Some(&config),
         )?;
@@ -606,6 +822,16 @@ impl TermWindow {
     }
 
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
+        self.note_pane_output(pane_id);
+        self.note_unseen_output(pane_id);
+        self.maybe_scroll_to_bottom_for_output(pane_id);
+        {
+            let mut state = self.pane_state(pane_id);
+            if state.viewport.is_some() {
+                state.output_while_scrolled = true;
+            }
+        }
+
         if let Some(pane) = self.get_active_pane_or_overlay() {
             if pane.pane_id() == pane_id {
                 let mux = Mux::get().expect("mux started and running on main thread");
@@ -659,6 +885,75 @@ impl TermWindow {
                     Ok(())
                 });
             }
+        } else if let MuxNotification::Alert {
+            pane_id,
+            alert: Alert::PaletteChanged,
+        } = n
+        {
+            let mux = Mux::get().expect("mux is calling us");
+            let pane_in_window = mux
+                .get_window(mux_window_id)
+                .map(|w| w.iter().any(|tab| tab.contains_pane(pane_id)))
+                .unwrap_or(false);
+
+            if pane_in_window {
+                window.apply(move |myself, _window| {
+                    if let Some(myself) = myself.downcast_mut::<Self>() {
+                        let mux = Mux::get().expect("mux is calling us");
+                        if let Some(pane) = mux.get_pane(pane_id) {
+                            myself.emit_pane_event(&pane, "palette-changed");
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        } else if let MuxNotification::DomainConnectionChanged(domain_id) = n {
+            window.apply(move |myself, _window| {
+                if let Some(myself) = myself.downcast_mut::<Self>() {
+                    myself.domain_connection_changed(domain_id);
+                }
+                Ok(())
+            });
+        } else if let MuxNotification::ScreenshotRequested(request) = n {
+            let mux = Mux::get().expect("mux is calling us");
+            let pane_in_window = mux
+                .get_window(mux_window_id)
+                .map(|w| w.iter().any(|tab| tab.contains_pane(request.pane_id)))
+                .unwrap_or(false);
+
+            if pane_in_window {
+                window.apply(move |myself, _window| {
+                    if let Some(myself) = myself.downcast_mut::<Self>() {
+                        let result = myself.screenshot_pane(request.pane_id, request.scale);
+                        request.reply.try_send(result).ok();
+                    }
+                    Ok(())
+                });
+            }
+        } else if let MuxNotification::EventRequested(request) = n {
+            let mux = Mux::get().expect("mux is calling us");
+            let pane_in_window = mux
+                .get_window(mux_window_id)
+                .map(|w| w.iter().any(|tab| tab.contains_pane(request.pane_id)))
+                .unwrap_or(false);
+
+            if pane_in_window {
+                window.apply(move |myself, _window| {
+                    if let Some(myself) = myself.downcast_mut::<Self>() {
+                        myself.emit_requested_event(request);
+                    }
+                    Ok(())
+                });
+            }
+        } else if let MuxNotification::PerfStatsRequested(request) = n {
+            if request.window_id == mux_window_id {
+                window.apply(move |myself, _window| {
+                    if let Some(myself) = myself.downcast_mut::<Self>() {
+                        request.reply.try_send(myself.window_perf()).ok();
+                    }
+                    Ok(())
+                });
+            }
         }
 
         true
@@ -725,6 +1020,256 @@ impl TermWindow {
         .detach();
     }
 
+    /// Returns the title to display for `pane`.  Whenever the default
+    /// title changes, it is passed to the format-pane-title event, and
+    /// the title that the event produces is displayed once it is known.
+    fn pane_title(&self, pane: &Rc<dyn Pane>) -> String {
+        let title = crate::tabbar::compute_pane_title(pane, &self.config);
+        let pane_id = pane.pane_id();
+        let changed = self.pane_state(pane_id).title_event_input.as_ref() != Some(&title);
+        if changed {
+            self.pane_state(pane_id)
+                .title_event_input
+                .replace(title.clone());
+            self.schedule_format_pane_title(pane, title.clone());
+        }
+        self.pane_state(pane_id)
+            .formatted_title
+            .clone()
+            .unwrap_or(title)
+    }
+
+    fn schedule_format_pane_title(&self, pane: &Rc<dyn Pane>, title: String) {
+        let window = GuiWin::new(self);
+        let pane_id = pane.pane_id();
+        let pane = PaneObject::new(pane);
+
+        async fn format_title(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            pane_id: PaneId,
+            title: String,
+        ) -> anyhow::Result<()> {
+            let formatted = match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window.clone(), pane))?;
+                    config::lua::emit_transform_event(
+                        &lua,
+                        ("format-pane-title".to_string(), args, title.clone()),
+                    )
+                    .await
+                    .map_err(|e| {
+                        log::error!("while processing format-pane-title event: {:#}", e);
+                        e
+                    })?
+                }
+                None => None,
+            };
+            // Returning false from a handler keeps the default title
+            let formatted = formatted.unwrap_or_else(|| title.clone());
+
+            window
+                .with_term_window(move |myself, _| {
+                    myself.apply_formatted_pane_title(pane_id, &title, formatted.clone());
+                    Ok(())
+                })
+                .await?;
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            format_title(lua, window, pane, pane_id, title)
+        }))
+        .detach();
+    }
+
+    fn apply_formatted_pane_title(&mut self, pane_id: PaneId, input: &str, formatted: String) {
+        {
+            let mut state = self.pane_state(pane_id);
+            // Ignore the result if the default title has changed since
+            if state.title_event_input.as_deref() != Some(input)
+                || state.formatted_title.as_ref() == Some(&formatted)
+            {
+                return;
+            }
+            state.formatted_title.replace(formatted);
+        }
+        self.update_title_post_status();
+    }
+
+    /// Opens `link` on behalf of `pane`.
+    /// We need to ensure that we spawn the `open` call outside of the context
+    /// of our window loop; on Windows it can cause a panic due to
+    /// triggering our WndProc recursively.
+    /// We get that assurance for free as part of the async dispatch that we
+    /// perform below; here we allow the user to define an `open-uri` event
+    /// handler that can bypass the normal `open::that` functionality.
+    pub fn open_link(&self, pane: &Rc<dyn Pane>, link: String) {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+
+        async fn open_uri(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            link: String,
+        ) -> anyhow::Result<()> {
+            let default_click = match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window, pane, link.clone()))?;
+                    config::lua::emit_event(&lua, ("open-uri".to_string(), args))
+                        .await
+                        .map_err(|e| {
+                            log::error!("while processing open-uri event: {:#}", e);
+                            e
+                        })?
+                }
+                None => true,
+            };
+            if default_click {
+                log::info!("clicking {}", link);
+                if let Err(err) = open::that(&link) {
+                    log::error!("failed to open {}: {:?}", link, err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            open_uri(lua, window, pane, link)
+        }))
+        .detach();
+    }
+
+    /// Opens `path` at `line` using the `editor_command` from the config.
+    /// A relative `path` is resolved against the working directory of
+    /// `pane`.  If no `editor_command` is configured then the file is
+    /// opened as a link, via `open_link`.
+    pub fn open_in_editor(&self, pane: &Rc<dyn Pane>, path: &str, line: usize) {
+        let cwd = pane
+            .get_current_working_dir()
+            .and_then(|url| url.to_file_path().ok());
+        let path = match &cwd {
+            Some(cwd) => cwd.join(path),
+            None => std::path::PathBuf::from(path),
+        };
+
+        let argv = match self.config.editor_command.as_ref() {
+            Some(argv) if !argv.is_empty() => argv.clone(),
+            _ => {
+                let url = match url::Url::from_file_path(&path) {
+                    Ok(url) => url,
+                    Err(()) => {
+                        log::error!("cannot open {} as a link", path.display());
+                        return;
+                    }
+                };
+                self.open_link(pane, url.to_string());
+                return;
+            }
+        };
+
+        let path = path.to_string_lossy().to_string();
+        let line = line.to_string();
+        let argv: Vec<String> = argv
+            .iter()
+            .map(|arg| arg.replace("{path}", &path).replace("{line}", &line))
+            .collect();
+
+        promise::spawn::spawn(async move {
+            let mut cmd = std::process::Command::new(&argv[0]);
+            cmd.args(&argv[1..]);
+            if let Some(cwd) = cwd {
+                cmd.current_dir(cwd);
+            }
+            if let Err(err) = cmd.spawn() {
+                log::error!("failed to run editor_command {:?}: {:#}", argv, err);
+            }
+        })
+        .detach();
+    }
+
+    /// Emits an event that relates to a specific pane, which is not
+    /// necessarily the active pane.  Unlike emit_window_event, each
+    /// call results in a call to the event handler.
+    fn emit_pane_event(&self, pane: &Rc<dyn Pane>, name: &str) {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        let name = name.to_string();
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            name: String,
+            window: GuiWin,
+            pane: PaneObject,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane))?;
+                if let Err(err) = config::lua::emit_event(&lua, (name.clone(), args)).await {
+                    log::error!("while processing {} event: {:#}", name, err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, name, window, pane)
+        }))
+        .detach();
+    }
+
+    /// Emits the event that was requested via `wezterm cli emit-event`.
+    /// The handlers are passed the window, the pane and then the
+    /// arguments from the command line, and the outcome is sent to
+    /// the reply channel of the request once they have all run.
+    fn emit_requested_event(&self, request: EventRequest) {
+        let mux = Mux::get().expect("emit_requested_event to be called on main thread");
+        let pane = match mux.get_pane(request.pane_id) {
+            Some(pane) => pane,
+            None => {
+                request
+                    .reply
+                    .try_send(Err(anyhow!("pane {} not found", request.pane_id)))
+                    .ok();
+                return;
+            }
+        };
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(&pane);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            name: String,
+            args: Vec<String>,
+            window: GuiWin,
+            pane: PaneObject,
+        ) -> anyhow::Result<()> {
+            let lua = lua.ok_or_else(|| anyhow!("there is no lua config to handle {}", name))?;
+            let args: mlua::Variadic<String> = args.into_iter().collect();
+            let args = lua.pack_multi((window, pane, args))?;
+            config::lua::emit_event(&lua, (name.clone(), args))
+                .await
+                .map_err(|err| anyhow!("while processing {} event: {:#}", name, err))?;
+            Ok(())
+        }
+
+        let EventRequest {
+            name, args, reply, ..
+        } = request;
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(
+            move |lua| async move {
+                let result = do_event(lua, name, args, window, pane).await;
+                if let Err(err) = &result {
+                    log::error!("{:#}", err);
+                }
+                reply.try_send(result).ok();
+                Ok(())
+            },
+        ))
+        .detach();
+    }
+
     /// Called as part of finishing up a callout to lua.
     /// If again==false it means that there isn't a lua config
     /// to execute against, so we should just mark as done.
@@ -799,8 +1344,19 @@ impl TermWindow {
         {
             self.last_status_call = now;
             self.schedule_status_update();
+            // Titles derived from the foreground process need to be
+            // refreshed even when the panes are not producing output
+            self.update_title_impl();
         }
 
+        self.check_pane_activity();
+        self.update_progress();
+        self.update_password_input();
+        if let Some(pane) = self.get_active_pane_or_overlay() {
+            self.update_ime_state(&pane);
+        }
+        self.maintain_presentation_zoom();
+
         // If self.has_animation is some, then the last render detected
         // image attachments with multiple frames, so we also need to
         // invalidate the viewport when the next frame is due
@@ -846,6 +1402,7 @@ impl TermWindow {
             if !dirty.is_empty() {
                 if pos.pane.downcast_ref::<SearchOverlay>().is_none()
                     && pos.pane.downcast_ref::<CopyOverlay>().is_none()
+                    && pos.pane.downcast_ref::<LinkHintOverlay>().is_none()
                 {
                     // If any of the changed lines intersect with the
                     // selection, then we need to clear the selection, but not
@@ -920,9 +1477,28 @@ impl TermWindow {
             }
         };
         self.config = config.clone();
-        self.palette.take();
+        if let Some(previous) = self.palette.take() {
+            self.start_window_palette_transition(previous);
+        }
+        self.mask_patterns = compile_mask_patterns(&config);
+        // The event handlers may have changed; have them format
+        // the titles again
+        for state in self.pane_state.borrow_mut().values_mut() {
+            state.title_event_input.take();
+        }
 
         self.window_background = reload_background_image(&config, &self.window_background);
+        let background_shader = load_background_shader(&config);
+        if background_shader != self.background_shader {
+            self.background_shader = background_shader;
+            if let Some(render_state) = self.render_state.as_mut() {
+                if let Err(err) =
+                    render_state.set_background_effect(self.background_shader.as_deref())
+                {
+                    log::error!("Failed to compile window_background_shader: {:#}", err);
+                }
+            }
+        }
 
         let mux = Mux::get().unwrap();
         let window = match mux.get_window(self.mux_window_id) {
@@ -944,7 +1520,7 @@ impl TermWindow {
         if let Err(err) = self.fonts.config_changed(&config) {
             log::error!("Failed to load font configuration: {:#}", err);
         }
-        self.apply_scale_change(&dimensions, self.fonts.get_font_scale());
+        self.apply_scale_change(&dimensions, self.base_font_scale());
         self.apply_dimensions(&dimensions, None);
         if let Some(window) = self.window.as_ref() {
             window.config_did_change(&config);
@@ -976,6 +1552,120 @@ impl TermWindow {
         }
     }
 
+    /// Reflects the OSC 9;4 progress of the panes in the tab bar and taskbar
+    fn update_progress(&mut self) {
+        let mux = Mux::get().unwrap();
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            _ => return,
+        };
+        let progress: Vec<Progress> = window
+            .iter()
+            .map(|tab| {
+                tab.get_active_pane()
+                    .map(|pane| pane.get_progress())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let active = progress
+            .get(window.get_active_idx())
+            .cloned()
+            .unwrap_or_default();
+        drop(window);
+
+        // The indeterminate state is rendered as a spinner, so it
+        // needs to be refreshed even if nothing has changed
+        let animating = progress.iter().any(|p| *p == Progress::Indeterminate);
+        if animating || progress != self.tab_progress {
+            self.tab_progress = progress;
+            self.update_title_impl();
+        }
+
+        let taskbar = match active {
+            Progress::None => TaskbarProgress::None,
+            Progress::SetPercentage(pct) => TaskbarProgress::Normal(pct),
+            Progress::SetError(pct) => TaskbarProgress::Error(pct),
+            Progress::Indeterminate => TaskbarProgress::Indeterminate,
+            Progress::Paused(pct) => TaskbarProgress::Paused(pct),
+        };
+        if taskbar != self.taskbar_progress {
+            self.taskbar_progress = taskbar;
+            if let Some(window) = self.window.as_ref() {
+                window.set_taskbar_progress(taskbar);
+            }
+        }
+    }
+
+    /// Tracks which of the active panes of the tabs in this window
+    /// are reading a password, so that the tab bar can indicate it
+    fn update_password_input(&mut self) {
+        let mux = Mux::get().unwrap();
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            _ => return,
+        };
+        let panes: Vec<Rc<dyn Pane>> = window
+            .iter()
+            .filter_map(|tab| tab.get_active_pane())
+            .collect();
+        drop(window);
+
+        let mut changed = false;
+        for pane in panes {
+            let reading = pane.is_reading_password();
+            let mut state = self.pane_state(pane.pane_id());
+            if reading != state.password_input.is_some() {
+                state.password_input = if reading { Some(0) } else { None };
+                changed = true;
+            }
+        }
+        if changed {
+            self.update_title_impl();
+        }
+    }
+
+    /// Flags the tab containing pane_id as having unseen output,
+    /// if it is not the active tab
+    fn note_unseen_output(&mut self, pane_id: PaneId) {
+        let mux = Mux::get().unwrap();
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            _ => return,
+        };
+        let active = window.get_active_idx();
+        let tab_id = match window
+            .iter()
+            .enumerate()
+            .find(|(idx, tab)| *idx != active && tab.contains_pane(pane_id))
+        {
+            Some((_, tab)) => tab.tab_id(),
+            None => return,
+        };
+        drop(window);
+
+        let was_unseen = std::mem::replace(&mut self.tab_state(tab_id).has_unseen_output, true);
+        if !was_unseen {
+            self.update_title_impl();
+        }
+    }
+
+    /// Returns the indices of the tabs in this window that have
+    /// produced output since they were last active
+    pub fn tabs_with_unseen_output(&self) -> Vec<usize> {
+        let mux = Mux::get().unwrap();
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            _ => return vec![],
+        };
+        let active = window.get_active_idx();
+        window
+            .iter()
+            .enumerate()
+            .filter(|(idx, tab)| *idx != active && self.tab_state(tab.tab_id()).has_unseen_output)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
     /// Called by various bits of code to update the title bar.
     /// Let's also trigger the status event so that it can choose
     /// to update the right-status.
@@ -991,12 +1681,37 @@ impl TermWindow {
     }
 
     fn update_title_impl(&mut self) {
+        let attention = self.tabs_needing_attention();
         let mux = Mux::get().unwrap();
         let window = match mux.get_window(self.mux_window_id) {
             Some(window) => window,
             _ => return,
         };
 
+        if let Some(tab) = window.get_active() {
+            self.tab_state(tab.tab_id()).has_unseen_output = false;
+        }
+        let unseen_output: HashSet<TabId> = window
+            .iter()
+            .filter(|tab| self.tab_state(tab.tab_id()).has_unseen_output)
+            .map(|tab| tab.tab_id())
+            .collect();
+        let password_input: HashMap<TabId, usize> = window
+            .iter()
+            .filter_map(|tab| {
+                let pane = tab.get_active_pane()?;
+                let count = self.pane_state(pane.pane_id()).password_input?;
+                Some((tab.tab_id(), count))
+            })
+            .collect();
+        let titles: HashMap<TabId, String> = window
+            .iter()
+            .filter_map(|tab| {
+                let pane = tab.get_active_pane()?;
+                Some((tab.tab_id(), self.pane_title(&pane)))
+            })
+            .collect();
+
         let new_tab_bar = TabBarState::new(
             self.terminal_size.cols as usize,
             if self.last_mouse_coords.1 == 0 {
@@ -1008,6 +1723,10 @@ impl TermWindow {
             self.config.colors.as_ref().and_then(|c| c.tab_bar.as_ref()),
             &self.config,
             &self.right_status,
+            &attention,
+            &unseen_output,
+            &password_input,
+            &titles,
         );
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
@@ -1027,7 +1746,7 @@ impl TermWindow {
 
         let panes = self.get_panes_to_render();
         if let Some(pos) = panes.iter().find(|p| p.is_active) {
-            let title = pos.pane.get_title();
+            let title = self.pane_title(&pos.pane);
 
             if let Some(window) = self.window.as_ref() {
                 let show_tab_bar;
@@ -1079,6 +1798,91 @@ impl TermWindow {
         }
     }
 
+    /// Enables or disables the input method for the window so
+    /// that it matches the IME state of the active pane
+    fn update_ime_state(&mut self, pane: &Rc<dyn Pane>) {
+        let enabled = pane.is_ime_enabled();
+        if enabled == self.ime_enabled {
+            return;
+        }
+        self.ime_enabled = enabled;
+        if !enabled {
+            self.ime_composing = false;
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.set_ime_enabled(enabled);
+        }
+        self.schedule_status_update();
+    }
+
+    /// Returns true if `pane` is read-only, in which case the input
+    /// that was directed at it is dropped and the pane is flashed
+    /// to show that it was rejected.  Modifier key presses are
+    /// dropped without flashing.
+    pub fn reject_read_only_input(&self, pane: &Rc<dyn Pane>, is_modifier: bool) -> bool {
+        if !pane.is_read_only() {
+            return false;
+        }
+        if !is_modifier {
+            self.pane_state(pane.pane_id())
+                .read_only_flash
+                .replace(Instant::now());
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+        true
+    }
+
+    /// Returns true if the pane belongs to a remote domain whose
+    /// connection is lost or has stopped answering keepalives
+    pub fn is_pane_unresponsive(&self, pane: &Rc<dyn Pane>) -> bool {
+        let mux = Mux::get().unwrap();
+        mux.get_domain(pane.domain_id())
+            .and_then(|domain| domain.connection_status())
+            .map(|status| !status.connected || !status.responsive)
+            .unwrap_or(false)
+    }
+
+    /// Called when the connection status of a remote domain changes.
+    /// If this window has panes in that domain, they are re-rendered
+    /// to reflect it and the `connection-status-changed` event is
+    /// emitted for the most relevant of them.
+    fn domain_connection_changed(&mut self, domain_id: DomainId) {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(active) if active.domain_id() == domain_id => Some(active),
+            _ => {
+                let mux = Mux::get().unwrap();
+                let window = match mux.get_window(self.mux_window_id) {
+                    Some(window) => window,
+                    None => return,
+                };
+                let pane = window
+                    .iter()
+                    .flat_map(|tab| tab.iter_panes())
+                    .map(|pos| pos.pane)
+                    .find(|pane| pane.domain_id() == domain_id);
+                pane
+            }
+        };
+        let pane = match pane {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        self.emit_pane_event(&pane, "connection-status-changed");
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+        self.schedule_status_update();
+    }
+
+    /// Returns true if the input method is in the middle of
+    /// composing text
+    pub fn is_ime_composing(&self) -> bool {
+        self.ime_composing
+    }
+
     fn activate_tab(&mut self, tab_idx: isize) -> anyhow::Result<()> {
         if let Some(tab) = self.get_active_pane_or_overlay() {
             tab.focus_changed(false);
@@ -1188,6 +1992,132 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    /// Replaces the search overlay for `pane_id` with a picker that
+    /// lists the matches that it found across its tab or window
+    pub fn show_search_results(&mut self, pane_id: PaneId, hits: Vec<SearchHit>) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        self.cancel_overlay_for_pane(pane_id);
+
+        let location_pattern = match Regex::new(&self.config.editor_location_pattern) {
+            Ok(regex) => Some(regex),
+            Err(err) => {
+                log::error!(
+                    "invalid editor_location_pattern {:?}: {}",
+                    self.config.editor_location_pattern,
+                    err
+                );
+                None
+            }
+        };
+
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            search_results(term, hits, location_pattern, window)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Prompts for a name with which to bookmark the row at the
+    /// top of the viewport of `pane`
+    fn add_bookmark(&mut self, pane: &Rc<dyn Pane>) {
+        let dims = pane.get_dimensions();
+        let row = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let (overlay, future) = start_overlay_pane(self, pane, move |pane_id, term| {
+            add_bookmark(pane_id, term, row)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Fetches the bookmarks of `pane`, which may be held by a
+    /// mux server, and then lists them in an overlay
+    fn show_bookmarks(&mut self, pane: &Rc<dyn Pane>) {
+        let mux = Mux::get().unwrap();
+        // Use the pane itself rather than any overlay over it
+        let pane = match mux.get_pane(pane.pane_id()) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let window = self.window.clone().unwrap();
+        promise::spawn::spawn(async move {
+            let mut bookmarks = Some(pane.get_bookmarks().await?);
+            window.apply(move |term_window, _window| {
+                if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                    term_window.show_bookmark_list(pane_id, bookmarks.take().unwrap());
+                }
+                Ok(())
+            });
+            anyhow::Result::<()>::Ok(())
+        })
+        .detach();
+    }
+
+    fn show_bookmark_list(&mut self, pane_id: PaneId, bookmarks: Vec<Bookmark>) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let physical_top = pane.get_dimensions().physical_top;
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
+            bookmark_list(pane_id, term, bookmarks, physical_top, window)
+        });
+        self.assign_overlay_for_pane(pane_id, overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_debug_overlay(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| debug_overlay(term));
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_keybinding_help(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        // Describe the bindings from the InputMap that is in use,
+        // so that the help reflects the defaults, the configured
+        // keys and any disabled assignments
+        let bindings = self.input_map.describe_keys();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            keybinding_help(term, bindings)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows an overlay over the pane whose content is produced by
+    /// a lua callback; this is the implementation of `window:show_overlay`
+    pub fn show_lua_overlay(&mut self, pane: &Rc<dyn Pane>, callback: Arc<mlua::RegistryKey>) {
+        let window = GuiWin::new(self);
+        let pane_object = PaneObject::new(pane);
+        let (overlay, future) = start_overlay_pane(self, pane, move |pane_id, term| {
+            lua_overlay(pane_id, term, window, pane_object, callback)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
     fn show_launcher(&mut self) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1277,6 +2207,79 @@ impl TermWindow {
         Ok(())
     }
 
+    /// Moves the cursor of the shell to the position of the mouse
+    /// within the command line that is being edited, by sending the
+    /// number of arrow key presses that it takes to get there.
+    /// The command line is found using the semantic zones, so this
+    /// requires shell integration that marks the prompt and the input.
+    fn move_cursor_to_mouse_cursor(&mut self, pane: &Rc<dyn Pane>) -> anyhow::Result<()> {
+        use wezterm_term::{KeyCode, KeyModifiers, SemanticType};
+
+        if pane.is_alt_screen_active() {
+            return Ok(());
+        }
+
+        let cursor = pane.get_cursor_position();
+        let cursor = (cursor.y, cursor.x);
+        let (x, y) = self.last_mouse_terminal_coords;
+        let click = (y, x);
+
+        // The command line is the input that follows the last prompt
+        // before the cursor
+        let zones = pane.get_semantic_zones()?;
+        let prompt_idx = match zones.iter().rposition(|zone| {
+            zone.semantic_type == SemanticType::Prompt && (zone.start_y, zone.start_x) <= cursor
+        }) {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let input = match zones.get(prompt_idx + 1) {
+            Some(zone) if zone.semantic_type == SemanticType::Input => zone,
+            _ => return Ok(()),
+        };
+        let start = (input.start_y, input.start_x);
+        // Allow the position just after the end of the input
+        let end = (input.end_y, input.end_x + 1);
+        if cursor < start || cursor > end || click.0 < start.0 || click.0 > end.0 {
+            return Ok(());
+        }
+        let click = click.max(start).min(end);
+
+        // Count the characters between the cursor and the click; moving
+        // between lines that were not wrapped costs a key press too
+        let (from, to) = if click < cursor {
+            (click, cursor)
+        } else {
+            (cursor, click)
+        };
+        let (first_row, lines) = pane.get_lines(from.0..to.0 + 1);
+        let mut presses = 0;
+        for (idx, line) in lines.iter().enumerate() {
+            let row = first_row + idx as StableRowIndex;
+            for (x, cell) in line.visible_cells() {
+                if (row, x) >= from
+                    && (row, x) < to
+                    && cell.attrs().semantic_type() == SemanticType::Input
+                {
+                    presses += 1;
+                }
+            }
+            if row < to.0 && !line.last_cell_was_wrapped() {
+                presses += 1;
+            }
+        }
+
+        let key = if click < cursor {
+            KeyCode::LeftArrow
+        } else {
+            KeyCode::RightArrow
+        };
+        for _ in 0..presses {
+            pane.key_down(key, KeyModifiers::NONE)?;
+        }
+        Ok(())
+    }
+
     fn scroll_by_page(&mut self, amount: isize) -> anyhow::Result<()> {
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
@@ -1375,6 +2378,11 @@ impl TermWindow {
                 let text = self.selection_text(pane);
                 self.copy_to_clipboard(*dest, text);
             }
+            CopyTextTo(copy) => {
+                let content = self.selection_rich_text(pane, copy.format);
+                self.copy_rich_text_to_clipboard(copy.destination, content);
+            }
+            ExportPane(export) => self.export_pane(pane, export),
             Paste => {
                 self.paste_from_clipboard(pane, ClipboardPasteSource::Clipboard);
             }
@@ -1391,10 +2399,17 @@ impl TermWindow {
             IncreaseFontSize => self.increase_font_size(),
             ResetFontSize => self.reset_font_size(),
             ResetFontAndWindowSize => self.reset_font_and_window_size()?,
+            IncreasePresentationZoom => self.increase_presentation_zoom(),
+            DecreasePresentationZoom => self.decrease_presentation_zoom(),
+            ResetPresentationZoom => self.reset_presentation_zoom(),
             ActivateTab(n) => {
                 self.activate_tab(*n)?;
             }
-            SendString(s) => pane.writer().write_all(s.as_bytes())?,
+            SendString(s) => {
+                if !self.reject_read_only_input(pane, false) {
+                    pane.writer().write_all(s.as_bytes())?;
+                }
+            }
             Hide => {
                 if let Some(w) = self.window.as_ref() {
                     w.hide();
@@ -1416,6 +2431,8 @@ impl TermWindow {
             ScrollToPrompt(n) => self.scroll_to_prompt(*n)?,
             ShowTabNavigator => self.show_tab_navigator(),
             ShowLauncher => self.show_launcher(),
+            ShowDebugOverlay => self.show_debug_overlay(),
+            ShowKeybindingHelp => self.show_keybinding_help(),
             HideApplication => {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
@@ -1451,49 +2468,11 @@ impl TermWindow {
             StartWindowDrag => {
                 self.window_drag_position = self.current_mouse_event.clone();
             }
+            MoveCursorToMouseCursor => self.move_cursor_to_mouse_cursor(pane)?,
             OpenLinkAtMouseCursor => {
                 // They clicked on a link, so let's open it!
-                // We need to ensure that we spawn the `open` call outside of the context
-                // of our window loop; on Windows it can cause a panic due to
-                // triggering our WndProc recursively.
-                // We get that assurance for free as part of the async dispatch that we
-                // perform below; here we allow the user to define an `open-uri` event
-                // handler that can bypass the normal `open::that` functionality.
                 if let Some(link) = self.current_highlight.as_ref().cloned() {
-                    let window = GuiWin::new(self);
-                    let pane = PaneObject::new(pane);
-
-                    async fn open_uri(
-                        lua: Option<Rc<mlua::Lua>>,
-                        window: GuiWin,
-                        pane: PaneObject,
-                        link: String,
-                    ) -> anyhow::Result<()> {
-                        let default_click = match lua {
-                            Some(lua) => {
-                                let args = lua.pack_multi((window, pane, link.clone()))?;
-                                config::lua::emit_event(&lua, ("open-uri".to_string(), args))
-                                    .await
-                                    .map_err(|e| {
-                                        log::error!("while processing open-uri event: {:#}", e);
-                                        e
-                                    })?
-                            }
-                            None => true,
-                        };
-                        if default_click {
-                            log::info!("clicking {}", link);
-                            if let Err(err) = open::that(&link) {
-                                log::error!("failed to open {}: {:?}", link, err);
-                            }
-                        }
-                        Ok(())
-                    }
-
-                    promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                        open_uri(lua, window, pane, link.uri().to_string())
-                    }))
-                    .detach();
+                    self.open_link(pane, link.uri().to_string());
                 }
             }
             EmitEvent(name) => {
@@ -1535,6 +2514,12 @@ impl TermWindow {
                     self.assign_overlay_for_pane(pane.pane_id(), copy);
                 }
             }
+            ActivateLinkHintMode(action) => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    let hints = LinkHintOverlay::with_pane(self, &pane, *action);
+                    self.assign_overlay_for_pane(pane.pane_id(), hints);
+                }
+            }
             AdjustPaneSize(direction, amount) => {
                 let mux = Mux::get().unwrap();
                 let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1569,6 +2554,43 @@ impl TermWindow {
                 };
                 tab.toggle_zoom();
             }
+            TogglePaneIme => {
+                pane.set_ime_enabled(!pane.is_ime_enabled());
+                self.update_ime_state(pane);
+            }
+            ToggleOutputMasking => {
+                let mut state = self.pane_state(pane.pane_id());
+                state.output_unmasked = !state.output_unmasked;
+            }
+            TogglePaneReadOnly => {
+                // Toggle the pane itself rather than any overlay
+                // that is currently displayed over it
+                let mux = Mux::get().unwrap();
+                if let Some(pane) = mux.get_pane(pane.pane_id()) {
+                    pane.set_read_only(!pane.is_read_only());
+                }
+                self.schedule_status_update();
+            }
+            AddBookmark => self.add_bookmark(pane),
+            ShowBookmarks => self.show_bookmarks(pane),
+            ToggleLineTimestamps => {
+                let mut state = self.pane_state(pane.pane_id());
+                state.show_line_timestamps = !state.show_line_timestamps;
+            }
+            TogglePaneOutputPaused => {
+                // Pause the pane itself rather than any overlay
+                // that is currently displayed over it
+                let mux = Mux::get().unwrap();
+                if let Some(pane) = mux.get_pane(pane.pane_id()) {
+                    pane.set_output_paused(!pane.is_output_paused());
+                }
+            }
+            ToggleDebugHud => {
+                self.show_debug_hud = !self.show_debug_hud;
+                if let Some(window) = self.window.as_ref() {
+                    window.invalidate();
+                }
+            }
         };
         Ok(())
     }
@@ -1669,6 +2691,9 @@ impl TermWindow {
         let mut state = self.pane_state(pane_id);
         if pos != state.viewport {
             state.viewport = pos;
+            if pos.is_none() {
+                state.output_while_scrolled = false;
+            }
 
             // This is a bit gross.  If we add other overlays that need this information,
             // this should get extracted out into a trait
@@ -1683,14 +2708,37 @@ impl TermWindow {
         }
     }
 
+    /// Scrolls to the bottom after input was sent to the pane,
+    /// unless an overlay such as copy mode or search is active
     fn maybe_scroll_to_bottom_for_input(&mut self, pane: &Rc<dyn Pane>) {
-        if self.config.scroll_to_bottom_on_input {
+        if self.config.scroll_to_bottom_on_input
+            && self.pane_state(pane.pane_id()).overlay.is_none()
+        {
             self.scroll_to_bottom(pane);
         }
     }
 
+    /// Scrolls to the bottom after the pane produced output,
+    /// unless an overlay such as copy mode or search is active
+    fn maybe_scroll_to_bottom_for_output(&mut self, pane_id: PaneId) {
+        if !self.config.scroll_to_bottom_on_output {
+            return;
+        }
+        let mut state = self.pane_state(pane_id);
+        if state.overlay.is_none() && state.viewport.is_some() {
+            state.viewport = None;
+            state.output_while_scrolled = false;
+            drop(state);
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
     fn scroll_to_bottom(&mut self, pane: &Rc<dyn Pane>) {
-        self.pane_state(pane.pane_id()).viewport = None;
+        let mut state = self.pane_state(pane.pane_id());
+        state.viewport = None;
+        state.output_while_scrolled = false;
     }
 
     fn get_active_pane_no_overlay(&self) -> Option<Rc<dyn Pane>> {
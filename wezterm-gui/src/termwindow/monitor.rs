@@ -0,0 +1,140 @@
+use mux::pane::{Pane, PaneId};
+use mux::tab::TabId;
+use mux::Mux;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use wezterm_toast_notification::persistent_toast_notification;
+
+/// Tracks output from a pane for the purposes of
+/// monitor_pane_activity and monitor_pane_silence
+#[derive(Clone, Debug)]
+pub struct PaneActivity {
+    last_output: Instant,
+    /// Set when output arrives; cleared when a silence
+    /// notification is fired or the pane is seen, so that
+    /// we notify at most once per burst of output.
+    silence_armed: bool,
+    /// The pane has triggered a notification that the
+    /// user has not yet seen
+    needs_attention: bool,
+}
+
+impl super::TermWindow {
+    fn is_pane_seen(&self, pane_id: PaneId) -> bool {
+        let mux = Mux::get().unwrap();
+        self.focused.is_some() && mux.is_pane_visible(pane_id)
+    }
+
+    /// Called when the pane produces output
+    pub(super) fn note_pane_output(&mut self, pane_id: PaneId) {
+        if !self.config.monitor_pane_activity && self.config.monitor_pane_silence == 0 {
+            return;
+        }
+
+        let seen = self.is_pane_seen(pane_id);
+        let activity = self
+            .pane_activity
+            .entry(pane_id)
+            .or_insert_with(|| PaneActivity {
+                last_output: Instant::now(),
+                silence_armed: false,
+                needs_attention: false,
+            });
+        activity.last_output = Instant::now();
+        activity.silence_armed = !seen;
+
+        if seen || !self.config.monitor_pane_activity || activity.needs_attention {
+            return;
+        }
+        activity.needs_attention = true;
+
+        let mux = Mux::get().unwrap();
+        if let Some(pane) = mux.get_pane(pane_id) {
+            self.notify_pane_monitor(&pane, "pane-activity", "Activity");
+        }
+    }
+
+    /// Called from periodic_window_maintenance to clear out state
+    /// for panes that the user has seen, and to detect silence
+    pub(super) fn check_pane_activity(&mut self) {
+        if self.pane_activity.is_empty() {
+            return;
+        }
+
+        let mux = Mux::get().unwrap();
+        self.pane_activity
+            .retain(|pane_id, _| mux.get_pane(*pane_id).is_some());
+
+        let silence = Duration::from_secs(self.config.monitor_pane_silence);
+        let now = Instant::now();
+        let mut changed = false;
+        let mut silent = vec![];
+
+        let pane_ids: Vec<PaneId> = self.pane_activity.keys().cloned().collect();
+        for pane_id in pane_ids {
+            let seen = self.is_pane_seen(pane_id);
+            let activity = self.pane_activity.get_mut(&pane_id).unwrap();
+            if seen {
+                if activity.needs_attention {
+                    activity.needs_attention = false;
+                    changed = true;
+                }
+                activity.silence_armed = false;
+            } else if activity.silence_armed
+                && self.config.monitor_pane_silence != 0
+                && now.duration_since(activity.last_output) >= silence
+            {
+                activity.silence_armed = false;
+                activity.needs_attention = true;
+                silent.push(pane_id);
+            }
+        }
+
+        for pane_id in silent {
+            if let Some(pane) = mux.get_pane(pane_id) {
+                self.notify_pane_monitor(&pane, "pane-silence", "Silence");
+            }
+        }
+
+        if changed {
+            self.update_title_impl();
+        }
+    }
+
+    /// Returns the set of tabs that should show the attention indicator
+    pub(super) fn tabs_needing_attention(&self) -> HashSet<TabId> {
+        let mut tabs = HashSet::new();
+        if !self.config.pane_monitor_notification.tab_indicator {
+            return tabs;
+        }
+        let mux = Mux::get().unwrap();
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            None => return tabs,
+        };
+        for (pane_id, activity) in &self.pane_activity {
+            if !activity.needs_attention {
+                continue;
+            }
+            for tab in window.iter() {
+                if tab.contains_pane(*pane_id) {
+                    tabs.insert(tab.tab_id());
+                }
+            }
+        }
+        tabs
+    }
+
+    fn notify_pane_monitor(&mut self, pane: &Rc<dyn Pane>, event_name: &str, what: &str) {
+        if self.config.pane_monitor_notification.desktop_notification {
+            persistent_toast_notification(
+                &format!("{} in pane {}", what, pane.pane_id()),
+                &pane.get_title(),
+            );
+        }
+
+        self.emit_pane_event(pane, event_name);
+        self.update_title_impl();
+    }
+}
@@ -6,6 +6,7 @@ use ::window::{
     WindowOps,
 };
 use config::keyassignment::{MouseEventTrigger, SpawnTabDomain};
+use config::ConfigHandle;
 use mux::pane::Pane;
 use mux::tab::SplitDirection;
 use mux::Mux;
@@ -13,7 +14,7 @@ use std::convert::TryInto;
 use std::ops::Sub;
 use std::rc::Rc;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use wezterm_term::input::MouseEventKind as TMEK;
 use wezterm_term::{LastMouseClick, StableRowIndex};
 
@@ -91,11 +92,12 @@ impl super::TermWindow {
 
             WMEK::VertWheel(amount) if !pane.is_mouse_grabbed() && !pane.is_alt_screen_active() => {
                 // adjust viewport
+                let lines = self.wheel_scroll.lines(amount, &self.config);
                 let dims = pane.get_dimensions();
                 let position = self
                     .get_viewport(pane.pane_id())
                     .unwrap_or(dims.physical_top)
-                    .saturating_sub(amount.into());
+                    .saturating_sub(lines);
                 self.set_viewport(pane.pane_id(), Some(position), dims);
                 context.invalidate();
                 return;
@@ -376,6 +378,18 @@ impl super::TermWindow {
             None
         };
 
+        let hovered_line_timestamp = match lines.get(0) {
+            Some(line) if top == stable_row && line.timestamp().is_some() => {
+                Some((pane.pane_id(), stable_row))
+            }
+            _ => None,
+        };
+        if hovered_line_timestamp != self.hovered_line_timestamp {
+            // Repaint to show the timestamp of the line under the mouse
+            self.hovered_line_timestamp = hovered_line_timestamp;
+            context.invalidate();
+        }
+
         match (self.current_highlight.as_ref(), new_highlight) {
             (Some(old_link), Some(new_link)) if Arc::ptr_eq(&old_link, &new_link) => {
                 // Unchanged
@@ -514,6 +528,51 @@ impl super::TermWindow {
     }
 }
 
+/// Converts wheel ticks into the number of lines to scroll the
+/// viewport by, applying the configured speed and acceleration
+#[derive(Default, Clone)]
+pub struct WheelScroll {
+    last_event: Option<Instant>,
+    /// The number of events that followed each other in quick succession
+    streak: u32,
+    /// The fractional lines that have not been scrolled yet
+    remainder: f64,
+}
+
+impl WheelScroll {
+    /// Events closer together than this accelerate the scrolling
+    const STREAK_INTERVAL: Duration = Duration::from_millis(100);
+    /// The maximum factor by which acceleration speeds up scrolling
+    const MAX_ACCELERATION: f64 = 10.;
+
+    pub fn lines(&mut self, amount: i16, config: &ConfigHandle) -> StableRowIndex {
+        let now = Instant::now();
+        let in_streak = self
+            .last_event
+            .map(|last| now.duration_since(last) < Self::STREAK_INTERVAL)
+            .unwrap_or(false);
+        if in_streak {
+            self.streak = self.streak.saturating_add(1);
+        } else {
+            self.streak = 0;
+            self.remainder = 0.;
+        }
+        self.last_event = Some(now);
+
+        let speed_up = config.mouse_wheel_scroll_acceleration.max(0.) * self.streak as f64;
+        let acceleration = (1. + speed_up).min(Self::MAX_ACCELERATION);
+        let speed = config.mouse_wheel_scroll_lines.max(0.) * acceleration;
+        let mut lines = amount as f64 * speed + self.remainder;
+        if !in_streak && lines != 0. && lines.abs() < 1. {
+            // Scroll immediately on the first tick after a pause,
+            // even when the speed is less than a line per tick
+            lines = lines.signum();
+        }
+        self.remainder = lines.fract();
+        lines.trunc() as StableRowIndex
+    }
+}
+
 fn mouse_press_to_tmb(press: &MousePress) -> TMB {
     match press {
         MousePress::Left => TMB::Left,
@@ -0,0 +1,285 @@
+//! Animates the change of the palette of the window and its panes
+//! when the configuration is reloaded, so that switching the color
+//! scheme doesn't abruptly flash the whole window.
+//!
+//! The quads are always colored from the current palette of their
+//! pane, and the colors that came from the palette record which entry
+//! they came from (see `QuadColor`).  While a transition is in progress,
+//! the palette that the pane is changing from is held by a row of the
+//! palette transition texture along with the progress of the change, and
+//! the vertex shader blends each of those colors from its earlier value.
+//! Animating the transition only updates that texture.
+use crate::quad::QuadColor;
+use mux::pane::{Pane, PaneId};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use wezterm_term::color::{ColorAttribute, ColorPalette, RgbColor};
+use window::color::LinearRgba;
+
+/// The number of panes whose palette changes can be animated at once;
+/// the palettes of any others change immediately
+pub const MAX_PALETTE_SLOTS: usize = 64;
+
+/// Identifies a color of a palette
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PaletteEntry {
+    Indexed(u8),
+    Foreground,
+    Background,
+    CursorFg,
+    CursorBg,
+    CursorBorder,
+    SelectionFg,
+    SelectionBg,
+    ScrollbarThumb,
+    Split,
+}
+
+impl PaletteEntry {
+    /// The number of entries in a palette
+    pub const COUNT: usize = 265;
+
+    /// Returns the entry that the foreground color `attr` resolves
+    /// to, or None if it is a true color
+    pub fn for_fg(attr: ColorAttribute) -> Option<Self> {
+        match attr {
+            ColorAttribute::Default => Some(Self::Foreground),
+            ColorAttribute::PaletteIndex(idx) => Some(Self::Indexed(idx)),
+            ColorAttribute::TrueColorWithPaletteFallback(..)
+            | ColorAttribute::TrueColorWithDefaultFallback(_) => None,
+        }
+    }
+
+    /// Returns the entry that the background color `attr` resolves
+    /// to, or None if it is a true color
+    pub fn for_bg(attr: ColorAttribute) -> Option<Self> {
+        match attr {
+            ColorAttribute::Default => Some(Self::Background),
+            attr => Self::for_fg(attr),
+        }
+    }
+
+    /// The position of the entry in a row of the palette
+    /// transition texture, after the progress texel
+    pub fn index(self) -> usize {
+        match self {
+            Self::Indexed(idx) => idx as usize,
+            Self::Foreground => 256,
+            Self::Background => 257,
+            Self::CursorFg => 258,
+            Self::CursorBg => 259,
+            Self::CursorBorder => 260,
+            Self::SelectionFg => 261,
+            Self::SelectionBg => 262,
+            Self::ScrollbarThumb => 263,
+            Self::Split => 264,
+        }
+    }
+
+    fn resolve(self, palette: &ColorPalette) -> RgbColor {
+        match self {
+            Self::Indexed(idx) => palette.colors.0[idx as usize],
+            Self::Foreground => palette.foreground,
+            Self::Background => palette.background,
+            Self::CursorFg => palette.cursor_fg,
+            Self::CursorBg => palette.cursor_bg,
+            Self::CursorBorder => palette.cursor_border,
+            Self::SelectionFg => palette.selection_fg,
+            Self::SelectionBg => palette.selection_bg,
+            Self::ScrollbarThumb => palette.scrollbar_thumb,
+            Self::Split => palette.split,
+        }
+    }
+
+    /// Every entry, in the order of `index`
+    fn all() -> impl Iterator<Item = Self> {
+        (0..=255u8)
+            .map(Self::Indexed)
+            .chain(NAMED_ENTRIES.iter().copied())
+    }
+}
+
+/// The entries that follow the 256 indexed colors
+static NAMED_ENTRIES: [PaletteEntry; 9] = [
+    PaletteEntry::Foreground,
+    PaletteEntry::Background,
+    PaletteEntry::CursorFg,
+    PaletteEntry::CursorBg,
+    PaletteEntry::CursorBorder,
+    PaletteEntry::SelectionFg,
+    PaletteEntry::SelectionBg,
+    PaletteEntry::ScrollbarThumb,
+    PaletteEntry::Split,
+];
+
+/// Returns `color`, which was resolved from `entry` of the palette
+/// of the pane in `slot`, for use by a quad
+pub fn palette_color(
+    color: LinearRgba,
+    slot: Option<usize>,
+    entry: Option<PaletteEntry>,
+) -> QuadColor {
+    QuadColor {
+        color,
+        palette_entry: slot.and_then(|slot| entry.map(|entry| (slot, entry.index()))),
+    }
+}
+
+#[derive(Clone)]
+pub struct PaletteTransition {
+    /// The palette that was displayed when the transition started
+    from: ColorPalette,
+    started: Instant,
+}
+
+impl PaletteTransition {
+    pub fn new(from: ColorPalette) -> Self {
+        Self {
+            from,
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns how far through the transition we are, between 0.0
+    /// and 1.0, or None once the transition is complete
+    fn progress(&self, duration_ms: u64) -> Option<f32> {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        if elapsed >= duration_ms {
+            return None;
+        }
+        let t = elapsed as f32 / duration_ms as f32;
+        // Ease in and out, so that the change doesn't start or end abruptly
+        Some(t * t * (3.0 - 2.0 * t))
+    }
+
+    /// Returns the palette that is displayed part way through the
+    /// transition towards `to`, or None once the transition is complete
+    fn palette(&self, to: &ColorPalette, duration_ms: u64) -> Option<ColorPalette> {
+        self.progress(duration_ms)
+            .map(|t| self.from.interpolate(to, t))
+    }
+
+    /// Returns the row of the palette transition texture that
+    /// animates the transition, which is `t` of the way through
+    fn texels(&self, t: f32) -> Vec<(f32, f32, f32, f32)> {
+        let mut texels = Vec::with_capacity(1 + PaletteEntry::COUNT);
+        texels.push((t, 0., 0., 0.));
+        texels.extend(PaletteEntry::all().map(|entry| {
+            let color = entry.resolve(&self.from);
+            LinearRgba::with_rgba(color.red, color.green, color.blue, 0xff).tuple()
+        }));
+        texels
+    }
+}
+
+impl super::TermWindow {
+    /// Returns the progress of `transition`, scheduling the next frame
+    /// of the animation.  The transition is cleared once it is complete.
+    fn palette_transition_progress(
+        &self,
+        transition: &mut Option<PaletteTransition>,
+    ) -> Option<f32> {
+        let duration_ms = self.config.color_scheme_transition_duration_ms;
+        if let Some(t) = transition.as_ref().and_then(|t| t.progress(duration_ms)) {
+            self.update_next_frame_time(Some(Instant::now() + Duration::from_millis(16)));
+            return Some(t);
+        }
+        transition.take();
+        None
+    }
+
+    /// Starts animating the window palette from `previous` to the
+    /// palette of the configuration that was just loaded
+    pub(super) fn start_window_palette_transition(&mut self, previous: ColorPalette) {
+        let duration_ms = self.config.color_scheme_transition_duration_ms;
+        let palette = self.palette().clone();
+        if duration_ms == 0 || palette == previous {
+            return;
+        }
+        // Start from what is on screen, which may be part way
+        // through an earlier transition
+        let from = self
+            .palette_transition
+            .as_ref()
+            .and_then(|t| t.palette(&previous, duration_ms))
+            .unwrap_or(previous);
+        self.palette_transition = Some(PaletteTransition::new(from));
+    }
+
+    /// Returns the color to clear the window background with
+    pub(super) fn window_background(&mut self) -> RgbColor {
+        let background = self.palette().background;
+        let mut transition = self.palette_transition.take();
+        let background = match self.palette_transition_progress(&mut transition) {
+            Some(t) => transition
+                .as_ref()
+                .map(|transition| {
+                    wezterm_term::color::interpolate(transition.from.background, background, t)
+                })
+                .unwrap_or(background),
+            None => background,
+        };
+        self.palette_transition = transition;
+        background
+    }
+
+    /// Forgets which panes hold the rows of the palette transition
+    /// texture, ahead of painting the window
+    pub(super) fn reset_palette_slots(&self) {
+        self.palette_slots.borrow_mut().clear();
+    }
+
+    /// Returns the palette to paint the pane with, along with the row of
+    /// the palette transition texture that animates changes to it that
+    /// are made by reloading the configuration, if any.
+    pub(super) fn pane_palette(&self, pane: &Rc<dyn Pane>) -> (ColorPalette, Option<usize>) {
+        let palette = pane.palette();
+        let duration_ms = self.config.color_scheme_transition_duration_ms;
+        let generation = self.config.generation();
+        let mut state = self.pane_state(pane.pane_id());
+
+        if let Some((previous, previous_generation)) = state.palette.take() {
+            // Changes that applications make to the palette using
+            // escape sequences take effect immediately
+            if duration_ms > 0 && previous_generation != generation && previous != palette {
+                let from = state
+                    .palette_transition
+                    .as_ref()
+                    .and_then(|t| t.palette(&previous, duration_ms))
+                    .unwrap_or(previous);
+                state.palette_transition = Some(PaletteTransition::new(from));
+            }
+        }
+        state.palette = Some((palette.clone(), generation));
+
+        let (slot, assigned) = self.palette_slot(pane.pane_id());
+        let mut transition = state.palette_transition.take();
+        let progress = self.palette_transition_progress(&mut transition);
+        if let (Some(slot), true, Some(render_state)) = (slot, assigned, self.render_state.as_ref())
+        {
+            let texels = match (progress, transition.as_ref()) {
+                (Some(t), Some(transition)) => transition.texels(t),
+                // Only the progress texel is consulted
+                _ => vec![(1., 0., 0., 0.)],
+            };
+            render_state.write_palette_transition(slot, texels);
+        }
+        state.palette_transition = transition;
+
+        (palette, slot)
+    }
+
+    /// Returns the row of the palette transition texture for `pane_id`,
+    /// and whether it was assigned by this call
+    fn palette_slot(&self, pane_id: PaneId) -> (Option<usize>, bool) {
+        let mut slots = self.palette_slots.borrow_mut();
+        if let Some(slot) = slots.iter().position(|&id| id == pane_id) {
+            return (Some(slot), false);
+        }
+        if slots.len() >= MAX_PALETTE_SLOTS {
+            return (None, false);
+        }
+        slots.push(pane_id);
+        (Some(slots.len() - 1), true)
+    }
+}
@@ -0,0 +1,150 @@
+//! Lightweight instrumentation of the render loop, whose results
+//! are reported by `wezterm cli perf` and shown by the debug HUD.
+use mux::{FrameTiming, GlyphCacheStats, WindowPerf};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The number of frames whose timings are retained
+const MAX_FRAMES: usize = 120;
+
+#[derive(Default)]
+pub struct FramePerf {
+    /// Accumulates the timings of the frame that is being painted
+    current: FrameTiming,
+    recent: VecDeque<FrameTiming>,
+}
+
+impl FramePerf {
+    pub fn shape_cache_lookup(&mut self, hit: bool) {
+        if hit {
+            self.current.shape_cache_hits += 1;
+        } else {
+            self.current.shape_cache_misses += 1;
+        }
+    }
+
+    /// Adds time spent shaping text to the frame that is being painted
+    pub fn shaped(&mut self, elapsed: Duration) {
+        self.current.shape += elapsed;
+    }
+
+    /// Completes the timings of the frame that was just painted with
+    /// those accumulated while it was being painted
+    pub fn finish_frame(&mut self, frame: FrameTiming) {
+        let current = std::mem::take(&mut self.current);
+        if self.recent.len() >= MAX_FRAMES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(FrameTiming {
+            shape: current.shape,
+            shape_cache_hits: current.shape_cache_hits,
+            shape_cache_misses: current.shape_cache_misses,
+            ..frame
+        });
+    }
+
+    /// Returns the timings of the most recent frames, oldest first
+    pub fn recent_frames(&self) -> Vec<FrameTiming> {
+        self.recent.iter().cloned().collect()
+    }
+
+    pub fn last_frame(&self) -> Option<&FrameTiming> {
+        self.recent.back()
+    }
+}
+
+fn percent(part: usize, total: usize) -> String {
+    if total == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", part as f64 * 100. / total as f64)
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+/// Returns the lines of text that the debug HUD shows
+fn debug_hud_lines(stats: &GlyphCacheStats, last_frame: Option<&FrameTiming>) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "glyphs {}/{} hits {}",
+            stats.glyphs,
+            stats.capacity,
+            percent(stats.hits, stats.hits + stats.misses)
+        ),
+        format!(
+            "rasterized {} in {:.1}ms",
+            stats.rasterized,
+            millis(stats.raster_time)
+        ),
+        format!(
+            "atlas {}px {:.0}% alpha {}px {:.0}% replaced {}",
+            stats.color_atlas.side,
+            stats.color_atlas.occupancy * 100.,
+            stats.alpha_atlas.side,
+            stats.alpha_atlas.occupancy * 100.,
+            stats.atlas_replacements
+        ),
+    ];
+    if let Some(frame) = last_frame {
+        lines.push(format!(
+            "frame {:.1}ms glyph misses {}",
+            millis(frame.total),
+            frame.glyph_cache_misses
+        ));
+    }
+    lines
+}
+
+impl super::TermWindow {
+    pub fn glyph_cache_stats(&self) -> GlyphCacheStats {
+        match self.render_state.as_ref() {
+            Some(render_state) => render_state.glyph_cache.borrow().stats(),
+            None => GlyphCacheStats::default(),
+        }
+    }
+
+    /// Returns the timings of the recent frames and the statistics
+    /// of the glyph cache, for `wezterm cli perf`
+    pub fn window_perf(&self) -> WindowPerf {
+        WindowPerf {
+            frames: self.perf.borrow().recent_frames(),
+            glyph_cache: self.glyph_cache_stats(),
+        }
+    }
+
+    /// Returns the lines of text that the debug HUD shows, if it
+    /// has been enabled by ToggleDebugHud
+    pub(super) fn debug_hud(&self) -> Option<Vec<String>> {
+        if !self.show_debug_hud {
+            return None;
+        }
+        let perf = self.perf.borrow();
+        Some(debug_hud_lines(
+            &self.glyph_cache_stats(),
+            perf.last_frame(),
+        ))
+    }
+
+    pub(super) fn record_frame_timing(
+        &mut self,
+        total: Duration,
+        render: Duration,
+        draw: Duration,
+    ) {
+        let (glyph_cache_hits, glyph_cache_misses) = match self.render_state.as_ref() {
+            Some(render_state) => render_state.glyph_cache.borrow_mut().take_lookup_counts(),
+            None => (0, 0),
+        };
+        self.perf.borrow_mut().finish_frame(FrameTiming {
+            total,
+            render,
+            draw,
+            glyph_cache_hits,
+            glyph_cache_misses,
+            ..FrameTiming::default()
+        });
+    }
+}
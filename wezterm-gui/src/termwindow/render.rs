@@ -1,8 +1,8 @@
-use crate::glium::texture::SrgbTexture2d;
-use crate::glyphcache::{BlockKey, CachedGlyph, GlyphCache};
+use super::palettetransition::{palette_color, PaletteEntry};
+use crate::glyphcache::{AtlasKind, AtlasTexture, BlockKey, CachedGlyph, GlyphCache};
+use crate::quad::QuadColor;
 use crate::shapecache::*;
 use crate::termwindow::{BorrowedShapeCacheKey, MappedQuads, RenderState, ScrollHit, ShapedInfo};
-use ::window::bitmaps::atlas::OutOfTextureSpace;
 use ::window::bitmaps::{TextureCoord, TextureRect, TextureSize};
 use ::window::glium;
 use ::window::glium::uniforms::{
@@ -11,24 +11,29 @@ use ::window::glium::uniforms::{
 use ::window::glium::{uniform, BlendingFunction, LinearBlendingFactor, Surface};
 use ::window::WindowOps;
 use anyhow::anyhow;
-use config::ConfigHandle;
-use config::TextStyle;
-use mux::pane::Pane;
+use config::{ConfigHandle, DisableLigatures, TextStyle};
+use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
+use std::collections::HashSet;
+use std::fmt::Write;
 use std::ops::Range;
 use std::rc::Rc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use termwiz::cellcluster::CellCluster;
 use termwiz::surface::{CursorShape, CursorVisibility};
+use wezterm_font::ftwrap;
 use wezterm_font::units::PixelLength;
 use wezterm_font::{ClearShapeCache, GlyphInfo};
 use wezterm_term::color::{ColorAttribute, ColorPalette, RgbColor};
-use wezterm_term::{CellAttributes, Line, StableRowIndex};
+use wezterm_term::{unicode_column_width, CellAttributes, Line, SemanticType, StableRowIndex};
 use window::bitmaps::atlas::SpriteSlice;
 use window::bitmaps::Texture2d;
 use window::color::LinearRgba;
 
+/// How long a read-only pane is flashed for after rejecting input
+const READ_ONLY_FLASH_DURATION: Duration = Duration::from_millis(250);
+
 pub struct RenderScreenLineOpenGLParams<'a> {
     pub line_idx: usize,
     pub stable_line_idx: Option<StableRowIndex>,
@@ -36,18 +41,25 @@ pub struct RenderScreenLineOpenGLParams<'a> {
     pub selection: Range<usize>,
     pub cursor: &'a StableCursorPosition,
     pub palette: &'a ColorPalette,
+    /// The row of the palette transition texture for the palette
+    pub palette_slot: Option<usize>,
     pub dims: &'a RenderableDimensions,
     pub config: &'a ConfigHandle,
     pub pos: &'a PositionedPane,
 
-    pub cursor_border_color: LinearRgba,
-    pub foreground: LinearRgba,
+    pub cursor_border_color: QuadColor,
+    pub foreground: QuadColor,
     pub is_active: bool,
-
-    pub selection_fg: LinearRgba,
-    pub selection_bg: LinearRgba,
-    pub cursor_fg: LinearRgba,
-    pub cursor_bg: LinearRgba,
+    /// The pane belongs to a remote domain whose connection is
+    /// lost or unresponsive
+    pub is_unresponsive: bool,
+
+    pub selection_fg: QuadColor,
+    pub selection_bg: QuadColor,
+    pub cursor_fg: QuadColor,
+    pub cursor_bg: QuadColor,
+    /// Composed over the background of the cells, beneath the text
+    pub background_tint: Option<LinearRgba>,
 }
 
 pub struct ComputeCellFgBgParams<'a> {
@@ -55,20 +67,20 @@ pub struct ComputeCellFgBgParams<'a> {
     pub cell_idx: usize,
     pub cursor: &'a StableCursorPosition,
     pub selection: &'a Range<usize>,
-    pub fg_color: LinearRgba,
-    pub bg_color: LinearRgba,
+    pub fg_color: QuadColor,
+    pub bg_color: QuadColor,
     pub palette: &'a ColorPalette,
     pub is_active_pane: bool,
     pub config: &'a ConfigHandle,
-    pub selection_fg: LinearRgba,
-    pub selection_bg: LinearRgba,
-    pub cursor_fg: LinearRgba,
-    pub cursor_bg: LinearRgba,
+    pub selection_fg: QuadColor,
+    pub selection_bg: QuadColor,
+    pub cursor_fg: QuadColor,
+    pub cursor_bg: QuadColor,
 }
 
 pub struct ComputeCellFgBgResult {
-    pub fg_color: LinearRgba,
-    pub bg_color: LinearRgba,
+    pub fg_color: QuadColor,
+    pub bg_color: QuadColor,
     pub cursor_shape: Option<CursorShape>,
 }
 
@@ -81,43 +93,44 @@ impl super::TermWindow {
         self.check_for_config_reload();
         let start = Instant::now();
 
-        {
-            let background_alpha = (self.config.window_background_opacity * 255.0) as u8;
-            let palette = self.palette();
-            let background = rgbcolor_alpha_to_window_color(palette.background, background_alpha);
+        self.clear_background(frame);
+        self.paint_opengl_pass_with_retry();
+        let render = start.elapsed();
+        log::debug!("paint_impl before call_draw elapsed={:?}", render);
 
-            let (r, g, b, a) = background.tuple();
-            frame.clear_color(r, g, b, a);
-        }
+        self.call_draw(frame).ok();
+        self.release_evicted_glyphs();
+        let total = start.elapsed();
+        log::debug!("paint_impl elapsed={:?}", total);
+        metrics::histogram!("gui.paint.opengl", total);
+        self.record_frame_timing(total, render, total - render);
+        self.update_title_post_status();
+    }
+
+    pub fn clear_background<S: Surface>(&mut self, frame: &mut S) {
+        let background_alpha = (self.config.window_background_opacity * 255.0) as u8;
+        let background = rgbcolor_alpha_to_window_color(self.window_background(), background_alpha);
+
+        let (r, g, b, a) = background.tuple();
+        frame.clear_color(r, g, b, a);
+    }
 
-        for pass in 0.. {
+    /// Populates the quads for the window, repeating the pass if the
+    /// texture atlas was replaced or the shape cache needs to be cleared
+    pub fn paint_opengl_pass_with_retry(&mut self) {
+        loop {
             match self.paint_opengl_pass() {
-                Ok(_) => break,
+                Ok(_) => match self.check_atlas_replaced() {
+                    // Some of the quads refer to sprites that are gone
+                    Ok(true) => log::trace!("texture atlas replaced; repainting"),
+                    Ok(false) => break,
+                    Err(err) => {
+                        log::error!("Failed to replace texture atlas: {:#}", err);
+                        break;
+                    }
+                },
                 Err(err) => {
-                    if let Some(&OutOfTextureSpace {
-                        size: Some(size),
-                        current_size,
-                    }) = err.root_cause().downcast_ref::<OutOfTextureSpace>()
-                    {
-                        let result = if pass == 0 {
-                            // Let's try clearing out the atlas and trying again
-                            // self.clear_texture_atlas()
-                            log::trace!("recreate_texture_atlas");
-                            self.recreate_texture_atlas(Some(current_size))
-                        } else {
-                            log::trace!("grow texture atlas to {}", size);
-                            self.recreate_texture_atlas(Some(size))
-                        };
-
-                        if let Err(err) = result {
-                            log::error!(
-                                "Failed to {} texture: {}",
-                                if pass == 0 { "clear" } else { "resize" },
-                                err
-                            );
-                            break;
-                        }
-                    } else if err.root_cause().downcast_ref::<ClearShapeCache>().is_some() {
+                    if err.root_cause().downcast_ref::<ClearShapeCache>().is_some() {
                         self.shape_cache.borrow_mut().clear();
                     } else {
                         log::error!("paint_opengl_pass failed: {:#}", err);
@@ -126,15 +139,17 @@ impl super::TermWindow {
                 }
             }
         }
-        log::debug!("paint_impl before call_draw elapsed={:?}", start.elapsed());
+    }
 
-        self.call_draw(frame).ok();
-        log::debug!("paint_impl elapsed={:?}", start.elapsed());
-        metrics::histogram!("gui.paint.opengl", start.elapsed());
-        self.update_title_post_status();
+    /// Returns true if the window background image, shader effect or
+    /// the desktop should show through cells with the default background
+    fn default_background_is_transparent(&self) -> bool {
+        self.window_background.is_some()
+            || self.background_shader.is_some()
+            || self.config.window_background_opacity != 1.0
     }
 
-    fn update_next_frame_time(&self, next_due: Option<Instant>) {
+    pub fn update_next_frame_time(&self, next_due: Option<Instant>) {
         if let Some(next_due) = next_due {
             let mut has_anim = self.has_animation.borrow_mut();
             match *has_anim {
@@ -149,9 +164,31 @@ impl super::TermWindow {
         }
     }
 
+    /// While the pane is being flashed because input was rejected
+    /// by a read-only pane, returns the tint to apply to its
+    /// background, fading out over READ_ONLY_FLASH_DURATION
+    fn read_only_flash_tint(&self, pane_id: PaneId, palette: &ColorPalette) -> Option<LinearRgba> {
+        let mut state = self.pane_state(pane_id);
+        let elapsed = state.read_only_flash?.elapsed();
+        if elapsed >= READ_ONLY_FLASH_DURATION {
+            state.read_only_flash.take();
+            return None;
+        }
+        self.update_next_frame_time(Some(Instant::now() + Duration::from_millis(16)));
+        let remaining = 1.0 - elapsed.as_secs_f32() / READ_ONLY_FLASH_DURATION.as_secs_f32();
+        // Use the palette's red, so that the flash suits the color scheme
+        Some(rgbcolor_alpha_to_window_color(
+            palette.colors.0[1],
+            (remaining * 0.5 * 255.0) as u8,
+        ))
+    }
+
     pub fn paint_pane_opengl(&mut self, pos: &PositionedPane) -> anyhow::Result<()> {
+        let (palette, palette_slot) = self.pane_palette(&pos.pane);
         let config = &self.config;
-        let palette = pos.pane.palette();
+        let entry_color = |entry: PaletteEntry, color: RgbColor| {
+            palette_color(rgbcolor_to_window_color(color), palette_slot, Some(entry))
+        };
 
         let background_color = palette.resolve_bg(wezterm_term::color::ColorAttribute::Default);
         let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
@@ -171,9 +208,47 @@ impl super::TermWindow {
                 None => dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
             };
 
-            let (top, vp_lines) = pos
+            let (top, mut vp_lines) = pos
                 .pane
                 .get_lines_with_hyperlinks_applied(stable_range, &self.config.hyperlink_rules);
+            if !self.mask_patterns.is_empty()
+                && !self.pane_state(pos.pane.pane_id()).output_unmasked
+            {
+                for line in &mut vp_lines {
+                    line.mask_matches(&self.mask_patterns, '*');
+                }
+            }
+            let show_all_timestamps = self.pane_state(pos.pane.pane_id()).show_line_timestamps;
+            let hovered_row = match self.hovered_line_timestamp {
+                Some((pane_id, row)) if pane_id == pos.pane.pane_id() => Some(row),
+                _ => None,
+            };
+            if show_all_timestamps || hovered_row.is_some() {
+                for (idx, line) in vp_lines.iter_mut().enumerate() {
+                    if show_all_timestamps || hovered_row == Some(top + idx as StableRowIndex) {
+                        overlay_line_timestamp(line, dims.cols, &config.line_timestamp_format);
+                    }
+                }
+            }
+            if let (Some(top), true) = (current_viewport, config.show_scroll_indicator) {
+                let new_output = config.scroll_indicator_shows_new_output
+                    && self.pane_state(pos.pane.pane_id()).output_while_scrolled;
+                if let Some(line) = vp_lines.first_mut() {
+                    overlay_scroll_indicator(line, dims.cols, dims.physical_top - top, new_output);
+                }
+            }
+            if pos.pane.is_output_paused() {
+                if let Some(line) = vp_lines.last_mut() {
+                    overlay_pause_indicator(line, dims.cols);
+                }
+            }
+            if pos.is_active {
+                if let Some(hud) = self.debug_hud() {
+                    for (line, text) in vp_lines.iter_mut().zip(hud.iter()) {
+                        overlay_debug_hud(line, dims.cols, text);
+                    }
+                }
+            }
             stable_top = top;
             lines = vp_lines;
         }
@@ -185,8 +260,15 @@ impl super::TermWindow {
         let mut quads = gl_state.quads.map(&mut vb);
         log::trace!("quad map elapsed {:?}", start.elapsed());
 
-        let cursor_border_color = rgbcolor_to_window_color(palette.cursor_border);
-        let foreground = rgbcolor_to_window_color(palette.foreground);
+        let cursor_border_color = entry_color(PaletteEntry::CursorBorder, palette.cursor_border);
+        let foreground = entry_color(PaletteEntry::Foreground, palette.foreground);
+        let background_tint = match self.read_only_flash_tint(pos.pane.pane_id(), &palette) {
+            Some(flash) => Some(flash),
+            None => pos.pane.get_background_tint().map(|tint| {
+                rgbcolor_alpha_to_window_color(tint, (config.background_tint_opacity * 255.0) as u8)
+            }),
+        };
+        let is_unresponsive = self.is_pane_unresponsive(&pos.pane);
 
         if self.show_tab_bar && pos.index == 0 {
             let tab_dims = RenderableDimensions {
@@ -201,16 +283,19 @@ impl super::TermWindow {
                     selection: 0..0,
                     cursor: &cursor,
                     palette: &palette,
+                    palette_slot,
                     dims: &tab_dims,
                     config: &config,
                     cursor_border_color,
                     foreground,
                     pos,
                     is_active: true,
-                    selection_fg: LinearRgba::default(),
-                    selection_bg: LinearRgba::default(),
-                    cursor_fg: LinearRgba::default(),
-                    cursor_bg: LinearRgba::default(),
+                    is_unresponsive: false,
+                    selection_fg: LinearRgba::default().into(),
+                    selection_bg: LinearRgba::default().into(),
+                    cursor_fg: LinearRgba::default().into(),
+                    cursor_bg: LinearRgba::default().into(),
+                    background_tint: None,
                 },
                 &mut quads,
             )?;
@@ -222,32 +307,29 @@ impl super::TermWindow {
         // changes to ScrollHit, mouse positioning, PositionedPane
         // and tab size calculation.
         if pos.is_active {
+            let surface = self.render_surface_dimensions();
             let (thumb_top, thumb_size, color) = if self.show_scroll_bar {
-                let info = ScrollHit::thumb(
-                    &*pos.pane,
-                    current_viewport,
-                    self.terminal_size,
-                    &self.dimensions,
-                );
+                let info =
+                    ScrollHit::thumb(&*pos.pane, current_viewport, self.terminal_size, &surface);
                 let thumb_top = info.top as f32;
                 let thumb_size = info.height as f32;
-                let color = rgbcolor_to_window_color(palette.scrollbar_thumb);
+                let color = entry_color(PaletteEntry::ScrollbarThumb, palette.scrollbar_thumb);
                 (thumb_top, thumb_size, color)
             } else {
-                let color = rgbcolor_to_window_color(background_color);
+                let color = entry_color(PaletteEntry::Background, background_color);
                 (0., 0., color)
             };
 
             let mut quad = quads.scroll_thumb();
 
             // Adjust the scrollbar thumb position
-            let top = (self.dimensions.pixel_height as f32 / -2.0) + thumb_top;
+            let top = (surface.pixel_height as f32 / -2.0) + thumb_top;
             let bottom = top + thumb_size;
 
             let config = &self.config;
             let padding = self.effective_right_padding(&config) as f32;
 
-            let right = self.dimensions.pixel_width as f32 / 2.;
+            let right = surface.pixel_width as f32 / 2.;
             let left = right - padding;
 
             let white_space = gl_state.util_sprites.white_space.texture_coords();
@@ -262,7 +344,7 @@ impl super::TermWindow {
             quad.set_underline(white_space);
             quad.set_has_color(false);
             quad.set_cursor(white_space);
-            quad.set_cursor_color(rgbcolor_to_window_color(background_color));
+            quad.set_cursor_color(entry_color(PaletteEntry::Background, background_color));
         }
 
         {
@@ -272,7 +354,11 @@ impl super::TermWindow {
             quad.set_cursor(white_space);
 
             let background_image_alpha = (config.window_background_opacity * 255.0) as u8;
-            let color = rgbcolor_alpha_to_window_color(palette.background, background_image_alpha);
+            let color = palette_color(
+                rgbcolor_alpha_to_window_color(palette.background, background_image_alpha),
+                palette_slot,
+                Some(PaletteEntry::Background),
+            );
 
             if let Some(im) = self.window_background.as_ref() {
                 let (sprite, next_due) =
@@ -295,10 +381,10 @@ impl super::TermWindow {
         let selrange = self.selection(pos.pane.pane_id()).range.clone();
 
         let start = Instant::now();
-        let selection_fg = rgbcolor_to_window_color(palette.selection_fg);
-        let selection_bg = rgbcolor_to_window_color(palette.selection_bg);
-        let cursor_fg = rgbcolor_to_window_color(palette.cursor_fg);
-        let cursor_bg = rgbcolor_to_window_color(palette.cursor_bg);
+        let selection_fg = entry_color(PaletteEntry::SelectionFg, palette.selection_fg);
+        let selection_bg = entry_color(PaletteEntry::SelectionBg, palette.selection_bg);
+        let cursor_fg = entry_color(PaletteEntry::CursorFg, palette.cursor_fg);
+        let cursor_bg = entry_color(PaletteEntry::CursorBg, palette.cursor_bg);
         for (line_idx, line) in lines.iter().enumerate() {
             let stable_row = stable_top + line_idx as StableRowIndex;
 
@@ -312,22 +398,48 @@ impl super::TermWindow {
                     selection: selrange,
                     cursor: &cursor,
                     palette: &palette,
+                    palette_slot,
                     dims: &dims,
                     config: &config,
                     cursor_border_color,
                     foreground,
                     pos,
                     is_active: pos.is_active,
+                    is_unresponsive,
                     selection_fg,
                     selection_bg,
                     cursor_fg,
                     cursor_bg,
+                    background_tint,
                 },
                 &mut quads,
             )?;
         }
         log::trace!("lines elapsed {:?}", start.elapsed());
 
+        // The wrap markers live in the left window padding, so only
+        // the panes that are at the left edge of the tab have them
+        if config.show_wrap_indicator && pos.left == 0 {
+            let marker = gl_state.util_sprites.wrap_marker.texture_coords();
+            let color = dim_color(foreground.color, rgbcolor_to_window_color(background_color));
+            let mut prior_was_wrapped = stable_top > 0 && {
+                let (_, above) = pos.pane.get_lines(stable_top - 1..stable_top);
+                above
+                    .first()
+                    .map(Line::last_cell_was_wrapped)
+                    .unwrap_or(false)
+            };
+            for (line_idx, line) in lines.iter().enumerate() {
+                let row = line_idx + first_line_offset + pos.top;
+                if let (true, Some(mut quad)) = (prior_was_wrapped, quads.wrap_marker(row)) {
+                    // The other attributes were reset by clear_wrap_markers
+                    quad.set_texture(marker);
+                    quad.set_fg_color(color);
+                }
+                prior_was_wrapped = line.last_cell_was_wrapped();
+            }
+        }
+
         let start = Instant::now();
         drop(quads);
         log::trace!("quad drop elapsed {:?}", start.elapsed());
@@ -335,20 +447,22 @@ impl super::TermWindow {
         Ok(())
     }
 
-    pub fn call_draw(&mut self, frame: &mut glium::Frame) -> anyhow::Result<()> {
+    pub fn call_draw<S: Surface>(&mut self, frame: &mut S) -> anyhow::Result<()> {
+        let projection = self.presentation_zoom_projection();
         let gl_state = self.render_state.as_ref().unwrap();
         let mut vb = gl_state.glyph_vertex_buffer.borrow_mut();
 
-        let tex = gl_state.glyph_cache.borrow().atlas.texture();
-        let projection = euclid::Transform3D::<f32, f32, f32>::ortho(
-            -(self.dimensions.pixel_width as f32) / 2.0,
-            self.dimensions.pixel_width as f32 / 2.0,
-            self.dimensions.pixel_height as f32 / 2.0,
-            -(self.dimensions.pixel_height as f32) / 2.0,
-            -1.0,
-            1.0,
-        )
-        .to_arrays_transposed();
+        let (tex, alpha_tex) = {
+            let glyph_cache = gl_state.glyph_cache.borrow();
+            (
+                glyph_cache.atlas.texture(),
+                glyph_cache.alpha_atlas.texture(),
+            )
+        };
+        let (tex, alpha_tex) = match (&*tex, &*alpha_tex) {
+            (AtlasTexture::Srgb(tex), AtlasTexture::Alpha(alpha_tex)) => (tex, alpha_tex),
+            _ => anyhow::bail!("glyph cache atlases have unexpected texture formats"),
+        };
 
         let alpha_blending = glium::DrawParameters {
             blend: glium::Blend {
@@ -369,16 +483,30 @@ impl super::TermWindow {
         // Clamp and use the nearest texel rather than interpolate.
         // This prevents things like the box cursor outlines from
         // being randomly doubled in width or height
-        let atlas_nearest_sampler = Sampler::new(&*tex)
+        let atlas_nearest_sampler = Sampler::new(tex)
             .wrap_function(SamplerWrapFunction::Clamp)
             .magnify_filter(MagnifySamplerFilter::Nearest)
             .minify_filter(MinifySamplerFilter::Nearest);
 
-        let atlas_linear_sampler = Sampler::new(&*tex)
+        let atlas_linear_sampler = Sampler::new(tex)
             .wrap_function(SamplerWrapFunction::Clamp)
             .magnify_filter(MagnifySamplerFilter::Linear)
             .minify_filter(MinifySamplerFilter::Linear);
 
+        // The monochrome glyphs, which are sampled from the single
+        // channel atlas
+        let atlas_alpha_sampler = Sampler::new(alpha_tex)
+            .wrap_function(SamplerWrapFunction::Clamp)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest);
+
+        // The vertex shader fetches the texels of the palette transition
+        // texture exactly; its float format can't be filtered anyway
+        let palette_transitions = Sampler::new(&gl_state.palette_transitions)
+            .wrap_function(SamplerWrapFunction::Clamp)
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest);
+
         let foreground_text_hsb = self.config.foreground_text_hsb;
         let foreground_text_hsb = (
             foreground_text_hsb.hue,
@@ -394,11 +522,39 @@ impl super::TermWindow {
             &uniform! {
                 projection: projection,
                 atlas_linear_sampler:  atlas_linear_sampler,
+                palette_transitions: palette_transitions,
                 foreground_text_hsb: foreground_text_hsb,
             },
             &alpha_blending,
         )?;
 
+        // Pass 1b: the window_background_shader effect, over the
+        // background but beneath the text
+        if let Some(effect_prog) = gl_state.background_effect_prog.as_ref() {
+            frame.draw(
+                &vb.bufs[vb.index],
+                &gl_state.glyph_index_buffer,
+                effect_prog,
+                &uniform! {
+                    projection: projection,
+                    palette_transitions: palette_transitions,
+                    time: self.created_at.elapsed().as_secs_f32(),
+                    resolution: (
+                        self.dimensions.pixel_width as f32,
+                        self.dimensions.pixel_height as f32,
+                    ),
+                },
+                &alpha_blending,
+            )?;
+
+            let fps = self.config.window_background_shader_fps;
+            if fps > 0 {
+                self.update_next_frame_time(Some(
+                    Instant::now() + Duration::from_millis(1000 / fps as u64),
+                ));
+            }
+        }
+
         // Pass 2: strikethrough and underline
         frame.draw(
             &vb.bufs[vb.index],
@@ -408,6 +564,7 @@ impl super::TermWindow {
                 projection: projection,
                 atlas_nearest_sampler:  atlas_nearest_sampler,
                 atlas_linear_sampler:  atlas_linear_sampler,
+                palette_transitions: palette_transitions,
                 foreground_text_hsb: foreground_text_hsb,
             },
             &alpha_blending,
@@ -453,18 +610,55 @@ impl super::TermWindow {
             ..Default::default()
         };
 
+        // When the glyphs hold the coverage of each LCD sub-pixel, the
+        // shader's second output holds the per-channel coverage and
+        // the first output is already multiplied by it:
+        //
+        // color = src_color + dest_color * (1 - src1_color)
+        //
+        // The alpha channel is treated as described above.
+        let dual_source_blending = glium::DrawParameters {
+            blend: glium::Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::OneMinusSourceOneColor,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::One,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+
+        // The rasterizer decides the render mode from the global
+        // configuration rather than any overrides of this window,
+        // so consult the same thing to learn whether the glyphs
+        // hold the coverage of each sub-pixel
+        let (_, render_mode) = ftwrap::compute_load_flags_from_config();
+        let (glyph_prog, glyph_blending) =
+            match (render_mode, gl_state.glyph_subpixel_prog.as_ref()) {
+                (ftwrap::FT_Render_Mode::FT_RENDER_MODE_LCD, Some(prog)) => {
+                    (prog, &dual_source_blending)
+                }
+                _ => (&gl_state.glyph_prog, &blend_but_set_alpha_to_one),
+            };
+
         // Pass 3: Draw glyphs
         frame.draw(
             &vb.bufs[vb.index],
             &gl_state.glyph_index_buffer,
-            &gl_state.glyph_prog,
+            glyph_prog,
             &uniform! {
                 projection: projection,
                 atlas_nearest_sampler:  atlas_nearest_sampler,
                 atlas_linear_sampler:  atlas_linear_sampler,
+                atlas_alpha_sampler:  atlas_alpha_sampler,
+                palette_transitions: palette_transitions,
                 foreground_text_hsb: foreground_text_hsb,
             },
-            &blend_but_set_alpha_to_one,
+            glyph_blending,
         )?;
 
         vb.index += 1;
@@ -475,11 +669,39 @@ impl super::TermWindow {
         Ok(())
     }
 
+    /// Hides the wrap markers of every row; the panes that show
+    /// them set their own rows as they are painted
+    fn clear_wrap_markers(&mut self) {
+        let gl_state = self.render_state.as_ref().unwrap();
+        let mut vb = gl_state.glyph_vertex_buffer.borrow_mut();
+        let mut quads = gl_state.quads.map(&mut vb);
+        let white_space = gl_state.util_sprites.white_space.texture_coords();
+        for row in 0..gl_state.quads.wrap_markers.len() {
+            if let Some(mut quad) = quads.wrap_marker(row) {
+                quad.set_texture(white_space);
+                quad.set_underline(white_space);
+                quad.set_cursor(white_space);
+                // A monochrome glyph whose foreground matches its
+                // background isn't rendered at all
+                quad.set_fg_color(LinearRgba::default());
+                quad.set_bg_color(LinearRgba::default());
+                quad.set_underline_color(LinearRgba::default());
+                quad.set_cursor_color(LinearRgba::default());
+                quad.set_texture_adjust(0., 0., 0., 0.);
+                quad.set_hsv(None);
+                quad.set_has_color(false);
+            }
+        }
+    }
+
     pub fn paint_split_opengl(
         &mut self,
         split: &PositionedSplit,
         pane: &Rc<dyn Pane>,
     ) -> anyhow::Result<()> {
+        // This may update the palette transition texture, so
+        // do it before the vertex buffer is mapped
+        let (palette, palette_slot) = self.pane_palette(pane);
         let gl_state = self.render_state.as_ref().unwrap();
         let mut vb = gl_state.glyph_vertex_buffer.borrow_mut();
         let mut quads = gl_state.quads.map(&mut vb);
@@ -489,27 +711,39 @@ impl super::TermWindow {
         } else {
             "─"
         };
-        let palette = pane.palette();
-        let foreground = rgbcolor_to_window_color(palette.split);
-        let background = rgbcolor_alpha_to_window_color(
-            palette.background,
-            if self.window_background.is_some() || config.window_background_opacity != 1.0 {
-                0x00
-            } else {
-                (config.text_background_opacity * 255.0) as u8
-            },
+        let foreground = palette_color(
+            rgbcolor_to_window_color(palette.split),
+            palette_slot,
+            Some(PaletteEntry::Split),
+        );
+        let background = palette_color(
+            rgbcolor_alpha_to_window_color(
+                palette.background,
+                if self.default_background_is_transparent() {
+                    0x00
+                } else {
+                    (config.text_background_opacity * 255.0) as u8
+                },
+            ),
+            palette_slot,
+            Some(PaletteEntry::Background),
         );
 
         let style = self.fonts.match_style(&config, &CellAttributes::default());
         let glyph_info = {
-            let key = BorrowedShapeCacheKey { style, text };
+            let key = BorrowedShapeCacheKey {
+                style,
+                text,
+                presentation: None,
+            };
             match self.lookup_cached_shape(&key) {
                 Some(Ok(info)) => info,
                 Some(Err(err)) => return Err(err),
                 None => {
+                    let shape_start = Instant::now();
                     let font = self.fonts.resolve_font(style)?;
                     let window = self.window.as_ref().unwrap().clone();
-                    match font.shape(text, || Self::invalidate_post_font_resolve(window)) {
+                    match font.shape(text, None, || Self::invalidate_post_font_resolve(window)) {
                         Ok(info) => {
                             let line = Line::from_text(&text, &CellAttributes::default());
                             let clusters = line.cluster();
@@ -520,16 +754,21 @@ impl super::TermWindow {
                                 &mut gl_state.glyph_cache.borrow_mut(),
                                 &info,
                             )?;
-                            let shaped = ShapedInfo::process(
+                            let shaped = Rc::new(ShapedInfo::process(
                                 &self.render_metrics,
                                 &clusters[0],
                                 &info,
                                 &glyphs,
-                            );
-                            self.shape_cache
-                                .borrow_mut()
-                                .put(key.to_owned(), Ok(Rc::new(shaped)));
-                            self.lookup_cached_shape(&key).unwrap().unwrap()
+                            ));
+                            self.perf.borrow_mut().shaped(shape_start.elapsed());
+                            // Shape the text again once its placeholders
+                            // have been rasterized
+                            if !glyphs.iter().any(|glyph| glyph.pending) {
+                                self.shape_cache
+                                    .borrow_mut()
+                                    .put(key.to_owned(), Ok(Rc::clone(&shaped)));
+                            }
+                            shaped
                         }
                         Err(err) => {
                             if err.root_cause().downcast_ref::<ClearShapeCache>().is_some() {
@@ -596,7 +835,11 @@ impl super::TermWindow {
                 quad.set_texture(texture_rect);
                 quad.set_texture_adjust(left, top, right, bottom);
                 quad.set_underline(underline_tex_rect);
-                quad.set_has_color(glyph.has_color);
+                if glyph.atlas == AtlasKind::Alpha {
+                    quad.set_is_alpha_glyph();
+                } else {
+                    quad.set_has_color(glyph.has_color);
+                }
                 quad.set_cursor(underline_tex_rect);
                 quad.set_cursor_color(background);
             }
@@ -607,6 +850,9 @@ impl super::TermWindow {
     pub fn paint_opengl_pass(&mut self) -> anyhow::Result<()> {
         let panes = self.get_panes_to_render();
 
+        self.clear_wrap_markers();
+        self.reset_palette_slots();
+
         if let Some(pane) = self.get_active_pane_or_overlay() {
             let splits = self.get_splits();
             for split in &splits {
@@ -617,6 +863,7 @@ impl super::TermWindow {
         for pos in panes {
             if pos.is_active {
                 self.update_text_cursor(&pos.pane);
+                self.update_ime_state(&pos.pane);
             }
             self.paint_pane_opengl(&pos)?;
         }
@@ -624,7 +871,7 @@ impl super::TermWindow {
         Ok(())
     }
 
-    fn invalidate_post_font_resolve(window: ::window::Window) {
+    pub(super) fn invalidate_post_font_resolve(window: ::window::Window) {
         promise::spawn::spawn_into_main_thread(async move {
             window
                 .apply(move |tw, _| {
@@ -652,14 +899,15 @@ impl super::TermWindow {
 
         let num_cols = params.dims.cols;
 
-        let hsv = if params.is_active {
+        let hsv = if params.is_unresponsive {
+            Some(params.config.unresponsive_pane_hsb)
+        } else if params.is_active {
             None
         } else {
             Some(params.config.inactive_pane_hsb)
         };
 
-        let window_is_transparent =
-            self.window_background.is_some() || params.config.window_background_opacity != 1.0;
+        let window_is_transparent = self.default_background_is_transparent();
 
         let white_space = gl_state.util_sprites.white_space.texture_coords();
 
@@ -682,8 +930,34 @@ impl super::TermWindow {
             quad.set_cursor(white_space);
         }
 
-        // Break the line into clusters of cells with the same attributes
-        let cell_clusters = params.line.cluster();
+        // Break the line into clusters of cells with the same attributes.
+        // Cells in which ligatures are disabled are placed into clusters
+        // of their own, so that they are shaped individually.
+        let cursor_word = if params
+            .config
+            .disable_ligatures
+            .contains(&DisableLigatures::Cursor)
+            && params.stable_line_idx == Some(params.cursor.y)
+        {
+            Some(word_range_at(params.line, params.cursor.x))
+        } else {
+            None
+        };
+        let disable_in_prompt = params
+            .config
+            .disable_ligatures
+            .contains(&DisableLigatures::Prompt);
+        let cell_clusters = if cursor_word.is_some() || disable_in_prompt {
+            params.line.cluster_with_isolation(|cell_idx, cell| {
+                cursor_word
+                    .as_ref()
+                    .map(|word| word.contains(&cell_idx))
+                    .unwrap_or(false)
+                    || (disable_in_prompt && cell.attrs().semantic_type() != SemanticType::Output)
+            })
+        } else {
+            params.line.cluster()
+        };
 
         let mut last_cell_idx = 0;
 
@@ -709,19 +983,25 @@ impl super::TermWindow {
 
             let bg_is_default = attrs.background == ColorAttribute::Default;
             let bg_color = params.palette.resolve_bg(attrs.background);
+            let bg_entry = PaletteEntry::for_bg(attrs.background);
 
+            /// Returns the color, along with the palette entry
+            /// that it came from, if any
             fn resolve_fg_color_attr(
                 attrs: &CellAttributes,
                 fg: &ColorAttribute,
                 params: &RenderScreenLineOpenGLParams,
                 style: &config::TextStyle,
-            ) -> RgbColor {
+            ) -> (RgbColor, Option<PaletteEntry>) {
                 match fg {
                     wezterm_term::color::ColorAttribute::Default => {
                         if let Some(fg) = style.foreground {
-                            fg
+                            (fg, None)
                         } else {
-                            params.palette.resolve_fg(attrs.foreground)
+                            (
+                                params.palette.resolve_fg(attrs.foreground),
+                                PaletteEntry::for_fg(attrs.foreground),
+                            )
                         }
                     }
                     wezterm_term::color::ColorAttribute::PaletteIndex(idx)
@@ -735,18 +1015,18 @@ impl super::TermWindow {
                         } else {
                             *idx
                         };
-                        params
-                            .palette
-                            .resolve_fg(wezterm_term::color::ColorAttribute::PaletteIndex(idx))
+                        let attr = wezterm_term::color::ColorAttribute::PaletteIndex(idx);
+                        (params.palette.resolve_fg(attr), PaletteEntry::for_fg(attr))
                     }
-                    _ => params.palette.resolve_fg(*fg),
+                    _ => (params.palette.resolve_fg(*fg), PaletteEntry::for_fg(*fg)),
                 }
             }
-            let fg_color = resolve_fg_color_attr(&attrs, &attrs.foreground, &params, &style);
+            let (fg_color, fg_entry) =
+                resolve_fg_color_attr(&attrs, &attrs.foreground, &params, &style);
 
-            let (fg_color, bg_color, bg_is_default) = {
-                let mut fg = fg_color;
-                let mut bg = bg_color;
+            let (fg_color, fg_entry, bg_color, bg_entry, bg_is_default) = {
+                let mut fg = (fg_color, fg_entry);
+                let mut bg = (bg_color, bg_entry);
                 let mut bg_default = bg_is_default;
 
                 if attrs.reverse() {
@@ -754,23 +1034,35 @@ impl super::TermWindow {
                     bg_default = false;
                 }
 
-                (fg, bg, bg_default)
+                (fg.0, fg.1, bg.0, bg.1, bg_default)
             };
 
-            let glyph_color = rgbcolor_to_window_color(fg_color);
-            let underline_color = match attrs.underline_color() {
-                ColorAttribute::Default => fg_color,
+            let glyph_color = palette_color(
+                rgbcolor_to_window_color(fg_color),
+                params.palette_slot,
+                fg_entry,
+            );
+            let (underline_color, underline_entry) = match attrs.underline_color() {
+                ColorAttribute::Default => (fg_color, fg_entry),
                 c => resolve_fg_color_attr(&attrs, &c, &params, &style),
             };
-            let underline_color = rgbcolor_to_window_color(underline_color);
+            let underline_color = palette_color(
+                rgbcolor_to_window_color(underline_color),
+                params.palette_slot,
+                underline_entry,
+            );
 
-            let bg_color = rgbcolor_alpha_to_window_color(
-                bg_color,
-                if window_is_transparent && bg_is_default {
-                    0x00
-                } else {
-                    (params.config.text_background_opacity * 255.0) as u8
-                },
+            let bg_color = tinted_background_color(
+                rgbcolor_alpha_to_window_color(
+                    bg_color,
+                    if window_is_transparent && bg_is_default {
+                        0x00
+                    } else {
+                        (params.config.text_background_opacity * 255.0) as u8
+                    },
+                ),
+                bg_entry,
+                &params,
             );
 
             // Shape the printable text from this cluster
@@ -778,16 +1070,18 @@ impl super::TermWindow {
                 let key = BorrowedShapeCacheKey {
                     style,
                     text: &cluster.text,
+                    presentation: cluster.attrs.presentation(),
                 };
                 match self.lookup_cached_shape(&key) {
                     Some(Ok(info)) => info,
                     Some(Err(err)) => return Err(err),
                     None => {
+                        let shape_start = Instant::now();
                         let font = self.fonts.resolve_font(style)?;
                         let window = self.window.as_ref().unwrap().clone();
-                        match font
-                            .shape(&cluster.text, || Self::invalidate_post_font_resolve(window))
-                        {
+                        match font.shape(&cluster.text, cluster.attrs.presentation(), || {
+                            Self::invalidate_post_font_resolve(window)
+                        }) {
                             Ok(info) => {
                                 let glyphs = self.glyph_infos_to_glyphs(
                                     cluster,
@@ -796,17 +1090,24 @@ impl super::TermWindow {
                                     &mut gl_state.glyph_cache.borrow_mut(),
                                     &info,
                                 )?;
-                                let shaped = ShapedInfo::process(
+                                let shaped = Rc::new(ShapedInfo::process(
                                     &self.render_metrics,
                                     cluster,
                                     &info,
                                     &glyphs,
-                                );
-
-                                self.shape_cache
-                                    .borrow_mut()
-                                    .put(key.to_owned(), Ok(Rc::new(shaped)));
-                                self.lookup_cached_shape(&key).unwrap().unwrap()
+                                ));
+                                self.perf.borrow_mut().shaped(shape_start.elapsed());
+
+                                // Runs that hold placeholders for glyphs
+                                // that are still being rasterized are
+                                // shaped again on the next paint, which
+                                // picks up the rasterized glyphs
+                                if !glyphs.iter().any(|glyph| glyph.pending) {
+                                    self.shape_cache
+                                        .borrow_mut()
+                                        .put(key.to_owned(), Ok(Rc::clone(&shaped)));
+                                }
+                                shaped
                             }
                             Err(err) => {
                                 if err.root_cause().downcast_ref::<ClearShapeCache>().is_some() {
@@ -884,7 +1185,11 @@ impl super::TermWindow {
                     }
 
                     if self.config.custom_block_glyphs && glyph_idx == 0 {
-                        if let Some(block) = BlockKey::from_cell(&params.line.cells()[cell_idx]) {
+                        if let Some(block) = BlockKey::from_cell_in_ranges(
+                            &params.line.cells()[cell_idx],
+                            &self.config.custom_block_glyph_ranges,
+                        ) {
+                            let block = block.with_shade_style(self.config.shade_block_style);
                             self.populate_block_quad(
                                 block,
                                 gl_state,
@@ -917,14 +1222,45 @@ impl super::TermWindow {
                     };
 
                     let pixel_rect = slice.pixel_rect(texture);
-                    let texture_rect = texture.texture.to_texture_coords(pixel_rect);
+                    let mut texture_rect = texture.texture.to_texture_coords(pixel_rect);
 
-                    let left = if glyph_idx == 0 { left } else { 0.0 };
-                    let bottom = (pixel_rect.size.height as f32 * glyph.scale as f32) + top
+                    let mut left = if glyph_idx == 0 { left } else { 0.0 };
+                    let mut top = top;
+                    let mut bottom = (pixel_rect.size.height as f32 * glyph.scale as f32) + top
                         - self.render_metrics.cell_size.height as f32;
-                    let right = pixel_rect.size.width as f32 + left
+                    let mut right = pixel_rect.size.width as f32 + left
                         - self.render_metrics.cell_size.width as f32;
 
+                    // Glyphs that are larger than their cells would otherwise
+                    // bleed across the edges of the pane and over the split
+                    // separator into the neighboring pane.  Clip the quad to
+                    // the pane, cropping the texture by the same amount so
+                    // that the visible part of the glyph isn't distorted.
+                    let texture_scale_x = glyph.scale as f32 * texture.texture.width() as f32;
+                    let texture_scale_y = glyph.scale as f32 * texture.texture.height() as f32;
+                    if cell_idx == 0 && left < 0. {
+                        let clip = (-left / texture_scale_x).min(texture_rect.size.width);
+                        texture_rect.origin.x += clip;
+                        texture_rect.size.width -= clip;
+                        left = 0.;
+                    }
+                    if cell_idx == num_cols - 1 && right > 0. {
+                        texture_rect.size.width -=
+                            (right / texture_scale_x).min(texture_rect.size.width);
+                        right = 0.;
+                    }
+                    if params.line_idx == 0 && top < 0. {
+                        let clip = (-top / texture_scale_y).min(texture_rect.size.height);
+                        texture_rect.origin.y += clip;
+                        texture_rect.size.height -= clip;
+                        top = 0.;
+                    }
+                    if params.line_idx + 1 >= params.dims.viewport_rows && bottom > 0. {
+                        texture_rect.size.height -=
+                            (bottom / texture_scale_y).min(texture_rect.size.height);
+                        bottom = 0.;
+                    }
+
                     let mut quad = match quads
                         .cell(cell_idx + params.pos.left, params.line_idx + params.pos.top)
                     {
@@ -939,7 +1275,11 @@ impl super::TermWindow {
                     quad.set_underline(underline_tex_rect);
                     quad.set_underline_color(underline_color);
                     quad.set_hsv(hsv);
-                    quad.set_has_color(glyph.has_color);
+                    if glyph.atlas == AtlasKind::Alpha {
+                        quad.set_is_alpha_glyph();
+                    } else {
+                        quad.set_has_color(glyph.has_color);
+                    }
                     quad.set_cursor(
                         gl_state
                             .util_sprites
@@ -958,13 +1298,17 @@ impl super::TermWindow {
         // the right pane with its prior contents instead of showing the
         // cleared lines from the shell in the main screen.
 
-        let bg_color = rgbcolor_alpha_to_window_color(
-            params.palette.resolve_bg(ColorAttribute::Default),
-            if window_is_transparent {
-                0x00
-            } else {
-                (params.config.text_background_opacity * 255.0) as u8
-            },
+        let bg_color = tinted_background_color(
+            rgbcolor_alpha_to_window_color(
+                params.palette.resolve_bg(ColorAttribute::Default),
+                if window_is_transparent {
+                    0x00
+                } else {
+                    (params.config.text_background_opacity * 255.0) as u8
+                },
+            ),
+            Some(PaletteEntry::Background),
+            &params,
         );
 
         for cell_idx in last_cell_idx + 1..num_cols {
@@ -1027,16 +1371,37 @@ impl super::TermWindow {
         params: &RenderScreenLineOpenGLParams,
         hsv: Option<config::HsbTransform>,
         cursor_shape: Option<CursorShape>,
-        glyph_color: LinearRgba,
-        underline_color: LinearRgba,
-        bg_color: LinearRgba,
+        glyph_color: QuadColor,
+        underline_color: QuadColor,
+        bg_color: QuadColor,
         white_space: TextureRect,
     ) -> anyhow::Result<()> {
-        let sprite = gl_state
-            .glyph_cache
-            .borrow_mut()
-            .cached_block(block)?
-            .texture_coords();
+        let sprite = gl_state.glyph_cache.borrow_mut().cached_block(block)?;
+
+        // The sprite is padded by whole pixels on each side; extend the
+        // quad into the adjacent cells by the configured bleed, but not
+        // across the edges of the pane, and crop the rest of the padding
+        let bleed = self.render_metrics.block_bleed;
+        let padding = bleed.ceil();
+        let bleed_left = if cell_idx == 0 { 0. } else { bleed };
+        let bleed_top = if params.line_idx == 0 { 0. } else { bleed };
+        let bleed_right = if cell_idx + 1 >= params.dims.cols {
+            0.
+        } else {
+            bleed
+        };
+        let bleed_bottom = if params.line_idx + 1 >= params.dims.viewport_rows {
+            0.
+        } else {
+            bleed
+        };
+        let texture_width = sprite.texture.width() as f32;
+        let texture_height = sprite.texture.height() as f32;
+        let mut texture_rect = sprite.texture_coords();
+        texture_rect.origin.x += (padding - bleed_left) / texture_width;
+        texture_rect.origin.y += (padding - bleed_top) / texture_height;
+        texture_rect.size.width -= (2. * padding - bleed_left - bleed_right) / texture_width;
+        texture_rect.size.height -= (2. * padding - bleed_top - bleed_bottom) / texture_height;
 
         let mut quad =
             match quads.cell(cell_idx + params.pos.left, params.line_idx + params.pos.top) {
@@ -1048,8 +1413,8 @@ impl super::TermWindow {
         quad.set_fg_color(glyph_color);
         quad.set_underline_color(underline_color);
         quad.set_bg_color(bg_color);
-        quad.set_texture(sprite);
-        quad.set_texture_adjust(0., 0., 0., 0.);
+        quad.set_texture(texture_rect);
+        quad.set_texture_adjust(-bleed_left, -bleed_top, bleed_right, bleed_bottom);
         quad.set_underline(white_space);
         quad.set_has_color(false);
         quad.set_cursor(
@@ -1073,9 +1438,9 @@ impl super::TermWindow {
         params: &RenderScreenLineOpenGLParams,
         hsv: Option<config::HsbTransform>,
         cursor_shape: Option<CursorShape>,
-        glyph_color: LinearRgba,
-        underline_color: LinearRgba,
-        bg_color: LinearRgba,
+        glyph_color: QuadColor,
+        underline_color: QuadColor,
+        bg_color: QuadColor,
         white_space: TextureRect,
     ) -> anyhow::Result<()> {
         let padding = self
@@ -1224,9 +1589,9 @@ impl super::TermWindow {
         cluster: &CellCluster,
         line: &Line,
         style: &TextStyle,
-        glyph_cache: &mut GlyphCache<SrgbTexture2d>,
+        glyph_cache: &mut GlyphCache<AtlasTexture>,
         infos: &[GlyphInfo],
-    ) -> anyhow::Result<Vec<Rc<CachedGlyph<SrgbTexture2d>>>> {
+    ) -> anyhow::Result<Vec<Rc<CachedGlyph<AtlasTexture>>>> {
         let mut glyphs = vec![];
         for info in infos {
             let cell_idx = cluster.byte_to_cell_idx[info.cluster as usize];
@@ -1243,12 +1608,14 @@ impl super::TermWindow {
     fn lookup_cached_shape(
         &self,
         key: &dyn ShapeCacheKeyTrait,
-    ) -> Option<anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>>> {
-        match self.shape_cache.borrow_mut().get(key) {
+    ) -> Option<anyhow::Result<Rc<Vec<ShapedInfo<AtlasTexture>>>>> {
+        let result = match self.shape_cache.borrow_mut().get(key) {
             Some(Ok(info)) => Some(Ok(Rc::clone(info))),
             Some(Err(err)) => Some(Err(anyhow!("cached shaper error: {}", err))),
             None => None,
-        }
+        };
+        self.perf.borrow_mut().shape_cache_lookup(result.is_some());
+        result
     }
 
     pub fn clear_texture_atlas(&mut self) -> anyhow::Result<()> {
@@ -1260,6 +1627,57 @@ impl super::TermWindow {
         Ok(())
     }
 
+    /// Returns true if the texture atlas was grown or compacted since
+    /// this was last called, in which case the shape cache is cleared
+    /// as the glyphs that it holds refer to sprites that are gone
+    fn check_atlas_replaced(&mut self) -> anyhow::Result<bool> {
+        let replaced = match self.render_state.as_mut() {
+            Some(render_state) => render_state.check_atlas_replaced(&self.render_metrics)?,
+            None => false,
+        };
+        if replaced {
+            self.shape_cache.borrow_mut().clear();
+        }
+        Ok(replaced)
+    }
+
+    /// Releases the sprites of the glyphs that were evicted from the glyph
+    /// cache, once the frame that may have used them has been drawn,
+    /// first purging the shaped runs that refer to them
+    fn release_evicted_glyphs(&mut self) {
+        let gl_state = match self.render_state.as_ref() {
+            Some(gl_state) => gl_state,
+            None => return,
+        };
+        let mut glyph_cache = gl_state.glyph_cache.borrow_mut();
+        if glyph_cache.evicted_glyphs().is_empty() {
+            return;
+        }
+        let evicted: HashSet<*const CachedGlyph<AtlasTexture>> = glyph_cache
+            .evicted_glyphs()
+            .iter()
+            .map(Rc::as_ptr)
+            .collect();
+        self.purge_shape_cache(|glyph| evicted.contains(&Rc::as_ptr(glyph)));
+        glyph_cache.release_evicted_glyphs();
+    }
+
+    /// Removes the shaped runs that contain a glyph matching `pred`
+    /// from the shape cache
+    fn purge_shape_cache(&self, pred: impl Fn(&Rc<CachedGlyph<AtlasTexture>>) -> bool) {
+        let mut shape_cache = self.shape_cache.borrow_mut();
+        let purged: Vec<ShapeCacheKey> = shape_cache
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                Ok(infos) if infos.iter().any(|info| pred(&info.glyph)) => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+        for key in purged {
+            shape_cache.pop(&key);
+        }
+    }
+
     pub fn recreate_texture_atlas(&mut self, size: Option<usize>) -> anyhow::Result<()> {
         self.shape_cache.borrow_mut().clear();
         if let Some(render_state) = self.render_state.as_mut() {
@@ -1269,6 +1687,140 @@ impl super::TermWindow {
     }
 }
 
+/// Returns the range of non-blank cells that contains `x`, or that
+/// ends immediately before it, such as the word that is being typed
+fn word_range_at(line: &Line, x: usize) -> Range<usize> {
+    let cells = line.cells();
+    let is_blank = |idx: usize| {
+        cells
+            .get(idx)
+            .map(|cell| cell.str().trim().is_empty())
+            .unwrap_or(true)
+    };
+    let mut start = x;
+    while start > 0 && !is_blank(start - 1) {
+        start -= 1;
+    }
+    let mut end = x;
+    while !is_blank(end) {
+        end += 1;
+    }
+    start..end
+}
+
+/// Overlays a notice that the output of the pane is paused
+/// at the right of `line`
+fn overlay_pause_indicator(line: &mut Line, num_cols: usize) {
+    let text = "[output paused]";
+    let width = unicode_column_width(text);
+    if width > num_cols {
+        return;
+    }
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    line.overlay_text_with_attribute(num_cols - width, text, attrs);
+}
+
+/// Overlays a line of the debug HUD at the right of `line`
+fn overlay_debug_hud(line: &mut Line, num_cols: usize, text: &str) {
+    let text = format!(" {} ", text);
+    let width = unicode_column_width(&text);
+    if width > num_cols {
+        return;
+    }
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    line.overlay_text_with_attribute(num_cols - width, &text, attrs);
+}
+
+/// Overlays the time at which output arrived on `line`, if that
+/// was recorded, at its right
+fn overlay_line_timestamp(line: &mut Line, num_cols: usize, format: &str) {
+    let when = match line.timestamp() {
+        Some(when) => chrono::DateTime::<chrono::Local>::from(when),
+        None => return,
+    };
+    // chrono reports an invalid format as a fmt::Error, which
+    // format! would turn into a panic
+    let mut text = String::new();
+    if write!(&mut text, " {} ", when.format(format)).is_err() {
+        return;
+    }
+    let width = unicode_column_width(&text);
+    if width > num_cols {
+        return;
+    }
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    line.overlay_text_with_attribute(num_cols - width, &text, attrs);
+}
+
+/// Returns `fg` blended half way towards `bg`, for the subtle
+/// decorations that shouldn't draw as much attention as text
+fn dim_color(fg: LinearRgba, bg: LinearRgba) -> LinearRgba {
+    let (r, g, b, a) = fg.tuple();
+    let (bg_r, bg_g, bg_b, _) = bg.tuple();
+    LinearRgba::with_components((r + bg_r) / 2., (g + bg_g) / 2., (b + bg_b) / 2., a)
+}
+
+/// Returns the background color `bg` of a cell, which was resolved
+/// from `entry` of the palette, with the background tint of the pane
+/// composed over it
+fn tinted_background_color(
+    bg: LinearRgba,
+    entry: Option<PaletteEntry>,
+    params: &RenderScreenLineOpenGLParams,
+) -> QuadColor {
+    match params.background_tint {
+        // The tinted color no longer matches the palette entry
+        Some(_) => compose_background_tint(bg, params.background_tint).into(),
+        None => palette_color(bg, params.palette_slot, entry),
+    }
+}
+
+/// Composes the pane background tint over a cell background color,
+/// using the "over" operator so that the tint also shows where the
+/// background is transparent
+fn compose_background_tint(bg: LinearRgba, tint: Option<LinearRgba>) -> LinearRgba {
+    let tint = match tint {
+        Some(tint) => tint,
+        None => return bg,
+    };
+    let (r, g, b, a) = bg.tuple();
+    let (tr, tg, tb, ta) = tint.tuple();
+    let alpha = ta + a * (1.0 - ta);
+    if alpha <= 0.0 {
+        return bg;
+    }
+    let over = |t: f32, c: f32| (t * ta + c * a * (1.0 - ta)) / alpha;
+    LinearRgba::with_components(over(tr, r), over(tg, g), over(tb, b), alpha)
+}
+
+/// Overlays the number of lines below the viewport, and whether there
+/// has been output since it was scrolled back, at the right of `line`
+fn overlay_scroll_indicator(
+    line: &mut Line,
+    num_cols: usize,
+    lines_below: StableRowIndex,
+    new_output: bool,
+) {
+    let mut text = format!(
+        "[{} line{} below]",
+        lines_below,
+        if lines_below == 1 { "" } else { "s" }
+    );
+    if new_output {
+        text.push_str(" [new output]");
+    }
+    let width = unicode_column_width(&text);
+    if width > num_cols {
+        return;
+    }
+    let mut attrs = CellAttributes::default();
+    attrs.set_reverse(true);
+    line.overlay_text_with_attribute(num_cols - width, &text, attrs);
+}
+
 fn rgbcolor_to_window_color(color: RgbColor) -> LinearRgba {
     rgbcolor_alpha_to_window_color(color, 0xff)
 }
@@ -14,6 +14,7 @@ pub struct RowsAndCols {
 
 impl super::TermWindow {
     pub fn apply_scale_change(&mut self, dimensions: &Dimensions, font_scale: f64) {
+        self.restore_presentation_zoom_raster();
         let config = &self.config;
         let font_size = config.font_size * font_scale;
         let theoretical_height = font_size * dimensions.dpi as f64 / 72.0;
@@ -57,6 +58,7 @@ impl super::TermWindow {
         dimensions: &Dimensions,
         mut scale_changed_cells: Option<RowsAndCols>,
     ) {
+        self.restore_presentation_zoom_raster();
         let orig_dimensions = self.dimensions;
 
         self.dimensions = *dimensions;
@@ -173,7 +175,7 @@ impl super::TermWindow {
     #[allow(clippy::float_cmp)]
     pub fn scaling_changed(&mut self, dimensions: Dimensions, font_scale: f64) {
         let scale_changed =
-            dimensions.dpi != self.dimensions.dpi || font_scale != self.fonts.get_font_scale();
+            dimensions.dpi != self.dimensions.dpi || font_scale != self.base_font_scale();
 
         let scale_changed_cells = if scale_changed {
             let cell_dims = self.current_cell_dimensions();
@@ -190,7 +192,8 @@ impl super::TermWindow {
     /// the `adjust_window_size_when_changing_font_size` configuration and
     /// revises the scaling/resize change accordingly
     pub fn adjust_font_scale(&mut self, font_scale: f64) {
-        if !self.is_full_screen && self.config.adjust_window_size_when_changing_font_size {
+        if self.window_state.can_resize() && self.config.adjust_window_size_when_changing_font_size
+        {
             self.scaling_changed(self.dimensions, font_scale);
         } else {
             let dimensions = self.dimensions;
@@ -202,11 +205,11 @@ impl super::TermWindow {
     }
 
     pub fn decrease_font_size(&mut self) {
-        self.adjust_font_scale(self.fonts.get_font_scale() * 0.9);
+        self.adjust_font_scale(self.base_font_scale() * 0.9);
     }
 
     pub fn increase_font_size(&mut self) {
-        self.adjust_font_scale(self.fonts.get_font_scale() * 1.1);
+        self.adjust_font_scale(self.base_font_scale() * 1.1);
     }
 
     pub fn reset_font_size(&mut self) {
@@ -0,0 +1,121 @@
+use ::window::glium::backend::Context as GliumContext;
+use ::window::glium::framebuffer::SimpleFrameBuffer;
+use ::window::glium::texture::{MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d};
+use ::window::WindowOps;
+use anyhow::{anyhow, bail};
+use mux::pane::PaneId;
+use mux::tab::PositionedPane;
+use mux::Mux;
+use std::rc::Rc;
+
+impl super::TermWindow {
+    /// Renders the window offscreen through the normal render pipeline,
+    /// with the glyphs rasterized at `scale` times the size at which
+    /// they are presented, including any presentation zoom, and
+    /// returns the region occupied by the pane as a PNG image.
+    /// The pane must be visible in the active tab of this window.
+    pub(super) fn screenshot_pane(
+        &mut self,
+        pane_id: PaneId,
+        scale: f32,
+    ) -> anyhow::Result<Vec<u8>> {
+        if !(scale > 0.0 && scale <= 8.0) {
+            bail!("scale must be greater than 0 and no larger than 8");
+        }
+
+        let mux = Mux::get().unwrap();
+        let tab = mux
+            .get_active_tab_for_window(self.mux_window_id)
+            .ok_or_else(|| anyhow!("window has no active tab"))?;
+        let pos = tab
+            .iter_panes()
+            .into_iter()
+            .find(|pos| pos.pane.pane_id() == pane_id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "pane {} is not visible; activate its tab before capturing it",
+                    pane_id
+                )
+            })?;
+
+        let context = Rc::clone(
+            &self
+                .render_state
+                .as_ref()
+                .ok_or_else(|| anyhow!("window has no render state"))?
+                .context,
+        );
+
+        // Rasterize the glyphs at the scaled size, in the same way as
+        // presentation zoom does, so that the text is crisp rather than
+        // the quads being stretched.  The whole surface is rendered, even
+        // when only part of it is presented in the zoomed window.
+        let zoom = self.presented_screenshot_zoom(scale);
+        self.restore_presentation_zoom_raster();
+        let result = self.without_presentation_zoom(|term_window| {
+            if !term_window.rasterize_presentation_zoom(zoom) {
+                bail!("failed to rasterize the glyphs at scale {}", zoom);
+            }
+            let result = term_window.render_screenshot(&context, &pos);
+            term_window.restore_presentation_zoom_raster();
+            result
+        });
+        // We've consumed a vertex buffer; make sure that the window
+        // is painted again with fresh quads
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+        let image = result?;
+
+        let mut png = vec![];
+        image::DynamicImage::ImageRgba8(image).write_to(&mut png, image::ImageOutputFormat::Png)?;
+        Ok(png)
+    }
+
+    /// Renders the window into a texture of the size of the render
+    /// surface and returns the region occupied by the pane at `pos`
+    fn render_screenshot(
+        &mut self,
+        context: &Rc<GliumContext>,
+        pos: &PositionedPane,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let surface = self.render_surface_dimensions();
+        let width = surface.pixel_width as u32;
+        let height = surface.pixel_height as u32;
+        let texture = SrgbTexture2d::empty_with_format(
+            context,
+            SrgbFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )?;
+
+        {
+            let mut frame = SimpleFrameBuffer::new(context, &texture)?;
+            self.clear_background(&mut frame);
+            self.paint_opengl_pass_with_retry();
+            self.call_draw(&mut frame)?;
+        }
+
+        let raw: RawImage2d<u8> = texture.read();
+        let image = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())
+            .ok_or_else(|| anyhow!("texture data has an unexpected size"))?;
+        // OpenGL stores the rows bottom to top
+        let image = image::imageops::flip_vertical(&image);
+
+        // The glyphs were rasterized at the scaled size, so the scaled
+        // metrics describe the render surface; the padding is unscaled
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
+        let left = self.config.window_padding.left as f32 + pos.left as f32 * cell_width;
+        let top = self.config.window_padding.top as f32
+            + (pos.top + first_line_offset) as f32 * cell_height;
+
+        let x = (left.round() as u32).min(width);
+        let y = (top.round() as u32).min(height);
+        let w = ((pos.width as f32 * cell_width).round() as u32).min(width - x);
+        let h = ((pos.height as f32 * cell_height).round() as u32).min(height - y);
+        Ok(image::imageops::crop_imm(&image, x, y, w, h).to_image())
+    }
+}
@@ -1,8 +1,14 @@
 use crate::selection::{SelectionCoordinate, SelectionMode, SelectionRange};
+use ::window::RichClipboardText;
 use ::window::WindowOps;
+use config::keyassignment::CopyTextFormat;
+use config::HyperlinkCopyFormat;
 use mux::pane::Pane;
+use std::ops::Range;
 use std::rc::Rc;
-use wezterm_term::StableRowIndex;
+use std::sync::Arc;
+use termwiz::hyperlink::Hyperlink;
+use wezterm_term::{Line, StableRowIndex};
 
 impl super::TermWindow {
     pub fn selection_text(&self, pane: &Rc<dyn Pane>) -> String {
@@ -22,7 +28,7 @@ impl super::TermWindow {
                 if !s.is_empty() && !last_was_wrapped {
                     s.push('\n');
                 }
-                s.push_str(line.columns_as_str(cols).trim_end());
+                s.push_str(self.columns_as_str_for_copy(line, cols).trim_end());
 
                 let last_cell = &line.cells()[last_col_idx];
                 // TODO: should really test for any unicode whitespace
@@ -33,6 +39,111 @@ impl super::TermWindow {
         s
     }
 
+    /// Returns the selected lines, along with the range of
+    /// columns that are selected in each of them
+    fn selection_lines(&self, pane: &Rc<dyn Pane>) -> Vec<(Line, Range<usize>)> {
+        let sel = match self
+            .selection(pane.pane_id())
+            .range
+            .as_ref()
+            .map(|r| r.normalize())
+        {
+            Some(sel) => sel,
+            None => return vec![],
+        };
+        let (first_row, lines) =
+            pane.get_lines_with_hyperlinks_applied(sel.rows(), &self.config.hyperlink_rules);
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                let cols = sel.cols_for_row(first_row + idx as StableRowIndex);
+                (line, cols)
+            })
+            .collect()
+    }
+
+    /// Returns the selection as plain text, along with HTML and/or
+    /// RTF representations that retain the colors and attributes
+    pub fn selection_rich_text(
+        &self,
+        pane: &Rc<dyn Pane>,
+        format: CopyTextFormat,
+    ) -> RichClipboardText {
+        let text = self.selection_text(pane);
+        if format == CopyTextFormat::PlainText {
+            return RichClipboardText {
+                text,
+                ..Default::default()
+            };
+        }
+
+        let lines = self.selection_lines(pane);
+        let palette = pane.palette();
+        let font_family = self.config.font.font.first().map(|f| f.family.as_str());
+
+        RichClipboardText {
+            text,
+            html: match format {
+                CopyTextFormat::Html | CopyTextFormat::HtmlAndRtf => Some(
+                    wezterm_term::export::lines_to_html(&lines, &palette, font_family),
+                ),
+                _ => None,
+            },
+            rtf: match format {
+                CopyTextFormat::Rtf | CopyTextFormat::HtmlAndRtf => Some(
+                    wezterm_term::export::lines_to_rtf(&lines, &palette, font_family),
+                ),
+                _ => None,
+            },
+        }
+    }
+
+    /// Like Line::columns_as_str, but represents explicit hyperlinks
+    /// according to the hyperlink_copy_format configuration
+    fn columns_as_str_for_copy(&self, line: &Line, cols: Range<usize>) -> String {
+        let format = self.config.hyperlink_copy_format;
+        if format == HyperlinkCopyFormat::Text {
+            return line.columns_as_str(cols);
+        }
+
+        fn flush(
+            s: &mut String,
+            run: &str,
+            link: Option<&Arc<Hyperlink>>,
+            format: HyperlinkCopyFormat,
+        ) {
+            match (link, format) {
+                (Some(link), HyperlinkCopyFormat::Url) => s.push_str(link.uri()),
+                (Some(link), HyperlinkCopyFormat::Markdown) => {
+                    s.push_str(&format!("[{}]({})", run.trim_end(), link.uri()))
+                }
+                _ => s.push_str(run),
+            }
+        }
+
+        let mut s = String::new();
+        let mut run = String::new();
+        let mut link: Option<&Arc<Hyperlink>> = None;
+        for (n, c) in line.visible_cells() {
+            if n < cols.start {
+                continue;
+            }
+            if n >= cols.end {
+                break;
+            }
+            let cell_link = c.attrs().hyperlink().filter(|l| !l.is_implicit());
+            if cell_link != link {
+                flush(&mut s, &run, link, format);
+                run.clear();
+                link = cell_link;
+            }
+            run.push_str(c.str());
+        }
+        flush(&mut s, &run, link, format);
+        s
+    }
+
     pub fn extend_selection_at_mouse_cursor(
         &mut self,
         mode: Option<SelectionMode>,
@@ -66,7 +66,10 @@ impl super::TermWindow {
         let mux_builder;
 
         let target_window_id = if spawn_where == SpawnWhere::NewWindow {
-            mux_builder = mux.new_empty_window();
+            mux_builder = mux.new_empty_window(None);
+            if let Some(cls) = spawn.class.as_ref() {
+                crate::termwindow::set_window_class_for_mux_window(*mux_builder, cls);
+            }
             *mux_builder
         } else {
             src_window_id
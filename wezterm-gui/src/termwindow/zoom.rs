@@ -0,0 +1,402 @@
+//! Presentation zoom magnifies the rendered window without changing
+//! the size of its panes, so that a live demo can be zoomed in on
+//! without the programs running in it having to reflow their output.
+//!
+//! While the zoom factor is changing, the GPU scales up the quads that
+//! were rendered at the normal size.  Once it settles, the glyphs are
+//! rasterized again at the zoomed size, into a render surface that is
+//! correspondingly larger than the window, so that the text is crisp.
+use crate::utilsprites::RenderMetrics;
+use ::window::{Dimensions, MouseEvent, WindowOps};
+use std::time::{Duration, Instant};
+
+/// The largest supported zoom factor
+const MAX_ZOOM: f32 = 8.0;
+/// The factor applied by each IncreasePresentationZoom
+/// and DecreasePresentationZoom
+const ZOOM_STEP: f32 = 1.25;
+/// Controls the speed of the zoom animation; the distance to the
+/// target zoom factor is reduced by ~63% in this amount of time
+const ZOOM_TIME_CONSTANT: Duration = Duration::from_millis(60);
+/// How long the zoom factor must remain unchanged before the
+/// glyphs are rasterized at the zoomed size
+const RASTERIZE_DELAY: Duration = Duration::from_millis(250);
+
+/// Describes the render surface while the glyphs are
+/// rasterized at a zoomed size
+struct ZoomRaster {
+    /// The zoom factor used to rasterize the glyphs
+    zoom: f32,
+    /// The font scale and metrics that apply when not zoomed
+    base_font_scale: f64,
+    base_metrics: RenderMetrics,
+    /// The ratio of the zoomed cell size to the normal cell size
+    scale_x: f32,
+    scale_y: f32,
+    /// The size of the render surface
+    surface: Dimensions,
+}
+
+pub struct PresentationZoom {
+    /// The zoom factor that we are animating towards
+    target: f32,
+    /// The zoom factor used for the current frame
+    current: f32,
+    /// The point, relative to the center of the window, that
+    /// remains in place while zooming
+    focus: (f32, f32),
+    last_step: Instant,
+    /// When the current zoom factor reached the target
+    settled_at: Option<Instant>,
+    raster: Option<ZoomRaster>,
+}
+
+impl Default for PresentationZoom {
+    fn default() -> Self {
+        Self {
+            target: 1.0,
+            current: 1.0,
+            focus: (0., 0.),
+            last_step: Instant::now(),
+            settled_at: None,
+            raster: None,
+        }
+    }
+}
+
+impl PresentationZoom {
+    #[allow(clippy::float_cmp)]
+    pub fn is_zoomed(&self) -> bool {
+        self.target != 1.0 || self.current != 1.0
+    }
+
+    /// Moves the current zoom factor towards the target.
+    /// Returns true if the target has not yet been reached.
+    #[allow(clippy::float_cmp)]
+    fn step(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_step);
+        self.last_step = now;
+        if self.current == self.target {
+            return false;
+        }
+
+        let t = 1.0 - (-elapsed.as_secs_f32() / ZOOM_TIME_CONSTANT.as_secs_f32()).exp();
+        self.current += (self.target - self.current) * t;
+        if (self.target - self.current).abs() < 0.002 {
+            self.current = self.target;
+            self.settled_at.replace(now);
+            return false;
+        }
+        true
+    }
+}
+
+impl super::TermWindow {
+    pub fn increase_presentation_zoom(&mut self) {
+        self.set_presentation_zoom(self.zoom.target * ZOOM_STEP);
+    }
+
+    pub fn decrease_presentation_zoom(&mut self) {
+        self.set_presentation_zoom(self.zoom.target / ZOOM_STEP);
+    }
+
+    pub fn reset_presentation_zoom(&mut self) {
+        self.set_presentation_zoom(1.0);
+    }
+
+    fn set_presentation_zoom(&mut self, target: f32) {
+        let target = target.max(1.0).min(MAX_ZOOM);
+        if !self.zoom.is_zoomed() {
+            self.zoom.focus = self.presentation_zoom_focus();
+        }
+        self.zoom.target = target;
+        self.zoom.last_step = Instant::now();
+        self.zoom.settled_at = None;
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns the center of the cursor cell of the active pane,
+    /// relative to the center of the window
+    fn presentation_zoom_focus(&mut self) -> (f32, f32) {
+        let panes = self.get_panes_to_render();
+        let pos = match panes.iter().find(|pos| pos.is_active) {
+            Some(pos) => pos,
+            None => return (0., 0.),
+        };
+        let cursor = pos.pane.get_cursor_position();
+        let dims = pos.pane.get_dimensions();
+        let top = self
+            .get_viewport(pos.pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
+        let row = (cursor.y - top).max(0).min(dims.viewport_rows as isize - 1) as usize;
+
+        let metrics = match self.zoom.raster.as_ref() {
+            Some(raster) => &raster.base_metrics,
+            None => &self.render_metrics,
+        };
+        let x = self.config.window_padding.left as f32
+            + (pos.left + cursor.x) as f32 * metrics.cell_size.width as f32
+            + metrics.cell_size.width as f32 / 2.;
+        let y = self.config.window_padding.top as f32
+            + (pos.top + first_line_offset + row) as f32 * metrics.cell_size.height as f32
+            + metrics.cell_size.height as f32 / 2.;
+
+        (
+            x - self.dimensions.pixel_width as f32 / 2.,
+            y - self.dimensions.pixel_height as f32 / 2.,
+        )
+    }
+
+    /// The font scale that applies when not zoomed
+    pub fn base_font_scale(&self) -> f64 {
+        match self.zoom.raster.as_ref() {
+            Some(raster) => raster.base_font_scale,
+            None => self.fonts.get_font_scale(),
+        }
+    }
+
+    /// Returns the dimensions of the surface into which the quads are
+    /// rendered; this is larger than the window while the glyphs are
+    /// rasterized at a zoomed size
+    pub fn render_surface_dimensions(&self) -> Dimensions {
+        match self.zoom.raster.as_ref() {
+            Some(raster) => raster.surface,
+            None => self.dimensions,
+        }
+    }
+
+    /// Maps a point in the unzoomed window, relative to its center,
+    /// to the corresponding point in the render surface
+    fn window_to_surface(&self, x: f32, y: f32) -> (f32, f32) {
+        match self.zoom.raster.as_ref() {
+            None => (x, y),
+            Some(raster) => {
+                let padding_left = self.config.window_padding.left as f32;
+                let padding_top = self.config.window_padding.top as f32;
+                let width = self.dimensions.pixel_width as f32;
+                let height = self.dimensions.pixel_height as f32;
+                (
+                    raster.surface.pixel_width as f32 / -2.
+                        + padding_left
+                        + (x + width / 2. - padding_left) * raster.scale_x,
+                    raster.surface.pixel_height as f32 / -2.
+                        + padding_top
+                        + (y + height / 2. - padding_top) * raster.scale_y,
+                )
+            }
+        }
+    }
+
+    /// Maps a point in the window, relative to its center, to the
+    /// point in the unzoomed window that is displayed there
+    fn unzoom_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let zoom = self.zoom.current;
+        let (focus_x, focus_y) = self.zoom.focus;
+        (
+            focus_x + (x - focus_x) / zoom,
+            focus_y + (y - focus_y) / zoom,
+        )
+    }
+
+    /// Advances the zoom animation and returns the projection that
+    /// maps the visible portion of the render surface onto the window
+    pub fn presentation_zoom_projection(&mut self) -> [[f32; 4]; 4] {
+        let now = Instant::now();
+        if self.zoom.step(now) {
+            self.update_next_frame_time(Some(now + Duration::from_millis(16)));
+        }
+
+        let width = self.dimensions.pixel_width as f32;
+        let height = self.dimensions.pixel_height as f32;
+        let (left, top) = self.unzoom_point(width / -2., height / -2.);
+        let (right, bottom) = self.unzoom_point(width / 2., height / 2.);
+        let (left, top) = self.window_to_surface(left, top);
+        let (right, bottom) = self.window_to_surface(right, bottom);
+
+        euclid::Transform3D::<f32, f32, f32>::ortho(left, right, bottom, top, -1.0, 1.0)
+            .to_arrays_transposed()
+    }
+
+    /// Maps the window coordinates of a mouse event to the
+    /// corresponding coordinates in the render surface
+    pub fn presentation_zoom_mouse_event(&self, event: &MouseEvent) -> MouseEvent {
+        let mut event = event.clone();
+        if !self.zoom.is_zoomed() && self.zoom.raster.is_none() {
+            return event;
+        }
+
+        let width = self.dimensions.pixel_width as f32;
+        let height = self.dimensions.pixel_height as f32;
+        let (x, y) = self.unzoom_point(
+            event.coords.x as f32 - width / 2.,
+            event.coords.y as f32 - height / 2.,
+        );
+        let (x, y) = self.window_to_surface(x, y);
+        let surface = self.render_surface_dimensions();
+        event.coords.x = (x + surface.pixel_width as f32 / 2.).round() as isize;
+        event.coords.y = (y + surface.pixel_height as f32 / 2.).round() as isize;
+        event
+    }
+
+    /// Called periodically; once the zoom factor has settled, the glyphs
+    /// are rasterized at the zoomed size, or at the normal size after
+    /// the zoom has been reset
+    #[allow(clippy::float_cmp)]
+    pub fn maintain_presentation_zoom(&mut self) {
+        let settled_at = match self.zoom.settled_at {
+            Some(settled_at) if self.zoom.current == self.zoom.target => settled_at,
+            _ => return,
+        };
+        if settled_at.elapsed() < RASTERIZE_DELAY {
+            return;
+        }
+
+        let target = self.zoom.target;
+        let raster_zoom = self.zoom.raster.as_ref().map(|r| r.zoom).unwrap_or(1.0);
+        if raster_zoom != target {
+            if target == 1.0 {
+                self.restore_presentation_zoom_raster();
+            } else {
+                self.rasterize_presentation_zoom(target);
+            }
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+        self.zoom.settled_at = None;
+    }
+
+    /// Returns the factor by which `scale` must be multiplied so that
+    /// a screenshot matches the presented size of the window, limited
+    /// to the largest supported zoom factor
+    pub(super) fn presented_screenshot_zoom(&self, scale: f32) -> f32 {
+        (scale * self.zoom.current).min(MAX_ZOOM)
+    }
+
+    /// Calls `func` with the presentation zoom temporarily reset, so
+    /// that it renders the whole surface rather than the magnified
+    /// portion shown in the window.  The glyphs must not be rasterized
+    /// at a zoomed size when this is called, and `func` must restore
+    /// them to their normal size before it returns.
+    pub(super) fn without_presentation_zoom<R>(&mut self, func: impl FnOnce(&mut Self) -> R) -> R {
+        let zoom = std::mem::take(&mut self.zoom);
+        let result = func(self);
+        self.zoom = zoom;
+        self.zoom.last_step = Instant::now();
+        result
+    }
+
+    /// Rasterizes the glyphs at `zoom` times their normal size.
+    /// Returns false if the fonts could not be scaled.
+    pub(super) fn rasterize_presentation_zoom(&mut self, zoom: f32) -> bool {
+        self.restore_presentation_zoom_raster();
+
+        let base_font_scale = self.fonts.get_font_scale();
+        let base_metrics = self.render_metrics.clone();
+        let base_padding_right = self.effective_right_padding(&self.config);
+        let dpi_scale = self.dimensions.dpi as f64 / ::window::default_dpi();
+
+        self.fonts
+            .change_scaling(base_font_scale * zoom as f64, dpi_scale);
+        match RenderMetrics::new(&self.fonts) {
+            Ok(metrics) => {
+                self.render_metrics = metrics;
+            }
+            Err(err) => {
+                log::error!(
+                    "{:#} while attempting to rasterize for presentation zoom {}",
+                    err,
+                    zoom
+                );
+                self.fonts.change_scaling(base_font_scale, dpi_scale);
+                return false;
+            }
+        }
+
+        let scale_x =
+            self.render_metrics.cell_size.width as f32 / base_metrics.cell_size.width as f32;
+        let scale_y =
+            self.render_metrics.cell_size.height as f32 / base_metrics.cell_size.height as f32;
+
+        // The cells occupy the zoomed size while the padding remains
+        // the same, so that the surface holds as many rows and
+        // columns as the window
+        let config = &self.config;
+        let padding_width = (config.window_padding.left + base_padding_right) as usize;
+        let padding_height = (config.window_padding.top + config.window_padding.bottom) as usize;
+        let surface = Dimensions {
+            pixel_width: (config.window_padding.left + self.effective_right_padding(config))
+                as usize
+                + (self.dimensions.pixel_width.saturating_sub(padding_width) as f32 * scale_x)
+                    .ceil() as usize,
+            pixel_height: padding_height
+                + (self.dimensions.pixel_height.saturating_sub(padding_height) as f32 * scale_y)
+                    .ceil() as usize,
+            dpi: self.dimensions.dpi,
+        };
+
+        self.zoom.raster.replace(ZoomRaster {
+            zoom,
+            base_font_scale,
+            base_metrics,
+            scale_x,
+            scale_y,
+            surface,
+        });
+        self.advise_render_surface_size();
+        if let Err(err) = self.recreate_texture_atlas(None) {
+            log::error!("recreate_texture_atlas: {:#}", err);
+        }
+        true
+    }
+
+    /// Returns to rasterizing the glyphs at their normal size.
+    /// This is called prior to changing the font scale or the
+    /// size of the window; the glyphs are rasterized at the zoomed
+    /// size again once things have settled.
+    pub fn restore_presentation_zoom_raster(&mut self) {
+        if self.discard_presentation_zoom_raster() {
+            self.advise_render_surface_size();
+            if let Err(err) = self.recreate_texture_atlas(None) {
+                log::error!("recreate_texture_atlas: {:#}", err);
+            }
+            self.zoom.settled_at.replace(Instant::now());
+        }
+    }
+
+    /// Restores the font scale and metrics that apply when not zoomed,
+    /// without touching the render state.
+    /// Returns true if the glyphs had been rasterized at a zoomed size.
+    pub fn discard_presentation_zoom_raster(&mut self) -> bool {
+        match self.zoom.raster.take() {
+            Some(raster) => {
+                let dpi_scale = self.dimensions.dpi as f64 / ::window::default_dpi();
+                self.fonts.change_scaling(raster.base_font_scale, dpi_scale);
+                self.render_metrics = raster.base_metrics;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn advise_render_surface_size(&mut self) {
+        let surface = self.render_surface_dimensions();
+        if let Some(render_state) = self.render_state.as_mut() {
+            if let Err(err) = render_state.advise_of_window_size_change(
+                &self.config,
+                &self.render_metrics,
+                surface.pixel_width,
+                surface.pixel_height,
+            ) {
+                log::error!(
+                    "failed to resize render surface to {:?}: {:#}",
+                    surface,
+                    err
+                );
+            }
+        }
+    }
+}
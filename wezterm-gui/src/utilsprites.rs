@@ -1,10 +1,10 @@
 use super::glyphcache::GlyphCache;
-use ::window::bitmaps::atlas::{OutOfTextureSpace, Sprite};
+use ::window::bitmaps::atlas::Sprite;
 use ::window::bitmaps::{BitmapImage, Image, Texture2d};
 use ::window::color::SrgbaPixel;
-use ::window::{Point, Rect, Size};
+use ::window::{Point, PointF, Rect, Size};
 use anyhow::Context;
-use config::configuration;
+use config::{configuration, ConfigHandle};
 use std::rc::Rc;
 use termwiz::surface::CursorShape;
 use wezterm_font::units::*;
@@ -16,6 +16,14 @@ pub struct RenderMetrics {
     pub descender_row: IntPixelLength,
     pub descender_plus_two: IntPixelLength,
     pub underline_height: IntPixelLength,
+    /// The thickness of the lines of underlines and of the
+    /// computed box drawing and Powerline glyphs
+    pub stroke_thickness: IntPixelLength,
+    /// How far the computed glyphs extend into the adjacent cells
+    pub block_bleed: f32,
+    /// The factor by which the computed glyphs with curved or
+    /// diagonal edges are supersampled
+    pub supersample: usize,
     pub strike_row: IntPixelLength,
     pub cell_size: Size,
 }
@@ -26,7 +34,8 @@ impl RenderMetrics {
             .default_font_metrics()
             .context("failed to get font metrics!?")?;
 
-        let line_height = configuration().line_height;
+        let config = configuration();
+        let line_height = config.line_height;
 
         let (cell_height, cell_width) = (
             (metrics.cell_height.get() * line_height).ceil() as usize,
@@ -35,10 +44,24 @@ impl RenderMetrics {
 
         let underline_height = metrics.underline_thickness.get().round().max(1.) as isize;
 
+        // Bold fonts have heavier strokes than the underline thickness
+        // suggests, so the lines are made heavier to match them
+        let stroke_scale = config.custom_block_glyph_stroke_scale.unwrap_or_else(|| {
+            let bold = config.font.font.first().map(|f| f.bold).unwrap_or(false);
+            if bold {
+                1.5
+            } else {
+                1.0
+            }
+        });
+        let stroke_thickness = (metrics.underline_thickness.get() * stroke_scale)
+            .round()
+            .max(1.) as isize;
+
         let descender_row =
             (cell_height as f64 + (metrics.descender - metrics.underline_position).get()) as isize;
         let descender_plus_two =
-            (2 * underline_height + descender_row).min(cell_height as isize - underline_height);
+            (2 * stroke_thickness + descender_row).min(cell_height as isize - stroke_thickness);
         let strike_row = descender_row / 2;
 
         Ok(Self {
@@ -48,8 +71,31 @@ impl RenderMetrics {
             strike_row,
             cell_size: Size::new(cell_width as isize, cell_height as isize),
             underline_height,
+            stroke_thickness,
+            block_bleed: config.custom_block_glyph_bleed.max(0.),
+            supersample: config.custom_block_glyph_supersample.max(1).min(4) as usize,
         })
     }
+
+    /// Returns the metrics for drawing a glyph at `factor` times
+    /// the size of the cell, for supersampling
+    pub fn scaled(&self, factor: usize) -> Self {
+        let factor_isize = factor as isize;
+        Self {
+            descender: self.descender * factor as f64,
+            descender_row: self.descender_row * factor_isize,
+            descender_plus_two: self.descender_plus_two * factor_isize,
+            underline_height: self.underline_height * factor_isize,
+            stroke_thickness: self.stroke_thickness * factor_isize,
+            block_bleed: self.block_bleed * factor as f32,
+            supersample: 1,
+            strike_row: self.strike_row * factor_isize,
+            cell_size: Size::new(
+                self.cell_size.width * factor_isize,
+                self.cell_size.height * factor_isize,
+            ),
+        }
+    }
 }
 
 pub struct UtilSprites<T: Texture2d> {
@@ -57,13 +103,19 @@ pub struct UtilSprites<T: Texture2d> {
     pub cursor_box: Sprite<T>,
     pub cursor_i_beam: Sprite<T>,
     pub cursor_underline: Sprite<T>,
+    /// Drawn in the left padding next to the continuation
+    /// of a wrapped line
+    pub wrap_marker: Sprite<T>,
+}
+
+/// Returns the width of the wrap marker, which is drawn in the
+/// left window padding and so can be no wider than it
+pub fn wrap_marker_width(config: &ConfigHandle, metrics: &RenderMetrics) -> usize {
+    (config.window_padding.left as usize).min(metrics.cell_size.width as usize)
 }
 
 impl<T: Texture2d> UtilSprites<T> {
-    pub fn new(
-        glyph_cache: &mut GlyphCache<T>,
-        metrics: &RenderMetrics,
-    ) -> Result<Self, OutOfTextureSpace> {
+    pub fn new(glyph_cache: &mut GlyphCache<T>, metrics: &RenderMetrics) -> anyhow::Result<Self> {
         let mut buffer = Image::new(
             metrics.cell_size.width as usize,
             metrics.cell_size.height as usize,
@@ -75,7 +127,7 @@ impl<T: Texture2d> UtilSprites<T> {
         let cell_rect = Rect::new(Point::new(0, 0), metrics.cell_size);
 
         buffer.clear_rect(cell_rect, black);
-        let white_space = glyph_cache.atlas.allocate(&buffer)?;
+        let white_space = glyph_cache.allocate(&buffer, None)?;
 
         // Derive a width for the border box from the underline height,
         // but aspect ratio adjusted for width.
@@ -130,7 +182,7 @@ impl<T: Texture2d> UtilSprites<T> {
                 white,
             );
         }
-        let cursor_box = glyph_cache.atlas.allocate(&buffer)?;
+        let cursor_box = glyph_cache.allocate(&buffer, None)?;
 
         buffer.clear_rect(cell_rect, black);
         for i in 0..border_width * 2 {
@@ -144,7 +196,7 @@ impl<T: Texture2d> UtilSprites<T> {
                 white,
             );
         }
-        let cursor_i_beam = glyph_cache.atlas.allocate(&buffer)?;
+        let cursor_i_beam = glyph_cache.allocate(&buffer, None)?;
 
         buffer.clear_rect(cell_rect, black);
         for i in 0..metrics.underline_height {
@@ -161,13 +213,51 @@ impl<T: Texture2d> UtilSprites<T> {
                 white,
             );
         }
-        let cursor_underline = glyph_cache.atlas.allocate(&buffer)?;
+        let cursor_underline = glyph_cache.allocate(&buffer, None)?;
+
+        let marker_width = wrap_marker_width(&configuration(), metrics);
+        let wrap_marker = if marker_width > 0 {
+            // A hooked arrow, like `↪`, that points at the start
+            // of the row
+            let mut buffer = Image::new(marker_width, metrics.cell_size.height as usize);
+            let width = marker_width as f32;
+            let height = metrics.cell_size.height as f32;
+            let thickness = (metrics.underline_height as f32).min(width / 4.).max(1.);
+            let left = width * 0.25;
+            let right = width - thickness;
+            let middle = height / 2.;
+            let head = (width * 0.35).min(height / 4.);
+            buffer.draw_thick_line(
+                PointF::new(left, height * 0.25),
+                PointF::new(left, middle),
+                thickness,
+                white,
+            );
+            buffer.draw_thick_line(
+                PointF::new(left, middle),
+                PointF::new(right - head / 2., middle),
+                thickness,
+                white,
+            );
+            buffer.fill_polygon(
+                &[
+                    PointF::new(right, middle),
+                    PointF::new(right - head, middle - head / 2.),
+                    PointF::new(right - head, middle + head / 2.),
+                ],
+                white,
+            );
+            glyph_cache.allocate(&buffer, None)?
+        } else {
+            white_space.clone()
+        };
 
         Ok(Self {
             white_space,
             cursor_box,
             cursor_i_beam,
             cursor_underline,
+            wrap_marker,
         })
     }
 
@@ -14,8 +14,13 @@ in float has_color;
 in vec2 cursor;
 in vec4 cursor_color;
 in vec3 hsv;
+in float palette_slot;
+in vec4 palette_entries;
 
 uniform mat4 projection;
+// Each row holds the progress of the change to the palette of a pane,
+// followed by the colors of the palette that it is changing from
+uniform sampler2D palette_transitions;
 
 out float o_has_color;
 out vec2 o_cursor;
@@ -27,15 +32,67 @@ out vec4 o_cursor_color;
 out vec4 o_fg_color;
 out vec4 o_underline_color;
 
+// sRGB (D65) to CIE XYZ, and back again
+const mat3 rgb_to_xyz = mat3(
+    0.4124564, 0.2126729, 0.0193339,
+    0.3575761, 0.7151522, 0.1191920,
+    0.1804375, 0.0721750, 0.9503041);
+const mat3 xyz_to_rgb = mat3(
+    3.2404542, -0.9692660, 0.0556434,
+    -1.5371385, 1.8760108, -0.2040259,
+    -0.4985314, 0.0415560, 1.0572252);
+const vec3 d65_white = vec3(0.95047, 1.0, 1.08883);
+
+// The vertex colors hold the sRGB encoded components of the palette
+vec3 srgb_to_linear(vec3 c) {
+  return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+vec3 linear_to_srgb(vec3 c) {
+  return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec3 rgb_to_lab(vec3 c) {
+  vec3 xyz = max(rgb_to_xyz * srgb_to_linear(c) / d65_white, 0.0);
+  vec3 f = mix(xyz * 7.787 + 16.0 / 116.0, pow(xyz, vec3(1.0 / 3.0)), step(0.008856, xyz));
+  return vec3(116.0 * f.y - 16.0, 500.0 * (f.x - f.y), 200.0 * (f.y - f.z));
+}
+
+vec3 lab_to_rgb(vec3 lab) {
+  float fy = (lab.x + 16.0) / 116.0;
+  vec3 f = vec3(fy + lab.y / 500.0, fy, fy - lab.z / 200.0);
+  vec3 f3 = f * f * f;
+  vec3 xyz = mix((f - 16.0 / 116.0) / 7.787, f3, step(0.008856, f3)) * d65_white;
+  return linear_to_srgb(clamp(xyz_to_rgb * xyz, 0.0, 1.0));
+}
+
+// If color was taken from the palette entry that is one less than
+// entry, and the palette of the pane is changing, returns the color
+// part way between the color that entry had before the change and color.
+// The colors are blended in the CIE L*a*b* color space so that the
+// perceived lightness changes evenly.
+vec4 palette_transition(vec4 color, float entry) {
+  if (entry == 0.0) {
+    return color;
+  }
+  int slot = int(palette_slot);
+  float t = texelFetch(palette_transitions, ivec2(0, slot), 0).r;
+  if (t >= 1.0) {
+    return color;
+  }
+  vec4 from = texelFetch(palette_transitions, ivec2(int(entry), slot), 0);
+  return vec4(lab_to_rgb(mix(rgb_to_lab(from.rgb), rgb_to_lab(color.rgb), t)), color.a);
+}
+
 void pass_through_vertex() {
   o_tex = tex;
   o_has_color = has_color;
-  o_fg_color = fg_color;
-  o_bg_color = bg_color;
+  o_fg_color = palette_transition(fg_color, palette_entries.x);
+  o_bg_color = palette_transition(bg_color, palette_entries.y);
   o_underline = underline;
-  o_underline_color = underline_color;
+  o_underline_color = palette_transition(underline_color, palette_entries.z);
   o_cursor = cursor;
-  o_cursor_color = cursor_color;
+  o_cursor_color = palette_transition(cursor_color, palette_entries.w);
   o_hsv = hsv;
 }
 
@@ -24,6 +24,19 @@ enum Item {
 }
 
 pub async fn process<T>(stream: T) -> anyhow::Result<()>
+where
+    T: 'static,
+    T: std::io::Read,
+    T: std::io::Write,
+    T: AsRawDesc,
+    T: std::fmt::Debug,
+{
+    process_with_auth_token(stream, None).await
+}
+
+/// Like `process`, but when `auth_token` is set, the client must
+/// present it before its requests are processed
+pub async fn process_with_auth_token<T>(stream: T, auth_token: Option<String>) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -32,10 +45,13 @@ where
     T: std::fmt::Debug,
 {
     let stream = smol::Async::new(stream)?;
-    process_async(stream).await
+    process_async(stream, auth_token).await
 }
 
-pub async fn process_async<T>(mut stream: Async<T>) -> anyhow::Result<()>
+pub async fn process_async<T>(
+    mut stream: Async<T>,
+    auth_token: Option<String>,
+) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -55,6 +71,9 @@ where
         }
     });
     let mut handler = SessionHandler::new(pdu_sender);
+    if let Some(token) = auth_token {
+        handler.require_auth_token(token);
+    }
 
     {
         let mux = Mux::get().expect("to be running on gui thread");
@@ -86,6 +105,10 @@ where
                 handler.schedule_pane_push(pane_id);
             }
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
+            Ok(Item::Notif(MuxNotification::ScreenshotRequested(_))) => {}
+            Ok(Item::Notif(MuxNotification::PerfStatsRequested(_))) => {}
+            Ok(Item::Notif(MuxNotification::EventRequested(_))) => {}
+            Ok(Item::Notif(MuxNotification::DomainConnectionChanged(_))) => {}
             Err(err) => {
                 log::error!("process_async Err {}", err);
                 return Ok(());
@@ -1,31 +1,52 @@
-use crate::UnixListener;
+use crate::{UnixListener, UnixStream};
 use anyhow::{anyhow, Context as _};
 use config::{create_user_owned_dirs, UnixDomain};
 use promise::spawn::spawn_into_main_thread;
 
 pub struct LocalListener {
     listener: UnixListener,
+    /// When true, connections from processes that are running as
+    /// a different user are rejected
+    check_peer: bool,
+    auth_token: Option<String>,
 }
 
 impl LocalListener {
     pub fn new(listener: UnixListener) -> Self {
-        Self { listener }
+        Self {
+            listener,
+            check_peer: true,
+            auth_token: None,
+        }
     }
 
     pub fn with_domain(unix_dom: &UnixDomain) -> anyhow::Result<Self> {
         let listener = safely_create_sock_path(unix_dom)?;
-        Ok(Self::new(listener))
+        Ok(Self {
+            listener,
+            check_peer: should_check_permissions(unix_dom),
+            auth_token: unix_dom.auth_token.clone(),
+        })
     }
 
     pub fn run(&mut self) {
         for stream in self.listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    if self.check_peer {
+                        if let Err(err) = verify_peer(&stream) {
+                            log::error!("{:#}", err);
+                            continue;
+                        }
+                    }
+                    let auth_token = self.auth_token.clone();
                     spawn_into_main_thread(async move {
-                        crate::dispatch::process(stream).await.map_err(|e| {
-                            log::error!("{:#}", e);
-                            e
-                        })
+                        crate::dispatch::process_with_auth_token(stream, auth_token)
+                            .await
+                            .map_err(|e| {
+                                log::error!("{:#}", e);
+                                e
+                            })
                     })
                     .detach();
                 }
@@ -50,18 +71,34 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
         .parent()
         .ok_or_else(|| anyhow!("sock_path {} has no parent dir", sock_path.display()))?;
 
+    // Only a directory that belongs to wezterm may have its permissions
+    // changed; the socket_path may name a directory such as the home
+    // directory of the user, or one that is shared with other programs
+    #[cfg(unix)]
+    let owned_by_wezterm = !sock_dir.exists() || sock_dir == config::RUNTIME_DIR.as_path();
     create_user_owned_dirs(sock_dir)?;
 
     #[cfg(unix)]
     {
-        use config::running_under_wsl;
-        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
-        if !running_under_wsl() && !unix_dom.skip_permissions_check {
+        if should_check_permissions(unix_dom) {
             // Let's be sure that the ownership looks sane
             let meta = sock_dir.symlink_metadata()?;
 
-            let permissions = meta.permissions();
+            let uid = unsafe { libc::geteuid() };
+            if meta.uid() != uid {
+                anyhow::bail!(
+                    "{} is owned by uid {} rather than uid {}, the user \
+                     running wezterm, so it is not safe to create the \
+                     socket in it",
+                    sock_dir.display(),
+                    meta.uid(),
+                    uid
+                );
+            }
+
+            let mut permissions = meta.permissions();
             if (permissions.mode() & 0o22) != 0 {
                 anyhow::bail!(
                     "The permissions for {} are insecure and currently \
@@ -70,6 +107,29 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
                     permissions
                 );
             }
+
+            // Other users have no business looking inside the
+            // directory either, so tighten it up to match the
+            // permissions that we create it with
+            if (permissions.mode() & 0o77) != 0 {
+                if owned_by_wezterm {
+                    log::warn!(
+                        "restricting the permissions of {} to the current user",
+                        sock_dir.display()
+                    );
+                    permissions.set_mode(0o700);
+                    std::fs::set_permissions(sock_dir, permissions).with_context(|| {
+                        format!("restricting permissions of {}", sock_dir.display())
+                    })?;
+                } else {
+                    log::warn!(
+                        "{} can be read by other users; leaving its permissions \
+                         alone as wezterm didn't create it.  The socket itself \
+                         is only accessible to the current user",
+                        sock_dir.display()
+                    );
+                }
+            }
         }
     }
 
@@ -85,6 +145,78 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
         },
     }
 
-    UnixListener::bind(sock_path)
-        .with_context(|| format!("Failed to bind to {}", sock_path.display()))
+    let listener = UnixListener::bind(sock_path)
+        .with_context(|| format!("Failed to bind to {}", sock_path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if should_check_permissions(unix_dom) {
+            std::fs::set_permissions(sock_path, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("restricting permissions of {}", sock_path.display()))?;
+        }
+    }
+
+    Ok(listener)
+}
+
+fn should_check_permissions(unix_dom: &UnixDomain) -> bool {
+    !config::running_under_wsl() && !unix_dom.skip_permissions_check
+}
+
+/// Rejects connections from processes that are running as a different
+/// user; the directory permissions should already prevent that, but
+/// this guards against a socket_path that is reachable by other users
+#[cfg(unix)]
+fn verify_peer(stream: &UnixStream) -> anyhow::Result<()> {
+    let peer_uid = peer_uid(stream).context("determining the uid of the peer")?;
+    let uid = unsafe { libc::geteuid() };
+    if peer_uid != uid {
+        anyhow::bail!(
+            "rejecting mux connection from uid {}; only uid {} may connect",
+            peer_uid,
+            uid
+        );
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn verify_peer(_stream: &UnixStream) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> anyhow::Result<libc::uid_t> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("getsockopt SO_PEERCRED");
+    }
+    Ok(cred.uid)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn peer_uid(stream: &UnixStream) -> anyhow::Result<libc::uid_t> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    let res = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("getpeereid");
+    }
+    Ok(uid)
 }
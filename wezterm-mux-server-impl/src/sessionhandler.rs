@@ -5,7 +5,8 @@ use config::keyassignment::SpawnTabDomain;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
-use mux::Mux;
+use mux::window::WindowId;
+use mux::{EventRequest, Mux, MuxNotification, PerfStatsRequest, ScreenshotRequest};
 use portable_pty::PtySize;
 use promise::spawn::spawn_into_main_thread;
 use rangeset::RangeSet;
@@ -15,7 +16,7 @@ use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use url::Url;
 use wezterm_term::terminal::{Clipboard, ClipboardSelection};
-use wezterm_term::StableRowIndex;
+use wezterm_term::{Line, StableRowIndex};
 
 #[derive(Clone)]
 pub struct PduSender {
@@ -43,6 +44,13 @@ struct PerPane {
     dimensions: RenderableDimensions,
     dirty_lines: RangeSet<StableRowIndex>,
     mouse_grabbed: bool,
+    /// The viewport lines most recently sent to the client, so that
+    /// subsequent updates only need to include the lines that differ
+    sent_lines: HashMap<StableRowIndex, Line>,
+    /// The seqno of the most recent update sent to the client
+    seqno: u64,
+    /// Set when the client has asked for the whole viewport to be resent
+    resync: bool,
 }
 
 impl PerPane {
@@ -84,6 +92,10 @@ impl PerPane {
             changed = true;
         }
 
+        if self.resync {
+            changed = true;
+        }
+
         if !changed && !force_with_input_serial.is_some() {
             return None;
         }
@@ -92,21 +104,35 @@ impl PerPane {
         let viewport_range =
             dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
 
+        // The lines that the client has from a previous update are only
+        // valid while the dimensions are unchanged
+        if self.resync || dims != self.dimensions {
+            self.sent_lines.clear();
+            self.resync = false;
+        }
+        let base_seqno = if self.sent_lines.is_empty() {
+            0
+        } else {
+            self.seqno
+        };
+        self.seqno += 1;
+
+        // Only send the viewport lines that differ from those that we
+        // last sent.  The client retains those, and will ask for a resync
+        // if it no longer has them.
         let (first_line, lines) = pane.get_lines(viewport_range);
-        let mut bonus_lines = lines
-            .into_iter()
-            .enumerate()
-            .map(|(idx, line)| {
-                let stable_row = first_line + idx as StableRowIndex;
-                all_dirty_lines.remove(stable_row);
-                (stable_row, line)
-            })
-            .collect::<Vec<_>>();
-
-        // Always send the cursor's row, as that tends to the busiest and we don't
-        // have a sequencing concept for our idea of the remote state.
-        let (cursor_line, lines) = pane.get_lines(cursor_position.y..cursor_position.y + 1);
-        bonus_lines.push((cursor_line, lines[0].clone()));
+        let mut sent_lines = HashMap::new();
+        let mut bonus_lines = vec![];
+        for (idx, mut line) in lines.into_iter().enumerate() {
+            let stable_row = first_line + idx as StableRowIndex;
+            all_dirty_lines.remove(stable_row);
+            line.clear_dirty();
+            if self.sent_lines.get(&stable_row) != Some(&line) {
+                bonus_lines.push((stable_row, line.clone()));
+            }
+            sent_lines.insert(stable_row, line);
+        }
+        self.sent_lines = sent_lines;
 
         self.cursor_position = cursor_position;
         self.title = title.clone();
@@ -127,12 +153,19 @@ impl PerPane {
             bonus_lines,
             working_dir: working_dir.map(Into::into),
             input_serial: force_with_input_serial,
+            seqno: self.seqno,
+            base_seqno,
         })
     }
 
     fn mark_clean(&mut self, stable_row: StableRowIndex) {
         self.dirty_lines.remove(stable_row);
     }
+
+    /// Arrange for the next update to include the whole viewport
+    fn request_resync(&mut self) {
+        self.resync = true;
+    }
 }
 
 fn maybe_push_pane_changes(
@@ -153,6 +186,8 @@ fn maybe_push_pane_changes(
 pub struct SessionHandler {
     to_write_tx: PduSender,
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
+    auth_token: Option<String>,
+    authenticated: bool,
 }
 
 impl SessionHandler {
@@ -160,8 +195,17 @@ impl SessionHandler {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
+            auth_token: None,
+            authenticated: true,
         }
     }
+
+    /// Requires the client to present `token` via an Authenticate
+    /// PDU before any of its other requests are processed
+    pub fn require_auth_token(&mut self, token: String) {
+        self.auth_token.replace(token);
+        self.authenticated = false;
+    }
     fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
         Arc::clone(
             self.per_pane
@@ -208,8 +252,32 @@ impl SessionHandler {
             send_response(f());
         }
 
+        if !self.authenticated {
+            match &decoded.pdu {
+                Pdu::Ping(_) | Pdu::GetCodecVersion(_) | Pdu::Authenticate(_) => {}
+                _ => {
+                    log::error!("rejecting {:?} from an unauthenticated client", decoded.pdu);
+                    send_response(Err(anyhow!("authentication is required")));
+                    return;
+                }
+            }
+        }
+
         match decoded.pdu {
             Pdu::Ping(Ping {}) => send_response(Ok(Pdu::Pong(Pong {}))),
+            Pdu::Authenticate(Authenticate { token }) => {
+                let valid = match &self.auth_token {
+                    Some(expected) => tokens_match(expected, &token),
+                    None => true,
+                };
+                if valid {
+                    self.authenticated = true;
+                    send_response(Ok(Pdu::UnitResponse(UnitResponse {})));
+                } else {
+                    log::error!("client presented an invalid auth_token");
+                    send_response(Err(anyhow!("invalid auth_token")));
+                }
+            }
             Pdu::ListPanes(ListPanes {}) => {
                 spawn_into_main_thread(async move {
                     catch(
@@ -231,6 +299,65 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::ListWorkspaces(ListWorkspaces {}) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let active = mux.active_workspace();
+                            let workspaces = mux
+                                .iter_workspaces()
+                                .into_iter()
+                                .map(|(name, num_windows)| WorkspaceInfo {
+                                    active: name == active,
+                                    name,
+                                    num_windows,
+                                })
+                                .collect();
+                            Ok(Pdu::ListWorkspacesResponse(ListWorkspacesResponse {
+                                workspaces,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::RenameWorkspace(RenameWorkspace {
+                old_workspace,
+                new_workspace,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.rename_workspace(&old_workspace, &new_workspace)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SetWindowWorkspace(SetWindowWorkspace {
+                window_id,
+                workspace,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.set_window_workspace(window_id, &workspace)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::WriteToPane(WriteToPane { pane_id, data }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -241,6 +368,9 @@ impl SessionHandler {
                             let pane = mux
                                 .get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            if pane.is_read_only() {
+                                return Err(anyhow!("pane {} is read-only", pane_id));
+                            }
                             pane.writer().write_all(&data)?;
                             maybe_push_pane_changes(&pane, sender, per_pane)?;
                             Ok(Pdu::UnitResponse(UnitResponse {}))
@@ -250,7 +380,7 @@ impl SessionHandler {
                 })
                 .detach();
             }
-            Pdu::KillPane(KillPane { pane_id }) => {
+            Pdu::KillPane(KillPane { pane_id, force }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
                 spawn_into_main_thread(async move {
@@ -260,8 +390,7 @@ impl SessionHandler {
                             let pane = mux
                                 .get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
-                            pane.kill();
-                            mux.remove_pane(pane_id);
+                            mux.close_pane(pane_id, force)?;
                             maybe_push_pane_changes(&pane, sender, per_pane)?;
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
@@ -270,6 +399,32 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::KillTab(KillTab { tab_id, force }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.close_tab(tab_id, force)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::KillWindow(KillWindow { window_id, force }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.close_window(window_id, force)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
             Pdu::SendPaste(SendPaste { pane_id, data }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -314,6 +469,53 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::GetPaneBookmarks(GetPaneBookmarks { pane_id }) => {
+                async fn get_bookmarks(pane_id: PaneId) -> anyhow::Result<Pdu> {
+                    let mux = Mux::get().unwrap();
+                    let pane = mux
+                        .get_pane(pane_id)
+                        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+                    pane.get_bookmarks().await.map(|bookmarks| {
+                        Pdu::GetPaneBookmarksResponse(GetPaneBookmarksResponse { bookmarks })
+                    })
+                }
+
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = get_bookmarks(pane_id).await;
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::SetPaneBookmark(SetPaneBookmark { pane_id, name, row }) => {
+                async fn set_bookmark(
+                    pane_id: PaneId,
+                    name: String,
+                    row: Option<StableRowIndex>,
+                ) -> anyhow::Result<Pdu> {
+                    let mux = Mux::get().unwrap();
+                    let pane = mux
+                        .get_pane(pane_id)
+                        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+                    pane.set_bookmark(name, row).await?;
+                    Ok(Pdu::UnitResponse(UnitResponse {}))
+                }
+
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = set_bookmark(pane_id, name, row).await;
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
             Pdu::SetPaneZoomed(SetPaneZoomed {
                 containing_tab_id,
                 pane_id,
@@ -442,9 +644,12 @@ impl SessionHandler {
                 .detach();
             }
 
-            Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. }) => {
+            Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, resync }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
+                if resync {
+                    per_pane.lock().unwrap().request_resync();
+                }
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -498,6 +703,168 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::GetPaneDimensions(GetPaneDimensions { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            Ok(Pdu::GetPaneDimensionsResponse(GetPaneDimensionsResponse {
+                                pane_id,
+                                dimensions: pane.get_dimensions(),
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::GetPanePalette(GetPanePalette { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            Ok(Pdu::GetPanePaletteResponse(GetPanePaletteResponse {
+                                pane_id,
+                                palette: pane.palette(),
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ScreenshotPane(ScreenshotPane { pane_id, scale }) => {
+                async fn do_screenshot(pane_id: PaneId, scale: f32) -> anyhow::Result<Pdu> {
+                    let mux = Mux::get().unwrap();
+                    mux.get_pane(pane_id)
+                        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+                    let (reply, rx) = smol::channel::bounded(1);
+                    mux.notify(MuxNotification::ScreenshotRequested(ScreenshotRequest {
+                        pane_id,
+                        scale,
+                        reply,
+                    }));
+
+                    let png = rx.recv().await.map_err(|_| {
+                        anyhow!(
+                            "pane {} is not being displayed by a gui window \
+                             and cannot be captured",
+                            pane_id
+                        )
+                    })??;
+                    Ok(Pdu::ScreenshotPaneResponse(ScreenshotPaneResponse {
+                        pane_id,
+                        png,
+                    }))
+                }
+
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = do_screenshot(pane_id, scale).await;
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::GetWindowPerf(GetWindowPerf { window_id }) => {
+                async fn get_perf(window_id: WindowId) -> anyhow::Result<Pdu> {
+                    let mux = Mux::get().unwrap();
+                    mux.get_window(window_id)
+                        .ok_or_else(|| anyhow!("no such window {}", window_id))?;
+
+                    let (reply, rx) = smol::channel::bounded(1);
+                    mux.notify(MuxNotification::PerfStatsRequested(PerfStatsRequest {
+                        window_id,
+                        reply,
+                    }));
+
+                    let perf = rx.recv().await.map_err(|_| {
+                        anyhow!(
+                            "window {} is not being displayed by a gui window",
+                            window_id
+                        )
+                    })?;
+                    Ok(Pdu::GetWindowPerfResponse(GetWindowPerfResponse {
+                        window_id,
+                        frames: perf.frames,
+                        glyph_cache: perf.glyph_cache,
+                    }))
+                }
+
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = get_perf(window_id).await;
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::EmitEvent(EmitEvent {
+                pane_id,
+                name,
+                args,
+            }) => {
+                async fn emit(
+                    pane_id: PaneId,
+                    name: String,
+                    args: Vec<String>,
+                ) -> anyhow::Result<Pdu> {
+                    let mux = Mux::get().unwrap();
+                    mux.get_pane(pane_id)
+                        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+                    let (reply, rx) = smol::channel::bounded(1);
+                    mux.notify(MuxNotification::EventRequested(EventRequest {
+                        pane_id,
+                        name,
+                        args,
+                        reply,
+                    }));
+
+                    rx.recv().await.map_err(|_| {
+                        anyhow!("pane {} is not being displayed by a gui window", pane_id)
+                    })??;
+                    Ok(Pdu::UnitResponse(UnitResponse {}))
+                }
+
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = emit(pane_id, name, args).await;
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::LogFilter(LogFilter { filter }) => {
+                catch(
+                    move || {
+                        if let Some(filter) = filter {
+                            env_bootstrap::ringlog::set_filter(&filter)?;
+                            log::info!("log filter changed to {:?}", filter);
+                        }
+                        Ok(Pdu::LogFilterResponse(LogFilterResponse {
+                            filter: env_bootstrap::ringlog::get_filter(),
+                        }))
+                    },
+                    send_response,
+                );
+            }
+
             Pdu::GetCodecVersion(_) => {
                 send_response(Ok(Pdu::GetCodecVersionResponse(GetCodecVersionResponse {
                     codec_vers: CODEC_VERSION,
@@ -528,7 +895,14 @@ impl SessionHandler {
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
             | Pdu::SearchScrollbackResponse { .. }
+            | Pdu::GetPaneBookmarksResponse { .. }
             | Pdu::GetLinesResponse { .. }
+            | Pdu::GetPaneDimensionsResponse { .. }
+            | Pdu::GetPanePaletteResponse { .. }
+            | Pdu::ScreenshotPaneResponse { .. }
+            | Pdu::GetWindowPerfResponse { .. }
+            | Pdu::LogFilterResponse { .. }
+            | Pdu::ListWorkspacesResponse { .. }
             | Pdu::GetCodecVersionResponse { .. }
             | Pdu::GetTlsCredsResponse { .. }
             | Pdu::ErrorResponse { .. } => {
@@ -650,7 +1024,7 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(None);
         *window_builder
     };
 
@@ -694,7 +1068,7 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(spawn.workspace);
         *window_builder
     };
 
@@ -719,3 +1093,17 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
         size: tab.get_size(),
     }))
 }
+
+/// Compares the tokens in constant time, so that the time taken to
+/// reject a token doesn't reveal how much of it was correct
+fn tokens_match(expected: &str, actual: &str) -> bool {
+    let (expected, actual) = (expected.as_bytes(), actual.as_bytes());
+    if expected.len() != actual.len() {
+        return false;
+    }
+    expected
+        .iter()
+        .zip(actual.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
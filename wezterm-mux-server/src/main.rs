@@ -41,6 +41,12 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Run in portable mode: the configuration, color schemes and
+    /// runtime state are kept in the directory that contains the
+    /// executable rather than in your home directory
+    #[structopt(long = "portable")]
+    portable: bool,
+
     /// Detach from the foreground and become a background process
     #[structopt(long = "daemonize")]
     daemonize: bool,
@@ -65,13 +71,18 @@ fn main() {
 }
 
 fn run() -> anyhow::Result<()> {
+    // Portable mode changes where the directories are, so it must
+    // be enabled before bootstrapping resolves them
+    let opts = Opt::from_args();
+    if opts.portable {
+        config::enable_portable_mode()?;
+    }
+
     env_bootstrap::bootstrap();
 
     //stats::Stats::init()?;
     config::designate_this_as_the_main_thread();
     let _saver = umask::UmaskSaver::new();
-
-    let opts = Opt::from_args();
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
@@ -198,7 +209,7 @@ async fn async_run(cmd: Option<CommandBuilder>) -> anyhow::Result<()> {
     domain.attach().await?;
 
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = mux
         .default_domain()
         .spawn(config.initial_size(), cmd, None, *window_id)
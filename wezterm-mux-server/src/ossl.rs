@@ -12,13 +12,19 @@ use wezterm_mux_server_impl::PKI;
 struct OpenSSLNetListener {
     acceptor: Arc<SslAcceptor>,
     listener: TcpListener,
+    authorized_client_cns: Vec<String>,
 }
 
 impl OpenSSLNetListener {
-    pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> Self {
+    pub fn new(
+        listener: TcpListener,
+        acceptor: SslAcceptor,
+        authorized_client_cns: Vec<String>,
+    ) -> Self {
         Self {
             listener,
             acceptor: Arc::new(acceptor),
+            authorized_client_cns,
         }
     }
 
@@ -27,11 +33,15 @@ impl OpenSSLNetListener {
     /// * The peer must have a certificate
     /// * The peer certificate must be trusted
     /// * The peer certificate must include a CN string that is
-    ///   either an exact match for the unix username of the
-    ///   user running this mux server instance, or must match
-    ///   a special encoded prefix set up by a proprietary PKI
-    ///   infrastructure in an environment used by the author.
-    fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<()> {
+    ///   one of the `authorized_client_cns` when that is configured.
+    ///   Otherwise it must be either an exact match for the unix
+    ///   username of the user running this mux server instance, or
+    ///   must match a special encoded prefix set up by a proprietary
+    ///   PKI infrastructure in an environment used by the author.
+    fn verify_peer_cert<T>(
+        stream: &SslStream<T>,
+        authorized_client_cns: &[String],
+    ) -> anyhow::Result<()> {
         let cert = stream
             .ssl()
             .peer_certificate()
@@ -43,6 +53,14 @@ impl OpenSSLNetListener {
             .ok_or_else(|| anyhow!("cert has no CN"))?;
         let cn_str = cn.data().as_utf8()?.to_string();
 
+        if !authorized_client_cns.is_empty() {
+            if authorized_client_cns.iter().any(|cn| *cn == cn_str) {
+                log::trace!("Peer certificate CN `{}` is authorized", cn_str);
+                return Ok(());
+            }
+            anyhow::bail!("CN `{}` is not one of the authorized_client_cns", cn_str);
+        }
+
         let wanted_unix_name = std::env::var("USER")?;
 
         if wanted_unix_name == cn_str {
@@ -78,9 +96,12 @@ impl OpenSSLNetListener {
 
                     match acceptor.accept(stream) {
                         Ok(stream) => {
-                            if let Err(err) = Self::verify_peer_cert(&stream) {
+                            if let Err(err) =
+                                Self::verify_peer_cert(&stream, &self.authorized_client_cns)
+                            {
+                                // Reject this client, but keep serving others
                                 log::error!("problem with peer cert: {}", err);
-                                break;
+                                continue;
                             }
                             spawn_into_main_thread(async move {
                                 log::error!("Making new AsyncSslStream");
@@ -180,6 +201,7 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
             )
         })?,
         acceptor,
+        tls_server.authorized_client_cns.clone(),
     );
     std::thread::spawn(move || {
         net_listener.run();
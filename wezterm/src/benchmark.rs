@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Context};
+use config::{ConfigHandle, TextStyle};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tabout::{tabulate_output, Alignment, Column};
+use wezterm_font::FontConfiguration;
+use wezterm_term::{Terminal, TerminalSize};
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct BenchmarkCommand {
+    /// Feed the contents of this file to the terminal model when
+    /// measuring throughput, rather than a synthetic workload.
+    /// A capture produced by eg: `script` is a good candidate.
+    #[structopt(long = "input", parse(from_os_str))]
+    input: Option<OsString>,
+
+    /// How many megabytes of output to process when measuring throughput
+    #[structopt(long = "megabytes", default_value = "64")]
+    megabytes: usize,
+
+    /// How many frames to shape and rasterize when measuring font time
+    #[structopt(long = "frames", default_value = "200")]
+    frames: usize,
+
+    /// How many keypresses to send when measuring latency
+    #[structopt(long = "keypresses", default_value = "200")]
+    keypresses: usize,
+
+    /// The width of the terminal, in cells
+    #[structopt(long = "cols", default_value = "80")]
+    cols: usize,
+
+    /// The height of the terminal, in cells
+    #[structopt(long = "rows", default_value = "24")]
+    rows: usize,
+}
+
+/// Summarizes a set of samples
+struct Samples(Vec<Duration>);
+
+impl Samples {
+    fn percentile(&self, p: f64) -> Duration {
+        let mut sorted = self.0.clone();
+        sorted.sort();
+        if sorted.is_empty() {
+            return Duration::default();
+        }
+        let idx = ((sorted.len() - 1) as f64 * p / 100.).round() as usize;
+        sorted[idx]
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "p50={:.2?} p95={:.2?} p99={:.2?}",
+            self.percentile(50.),
+            self.percentile(95.),
+            self.percentile(99.)
+        )
+    }
+}
+
+impl BenchmarkCommand {
+    pub fn run(&self, config: ConfigHandle) -> anyhow::Result<()> {
+        let mut data = vec![];
+
+        let throughput = self.throughput()?;
+        data.push(vec![
+            "parse+apply throughput".to_string(),
+            format!(
+                "{:.1} MB/s ({} bytes in {:.2?})",
+                throughput.0 as f64 / throughput.1.as_secs_f64() / 1_000_000.,
+                throughput.0,
+                throughput.1
+            ),
+        ]);
+
+        let (shaping, rasterizing) = self.shaping(&config)?;
+        data.push(vec!["shape time per frame".to_string(), shaping.summary()]);
+        data.push(vec![
+            "glyph raster time per frame".to_string(),
+            rasterizing.summary(),
+        ]);
+
+        match self.latency() {
+            Ok(latency) => data.push(vec![
+                "keypress to pty latency".to_string(),
+                latency.summary(),
+            ]),
+            Err(err) => data.push(vec![
+                "keypress to pty latency".to_string(),
+                format!("failed: {:#}", err),
+            ]),
+        }
+
+        let cols = vec![
+            Column {
+                name: "BENCHMARK".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "RESULT".to_string(),
+                alignment: Alignment::Left,
+            },
+        ];
+        tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+        Ok(())
+    }
+
+    fn workload(&self) -> anyhow::Result<Vec<u8>> {
+        if let Some(input) = self.input.as_ref() {
+            return std::fs::read(input).with_context(|| anyhow!("reading {:?}", input));
+        }
+
+        let mut data = vec![];
+        let mut i = 0usize;
+        let target = self.megabytes * 1024 * 1024;
+        while data.len() < target {
+            let color = 31 + (i % 7);
+            write!(
+                data,
+                "\x1b[1;{}m{:8}\x1b[0m the quick brown fox \x1b[4mjumps\x1b[24m over \
+                 the lazy dög \u{1f98a}\r\n",
+                color, i
+            )?;
+            i += 1;
+        }
+        Ok(data)
+    }
+
+    /// Measures how quickly output is parsed and applied to the
+    /// terminal model
+    fn throughput(&self) -> anyhow::Result<(usize, Duration)> {
+        let data = self.workload()?;
+        let mut term = Terminal::new(
+            TerminalSize {
+                physical_rows: self.rows,
+                physical_cols: self.cols,
+                pixel_width: self.cols * 8,
+                pixel_height: self.rows * 16,
+            },
+            // Uses the scrollback and palette from the configuration
+            Arc::new(config::TermConfig),
+            "WezTerm",
+            config::wezterm_version(),
+            Box::new(std::io::sink()),
+        );
+        let start = Instant::now();
+        for chunk in data.chunks(64 * 1024) {
+            term.advance_bytes(chunk);
+        }
+        Ok((data.len(), start.elapsed()))
+    }
+
+    /// Measures how long it takes to shape a screenful of text, and
+    /// then to rasterize the resulting glyphs, using the configured
+    /// font.  Each frame has distinct text, so that this reflects the
+    /// cost when the shape and glyph caches miss.  Building the quads
+    /// and uploading the glyphs to the GPU are not included, as they
+    /// need a window.
+    fn shaping(&self, config: &ConfigHandle) -> anyhow::Result<(Samples, Samples)> {
+        let fonts = FontConfiguration::new(Some(config.clone()))?;
+        let font = fonts.resolve_font(&TextStyle::default())?;
+        let words = [
+            "fn", "main()", "{", "let", "mut", "->", "=>", "!=", "===", "wezterm", "λ", "résumé",
+        ];
+
+        let mut shape_samples = vec![];
+        let mut raster_samples = vec![];
+        for frame in 0..self.frames {
+            let lines: Vec<String> = (0..self.rows)
+                .map(|row| {
+                    let mut line = format!("{}:{} ", frame, row);
+                    let mut idx = frame + row;
+                    while line.len() < self.cols {
+                        line.push_str(words[idx % words.len()]);
+                        line.push(' ');
+                        idx += 1;
+                    }
+                    line.chars().take(self.cols).collect()
+                })
+                .collect();
+
+            let start = Instant::now();
+            let mut glyphs = vec![];
+            for line in &lines {
+                glyphs.append(&mut font.shape(line, None, || {})?);
+            }
+            shape_samples.push(start.elapsed());
+
+            let start = Instant::now();
+            for glyph in glyphs.iter().filter(|g| !g.is_space) {
+                font.rasterize_glyph(glyph.glyph_pos, glyph.font_idx, 0.)?;
+            }
+            raster_samples.push(start.elapsed());
+        }
+        Ok((Samples(shape_samples), Samples(raster_samples)))
+    }
+
+    /// Measures the time between writing a keypress to the pty and
+    /// reading back its echo from a program running in the pty.
+    /// This is the portion of the input latency that is outside of
+    /// the gui.
+    fn latency(&self) -> anyhow::Result<Samples> {
+        if cfg!(windows) {
+            anyhow::bail!("not supported on this system");
+        }
+
+        let pair = native_pty_system().openpty(PtySize {
+            rows: self.rows as u16,
+            cols: self.cols as u16,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        // Disable the kernel echo so that the echo that we measure
+        // comes from the program, as it would for a shell
+        let mut cmd = CommandBuilder::new("sh");
+        cmd.args(&["-c", "stty raw -echo && echo ready && exec cat"]);
+        let mut child = pair.slave.spawn_command(cmd)?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let mut writer = pair.master.try_clone_writer()?;
+
+        let mut buf = [0u8; 256];
+        let mut startup = vec![];
+        while !String::from_utf8_lossy(&startup).contains("ready") {
+            let len = reader.read(&mut buf)?;
+            if len == 0 {
+                anyhow::bail!("pty closed while waiting for the program to start");
+            }
+            startup.extend_from_slice(&buf[..len]);
+        }
+
+        let mut samples = vec![];
+        for i in 0..self.keypresses {
+            let key = b'a' + (i % 26) as u8;
+            let start = Instant::now();
+            writer.write_all(&[key])?;
+            writer.flush()?;
+            loop {
+                let len = reader.read(&mut buf)?;
+                if len == 0 {
+                    anyhow::bail!("pty closed while waiting for the echo");
+                }
+                if buf[..len].contains(&key) {
+                    break;
+                }
+            }
+            samples.push(start.elapsed());
+        }
+
+        child.kill().ok();
+        Ok(Samples(samples))
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::{anyhow, Context};
+use config::{FontAttributes, FontLocatorSelection};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use structopt::StructOpt;
+use wezterm_font::locator::new_locator;
+
+/// Monospace fonts that are worth suggesting, in order of preference.
+/// The first of these that is installed becomes the default font of
+/// the generated config.
+const CANDIDATE_FONTS: &[&str] = &[
+    "JetBrains Mono",
+    "Fira Code",
+    "Cascadia Code",
+    "Source Code Pro",
+    "Hack",
+    "SF Mono",
+    "Menlo",
+    "Consolas",
+    "DejaVu Sans Mono",
+    "Noto Sans Mono",
+    "Liberation Mono",
+];
+
+const DARK_SCHEME: &str = "Builtin Tango Dark";
+const LIGHT_SCHEME: &str = "Builtin Tango Light";
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct InitCommand {
+    /// Where to write the configuration.  If omitted, the config is
+    /// written to the first location that wezterm searches for it,
+    /// unless a config file already exists.
+    #[structopt(parse(from_os_str))]
+    config_file: Option<PathBuf>,
+
+    /// Print the configuration rather than writing it to a file
+    #[structopt(long = "stdout")]
+    stdout: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Appearance {
+    Light,
+    Dark,
+}
+
+/// The platform defaults that are written to the starter config
+struct Detected {
+    fonts: Vec<&'static str>,
+    shell: Option<String>,
+    appearance: Option<Appearance>,
+}
+
+impl InitCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let detected = Detected {
+            fonts: installed_fonts(),
+            shell: detect_shell(),
+            appearance: detect_appearance(),
+        };
+        let text = starter_config(&detected);
+
+        if self.stdout {
+            print!("{}", text);
+            return Ok(());
+        }
+
+        let path = match self.config_file.clone() {
+            Some(path) => path,
+            None => {
+                // Refuse to shadow a config that is already in use
+                match config::Config::config_file_path() {
+                    Some(existing) if existing.exists() => anyhow::bail!(
+                        "{} already exists; not overwriting it. \
+                         Use `wezterm init --stdout` to see the starter config",
+                        existing.display()
+                    ),
+                    Some(path) => path,
+                    None => config::CONFIG_DIR.join("wezterm.lua"),
+                }
+            }
+        };
+        write_new_file(&path, &text)?;
+        println!("Wrote {}", path.display());
+        Ok(())
+    }
+}
+
+/// Creates `path` holding `text`, failing if it already exists
+fn write_new_file(path: &Path, text: &str) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("creating directory {}", dir.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                anyhow!("{} already exists; not overwriting it", path.display())
+            }
+            _ => anyhow!("creating {}: {}", path.display(), err),
+        })?;
+    file.write_all(text.as_bytes())
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+/// Returns those of the candidate fonts that are installed
+fn installed_fonts() -> Vec<&'static str> {
+    let locator = new_locator(FontLocatorSelection::default());
+    CANDIDATE_FONTS
+        .iter()
+        .copied()
+        .filter(|&family| {
+            let attr = FontAttributes::new(family);
+            let mut loaded = HashSet::new();
+            match locator.load_fonts(&[attr.clone()], &mut loaded) {
+                Ok(_) => loaded.contains(&attr),
+                Err(err) => {
+                    log::trace!("while looking for {}: {:#}", family, err);
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
+/// Returns the shell that the user would like to run, if it differs
+/// from the program that wezterm runs by default
+#[cfg(unix)]
+fn detect_shell() -> Option<String> {
+    std::env::var("SHELL").ok()
+}
+
+#[cfg(windows)]
+fn detect_shell() -> Option<String> {
+    // wezterm runs cmd.exe by default, but prefer PowerShell when
+    // it is installed
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join("pwsh.exe"))
+        .find(|candidate| candidate.exists())
+        .map(|pwsh| pwsh.to_string_lossy().into_owned())
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn detect_appearance() -> Option<Appearance> {
+    // The key is only present while dark mode is enabled
+    match command_output("defaults", &["read", "-g", "AppleInterfaceStyle"]) {
+        Some(style) if style.eq_ignore_ascii_case("dark") => Some(Appearance::Dark),
+        _ => Some(Appearance::Light),
+    }
+}
+
+#[cfg(windows)]
+fn detect_appearance() -> Option<Appearance> {
+    let output = command_output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+            "/v",
+            "AppsUseLightTheme",
+        ],
+    )?;
+    if output.contains("0x0") {
+        Some(Appearance::Dark)
+    } else if output.contains("0x1") {
+        Some(Appearance::Light)
+    } else {
+        None
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn detect_appearance() -> Option<Appearance> {
+    let from_setting = |value: &str| {
+        let value = value.to_lowercase();
+        if value.contains("dark") {
+            Some(Appearance::Dark)
+        } else if value.contains("light") || value.contains("default") {
+            Some(Appearance::Light)
+        } else {
+            None
+        }
+    };
+    command_output(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "color-scheme"],
+    )
+    .and_then(|scheme| from_setting(&scheme))
+    .or_else(|| {
+        command_output(
+            "gsettings",
+            &["get", "org.gnome.desktop.interface", "gtk-theme"],
+        )
+        .map(|theme| {
+            if theme.to_lowercase().contains("dark") {
+                Appearance::Dark
+            } else {
+                Appearance::Light
+            }
+        })
+    })
+}
+
+/// Quotes `s` as a lua string literal
+fn lua_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn starter_config(detected: &Detected) -> String {
+    let mut config = String::new();
+    config.push_str(
+        "-- This is a starter configuration generated by `wezterm init`.\n\
+         -- The documentation of every option is at\n\
+         -- https://wezfurlong.org/wezterm/config/files.html\n\
+         -- Run `wezterm check-config` after editing this file to find\n\
+         -- mistakes before they take effect.\n\
+         local wezterm = require 'wezterm';\n\
+         \n\
+         return {\n",
+    );
+
+    config.push_str("  -- The font that is used to render text.\n");
+    match detected.fonts.split_first() {
+        Some((first, rest)) => {
+            if !rest.is_empty() {
+                config.push_str(&format!(
+                    "  -- These other monospace fonts are also installed: {}.\n",
+                    rest.join(", ")
+                ));
+            }
+            config.push_str(&format!("  font = wezterm.font({}),\n", lua_string(first)));
+        }
+        None => {
+            config.push_str(
+                "  -- None of the usual monospace fonts were found, so wezterm\n  \
+                 -- will use its built-in font unless you name another here.\n  \
+                 -- font = wezterm.font(\"JetBrains Mono\"),\n",
+            );
+        }
+    }
+    config.push_str("  font_size = 12.0,\n\n");
+
+    let (scheme, other, reason) = match detected.appearance {
+        Some(Appearance::Light) => (
+            LIGHT_SCHEME,
+            DARK_SCHEME,
+            "your system is using a light appearance",
+        ),
+        Some(Appearance::Dark) => (
+            DARK_SCHEME,
+            LIGHT_SCHEME,
+            "your system is using a dark appearance",
+        ),
+        None => (
+            DARK_SCHEME,
+            LIGHT_SCHEME,
+            "the appearance of your system could not be determined",
+        ),
+    };
+    config.push_str(&format!(
+        "  -- This color scheme was chosen because {}.\n  \
+         -- Try {} for the opposite, or browse the others at\n  \
+         -- https://wezfurlong.org/wezterm/colorschemes/index.html\n  \
+         color_scheme = {},\n\n",
+        reason,
+        lua_string(other),
+        lua_string(scheme)
+    ));
+
+    match &detected.shell {
+        Some(shell) if cfg!(windows) => {
+            config.push_str(&format!(
+                "  -- PowerShell was found, so it is run rather than cmd.exe.\n  \
+                 default_prog = {{{}}},\n\n",
+                lua_string(shell)
+            ));
+        }
+        Some(shell) => {
+            config.push_str(&format!(
+                "  -- wezterm runs your login shell ({}) by default.\n  \
+                 -- Uncomment this to run a different program instead.\n  \
+                 -- default_prog = {{{}, \"-l\"}},\n\n",
+                shell,
+                lua_string(shell)
+            ));
+        }
+        None => {
+            config.push_str(
+                "  -- Uncomment this to run a different program in new tabs.\n  \
+                 -- default_prog = {\"bash\", \"-l\"},\n\n",
+            );
+        }
+    }
+
+    config.push_str(
+        "  -- How many lines of output each tab keeps for scrolling back.\n  \
+         scrollback_lines = 3500,\n\n  \
+         -- Add your own key bindings here, for example:\n  \
+         -- {key=\"t\", mods=\"CTRL|SHIFT\", action=wezterm.action{SpawnTab=\"CurrentPaneDomain\"}},\n  \
+         keys = {\n  \
+         },\n\
+         }\n",
+    );
+    config
+}
@@ -3,18 +3,23 @@ use config::keyassignment::SpawnTabDomain;
 use config::wezterm_version;
 use mux::activity::Activity;
 use mux::pane::PaneId;
-use mux::tab::SplitDirection;
+use mux::tab::{SplitDirection, TabId};
 use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
 use std::ffi::OsString;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 use structopt::StructOpt;
 use tabout::{tabulate_output, Alignment, Column};
 use umask::UmaskSaver;
-use wezterm_client::client::{unix_connect_with_retry, Client};
+use wezterm_client::client::{authenticate_unix_stream, unix_connect_with_retry, Client};
 use wezterm_gui_subcommands::*;
+use wezterm_term::StableRowIndex;
+
+mod benchmark;
+mod init;
 
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
@@ -46,6 +51,12 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Run in portable mode: the configuration, color schemes and
+    /// runtime state are kept in the directory that contains the
+    /// executable rather than in your home directory
+    #[structopt(long = "portable")]
+    portable: bool,
+
     #[structopt(subcommand)]
     cmd: Option<SubCommand>,
 }
@@ -70,12 +81,37 @@ enum SubCommand {
     #[structopt(name = "imgcat", about = "Output an image to the terminal")]
     ImageCat(ImgCatCommand),
 
+    #[structopt(
+        name = "benchmark",
+        about = "Measure the throughput and latency of the terminal"
+    )]
+    Benchmark(benchmark::BenchmarkCommand),
+
     #[structopt(
         name = "set-working-directory",
         about = "Advise the terminal of the current working directory by \
                  emitting an OSC 7 escape sequence"
     )]
     SetCwd(SetCwdCommand),
+
+    #[structopt(
+        name = "paths",
+        about = "Print the locations of the files and directories used by wezterm"
+    )]
+    Paths(PathsCommand),
+
+    #[structopt(
+        name = "check-config",
+        about = "Check the configuration file for errors without applying it"
+    )]
+    CheckConfig(CheckConfigCommand),
+
+    #[structopt(
+        name = "init",
+        about = "Write a starter configuration file based on the defaults \
+                 detected for this system"
+    )]
+    Init(init::InitCommand),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -161,6 +197,173 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(parse(from_os_str))]
         prog: Vec<OsString>,
     },
+
+    #[structopt(
+        name = "export",
+        about = "Render the contents of a pane to an HTML or SVG file"
+    )]
+    Export {
+        /// Specify the pane that should be exported.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The format of the output
+        #[structopt(
+            long = "format",
+            default_value = "html",
+            possible_values = &["html", "svg"]
+        )]
+        format: String,
+
+        /// Include the scrollback rather than just the viewport
+        #[structopt(long = "scrollback")]
+        scrollback: bool,
+
+        /// The file to write.
+        /// If omitted, the output is written to stdout.
+        #[structopt(long = "out", parse(from_os_str))]
+        out: Option<OsString>,
+    },
+
+    #[structopt(
+        name = "screenshot",
+        about = "Render a pane to a PNG image using the gui renderer.
+The pane must be visible in a gui window"
+    )]
+    Screenshot {
+        /// Specify the pane that should be captured.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Scale the image relative to the size of the pane
+        /// in the window, including any presentation zoom
+        #[structopt(long = "scale", default_value = "1.0")]
+        scale: f32,
+
+        /// The PNG file to write
+        #[structopt(long = "out", parse(from_os_str))]
+        out: OsString,
+    },
+
+    #[structopt(
+        name = "perf",
+        about = "Report how long a gui window took to paint its recent frames,
+and how effective its shape and glyph caches were"
+    )]
+    Perf {
+        /// Specify the window whose frames should be reported
+        #[structopt(long = "window-id")]
+        window_id: WindowId,
+    },
+
+    #[structopt(
+        name = "log-filter",
+        about = "Show or change the log filter of the wezterm process
+that is serving the mux.
+The filter uses the same syntax as the WEZTERM_LOG environment
+variable, eg: `info,wezterm_font=debug`"
+    )]
+    LogFilter {
+        /// The new filter.  If omitted, the filter that is
+        /// currently in effect is printed.
+        filter: Option<String>,
+    },
+
+    #[structopt(name = "kill-pane", about = "Kill a pane")]
+    KillPane {
+        /// Specify the pane that should be killed.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Kill the pane even if it is running a program that
+        /// would normally require confirmation to close
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    #[structopt(name = "kill-tab", about = "Kill a tab and all of its panes")]
+    KillTab {
+        /// Specify the tab that should be killed
+        #[structopt(long = "tab-id")]
+        tab_id: TabId,
+
+        /// Kill the tab even if one of its panes is running a
+        /// program that would normally require confirmation to close
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    #[structopt(
+        name = "kill-window",
+        about = "Kill a window and all of its tabs and panes"
+    )]
+    KillWindow {
+        /// Specify the window that should be killed
+        #[structopt(long = "window-id")]
+        window_id: WindowId,
+
+        /// Kill the window even if one of its panes is running a
+        /// program that would normally require confirmation to close
+        #[structopt(long = "force")]
+        force: bool,
+    },
+
+    #[structopt(
+        name = "list-workspaces",
+        about = "list workspaces and the number of windows in each of them"
+    )]
+    ListWorkspaces,
+
+    #[structopt(
+        name = "rename-workspace",
+        about = "Rename a workspace, moving all of its windows into the new name"
+    )]
+    RenameWorkspace {
+        /// The current name of the workspace
+        old_workspace: String,
+        /// The new name for the workspace
+        new_workspace: String,
+    },
+
+    #[structopt(
+        name = "move-window-to-workspace",
+        about = "Move a window into a different workspace"
+    )]
+    MoveWindowToWorkspace {
+        /// Specify the window that should be moved
+        #[structopt(long = "window-id")]
+        window_id: WindowId,
+
+        /// The workspace into which the window should be moved.
+        /// The workspace is created if it doesn't already exist.
+        workspace: String,
+    },
+
+    #[structopt(
+        name = "emit-event",
+        about = "Emit an event, calling the handlers that were registered
+for it using wezterm.on.  The pane must be visible in a gui window"
+    )]
+    EmitEvent {
+        /// Specify the pane that is passed to the handlers.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The name of the event
+        name: String,
+
+        /// Additional string arguments that are passed to the
+        /// handlers after the window and pane
+        args: Vec<String>,
+    },
 }
 
 use termwiz::escape::osc::{
@@ -235,6 +438,102 @@ struct SetCwdCommand {
     host: Option<OsString>,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+struct PathsCommand {}
+
+impl PathsCommand {
+    fn run(&self, config: config::ConfigHandle) -> anyhow::Result<()> {
+        fn display(p: &std::path::Path) -> String {
+            p.display().to_string()
+        }
+
+        // This is set when the config file is loaded
+        let config_file = std::env::var_os("WEZTERM_CONFIG_FILE")
+            .map(|p| display(p.as_ref()))
+            .unwrap_or_else(|| "(none; using the defaults)".to_string());
+
+        let mut data = vec![
+            vec!["config file".to_string(), config_file],
+            vec!["config dir".to_string(), display(&config::CONFIG_DIR)],
+            vec!["plugin dir".to_string(), display(&config::PLUGIN_DIR)],
+            vec!["runtime dir".to_string(), display(&config::RUNTIME_DIR)],
+            vec!["cache dir".to_string(), display(&config::CACHE_DIR)],
+            vec!["state dir".to_string(), display(&config::STATE_DIR)],
+        ];
+        for unix_dom in &config.unix_domains {
+            data.push(vec![
+                format!("socket for unix domain {}", unix_dom.name),
+                display(&unix_dom.socket_path()),
+            ]);
+        }
+        if let Some(portable) = config::PORTABLE_DIR.as_ref() {
+            data.push(vec!["portable dir".to_string(), display(portable)]);
+        }
+
+        let cols = vec![
+            Column {
+                name: "NAME".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "PATH".to_string(),
+                alignment: Alignment::Left,
+            },
+        ];
+        tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct CheckConfigCommand {
+    /// The configuration file to check.  If omitted, the file that
+    /// would be loaded is checked.
+    #[structopt(parse(from_os_str))]
+    config_file: Option<PathBuf>,
+}
+
+impl CheckConfigCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        let path = match self.config_file.clone() {
+            Some(path) => path,
+            None => config::Config::config_file_path()
+                .ok_or_else(|| anyhow!("there is no configuration file to check"))?,
+        };
+
+        let report = config::check::check_config_file(&path)?;
+        for diag in &report.diagnostics {
+            let severity = match diag.severity {
+                config::check::Severity::Warning => "warning",
+                config::check::Severity::Error => "error",
+            };
+            match diag.line {
+                Some(line) => println!(
+                    "{}:{}: {}: {}",
+                    report.file_name.display(),
+                    line,
+                    severity,
+                    diag.message
+                ),
+                None => println!(
+                    "{}: {}: {}",
+                    report.file_name.display(),
+                    severity,
+                    diag.message
+                ),
+            }
+        }
+
+        if report.has_errors() {
+            std::process::exit(1);
+        }
+        if report.diagnostics.is_empty() {
+            println!("{}: OK", report.file_name.display());
+        }
+        Ok(())
+    }
+}
+
 impl SetCwdCommand {
     fn run(&self) -> anyhow::Result<()> {
         let cwd: std::path::PathBuf = match self.cwd.as_ref() {
@@ -276,15 +575,28 @@ fn main() {
 }
 
 fn run() -> anyhow::Result<()> {
+    // Portable mode changes where the directories are, so it must
+    // be enabled before bootstrapping resolves them
+    let opts = Opt::from_args();
+    if opts.portable {
+        config::enable_portable_mode()?;
+    }
+
     env_bootstrap::bootstrap();
 
     let saver = UmaskSaver::new();
-
-    let opts = Opt::from_args();
+    // check-config evaluates the config file itself, and reports
+    // any problems with it rather than logging them, while init
+    // doesn't need a config at all
+    let skip_config = opts.skip_config
+        || matches!(
+            opts.cmd,
+            Some(SubCommand::CheckConfig(_)) | Some(SubCommand::Init(_))
+        );
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
-        opts.skip_config,
+        skip_config,
     );
     let config = config::configuration();
 
@@ -300,13 +612,31 @@ fn run() -> anyhow::Result<()> {
         | SubCommand::Connect(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
+        SubCommand::Paths(cmd) => cmd.run(config),
+        SubCommand::CheckConfig(cmd) => cmd.run(),
+        SubCommand::Init(cmd) => cmd.run(),
+        SubCommand::Benchmark(cmd) => cmd.run(config),
         SubCommand::Cli(cli) => run_cli(config, cli),
     }
 }
 
-fn delegate_to_gui(saver: UmaskSaver) -> anyhow::Result<()> {
-    use std::process::Command;
+/// Builds the command that runs the gui binary `exe` with `args`.
+/// We removed WEZTERM_PORTABLE_DIR from our own environment during
+/// startup, so pass it on explicitly to keep the gui in portable mode.
+fn gui_command(
+    exe: PathBuf,
+    args: impl IntoIterator<Item = OsString>,
+    portable_dir: Option<&std::path::Path>,
+) -> std::process::Command {
+    let mut cmd = std::process::Command::new(exe);
+    cmd.args(args);
+    if let Some(dir) = portable_dir {
+        cmd.env("WEZTERM_PORTABLE_DIR", dir);
+    }
+    cmd
+}
 
+fn delegate_to_gui(saver: UmaskSaver) -> anyhow::Result<()> {
     // Restore the original umask
     drop(saver);
 
@@ -321,8 +651,11 @@ fn delegate_to_gui(saver: UmaskSaver) -> anyhow::Result<()> {
         .ok_or_else(|| anyhow!("exe has no parent dir!?"))?
         .join(exe_name);
 
-    let mut cmd = Command::new(exe);
-    cmd.args(std::env::args_os().skip(1));
+    let mut cmd = gui_command(
+        exe,
+        std::env::args_os().skip(1),
+        config::PORTABLE_DIR.as_deref(),
+    );
 
     #[cfg(unix)]
     {
@@ -377,6 +710,10 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     name: "CWD".to_string(),
                     alignment: Alignment::Left,
                 },
+                Column {
+                    name: "WORKSPACE".to_string(),
+                    alignment: Alignment::Left,
+                },
             ];
             let mut data = vec![];
             let panes = client.list_panes().await?;
@@ -398,6 +735,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                                 .map(|url| url.url.as_str())
                                 .unwrap_or("")
                                 .to_string(),
+                            entry.workspace.clone(),
                         ]);
                     }
                     match cursor.preorder_next() {
@@ -507,12 +845,307 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     },
                     command_dir: cwd.and_then(|c| c.to_str().map(|s| s.to_string())),
                     size: config::configuration().initial_size(),
+                    workspace: None,
                 })
                 .await?;
 
             log::debug!("{:?}", spawned);
             println!("{}", spawned.pane_id);
         }
+        CliSubCommand::Export {
+            pane_id,
+            format,
+            scrollback,
+            out,
+        } => {
+            let pane_id: PaneId = match pane_id {
+                Some(p) => p,
+                None => std::env::var("WEZTERM_PANE")
+                    .map_err(|_| {
+                        anyhow!(
+                            "--pane-id was not specified and $WEZTERM_PANE
+                                    is not set in the environment"
+                        )
+                    })?
+                    .parse()?,
+            };
+
+            let dims = client
+                .get_dimensions(codec::GetPaneDimensions { pane_id })
+                .await?
+                .dimensions;
+            let top = if scrollback {
+                dims.scrollback_top
+            } else {
+                dims.physical_top
+            };
+            let range = top..dims.physical_top + dims.viewport_rows as StableRowIndex;
+
+            let lines: Vec<_> = client
+                .get_lines(codec::GetLines {
+                    pane_id,
+                    lines: vec![range],
+                })
+                .await?
+                .lines
+                .lines()
+                .into_iter()
+                .map(|(_, line)| {
+                    let len = line.cells().len();
+                    (line, 0..len)
+                })
+                .collect();
+
+            let palette = client
+                .get_pane_palette(codec::GetPanePalette { pane_id })
+                .await?
+                .palette;
+            // The font is taken from the local configuration
+            let font_family = config.font.font.first().map(|f| f.family.as_str());
+
+            let content = match format.as_str() {
+                "svg" => wezterm_term::export::lines_to_svg(&lines, &palette, font_family),
+                _ => wezterm_term::export::lines_to_html_document(
+                    &lines,
+                    &palette,
+                    font_family,
+                    &format!("wezterm pane {}", pane_id),
+                ),
+            };
+
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, content).with_context(|| anyhow!("writing {:?}", out))?
+                }
+                None => std::io::stdout().lock().write_all(content.as_bytes())?,
+            }
+        }
+        CliSubCommand::Screenshot {
+            pane_id,
+            scale,
+            out,
+        } => {
+            let pane_id: PaneId = match pane_id {
+                Some(p) => p,
+                None => std::env::var("WEZTERM_PANE")
+                    .map_err(|_| {
+                        anyhow!(
+                            "--pane-id was not specified and $WEZTERM_PANE
+                                    is not set in the environment"
+                        )
+                    })?
+                    .parse()?,
+            };
+
+            let screenshot = client
+                .screenshot_pane(codec::ScreenshotPane { pane_id, scale })
+                .await?;
+            std::fs::write(&out, screenshot.png).with_context(|| anyhow!("writing {:?}", out))?;
+        }
+        CliSubCommand::Perf { window_id } => {
+            let perf = client
+                .get_window_perf(codec::GetWindowPerf { window_id })
+                .await?;
+            let frames = perf.frames;
+            if frames.is_empty() {
+                println!("window {} has not painted any frames", window_id);
+            } else {
+                println!("{} recent frames, times in milliseconds", frames.len());
+                let cols = vec![
+                    Column {
+                        name: "STAGE".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "MIN".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "AVG".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "P95".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "MAX".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                ];
+                let data = vec![
+                    summarize_frame_times("frame", frames.iter().map(|f| f.total)),
+                    summarize_frame_times("render", frames.iter().map(|f| f.render)),
+                    summarize_frame_times("shape", frames.iter().map(|f| f.shape)),
+                    summarize_frame_times("draw", frames.iter().map(|f| f.draw)),
+                ];
+                tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+
+                println!(
+                    "shape cache hits: {}",
+                    hit_rate(
+                        frames.iter().map(|f| f.shape_cache_hits).sum(),
+                        frames.iter().map(|f| f.shape_cache_misses).sum()
+                    )
+                );
+                println!(
+                    "glyph cache hits: {}",
+                    hit_rate(
+                        frames.iter().map(|f| f.glyph_cache_hits).sum(),
+                        frames.iter().map(|f| f.glyph_cache_misses).sum()
+                    )
+                );
+            }
+
+            let stats = perf.glyph_cache;
+            println!();
+            println!("glyph cache since it was created");
+            let cols = vec![
+                Column {
+                    name: "STATISTIC".to_string(),
+                    alignment: Alignment::Left,
+                },
+                Column {
+                    name: "VALUE".to_string(),
+                    alignment: Alignment::Right,
+                },
+            ];
+            let atlas = |atlas: mux::AtlasOccupancy| {
+                format!(
+                    "{}px, {} sprites, {:.1}% occupied",
+                    atlas.side,
+                    atlas.sprites,
+                    atlas.occupancy * 100.
+                )
+            };
+            let data = vec![
+                vec!["hits".to_string(), hit_rate(stats.hits, stats.misses)],
+                vec![
+                    "glyphs".to_string(),
+                    format!("{} of {}", stats.glyphs, stats.capacity),
+                ],
+                vec!["image frames".to_string(), stats.images.to_string()],
+                vec!["line sprites".to_string(), stats.lines.to_string()],
+                vec!["block sprites".to_string(), stats.blocks.to_string()],
+                vec![
+                    "rasterized".to_string(),
+                    format!(
+                        "{} in {:.2}ms",
+                        stats.rasterized,
+                        stats.raster_time.as_secs_f64() * 1000.
+                    ),
+                ],
+                vec!["color atlas".to_string(), atlas(stats.color_atlas)],
+                vec!["alpha atlas".to_string(), atlas(stats.alpha_atlas)],
+                vec![
+                    "atlas replacements".to_string(),
+                    stats.atlas_replacements.to_string(),
+                ],
+            ];
+            tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+        }
+        CliSubCommand::LogFilter { filter } => {
+            let response = client.log_filter(codec::LogFilter { filter }).await?;
+            println!("{}", response.filter);
+        }
+        CliSubCommand::KillPane { pane_id, force } => {
+            let pane_id: PaneId = match pane_id {
+                Some(p) => p,
+                None => std::env::var("WEZTERM_PANE")
+                    .map_err(|_| {
+                        anyhow!(
+                            "--pane-id was not specified and $WEZTERM_PANE
+                                    is not set in the environment"
+                        )
+                    })?
+                    .parse()?,
+            };
+            client.kill_pane(codec::KillPane { pane_id, force }).await?;
+        }
+        CliSubCommand::KillTab { tab_id, force } => {
+            client.kill_tab(codec::KillTab { tab_id, force }).await?;
+        }
+        CliSubCommand::ListWorkspaces => {
+            let cols = vec![
+                Column {
+                    name: "WORKSPACE".to_string(),
+                    alignment: Alignment::Left,
+                },
+                Column {
+                    name: "WINDOWS".to_string(),
+                    alignment: Alignment::Right,
+                },
+                Column {
+                    name: "ACTIVE".to_string(),
+                    alignment: Alignment::Left,
+                },
+            ];
+            let data: Vec<Vec<String>> = client
+                .list_workspaces()
+                .await?
+                .workspaces
+                .into_iter()
+                .map(|workspace| {
+                    vec![
+                        workspace.name,
+                        workspace.num_windows.to_string(),
+                        if workspace.active { "*" } else { "" }.to_string(),
+                    ]
+                })
+                .collect();
+            tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+        }
+        CliSubCommand::RenameWorkspace {
+            old_workspace,
+            new_workspace,
+        } => {
+            client
+                .rename_workspace(codec::RenameWorkspace {
+                    old_workspace,
+                    new_workspace,
+                })
+                .await?;
+        }
+        CliSubCommand::MoveWindowToWorkspace {
+            window_id,
+            workspace,
+        } => {
+            client
+                .set_window_workspace(codec::SetWindowWorkspace {
+                    window_id,
+                    workspace,
+                })
+                .await?;
+        }
+        CliSubCommand::KillWindow { window_id, force } => {
+            client
+                .kill_window(codec::KillWindow { window_id, force })
+                .await?;
+        }
+        CliSubCommand::EmitEvent {
+            pane_id,
+            name,
+            args,
+        } => {
+            let pane_id: PaneId = match pane_id {
+                Some(p) => p,
+                None => std::env::var("WEZTERM_PANE")
+                    .map_err(|_| {
+                        anyhow!(
+                            "--pane-id was not specified and $WEZTERM_PANE
+                                    is not set in the environment"
+                        )
+                    })?
+                    .parse()?,
+            };
+            client
+                .emit_event(codec::EmitEvent {
+                    pane_id,
+                    name,
+                    args,
+                })
+                .await?;
+        }
         CliSubCommand::Proxy => {
             // The client object we created above will have spawned
             // the server if needed, so now all we need to do is turn
@@ -523,7 +1156,10 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             Mux::set_mux(&mux);
             let unix_dom = config.unix_domains.first().unwrap();
             let sock_path = unix_dom.socket_path();
-            let stream = unix_connect_with_retry(&sock_path, false)?;
+            let mut stream = unix_connect_with_retry(&sock_path, false)?;
+            // The client on the other side of the proxy doesn't know
+            // the token for this server, so present it on its behalf
+            authenticate_unix_stream(&mut stream, unix_dom)?;
 
             // Spawn a thread to pull data from the socket and write
             // it to stdout
@@ -552,6 +1188,39 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
     Ok(())
 }
 
+/// Returns a row holding the minimum, mean, 95th percentile and
+/// maximum of `times`, in milliseconds
+fn summarize_frame_times<I: Iterator<Item = std::time::Duration>>(
+    stage: &str,
+    times: I,
+) -> Vec<String> {
+    let mut times: Vec<f64> = times.map(|t| t.as_secs_f64() * 1000.0).collect();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mean = times.iter().sum::<f64>() / times.len() as f64;
+    let p95 = times[((times.len() * 95) / 100).min(times.len() - 1)];
+    vec![
+        stage.to_string(),
+        format!("{:.2}", times[0]),
+        format!("{:.2}", mean),
+        format!("{:.2}", p95),
+        format!("{:.2}", times[times.len() - 1]),
+    ]
+}
+
+fn hit_rate(hits: usize, misses: usize) -> String {
+    let lookups = hits + misses;
+    if lookups == 0 {
+        "no lookups".to_string()
+    } else {
+        format!(
+            "{:.1}% ({} of {} lookups)",
+            hits as f64 * 100.0 / lookups as f64,
+            hits,
+            lookups
+        )
+    }
+}
+
 fn run_cli(config: config::ConfigHandle, cli: CliCommand) -> anyhow::Result<()> {
     let executor = promise::spawn::SimpleExecutor::new();
     promise::spawn::spawn(async move {
@@ -592,3 +1261,36 @@ fn consume_stream_then_exit_process<F: Read, T: Write>(
     drop(activity);
     std::process::exit(0);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gui_command_carries_portable_dir() {
+        let dir = PathBuf::from("/portable/wezterm");
+        let cmd = gui_command(
+            PathBuf::from("wezterm-gui"),
+            vec![OsString::from("start")],
+            Some(&dir),
+        );
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            vec![(
+                std::ffi::OsStr::new("WEZTERM_PORTABLE_DIR"),
+                Some(dir.as_os_str())
+            )]
+        );
+        assert_eq!(
+            cmd.get_args().collect::<Vec<_>>(),
+            vec![std::ffi::OsStr::new("start")]
+        );
+    }
+
+    #[test]
+    fn gui_command_without_portable_dir() {
+        let cmd = gui_command(PathBuf::from("wezterm-gui"), vec![], None);
+        assert_eq!(cmd.get_envs().count(), 0);
+    }
+}
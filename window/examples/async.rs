@@ -29,8 +29,8 @@ impl WindowCallbacks for MyWindow {
         Connection::get().unwrap().terminate_message_loop();
     }
 
-    fn resize(&mut self, dims: Dimensions, is_full_screen: bool) {
-        eprintln!("resize {:?} is_full_screen={}", dims, is_full_screen);
+    fn resize(&mut self, dims: Dimensions, window_state: WindowState) {
+        eprintln!("resize {:?} state={:?}", dims, window_state);
     }
 
     fn key_event(&mut self, key: &KeyEvent, ctx: &dyn WindowOps) -> bool {
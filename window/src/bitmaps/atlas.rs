@@ -1,13 +1,16 @@
 use crate::bitmaps::{BitmapImage, Texture2d, TextureRect};
 use crate::{Point, Rect, Size};
 use anyhow::{ensure, Result as Fallible};
-use guillotiere::{AtlasAllocator, Size as AtlasSize};
 use std::convert::TryInto;
 use std::rc::Rc;
 use thiserror::*;
 
 const PADDING: i32 = 1;
 
+/// Shelves are opened with heights that are a multiple of this,
+/// so that sprites of similar heights share them
+const SHELF_ALIGNMENT: u32 = 4;
+
 #[derive(Debug, Error)]
 #[error("Texture Size exceeded, need {:?}", size)]
 pub struct OutOfTextureSpace {
@@ -15,8 +18,235 @@ pub struct OutOfTextureSpace {
     pub current_size: usize,
 }
 
+/// Describes how much of the space in an atlas is in use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AtlasStats {
+    /// The width and height of the texture
+    pub side: usize,
+    /// The number of sprites that are allocated
+    pub allocations: usize,
+    /// The area occupied by the allocated sprites, including
+    /// their padding
+    pub used_area: usize,
+    /// The area of the shelves that have been opened.  The space in
+    /// a shelf that isn't used by its sprites can only be used by
+    /// sprites of a similar height.
+    pub shelf_area: usize,
+}
+
+impl AtlasStats {
+    /// Returns the fraction of the texture that is occupied by sprites
+    pub fn occupancy(&self) -> f32 {
+        if self.side == 0 {
+            0.
+        } else {
+            self.used_area as f32 / (self.side * self.side) as f32
+        }
+    }
+
+    /// Returns the fraction of the area of the shelves that is occupied
+    /// by sprites.  When this is low, releasing sprites has left holes
+    /// that sprites of other heights can't use.
+    pub fn utilization(&self) -> f32 {
+        if self.shelf_area == 0 {
+            1.
+        } else {
+            self.used_area as f32 / self.shelf_area as f32
+        }
+    }
+}
+
+/// Identifies an allocation made by a ShelfAllocator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AllocId {
+    /// Allocations made before the allocator was last cleared
+    /// are ignored when they are deallocated
+    epoch: usize,
+    shelf: usize,
+    x: u32,
+    width: u32,
+    height: u32,
+}
+
+/// A horizontal strip of the atlas, in which sprites that are no
+/// taller than the strip are placed side by side
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// The unused spans of the shelf as (x, width), sorted by x
+    free: Vec<(u32, u32)>,
+    allocations: usize,
+}
+
+impl Shelf {
+    fn largest_free(&self) -> u32 {
+        self.free.iter().map(|&(_, width)| width).max().unwrap_or(0)
+    }
+}
+
+/// Packs rectangles into a square using shelf-next-fit: rectangles
+/// are placed in the shortest shelf that is tall enough, has room,
+/// and wastes no more than a third of its height, and otherwise in
+/// a new shelf above the existing ones.
+/// Unlike a guillotine packer, mixing tall and short sprites doesn't
+/// fragment the space for the common case of many sprites with the
+/// same height, as they fill shelves of that height.
+struct ShelfAllocator {
+    side: u32,
+    shelves: Vec<Shelf>,
+    /// The y coordinate at which the next shelf would be opened
+    top: u32,
+    epoch: usize,
+    allocations: usize,
+    used_area: usize,
+}
+
+impl ShelfAllocator {
+    fn new(side: u32) -> Self {
+        Self {
+            side,
+            shelves: vec![],
+            top: 0,
+            epoch: 0,
+            allocations: 0,
+            used_area: 0,
+        }
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(AllocId, Point)> {
+        if width > self.side || height > self.side {
+            return None;
+        }
+
+        let shelf = self.find_shelf(width, height).or_else(|| {
+            // Open a new shelf above the existing ones
+            let shelf_height = (height + SHELF_ALIGNMENT - 1) / SHELF_ALIGNMENT * SHELF_ALIGNMENT;
+            let shelf_height = shelf_height.min(self.side - self.top);
+            if shelf_height < height {
+                return None;
+            }
+            self.shelves.push(Shelf {
+                y: self.top,
+                height: shelf_height,
+                free: vec![(0, self.side)],
+                allocations: 0,
+            });
+            self.top += shelf_height;
+            Some(self.shelves.len() - 1)
+        })?;
+
+        let shelf_idx = shelf;
+        let shelf = &mut self.shelves[shelf_idx];
+        let span = shelf.free.iter().position(|&(_, w)| w >= width)?;
+        let (x, span_width) = shelf.free[span];
+        if span_width == width {
+            shelf.free.remove(span);
+        } else {
+            shelf.free[span] = (x + width, span_width - width);
+        }
+        shelf.allocations += 1;
+
+        self.allocations += 1;
+        self.used_area += (width * height) as usize;
+
+        Some((
+            AllocId {
+                epoch: self.epoch,
+                shelf: shelf_idx,
+                x,
+                width,
+                height,
+            },
+            Point::new(x as isize, shelf.y as isize),
+        ))
+    }
+
+    /// Returns the index of the shortest shelf that can hold the
+    /// rectangle without wasting too much of its height.  An empty
+    /// shelf can be used for a rectangle of any height that it fits.
+    fn find_shelf(&self, width: u32, height: u32) -> Option<usize> {
+        self.shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| {
+                shelf.height >= height
+                    && (shelf.allocations == 0 || shelf.height <= height + height / 3 + 1)
+                    && shelf.largest_free() >= width
+            })
+            .min_by_key(|(_, shelf)| (shelf.allocations == 0, shelf.height))
+            .map(|(idx, _)| idx)
+    }
+
+    fn deallocate(&mut self, id: AllocId) {
+        if id.epoch != self.epoch {
+            return;
+        }
+        let shelf = match self.shelves.get_mut(id.shelf) {
+            Some(shelf) => shelf,
+            None => return,
+        };
+
+        // Return the span to the free list, merging it with its neighbors
+        let idx = shelf
+            .free
+            .iter()
+            .position(|&(x, _)| x > id.x)
+            .unwrap_or(shelf.free.len());
+        shelf.free.insert(idx, (id.x, id.width));
+        if idx + 1 < shelf.free.len() {
+            let (x, width) = shelf.free[idx];
+            let (next_x, next_width) = shelf.free[idx + 1];
+            if x + width == next_x {
+                shelf.free[idx].1 += next_width;
+                shelf.free.remove(idx + 1);
+            }
+        }
+        if idx > 0 {
+            let (prev_x, prev_width) = shelf.free[idx - 1];
+            let (x, width) = shelf.free[idx];
+            if prev_x + prev_width == x {
+                shelf.free[idx - 1].1 += width;
+                shelf.free.remove(idx);
+            }
+        }
+        shelf.allocations = shelf.allocations.saturating_sub(1);
+
+        self.allocations = self.allocations.saturating_sub(1);
+        self.used_area = self
+            .used_area
+            .saturating_sub((id.width * id.height) as usize);
+
+        // Give the space of empty shelves at the top back, so that
+        // it can be used by shelves of other heights
+        while let Some(shelf) = self.shelves.last() {
+            if shelf.allocations > 0 {
+                break;
+            }
+            self.top = shelf.y;
+            self.shelves.pop();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.shelves.clear();
+        self.top = 0;
+        self.epoch += 1;
+        self.allocations = 0;
+        self.used_area = 0;
+    }
+
+    fn stats(&self) -> AtlasStats {
+        AtlasStats {
+            side: self.side as usize,
+            allocations: self.allocations,
+            used_area: self.used_area,
+            shelf_area: self.top as usize * self.side as usize,
+        }
+    }
+}
+
 /// Atlases are bitmaps of srgba data that are sized as a power of 2.
-/// We allocate sprites out of the available space, using AtlasAllocator
+/// We allocate sprites out of the available space, using ShelfAllocator
 /// to manage the available rectangles.
 pub struct Atlas<T>
 where
@@ -24,7 +254,7 @@ where
 {
     texture: Rc<T>,
 
-    allocator: AtlasAllocator,
+    allocator: ShelfAllocator,
 
     /// Dimensions of the texture
     side: usize,
@@ -46,7 +276,7 @@ where
         let rect = Rect::new(Point::new(0, 0), Size::new(iside, iside));
         texture.write(rect, &image);
 
-        let allocator = AtlasAllocator::new(AtlasSize::new(side.try_into()?, side.try_into()?));
+        let allocator = ShelfAllocator::new(side.try_into()?);
         Ok(Self {
             texture: Rc::clone(texture),
             side,
@@ -88,12 +318,12 @@ where
         let reserve_width = reserve_width + padding.unwrap_or(0) as i32 + PADDING * 2;
         let reserve_height = reserve_height + padding.unwrap_or(0) as i32 + PADDING * 2;
 
-        if let Some(allocation) = self
+        if let Some((alloc_id, origin)) = self
             .allocator
-            .allocate(AtlasSize::new(reserve_width, reserve_height))
+            .allocate(reserve_width as u32, reserve_height as u32)
         {
-            let left = allocation.rectangle.min.x;
-            let top = allocation.rectangle.min.y;
+            let left = origin.x as i32;
+            let top = origin.y as i32;
             let rect = Rect::new(
                 Point::new((left + PADDING) as isize, (top + PADDING) as isize),
                 Size::new(width as isize, height as isize),
@@ -104,6 +334,7 @@ where
             Ok(Sprite {
                 texture: Rc::clone(&self.texture),
                 coords: rect,
+                alloc_id,
             })
         } else {
             // It's not possible to satisfy that request
@@ -119,6 +350,32 @@ where
         self.side
     }
 
+    /// Returns statistics about how much of the atlas is in use,
+    /// so that the caller can decide whether it is worth compacting
+    /// the atlas rather than growing it when it runs out of space
+    pub fn stats(&self) -> AtlasStats {
+        self.allocator.stats()
+    }
+
+    /// Zero out the region used by a sprite and make it available
+    /// for allocation again.  The caller must ensure that nothing
+    /// is still rendering using that sprite.
+    pub fn release(&mut self, sprite: &Sprite<T>) {
+        if !Rc::ptr_eq(&sprite.texture, &self.texture) {
+            // The sprite belongs to an atlas that has since been replaced
+            return;
+        }
+        let (width, height) = (
+            sprite.coords.size.width as usize,
+            sprite.coords.size.height as usize,
+        );
+        if width > 0 && height > 0 {
+            let image = crate::Image::new(width, height);
+            self.texture.write(sprite.coords, &image);
+        }
+        self.allocator.deallocate(sprite.alloc_id);
+    }
+
     /// Zero out the texture, and forget all allocated regions
     pub fn clear(&mut self) {
         let iside = self.side as isize;
@@ -135,6 +392,7 @@ where
 {
     pub texture: Rc<T>,
     pub coords: Rect,
+    alloc_id: AllocId,
 }
 
 impl<T: Texture2d> std::fmt::Debug for Sprite<T> {
@@ -155,6 +413,7 @@ where
         Self {
             texture: Rc::clone(&self.texture),
             coords: self.coords,
+            alloc_id: self.alloc_id,
         }
     }
 }
@@ -239,3 +498,108 @@ impl SpriteSlice {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn origin(alloc: Option<(AllocId, Point)>) -> (AllocId, (isize, isize)) {
+        let (id, point) = alloc.expect("allocation to succeed");
+        (id, (point.x, point.y))
+    }
+
+    #[test]
+    fn allocate_fills_shelves() {
+        let mut alloc = ShelfAllocator::new(64);
+        assert_eq!(origin(alloc.allocate(10, 10)).1, (0, 0));
+        assert_eq!(origin(alloc.allocate(10, 10)).1, (10, 0));
+
+        // Too tall for the first shelf, whose height is aligned to 12
+        assert_eq!(origin(alloc.allocate(10, 20)).1, (0, 12));
+        // Short enough to share the first shelf
+        assert_eq!(origin(alloc.allocate(10, 11)).1, (20, 0));
+        // Would waste too much of the second shelf
+        assert_eq!(origin(alloc.allocate(10, 8)).1, (0, 32));
+
+        assert_eq!(
+            alloc.stats(),
+            AtlasStats {
+                side: 64,
+                allocations: 5,
+                used_area: 100 + 100 + 200 + 110 + 80,
+                shelf_area: 40 * 64,
+            }
+        );
+
+        assert!(alloc.allocate(65, 1).is_none());
+        assert!(alloc.allocate(1, 65).is_none());
+        // There are only 24 rows left for new shelves
+        assert!(alloc.allocate(64, 25).is_none());
+        assert_eq!(origin(alloc.allocate(64, 24)).1, (0, 40));
+    }
+
+    #[test]
+    fn deallocate_merges_free_spans() {
+        let mut alloc = ShelfAllocator::new(64);
+        let (a, _) = origin(alloc.allocate(10, 10));
+        let (b, _) = origin(alloc.allocate(10, 10));
+        let (c, _) = origin(alloc.allocate(10, 10));
+        // Keep the first shelf from being the top one
+        origin(alloc.allocate(10, 20));
+
+        alloc.deallocate(b);
+        assert_eq!(alloc.shelves[0].free, vec![(10, 10), (30, 34)]);
+        alloc.deallocate(c);
+        assert_eq!(alloc.shelves[0].free, vec![(10, 54)]);
+        alloc.deallocate(a);
+        assert_eq!(alloc.shelves[0].free, vec![(0, 64)]);
+        assert_eq!(alloc.shelves[0].allocations, 0);
+
+        // The whole width is available again
+        assert_eq!(origin(alloc.allocate(64, 10)).1, (0, 0));
+        assert_eq!(alloc.stats().allocations, 2);
+        assert_eq!(alloc.stats().used_area, 640 + 200);
+    }
+
+    #[test]
+    fn deallocate_pops_empty_top_shelves() {
+        let mut alloc = ShelfAllocator::new(64);
+        origin(alloc.allocate(10, 10));
+        let (middle, _) = origin(alloc.allocate(10, 20));
+        let (top, _) = origin(alloc.allocate(10, 32));
+        assert_eq!(alloc.stats().shelf_area, 64 * 64);
+
+        // An empty shelf below a used one keeps its space
+        alloc.deallocate(middle);
+        assert_eq!(alloc.shelves.len(), 3);
+        assert_eq!(alloc.stats().shelf_area, 64 * 64);
+
+        // Both empty shelves at the top give their space back
+        alloc.deallocate(top);
+        assert_eq!(alloc.shelves.len(), 1);
+        assert_eq!(alloc.stats().shelf_area, 12 * 64);
+        assert_eq!(origin(alloc.allocate(10, 52)).1, (0, 12));
+    }
+
+    #[test]
+    fn deallocate_ignores_earlier_epochs() {
+        let mut alloc = ShelfAllocator::new(64);
+        let (stale, _) = origin(alloc.allocate(10, 10));
+        alloc.clear();
+        assert_eq!(alloc.stats().allocations, 0);
+        assert_eq!(alloc.stats().shelf_area, 0);
+
+        let (fresh, pos) = origin(alloc.allocate(10, 10));
+        assert_eq!(pos, (0, 0));
+        assert_eq!(stale.x, fresh.x);
+
+        // Releasing the allocation from before the clear must not
+        // free the space that now belongs to the new one
+        alloc.deallocate(stale);
+        assert_eq!(alloc.stats().allocations, 1);
+        assert_eq!(origin(alloc.allocate(10, 10)).1, (10, 0));
+
+        alloc.deallocate(fresh);
+        assert_eq!(alloc.stats().allocations, 1);
+    }
+}
@@ -1,5 +1,5 @@
-use crate::color::{LinearRgba, SrgbaPixel};
-use crate::{Point, Rect, Size};
+use crate::color::{linear_u8_to_srgb8, LinearRgba, SrgbaPixel};
+use crate::{Point, PointF, Rect, Size};
 use glium::texture::SrgbTexture2d;
 use std::cell::RefCell;
 
@@ -64,8 +64,16 @@ impl Texture2d for SrgbTexture2d {
         )
     }
 
-    fn read(&self, _rect: Rect, _im: &mut dyn BitmapImage) {
-        unimplemented!();
+    fn read(&self, rect: Rect, im: &mut dyn BitmapImage) {
+        // Read back the whole texture; the rows are in the same
+        // order in which they were written
+        let raw: glium::texture::RawImage2d<u8> = SrgbTexture2d::read(self);
+        let texture = Image::from_raw(
+            raw.width as usize,
+            raw.height as usize,
+            raw.data.into_owned(),
+        );
+        im.draw_image(Point::new(0, 0), Some(rect), &texture);
     }
 
     fn width(&self) -> usize {
@@ -77,6 +85,65 @@ impl Texture2d for SrgbTexture2d {
     }
 }
 
+/// A single channel texture that holds only the alpha channel of the
+/// images that are written to it, such as the coverage of monochrome
+/// glyphs, in a quarter of the memory of an sRGBA texture.
+impl Texture2d for glium::texture::Texture2d {
+    fn write(&self, rect: Rect, im: &dyn BitmapImage) {
+        let (im_width, im_height) = im.image_dimensions();
+
+        let alpha: Vec<u8> = im
+            .pixels()
+            .iter()
+            .map(|&pixel| SrgbaPixel::with_srgba_u32(pixel).as_rgba().3)
+            .collect();
+
+        let source = glium::texture::RawImage2d {
+            data: std::borrow::Cow::Owned(alpha),
+            width: im_width as u32,
+            height: im_height as u32,
+            format: glium::texture::ClientFormat::U8,
+        };
+
+        glium::texture::Texture2d::write(
+            self,
+            glium::Rect {
+                left: rect.min_x() as u32,
+                bottom: rect.min_y() as u32,
+                width: rect.size.width as u32,
+                height: rect.size.height as u32,
+            },
+            source,
+        )
+    }
+
+    fn read(&self, rect: Rect, im: &mut dyn BitmapImage) {
+        // The red channel of what is read back holds the alpha that
+        // was written; expand it to the gray that the glyph rasterizer
+        // produces, whose sRGB color matches its linear alpha
+        let raw: glium::texture::RawImage2d<u8> = glium::texture::Texture2d::read(self);
+        let mut texture = Image::new(raw.width as usize, raw.height as usize);
+        for (pixel, rgba) in texture
+            .pixels_mut()
+            .iter_mut()
+            .zip(raw.data.chunks_exact(4))
+        {
+            let alpha = rgba[0];
+            let gray = linear_u8_to_srgb8(alpha);
+            *pixel = SrgbaPixel::rgba(gray, gray, gray, alpha).as_srgba32();
+        }
+        im.draw_image(Point::new(0, 0), Some(rect), &texture);
+    }
+
+    fn width(&self) -> usize {
+        glium::texture::Texture2d::width(self) as usize
+    }
+
+    fn height(&self) -> usize {
+        glium::texture::Texture2d::height(self) as usize
+    }
+}
+
 /// A bitmap in big endian rbga32 color format with abstract
 /// storage filled in by the trait implementation.
 pub trait BitmapImage {
@@ -234,6 +301,142 @@ pub trait BitmapImage {
         );
     }
 
+    /// Composites `color`, scaled by `coverage`, over the pixel at `x`, `y`.
+    /// The pixel data is treated as premultiplied alpha.
+    fn blend_pixel(&mut self, x: usize, y: usize, color: LinearRgba, coverage: f32) {
+        let pixel = self.pixel_mut(x, y);
+        let (red, green, blue, alpha) = color.tuple();
+        let (red, green, blue, alpha) = (
+            red * coverage,
+            green * coverage,
+            blue * coverage,
+            alpha * coverage,
+        );
+        let (dest_red, dest_green, dest_blue, dest_alpha) =
+            SrgbaPixel::with_srgba_u32(*pixel).to_linear().tuple();
+        let remain = 1. - alpha;
+        *pixel = LinearRgba::with_components(
+            red + dest_red * remain,
+            green + dest_green * remain,
+            blue + dest_blue * remain,
+            alpha + dest_alpha * remain,
+        )
+        .srgba_pixel()
+        .as_srgba32();
+    }
+
+    /// Fills the pixels that are inside a shape, using the fraction of
+    /// each pixel that is covered by the shape to anti-alias its edges.
+    /// The coverage is estimated by sampling `inside` at a grid of
+    /// points within each pixel.
+    fn fill_shape(&mut self, color: SrgbaPixel, inside: &dyn Fn(f32, f32) -> bool) {
+        const SAMPLES: usize = 4;
+        let (width, height) = self.image_dimensions();
+        let linear = color.to_linear();
+        for py in 0..height {
+            for px in 0..width {
+                let mut count = 0;
+                for sy in 0..SAMPLES {
+                    for sx in 0..SAMPLES {
+                        let x = px as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                        let y = py as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                        if inside(x, y) {
+                            count += 1;
+                        }
+                    }
+                }
+                if count > 0 {
+                    let coverage = count as f32 / (SAMPLES * SAMPLES) as f32;
+                    self.blend_pixel(px, py, linear, coverage);
+                }
+            }
+        }
+    }
+
+    /// Draws a stroke of the specified thickness with anti-aliased edges.
+    /// `distance` returns the distance from a point to the center line
+    /// of the stroke.
+    fn stroke_shape(
+        &mut self,
+        color: SrgbaPixel,
+        thickness: f32,
+        distance: &dyn Fn(f32, f32) -> f32,
+    ) {
+        let (width, height) = self.image_dimensions();
+        let linear = color.to_linear();
+        for py in 0..height {
+            for px in 0..width {
+                // Sample at the center of the pixel
+                let distance = distance(px as f32 + 0.5, py as f32 + 0.5);
+                let coverage = (thickness / 2. + 0.5 - distance).max(0.).min(1.);
+                if coverage > 0. {
+                    self.blend_pixel(px, py, linear, coverage);
+                }
+            }
+        }
+    }
+
+    /// Fills a polygon, using the even-odd rule, with anti-aliased edges
+    fn fill_polygon(&mut self, points: &[PointF], color: SrgbaPixel) {
+        self.fill_shape(color, &|x, y| point_in_polygon(PointF::new(x, y), points));
+    }
+
+    /// Fills a circle with anti-aliased edges
+    fn fill_circle(&mut self, center: PointF, radius: f32, color: SrgbaPixel) {
+        self.stroke_shape(color, radius * 2., &|x, y| {
+            (x - center.x).hypot(y - center.y)
+        });
+    }
+
+    /// Draws a line of the specified thickness, with anti-aliased
+    /// edges, starting at `start` and ending at `end`.
+    fn draw_thick_line(&mut self, start: PointF, end: PointF, thickness: f32, color: SrgbaPixel) {
+        self.stroke_shape(color, thickness, &|x, y| {
+            segment_distance(PointF::new(x, y), start, end)
+        });
+    }
+
+    /// Draws an arc of a circle with anti-aliased edges.  The angles
+    /// are in radians, measured clockwise from the positive x axis
+    /// (as y increases downwards), and the arc runs from `start_angle`
+    /// to `end_angle`.
+    fn draw_arc(
+        &mut self,
+        center: PointF,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        thickness: f32,
+        color: SrgbaPixel,
+    ) {
+        let tau = 2. * std::f32::consts::PI;
+        let sweep = (end_angle - start_angle).rem_euclid(tau);
+        let sweep = if sweep == 0. && end_angle != start_angle {
+            tau
+        } else {
+            sweep
+        };
+        let point_at = |angle: f32| {
+            PointF::new(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            )
+        };
+        let (start, end) = (point_at(start_angle), point_at(start_angle + sweep));
+
+        self.stroke_shape(color, thickness, &|x, y| {
+            let angle = (y - center.y).atan2(x - center.x);
+            if (angle - start_angle).rem_euclid(tau) <= sweep {
+                ((x - center.x).hypot(y - center.y) - radius).abs()
+            } else {
+                // Outside of the sweep the closest point is one of the ends
+                (x - start.x)
+                    .hypot(y - start.y)
+                    .min((x - end.x).hypot(y - end.y))
+            }
+        });
+    }
+
     fn draw_image(&mut self, dest_top_left: Point, src_rect: Option<Rect>, im: &dyn BitmapImage) {
         let (im_width, im_height) = im.image_dimensions();
         let src_rect = src_rect
@@ -289,6 +492,39 @@ pub trait BitmapImage {
     }
 }
 
+/// Returns true if the point is inside the polygon, using the even-odd rule
+pub fn point_in_polygon(point: PointF, polygon: &[PointF]) -> bool {
+    if polygon.is_empty() {
+        return false;
+    }
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Returns the distance from the point to the line segment
+pub fn segment_distance(point: PointF, start: PointF, end: PointF) -> f32 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0. {
+        (((point.x - start.x) * dx + (point.y - start.y) * dy) / length)
+            .max(0.)
+            .min(1.)
+    } else {
+        0.
+    };
+    (point.x - (start.x + t * dx)).hypot(point.y - (start.y + t * dy))
+}
+
 /// A bitmap in big endian bgra32 color format, with storage
 /// in a Vec<u8>.
 #[derive(Clone)]
@@ -378,6 +614,44 @@ impl Image {
         dest
     }
 
+    /// Creates a new image that is `factor` times smaller than the
+    /// current image, by averaging each `factor` x `factor` block of
+    /// pixels.  The average is computed in linear space, so that
+    /// supersampled edges are anti-aliased with the correct weight.
+    pub fn downsample(&self, factor: usize) -> Image {
+        let width = self.width / factor;
+        let height = self.height / factor;
+        let mut dest = Image::new(width, height);
+        let count = (factor * factor) as f32;
+        for y in 0..height {
+            for x in 0..width {
+                let (mut red, mut green, mut blue, mut alpha) = (0., 0., 0., 0.);
+                for sy in 0..factor {
+                    for sx in 0..factor {
+                        let (r, g, b, a) = SrgbaPixel::with_srgba_u32(
+                            *self.pixel(x * factor + sx, y * factor + sy),
+                        )
+                        .to_linear()
+                        .tuple();
+                        red += r;
+                        green += g;
+                        blue += b;
+                        alpha += a;
+                    }
+                }
+                *dest.pixel_mut(x, y) = LinearRgba::with_components(
+                    red / count,
+                    green / count,
+                    blue / count,
+                    alpha / count,
+                )
+                .srgba_pixel()
+                .as_srgba32();
+            }
+        }
+        dest
+    }
+
     pub fn scale_by(&self, scale: f64) -> Image {
         let width = (self.width as f64 * scale) as usize;
         let height = (self.height as f64 * scale) as usize;
@@ -432,8 +706,9 @@ impl Texture2d for ImageTexture {
         image.draw_image(rect.origin, None, im);
     }
 
-    fn read(&self, _rect: Rect, _im: &mut dyn BitmapImage) {
-        unimplemented!();
+    fn read(&self, rect: Rect, im: &mut dyn BitmapImage) {
+        let image = self.image.borrow();
+        im.draw_image(Point::new(0, 0), Some(rect), &*image);
     }
 
     /// Returns the width of the texture in pixels
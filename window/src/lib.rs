@@ -40,6 +40,54 @@ impl Default for Clipboard {
     }
 }
 
+/// Text destined for the clipboard, along with optional richer
+/// representations for applications that understand them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RichClipboardText {
+    /// The plain text; this is always made available
+    pub text: String,
+    /// An HTML fragment, offered as `text/html`
+    pub html: Option<String>,
+    /// An RTF document, offered as `text/rtf`
+    pub rtf: Option<String>,
+}
+
+/// Progress to display on the taskbar or dock entry for a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskbarProgress {
+    None,
+    Normal(u8),
+    Error(u8),
+    Indeterminate,
+    Paused(u8),
+}
+
+impl Default for TaskbarProgress {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+bitflags::bitflags! {
+    /// Describes how the window is currently presented by
+    /// the window manager
+    pub struct WindowState: u8 {
+        /// The window occupies the whole screen
+        const FULL_SCREEN = 1<<0;
+        /// The window has been maximized
+        const MAXIMIZED = 1<<1;
+    }
+}
+
+impl WindowState {
+    /// Returns true if the window is neither full screen nor
+    /// maximized, which means that its size can be changed
+    /// by the application
+    pub fn can_resize(self) -> bool {
+        self.is_empty()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Dimensions {
     pub pixel_width: usize,
@@ -49,6 +97,7 @@ pub struct Dimensions {
 
 pub type Rect = euclid::Rect<isize, PixelUnit>;
 pub type Size = euclid::Size2D<isize, PixelUnit>;
+pub type PointF = euclid::Point2D<f32, PixelUnit>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MouseCursor {
@@ -71,12 +120,17 @@ pub trait WindowCallbacks: Any {
     /// Called when the window is being destroyed by the gui system
     fn destroy(&mut self) {}
 
-    /// Called when the window is resized, or when the dpi has changed
-    fn resize(&mut self, dimensions: Dimensions, is_full_screen: bool) {}
+    /// Called when the window is resized, or when the dpi
+    /// or the window state has changed
+    fn resize(&mut self, dimensions: Dimensions, window_state: WindowState) {}
 
     /// Called when window gains/loses focus
     fn focus_change(&mut self, focused: bool) {}
 
+    /// Called when the input method starts or finishes
+    /// composing text
+    fn ime_composition_status(&mut self, composing: bool) {}
+
     /// Called when the window has opengl mode enabled and the window
     /// contents need painting.
     fn paint(&mut self, frame: &mut glium::Frame) {
@@ -156,6 +210,12 @@ pub trait WindowOps {
         Future::ok(())
     }
 
+    /// Enable or disable the platform specific input method editor
+    /// for this window, on systems that support it
+    fn set_ime_enabled(&self, _enabled: bool) -> Future<()> {
+        Future::ok(())
+    }
+
     /// Schedule a callback on the data associated with the window.
     /// The `Any` that is passed in corresponds to the WindowCallbacks
     /// impl you passed to `new_window`, pre-converted to Any so that
@@ -174,6 +234,13 @@ pub trait WindowOps {
     /// Set some text in the clipboard
     fn set_clipboard(&self, clipboard: Clipboard, text: String) -> Future<()>;
 
+    /// Set some text in the clipboard, offering the richer
+    /// representations where the system supports them.
+    /// The default implementation places only the plain text.
+    fn set_clipboard_rich(&self, clipboard: Clipboard, content: RichClipboardText) -> Future<()> {
+        self.set_clipboard(clipboard, content.text)
+    }
+
     /// Set the icon for the window.
     /// Depending on the system this may be shown in its titlebar
     /// and/or in the task manager/task switcher
@@ -181,6 +248,12 @@ pub trait WindowOps {
         Future::ok(())
     }
 
+    /// Show progress on the taskbar or dock entry for the window,
+    /// on systems that support it
+    fn set_taskbar_progress(&self, _progress: TaskbarProgress) -> Future<()> {
+        Future::ok(())
+    }
+
     fn toggle_fullscreen(&self) -> Future<()> {
         Future::ok(())
     }
@@ -218,6 +291,10 @@ pub trait WindowOpsMut {
     /// the platform specific input method editor
     fn set_text_cursor_position(&mut self, _cursor: Rect) {}
 
+    /// Enable or disable the platform specific input method editor
+    /// for this window, on systems that support it
+    fn set_ime_enabled(&mut self, _enabled: bool) {}
+
     /// Changes the location of the window on the screen.
     /// The coordinates are of the top left pixel of the
     /// client area.
@@ -228,6 +305,10 @@ pub trait WindowOpsMut {
     /// and/or in the task manager/task switcher
     fn set_icon(&mut self, _image: &dyn BitmapImage) {}
 
+    /// Show progress on the taskbar or dock entry for the window,
+    /// on systems that support it
+    fn set_taskbar_progress(&mut self, _progress: TaskbarProgress) {}
+
     fn toggle_fullscreen(&mut self) {}
 
     fn config_did_change(&mut self, _config: &config::ConfigHandle) {}
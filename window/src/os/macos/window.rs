@@ -6,7 +6,7 @@ use crate::connection::ConnectionOps;
 use crate::{
     Clipboard, Connection, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor,
     MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint, Size, WindowCallbacks,
-    WindowDecorations, WindowOps, WindowOpsMut,
+    WindowDecorations, WindowOps, WindowOpsMut, WindowState,
 };
 use anyhow::{anyhow, bail, ensure};
 use cocoa::appkit::{
@@ -388,6 +388,7 @@ impl Window {
                 vscroll_remainder: 0.,
                 last_wheel: Instant::now(),
                 key_is_down: None,
+                ime_enabled: true,
                 dead_pending: None,
                 fullscreen: None,
                 config: config.clone(),
@@ -474,7 +475,7 @@ impl Window {
                     dpi: (crate::DEFAULT_DPI * (backing_frame.size.width / frame.size.width))
                         as usize,
                 },
-                false,
+                WindowState::empty(),
             );
 
             Ok(window)
@@ -547,6 +548,13 @@ impl WindowOps for Window {
         })
     }
 
+    fn set_ime_enabled(&self, enabled: bool) -> Future<()> {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_ime_enabled(enabled);
+            Ok(())
+        })
+    }
+
     fn apply<R, F: Send + 'static + FnMut(&mut dyn Any, &dyn WindowOps) -> anyhow::Result<R>>(
         &self,
         mut func: F,
@@ -852,6 +860,19 @@ impl WindowOpsMut for WindowInner {
         }
     }
 
+    fn set_ime_enabled(&mut self, enabled: bool) {
+        if let Some(window_view) = WindowView::get_this(unsafe { &**self.view }) {
+            window_view.inner.borrow_mut().ime_enabled = enabled;
+        }
+        if !enabled && self.config.use_ime {
+            // Abandon any text that is part way through being composed
+            unsafe {
+                let input_context: id = msg_send![&**self.view, inputContext];
+                let () = msg_send![input_context, discardMarkedText];
+            }
+        }
+    }
+
     fn toggle_fullscreen(&mut self) {
         let native_fullscreen = self.config.native_macos_fullscreen_mode;
 
@@ -917,6 +938,10 @@ struct Inner {
     /// procesing key-up events.
     key_is_down: Option<bool>,
 
+    /// Whether the IME may process key events; this is only
+    /// consulted when use_ime is true
+    ime_enabled: bool,
+
     /// First in a dead-key sequence
     dead_pending: Option<(u16, u32)>,
 
@@ -1296,6 +1321,8 @@ impl WindowView {
             let mut inner = myself.inner.borrow_mut();
             let key_is_down = inner.key_is_down.take().unwrap_or(true);
 
+            inner.callbacks.ime_composition_status(false);
+
             let event = KeyEvent {
                 key: KeyCode::Composed(s.to_string()),
                 raw_key: None,
@@ -1313,7 +1340,7 @@ impl WindowView {
     }
 
     extern "C" fn set_marked_text_selected_range_replacement_range(
-        _this: &mut Object,
+        this: &mut Object,
         _sel: Sel,
         _astring: id,
         selected_range: NSRange,
@@ -1324,10 +1351,18 @@ impl WindowView {
             "set_marked_text_selected_range_replacement_range {} {:?} {:?}",
             s, selected_range, replacement_range
         );
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner.callbacks.ime_composition_status(!s.is_empty());
+        }
     }
 
-    extern "C" fn unmark_text(_this: &mut Object, _sel: Sel) {
+    extern "C" fn unmark_text(this: &mut Object, _sel: Sel) {
         eprintln!("unmarkText");
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner.callbacks.ime_composition_status(false);
+        }
     }
 
     extern "C" fn valid_attributes_for_marked_text(_this: &mut Object, _sel: Sel) -> id {
@@ -1502,10 +1537,12 @@ impl WindowView {
             // Devices with precise deltas report number of pixels scrolled.
             // At this layer we don't know how many pixels comprise a cell
             // in the terminal widget, and our abstraction doesn't allow being
-            // told what that amount should be, so we come up with a hard
-            // coded factor based on the likely default font size and dpi
-            // to make the scroll speed feel a bit better.
-            15.0
+            // told what that amount should be, so we use a configurable
+            // factor, which defaults to one based on the likely default font
+            // size and dpi to make the scroll speed feel a bit better.
+            config::configuration()
+                .trackpad_scroll_pixels_per_line
+                .max(1.0)
         } else {
             // Whereas imprecise deltas report the number of lines scrolled,
             // so we want to report those lines here wholesale.
@@ -1615,7 +1652,10 @@ impl WindowView {
             return;
         };
 
-        let use_ime = config::configuration().use_ime;
+        let use_ime = config::configuration().use_ime
+            && Self::get_this(this)
+                .map(|myself| myself.inner.borrow().ime_enabled)
+                .unwrap_or(true);
 
         // `Delete` on macos is really Backspace and emits BS.
         // `Fn-Delete` emits DEL.
@@ -1769,6 +1809,20 @@ impl WindowView {
                     let style_mask = unsafe { NSWindow::styleMask(*window) };
                     style_mask.contains(NSWindowStyleMask::NSFullScreenWindowMask)
                 });
+            let is_zoomed = !is_fullscreen
+                && inner.window.as_ref().map_or(false, |window| {
+                    let window = window.load();
+                    let zoomed: BOOL = unsafe { msg_send![*window, isZoomed] };
+                    zoomed == YES
+                });
+
+            let mut window_state = WindowState::empty();
+            if is_fullscreen {
+                window_state |= WindowState::FULL_SCREEN;
+            }
+            if is_zoomed {
+                window_state |= WindowState::MAXIMIZED;
+            }
 
             inner.callbacks.resize(
                 Dimensions {
@@ -1777,7 +1831,7 @@ impl WindowView {
                     dpi: (crate::DEFAULT_DPI * (backing_frame.size.width / frame.size.width))
                         as usize,
                 },
-                is_fullscreen,
+                window_state,
             );
         }
     }
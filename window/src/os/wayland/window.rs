@@ -6,7 +6,7 @@ use crate::os::wayland::connection::WaylandConnection;
 use crate::os::xkeysyms::keysym_to_keycode;
 use crate::{
     Clipboard, Connection, Dimensions, MouseCursor, Point, ScreenPoint, Window, WindowCallbacks,
-    WindowOps, WindowOpsMut,
+    WindowOps, WindowOpsMut, WindowState,
 };
 use anyhow::{anyhow, bail, Context};
 use config::ConfigHandle;
@@ -86,7 +86,7 @@ pub struct WaylandWindowInner {
     window: Option<toolkit::window::Window<ConceptFrame>>,
     dimensions: Dimensions,
     need_paint: bool,
-    full_screen: bool,
+    window_state: WindowState,
     last_mouse_coords: Point,
     mouse_buttons: MouseButtons,
     modifiers: Modifiers,
@@ -106,7 +106,7 @@ struct PendingEvent {
     refresh_decorations: bool,
     configure: Option<(u32, u32)>,
     dpi: Option<i32>,
-    full_screen: Option<bool>,
+    window_state: Option<WindowState>,
 }
 
 impl PendingEvent {
@@ -137,17 +137,23 @@ impl PendingEvent {
                 } else {
                     changed = true;
                 }
-                let full_screen = states.contains(&State::Fullscreen);
+                let mut window_state = WindowState::empty();
+                if states.contains(&State::Fullscreen) {
+                    window_state |= WindowState::FULL_SCREEN;
+                }
+                if states.contains(&State::Maximized) {
+                    window_state |= WindowState::MAXIMIZED;
+                }
                 log::debug!(
-                    "Config: self.full_screen={:?}, states:{:?} {:?}",
-                    self.full_screen,
-                    full_screen,
+                    "Config: self.window_state={:?}, states:{:?} {:?}",
+                    self.window_state,
+                    window_state,
                     states
                 );
-                match (self.full_screen, full_screen) {
-                    (None, false) => {}
+                match (self.window_state, window_state) {
+                    (None, s) if s.is_empty() => {}
                     _ => {
-                        self.full_screen.replace(full_screen);
+                        self.window_state.replace(window_state);
                         changed = true;
                     }
                 }
@@ -253,7 +259,7 @@ impl WaylandWindow {
             window: Some(window),
             dimensions,
             need_paint: true,
-            full_screen: false,
+            window_state: WindowState::empty(),
             last_mouse_coords: Point::new(0, 0),
             mouse_buttons: MouseButtons::NONE,
             modifiers: Modifiers::NONE,
@@ -458,13 +464,13 @@ impl WaylandWindowInner {
             self.window.take();
         }
 
-        if let Some(full_screen) = pending.full_screen.take() {
+        if let Some(window_state) = pending.window_state.take() {
             log::debug!(
-                "dispatch_pending_event self.full_screen={} pending:{}",
-                self.full_screen,
-                full_screen
+                "dispatch_pending_event self.window_state={:?} pending:{:?}",
+                self.window_state,
+                window_state
             );
-            self.full_screen = full_screen;
+            self.window_state = window_state;
         }
 
         if pending.configure.is_none() && pending.dpi.is_some() {
@@ -503,7 +509,7 @@ impl WaylandWindowInner {
                 if new_dimensions != self.dimensions {
                     self.dimensions = new_dimensions;
 
-                    self.callbacks.resize(self.dimensions, self.full_screen);
+                    self.callbacks.resize(self.dimensions, self.window_state);
                     if let Some(wegl_surface) = self.wegl_surface.as_mut() {
                         wegl_surface.resize(pixel_width, pixel_height, 0, 0);
                     }
@@ -810,7 +816,7 @@ impl WindowOpsMut for WaylandWindowInner {
 
     fn toggle_fullscreen(&mut self) {
         if let Some(window) = self.window.as_ref() {
-            if self.full_screen {
+            if self.window_state.contains(WindowState::FULL_SCREEN) {
                 window.unset_fullscreen();
             } else {
                 window.set_fullscreen(None);
@@ -2,8 +2,8 @@ use super::*;
 use crate::connection::ConnectionOps;
 use crate::{
     Clipboard, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor, MouseEvent,
-    MouseEventKind, MousePress, Point, Rect, ScreenPoint, WindowCallbacks, WindowDecorations,
-    WindowOps, WindowOpsMut,
+    MouseEventKind, MousePress, Point, Rect, ScreenPoint, TaskbarProgress, WindowCallbacks,
+    WindowDecorations, WindowOps, WindowOpsMut, WindowState,
 };
 use anyhow::{bail, Context};
 use config::ConfigHandle;
@@ -22,14 +22,24 @@ use std::rc::Rc;
 use winapi::shared::minwindef::*;
 use winapi::shared::ntdef::*;
 use winapi::shared::windef::*;
+use winapi::shared::winerror::SUCCEEDED;
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx};
 use winapi::um::imm::*;
 use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+use winapi::um::shobjidl_core::{
+    CLSID_TaskbarList, ITaskbarList3, TBPF_ERROR, TBPF_INDETERMINATE, TBPF_NOPROGRESS, TBPF_NORMAL,
+    TBPF_PAUSED,
+};
 use winapi::um::winuser::*;
+use winapi::Interface;
 use winreg::{enums::HKEY_CURRENT_USER, RegKey};
 
 const GCS_RESULTSTR: DWORD = 0x800;
 extern "system" {
     pub fn ImmGetCompositionStringW(himc: HIMC, index: DWORD, buf: LPVOID, buflen: DWORD) -> LONG;
+    pub fn ImmSetOpenStatus(himc: HIMC, open: BOOL) -> BOOL;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -210,9 +220,17 @@ impl WindowInner {
             let imc = ImmContext::get(self.hwnd.0);
             imc.set_position(0, 0);
 
+            let mut window_state = WindowState::empty();
+            if self.saved_placement.is_some() {
+                window_state |= WindowState::FULL_SCREEN;
+            }
+            if unsafe { IsZoomed(self.hwnd.0) } != 0 {
+                window_state |= WindowState::MAXIMIZED;
+            }
+
             self.callbacks
                 .borrow_mut()
-                .resize(current_dims, self.saved_placement.is_some());
+                .resize(current_dims, window_state);
         }
 
         !same
@@ -527,11 +545,50 @@ impl WindowOpsMut for WindowInner {
         imc.set_position(cursor.origin.x.max(0) as i32, cursor.origin.y.max(0) as i32);
     }
 
+    fn set_ime_enabled(&mut self, enabled: bool) {
+        let imc = ImmContext::get(self.hwnd.0);
+        imc.set_open(enabled);
+    }
+
     fn config_did_change(&mut self, config: &ConfigHandle) {
         self.config = config.clone();
         self.apply_decoration();
     }
 
+    fn set_taskbar_progress(&mut self, progress: TaskbarProgress) {
+        let (state, value) = match progress {
+            TaskbarProgress::None => (TBPF_NOPROGRESS, None),
+            TaskbarProgress::Normal(pct) => (TBPF_NORMAL, Some(pct)),
+            TaskbarProgress::Error(pct) => (TBPF_ERROR, Some(pct)),
+            TaskbarProgress::Indeterminate => (TBPF_INDETERMINATE, None),
+            TaskbarProgress::Paused(pct) => (TBPF_PAUSED, Some(pct)),
+        };
+        unsafe {
+            // The taskbar is only reachable via COM; ensure that it
+            // is initialized on this thread.  This is a no-op if
+            // it has already been initialized.
+            CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED);
+
+            let mut taskbar: *mut ITaskbarList3 = null_mut();
+            let res = CoCreateInstance(
+                &CLSID_TaskbarList,
+                null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &ITaskbarList3::uuidof(),
+                &mut taskbar as *mut *mut ITaskbarList3 as *mut _,
+            );
+            if !SUCCEEDED(res) || taskbar.is_null() {
+                log::error!("unable to create ITaskbarList3: {:#x}", res);
+                return;
+            }
+            (*taskbar).SetProgressState(self.hwnd.0, state);
+            if let Some(pct) = value {
+                (*taskbar).SetProgressValue(self.hwnd.0, pct.into(), 100);
+            }
+            (*taskbar).Release();
+        }
+    }
+
     fn toggle_fullscreen(&mut self) {
         unsafe {
             let hwnd = self.hwnd.0;
@@ -647,6 +704,13 @@ impl WindowOps for Window {
         })
     }
 
+    fn set_ime_enabled(&self, enabled: bool) -> Future<()> {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_ime_enabled(enabled);
+            Ok(())
+        })
+    }
+
     fn set_inner_size(&self, width: usize, height: usize) -> Future<()> {
         Connection::with_window_inner(self.0, move |inner| {
             inner.set_inner_size(width, height);
@@ -661,6 +725,13 @@ impl WindowOps for Window {
         })
     }
 
+    fn set_taskbar_progress(&self, progress: TaskbarProgress) -> Future<()> {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_taskbar_progress(progress);
+            Ok(())
+        })
+    }
+
     fn apply<R, F: Send + 'static + FnMut(&mut dyn Any, &dyn WindowOps) -> anyhow::Result<R>>(
         &self,
         mut func: F,
@@ -1154,6 +1225,14 @@ impl ImmContext {
             ImmSetCompositionWindow(self.imc, &mut cf);
         }
     }
+
+    /// Open or close the IME; while it is closed, keys are
+    /// delivered directly to the window
+    pub fn set_open(&self, open: bool) {
+        unsafe {
+            ImmSetOpenStatus(self.imc, if open { TRUE } else { FALSE });
+        }
+    }
 }
 
 impl Drop for ImmContext {
@@ -1164,6 +1243,23 @@ impl Drop for ImmContext {
     }
 }
 
+unsafe fn ime_start_end_composition(
+    hwnd: HWND,
+    msg: UINT,
+    _wparam: WPARAM,
+    _lparam: LPARAM,
+) -> Option<LRESULT> {
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        let inner = inner.borrow();
+        inner
+            .callbacks
+            .borrow_mut()
+            .ime_composition_status(msg == WM_IME_STARTCOMPOSITION);
+    }
+    // Allow the default processing to show the composition window
+    None
+}
+
 unsafe fn ime_composition(
     hwnd: HWND,
     _msg: UINT,
@@ -1905,6 +2001,9 @@ unsafe fn do_wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         WM_DEADCHAR | WM_KEYDOWN | WM_KEYUP | WM_SYSCHAR | WM_CHAR | WM_IME_CHAR | WM_SYSKEYUP
         | WM_SYSKEYDOWN => key(hwnd, msg, wparam, lparam),
         WM_IME_COMPOSITION => ime_composition(hwnd, msg, wparam, lparam),
+        WM_IME_STARTCOMPOSITION | WM_IME_ENDCOMPOSITION => {
+            ime_start_end_composition(hwnd, msg, wparam, lparam)
+        }
         WM_MOUSEMOVE => mouse_move(hwnd, msg, wparam, lparam),
         WM_MOUSEHWHEEL | WM_MOUSEWHEEL => mouse_wheel(hwnd, msg, wparam, lparam),
         WM_LBUTTONDBLCLK | WM_RBUTTONDBLCLK | WM_MBUTTONDBLCLK | WM_LBUTTONDOWN | WM_LBUTTONUP
@@ -29,6 +29,8 @@ pub struct XConnection {
     pub atom_xsel_data: xcb::Atom,
     pub atom_targets: xcb::Atom,
     pub atom_clipboard: xcb::Atom,
+    pub atom_text_html: xcb::Atom,
+    pub atom_text_rtf: xcb::Atom,
     keysyms: *mut xcb_key_symbols_t,
     pub(crate) xrm: HashMap<String, String>,
     pub(crate) windows: RefCell<HashMap<xcb::xproto::Window, Arc<Mutex<XWindowInner>>>>,
@@ -315,6 +317,12 @@ impl XConnection {
         let atom_clipboard = xcb::intern_atom(&conn, false, "CLIPBOARD")
             .get_reply()?
             .atom();
+        let atom_text_html = xcb::intern_atom(&conn, false, "text/html")
+            .get_reply()?
+            .atom();
+        let atom_text_rtf = xcb::intern_atom(&conn, false, "text/rtf")
+            .get_reply()?
+            .atom();
 
         let keysyms = unsafe { xcb_key_symbols_alloc((*conn).get_raw_conn()) };
 
@@ -384,6 +392,8 @@ impl XConnection {
             xrm,
             atom_protocols,
             atom_clipboard,
+            atom_text_html,
+            atom_text_rtf,
             atom_delete,
             keysyms,
             keyboard,
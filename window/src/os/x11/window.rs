@@ -5,7 +5,8 @@ use crate::os::xkeysyms;
 use crate::os::{Connection, Window};
 use crate::{
     Clipboard, Dimensions, MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress,
-    Point, Rect, ScreenPoint, Size, WindowCallbacks, WindowDecorations, WindowOps, WindowOpsMut,
+    Point, Rect, RichClipboardText, ScreenPoint, Size, WindowCallbacks, WindowDecorations,
+    WindowOps, WindowOpsMut, WindowState,
 };
 use anyhow::{anyhow, Context as _};
 use config::ConfigHandle;
@@ -18,22 +19,22 @@ use std::sync::{Arc, Mutex};
 
 #[derive(Default)]
 struct CopyAndPaste {
-    clipboard_owned: Option<String>,
-    primary_selection_owned: Option<String>,
+    clipboard_owned: Option<RichClipboardText>,
+    primary_selection_owned: Option<RichClipboardText>,
     clipboard_request: Option<Promise<String>>,
     selection_request: Option<Promise<String>>,
     time: u32,
 }
 
 impl CopyAndPaste {
-    fn clipboard(&self, clipboard: Clipboard) -> &Option<String> {
+    fn clipboard(&self, clipboard: Clipboard) -> &Option<RichClipboardText> {
         match clipboard {
             Clipboard::PrimarySelection => &self.primary_selection_owned,
             Clipboard::Clipboard => &self.clipboard_owned,
         }
     }
 
-    fn clipboard_mut(&mut self, clipboard: Clipboard) -> &mut Option<String> {
+    fn clipboard_mut(&mut self, clipboard: Clipboard) -> &mut Option<RichClipboardText> {
         match clipboard {
             Clipboard::PrimarySelection => &mut self.primary_selection_owned,
             Clipboard::Clipboard => &mut self.clipboard_owned,
@@ -190,7 +191,8 @@ impl XWindowInner {
                         pixel_height: self.height as usize,
                         dpi: conn.default_dpi as usize,
                     },
-                    self.is_fullscreen().unwrap_or(false),
+                    self.get_window_state()
+                        .unwrap_or_else(|_| WindowState::empty()),
                 )
             }
             xcb::KEY_PRESS | xcb::KEY_RELEASE => {
@@ -386,7 +388,18 @@ impl XWindowInner {
 
         let selprop = if request.target() == conn.atom_targets {
             // They want to know which targets we support
-            let atoms: [u32; 1] = [conn.atom_utf8_string];
+            let mut atoms: Vec<u32> = vec![conn.atom_utf8_string];
+            if let Some(content) = self
+                .selection_atom_to_clipboard(request.selection())
+                .and_then(|clipboard| self.copy_and_paste.clipboard(clipboard).as_ref())
+            {
+                if content.html.is_some() {
+                    atoms.push(conn.atom_text_html);
+                }
+                if content.rtf.is_some() {
+                    atoms.push(conn.atom_text_rtf);
+                }
+            }
             xcb::xproto::change_property(
                 &conn,
                 xcb::xproto::PROP_MODE_REPLACE as u8,
@@ -407,7 +420,7 @@ impl XWindowInner {
                 // We don't and won't do any conversion from UTF-8 to
                 // whatever STRING represents; let's just assume that
                 // the other end is going to handle it correctly.
-                if let Some(text) = self.copy_and_paste.clipboard(clipboard) {
+                if let Some(content) = self.copy_and_paste.clipboard(clipboard) {
                     xcb::xproto::change_property(
                         &conn,
                         xcb::xproto::PROP_MODE_REPLACE as u8,
@@ -415,7 +428,7 @@ impl XWindowInner {
                         request.property(),
                         request.target(),
                         8, /* 8-bit string data */
-                        text.as_bytes(),
+                        content.text.as_bytes(),
                     );
                     // let the requestor know that we set their property
                     request.property()
@@ -426,6 +439,34 @@ impl XWindowInner {
             } else {
                 xcb::NONE
             }
+        } else if request.target() == conn.atom_text_html || request.target() == conn.atom_text_rtf
+        {
+            // Rich text representations, which are only available if
+            // they were provided via set_clipboard_rich
+            let data = self
+                .selection_atom_to_clipboard(request.selection())
+                .and_then(|clipboard| self.copy_and_paste.clipboard(clipboard).as_ref())
+                .and_then(|content| {
+                    if request.target() == conn.atom_text_html {
+                        content.html.as_ref()
+                    } else {
+                        content.rtf.as_ref()
+                    }
+                });
+            if let Some(data) = data {
+                xcb::xproto::change_property(
+                    &conn,
+                    xcb::xproto::PROP_MODE_REPLACE as u8,
+                    request.requestor(),
+                    request.property(),
+                    request.target(),
+                    8, /* 8-bit string data */
+                    data.as_bytes(),
+                );
+                request.property()
+            } else {
+                xcb::NONE
+            }
         } else {
             // We didn't support their request, so there is nothing
             // we can report back to them.
@@ -490,15 +531,21 @@ impl XWindowInner {
     }
 
     fn is_fullscreen(&self) -> anyhow::Result<bool> {
+        Ok(self.get_window_state()?.contains(WindowState::FULL_SCREEN))
+    }
+
+    fn get_window_state(&self) -> anyhow::Result<WindowState> {
         let conn = self.conn();
 
-        let net_wm_state = xcb::intern_atom(conn.conn(), false, "_NET_WM_STATE")
-            .get_reply()?
-            .atom();
-        let net_wm_state_fullscreen =
-            xcb::intern_atom(conn.conn(), false, "_NET_WM_STATE_FULLSCREEN")
+        let intern = |name: &str| -> anyhow::Result<xcb::Atom> {
+            Ok(xcb::intern_atom(conn.conn(), false, name)
                 .get_reply()?
-                .atom();
+                .atom())
+        };
+        let net_wm_state = intern("_NET_WM_STATE")?;
+        let net_wm_state_fullscreen = intern("_NET_WM_STATE_FULLSCREEN")?;
+        let net_wm_state_maximized_vert = intern("_NET_WM_STATE_MAXIMIZED_VERT")?;
+        let net_wm_state_maximized_horz = intern("_NET_WM_STATE_MAXIMIZED_HORZ")?;
 
         let reply = xcb::xproto::get_property(
             &conn,
@@ -511,12 +558,15 @@ impl XWindowInner {
         )
         .get_reply()?;
 
-        let state = reply.value::<u32>();
-
-        Ok(state
-            .iter()
-            .position(|&x| x == net_wm_state_fullscreen)
-            .is_some())
+        let mut window_state = WindowState::empty();
+        for &atom in reply.value::<u32>() {
+            if atom == net_wm_state_fullscreen {
+                window_state |= WindowState::FULL_SCREEN;
+            } else if atom == net_wm_state_maximized_vert || atom == net_wm_state_maximized_horz {
+                window_state |= WindowState::MAXIMIZED;
+            }
+        }
+        Ok(window_state)
     }
 
     fn set_fullscreen_hint(&mut self, enable: bool) -> anyhow::Result<()> {
@@ -949,8 +999,8 @@ impl WindowOps for XWindow {
         let mut promise = Some(promise);
         XConnection::with_window_inner(self.0, move |inner| {
             let mut promise = promise.take().unwrap();
-            if let Some(text) = inner.copy_and_paste.clipboard(clipboard) {
-                promise.ok(text.to_owned());
+            if let Some(content) = inner.copy_and_paste.clipboard(clipboard) {
+                promise.ok(content.text.to_owned());
 
                 // Cancel any outstanding promise from the other branch
                 // below.
@@ -982,11 +1032,21 @@ impl WindowOps for XWindow {
 
     /// Set some text in the clipboard
     fn set_clipboard(&self, clipboard: Clipboard, text: String) -> Future<()> {
+        self.set_clipboard_rich(
+            clipboard,
+            RichClipboardText {
+                text,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn set_clipboard_rich(&self, clipboard: Clipboard, content: RichClipboardText) -> Future<()> {
         XConnection::with_window_inner(self.0, move |inner| {
             inner
                 .copy_and_paste
                 .clipboard_mut(clipboard)
-                .replace(text.clone());
+                .replace(content.clone());
             inner.update_selection_owner(clipboard);
             Ok(())
         })
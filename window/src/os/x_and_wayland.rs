@@ -7,7 +7,7 @@ use crate::os::wayland::connection::WaylandConnection;
 use crate::os::wayland::window::WaylandWindow;
 use crate::os::x11::connection::XConnection;
 use crate::os::x11::window::XWindow;
-use crate::{Clipboard, MouseCursor, ScreenPoint, WindowCallbacks, WindowOps};
+use crate::{Clipboard, MouseCursor, RichClipboardText, ScreenPoint, WindowCallbacks, WindowOps};
 use config::ConfigHandle;
 use promise::*;
 use std::any::Any;
@@ -238,4 +238,11 @@ impl WindowOps for Window {
             Self::Wayland(w) => w.set_clipboard(clipboard, text),
         }
     }
+    fn set_clipboard_rich(&self, clipboard: Clipboard, content: RichClipboardText) -> Future<()> {
+        match self {
+            Self::X11(x) => x.set_clipboard_rich(clipboard, content),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.set_clipboard_rich(clipboard, content),
+        }
+    }
 }